@@ -0,0 +1,88 @@
+//! Compares the buffered `AccountWriter` against a plain `csv::Writer` fed
+//! with one `String` per field, for a wide run of accounts.
+//!
+//! Run with `cargo bench --bench account_output`.
+
+use rust_decimal::Decimal;
+use rust_payments_engine::{
+    ACCOUNT_HEADER,
+    client::Client,
+    formatting::format_decimal,
+    ids::{ClientId, TxId},
+    money::Money,
+    output::AccountWriter,
+};
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const CLIENTS: u32 = 1_000_000;
+const ROUNDS: u32 = 5;
+
+fn clients() -> Vec<Client> {
+    (0..CLIENTS)
+        .map(|index| {
+            let mut client = Client::new(ClientId(index as u16));
+            let amount = Decimal::new(i64::from(index) * 37 + 1, 4);
+            client
+                .deposit(TxId(1), Money::new(amount).unwrap())
+                .unwrap();
+            client
+        })
+        .collect()
+}
+
+fn csv_writer(clients: &[Client]) -> usize {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(ACCOUNT_HEADER).unwrap();
+    for client in clients {
+        writer
+            .write_record([
+                client.id.to_string(),
+                format_decimal(client.available),
+                format_decimal(client.held.value()),
+                format_decimal(client.total),
+                client.locked.to_string(),
+            ])
+            .unwrap();
+    }
+    writer.into_inner().unwrap().len()
+}
+
+fn account_writer(clients: &[Client]) -> usize {
+    let mut output = Vec::new();
+    let mut writer = AccountWriter::new(&mut output);
+    writer.write_header().unwrap();
+    for client in clients {
+        writer.write_account(client).unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+    output.len()
+}
+
+fn best_of(clients: &[Client], run: fn(&[Client]) -> usize) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let started = Instant::now();
+            black_box(run(black_box(clients)));
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let clients = clients();
+    assert_eq!(csv_writer(&clients), account_writer(&clients));
+
+    let baseline = best_of(&clients, csv_writer);
+    let buffered = best_of(&clients, account_writer);
+    println!("csv::Writer    {baseline:?}");
+    println!("AccountWriter  {buffered:?}");
+    println!(
+        "speedup        {:.1}x",
+        baseline.as_secs_f64() / buffered.as_secs_f64()
+    );
+}
@@ -0,0 +1,62 @@
+//! Compares parsing and summing amounts with `Decimal` against the `i64`
+//! `MinorUnits` backend.
+//!
+//! Run with `cargo bench --bench numeric`.
+
+use rust_decimal::Decimal;
+use rust_payments_engine::numeric::{MinorUnits, Numeric};
+use std::{
+    hint::black_box,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+const AMOUNTS: u32 = 2_000_000;
+const ROUNDS: u32 = 5;
+
+fn amounts() -> Vec<String> {
+    (0..AMOUNTS)
+        .map(|index| Decimal::new(i64::from(index) * 37 + 1, 4).to_string())
+        .collect()
+}
+
+fn sum<N: Numeric + FromStr>(amounts: &[String]) -> Decimal
+where
+    N::Err: std::fmt::Debug,
+{
+    let mut balance = N::ZERO;
+    for (index, text) in amounts.iter().enumerate() {
+        let amount: N = text.parse().unwrap();
+        balance = if index % 4 == 3 {
+            balance.checked_sub(amount).unwrap()
+        } else {
+            balance.checked_add(amount).unwrap()
+        };
+    }
+    balance.to_decimal()
+}
+
+fn best_of(amounts: &[String], run: fn(&[String]) -> Decimal) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let started = Instant::now();
+            black_box(run(black_box(amounts)));
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let amounts = amounts();
+    assert_eq!(sum::<Decimal>(&amounts), sum::<MinorUnits>(&amounts));
+
+    let decimal = best_of(&amounts, sum::<Decimal>);
+    let minor = best_of(&amounts, sum::<MinorUnits>);
+    println!("Decimal     {decimal:?}");
+    println!("MinorUnits  {minor:?}");
+    println!(
+        "speedup     {:.1}x",
+        decimal.as_secs_f64() / minor.as_secs_f64()
+    );
+}
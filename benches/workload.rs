@@ -0,0 +1,192 @@
+//! Runs the engine over synthetic workloads and reports throughput, to catch
+//! performance regressions before release.
+//!
+//! Run with `cargo bench --bench workload -- [--workload <name>] [--rows <n>]
+//! [--balance decimal|minor-units]`, where the workload is one of
+//! `deposit-heavy`, `dispute-heavy`, `many-clients` or `few-clients` (all of
+//! them when omitted) and the balance type is the engine's `B`, `Decimal`
+//! unless `minor-units` picks `MinorUnits`. Add
+//! `--features dhat-heap` for allocation counts, or profile with
+//! `cargo flamegraph --bench workload -- --workload dispute-heavy`.
+
+use rust_decimal::Decimal;
+use rust_payments_engine::{
+    Engine,
+    numeric::{Balance, MinorUnits},
+};
+use std::{
+    hint::black_box,
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+const DEFAULT_ROWS: usize = 1_000_000;
+const ROUNDS: u32 = 3;
+
+#[derive(Clone, Copy)]
+struct Workload {
+    name: &'static str,
+    clients: u64,
+    /// Out of every 100 rows, how many are withdrawals and how many are
+    /// dispute rows: a dispute of the client's last deposit, or else the
+    /// resolve or chargeback of an open dispute. The rest are deposits.
+    withdrawals: u64,
+    disputes: u64,
+}
+
+const WORKLOADS: [Workload; 4] = [
+    Workload {
+        name: "deposit-heavy",
+        clients: 1_000,
+        withdrawals: 10,
+        disputes: 0,
+    },
+    Workload {
+        name: "dispute-heavy",
+        clients: 1_000,
+        withdrawals: 10,
+        disputes: 30,
+    },
+    Workload {
+        name: "many-clients",
+        clients: u16::MAX as u64,
+        withdrawals: 30,
+        disputes: 5,
+    },
+    Workload {
+        name: "few-clients",
+        clients: 10,
+        withdrawals: 30,
+        disputes: 5,
+    },
+];
+
+/// A small LCG, so every run replays the same rows.
+struct Lcg(u64);
+
+impl Lcg {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) % bound
+    }
+}
+
+fn generate(workload: Workload, rows: usize) -> Vec<u8> {
+    let mut random = Lcg(0x5eed);
+    let mut csv = String::from("type,client,tx,amount\n");
+    // Per client, the last deposit still open to dispute, if any.
+    let mut disputable = vec![None; workload.clients as usize];
+    let mut disputed: Vec<(u64, u32)> = Vec::new();
+    for tx in 1..=rows as u32 {
+        let client = random.below(workload.clients);
+        let roll = random.below(100);
+        let amount = random.below(100_000) + 1;
+        if roll < workload.disputes {
+            if let Some(deposit) = disputable[client as usize].take() {
+                csv.push_str(&format!("dispute,{client},{deposit},\n"));
+                disputed.push((client, deposit));
+                continue;
+            }
+            if let Some((client, deposit)) = disputed.pop() {
+                let settle = if tx % 2 == 0 { "resolve" } else { "chargeback" };
+                csv.push_str(&format!("{settle},{client},{deposit},\n"));
+                continue;
+            }
+        } else if roll < workload.disputes + workload.withdrawals {
+            csv.push_str(&format!(
+                "withdrawal,{client},{tx},{}.{:04}\n",
+                amount / 10_000,
+                amount % 10_000
+            ));
+            continue;
+        }
+        csv.push_str(&format!(
+            "deposit,{client},{tx},{}.{:04}\n",
+            amount / 10_000,
+            amount % 10_000
+        ));
+        disputable[client as usize] = Some(tx);
+    }
+    csv.into_bytes()
+}
+
+fn run<B: Balance>(input: &[u8]) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let started = Instant::now();
+            let mut engine = Engine::<B>::default();
+            engine.process(Cursor::new(black_box(input))).unwrap();
+            black_box(engine.row_counts());
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let mut selected = None;
+    let mut rows = DEFAULT_ROWS;
+    let mut minor_units = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--workload" => selected = args.next(),
+            "--rows" => {
+                rows = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("--rows <n>")
+            }
+            "--balance" => {
+                minor_units = match args.next().as_deref() {
+                    Some("decimal") => false,
+                    Some("minor-units") => true,
+                    _ => panic!("--balance decimal|minor-units"),
+                }
+            }
+            // Passed by `cargo bench`.
+            "--bench" => {}
+            other => panic!("unknown argument {other}"),
+        }
+    }
+
+    #[cfg(feature = "dhat-heap")]
+    let _profiler = dhat::Profiler::builder().testing().build();
+
+    for workload in WORKLOADS
+        .into_iter()
+        .filter(|workload| selected.as_deref().is_none_or(|name| name == workload.name))
+    {
+        let input = generate(workload, rows);
+        #[cfg(feature = "dhat-heap")]
+        let before = dhat::HeapStats::get();
+        let elapsed = if minor_units {
+            run::<MinorUnits>(&input)
+        } else {
+            run::<Decimal>(&input)
+        };
+        println!(
+            "{:<14} {rows} rows in {elapsed:?}, {:.0} rows/s",
+            workload.name,
+            rows as f64 / elapsed.as_secs_f64()
+        );
+        #[cfg(feature = "dhat-heap")]
+        {
+            let after = dhat::HeapStats::get();
+            println!(
+                "{:<14} {} allocations, {} bytes allocated, {} bytes at peak",
+                "",
+                (after.total_blocks - before.total_blocks) / u64::from(ROUNDS),
+                (after.total_bytes - before.total_bytes) / u64::from(ROUNDS),
+                after.max_bytes
+            );
+        }
+    }
+}
@@ -0,0 +1,66 @@
+/* C interface to the payments engine, built with `cargo build --release
+ * --features ffi`. See src/ffi.rs for the full contract of each function. */
+#ifndef PAYMENTS_ENGINE_H
+#define PAYMENTS_ENGINE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum PaymentsStatus {
+    PAYMENTS_OK = 0,
+    PAYMENTS_NULL_POINTER = 1,
+    PAYMENTS_INVALID_ARGUMENT = 2,
+    PAYMENTS_ENGINE_FAILURE = 3,
+    PAYMENTS_REJECTED = 4,
+    PAYMENTS_ACCOUNT_LOCKED = 10,
+    PAYMENTS_INVALID_TRANSACTION_ID = 11,
+    PAYMENTS_INSUFFICIENT_AVAILABLE_FUNDS = 12,
+    PAYMENTS_MISSING_AMOUNT = 13,
+    PAYMENTS_INVALID_AMOUNT = 14,
+    PAYMENTS_INSUFFICIENT_HELD_FUNDS = 15,
+    PAYMENTS_UNKNOWN_TRANSACTION = 16,
+    PAYMENTS_ALREADY_IN_DISPUTE = 17,
+    PAYMENTS_NOT_IN_DISPUTE = 18,
+    PAYMENTS_WITHDRAWAL_ALREADY_HELD = 19,
+    PAYMENTS_UNKNOWN_WITHDRAWAL_HOLD = 20,
+    PAYMENTS_INCONSISTENT_BALANCES = 21,
+    PAYMENTS_ARITHMETIC = 22,
+    PAYMENTS_WITHDRAWALS_FROZEN = 23,
+    PAYMENTS_UNEXPECTED_AMOUNT = 24,
+    PAYMENTS_CAPABILITY_REVOKED = 25,
+    PAYMENTS_TX_ID_OUT_OF_ORDER = 26,
+    PAYMENTS_OPEN_TRANSACTIONS = 27,
+    PAYMENTS_ZERO_AMOUNT = 28,
+    PAYMENTS_CLIENT_ERROR = 29,
+    PAYMENTS_BALANCE_LIMIT_EXCEEDED = 30,
+    PAYMENTS_NEGATIVE_BALANCE = 31,
+    PAYMENTS_INTERNAL = 99
+} PaymentsStatus;
+
+typedef struct PaymentsEngine PaymentsEngine;
+
+/* Bytes owned by the library; release with payments_buffer_free. */
+typedef struct PaymentsBuffer {
+    uint8_t *data;
+    size_t len;
+} PaymentsBuffer;
+
+PaymentsStatus payments_engine_new(PaymentsEngine **engine);
+void payments_engine_free(PaymentsEngine *engine);
+/* amount may be NULL for dispute, resolve, chargeback and similar types. */
+PaymentsStatus payments_engine_push(PaymentsEngine *engine, const char *tx_type,
+                                    uint16_t client, uint32_t tx, const char *amount);
+/* Accounts as CSV with a header row, as the CLI writes them. */
+PaymentsStatus payments_engine_accounts(const PaymentsEngine *engine, PaymentsBuffer *buffer);
+void payments_buffer_free(PaymentsBuffer buffer);
+const char *payments_status_message(PaymentsStatus status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PAYMENTS_ENGINE_H */
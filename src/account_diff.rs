@@ -0,0 +1,198 @@
+use rust_decimal::{Decimal, dec};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Write},
+};
+
+use crate::{
+    errors::EngineError,
+    formatting::format_decimal,
+    ids::{ClientId, TxId},
+    transaction::Transaction,
+};
+
+/// Smallest amount the engine accepts, deposited and charged back to lock an
+/// account without moving its balances.
+const LOCKING_AMOUNT: Decimal = dec!(0.0001);
+
+/// The balances of one row of an accounts CSV. `total` and any columns past
+/// `locked` are ignored; pending withdrawal holds cannot be recreated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct AccountBalances {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub locked: bool,
+}
+
+/// Reads an accounts CSV, as `run` writes it, keyed by client.
+pub fn read_accounts<R: Read>(
+    reader: R,
+) -> Result<BTreeMap<ClientId, AccountBalances>, EngineError> {
+    let mut accounts = BTreeMap::new();
+    for row in csv::Reader::from_reader(reader).deserialize() {
+        let account: AccountBalances = row?;
+        accounts.insert(account.client, account);
+    }
+    Ok(accounts)
+}
+
+/// The transactions that take the engine from the `before` accounts to the
+/// `after` accounts, numbered from `first_tx`, for loading balances kept by
+/// another system. Pass empty `before` accounts to start from nothing.
+///
+/// Per client, in client order:
+/// - more held funds become a deposit disputed at once;
+/// - the change in available funds becomes one deposit or withdrawal;
+/// - a newly locked account gets a deposit of `0.0001` that is disputed
+///   and charged back, which locks it without moving its balances.
+///
+/// Clients missing from `after` are taken as emptied. Changes no sequence
+/// of transactions can make (held funds going down, available funds going
+/// below zero, an account unlocking or a locked account changing) fail with
+/// `EngineError::IrreversibleChange`.
+pub fn diff_accounts(
+    before: &BTreeMap<ClientId, AccountBalances>,
+    after: &BTreeMap<ClientId, AccountBalances>,
+    first_tx: TxId,
+) -> Result<Vec<Transaction>, EngineError> {
+    let mut tx_ids = (first_tx.0..=u32::MAX).map(TxId);
+    let mut next_tx = || tx_ids.next().ok_or(EngineError::TxIdsExhausted);
+    let mut transactions = Vec::new();
+
+    let clients: BTreeSet<ClientId> = before.keys().chain(after.keys()).copied().collect();
+    for client in clients {
+        let from = before.get(&client).copied().unwrap_or(AccountBalances {
+            client,
+            ..AccountBalances::default()
+        });
+        let to = after.get(&client).copied().unwrap_or(AccountBalances {
+            client,
+            ..AccountBalances::default()
+        });
+        let irreversible = |reason| EngineError::IrreversibleChange { client, reason };
+        let amount_error = |err| EngineError::Usage(format!("client {client}: {err}"));
+
+        if from == to {
+            continue;
+        }
+        if from.locked {
+            return Err(irreversible("the account is locked"));
+        }
+        if to.held < from.held {
+            return Err(irreversible("held funds cannot go down"));
+        }
+        if to.available < Decimal::ZERO {
+            return Err(irreversible("available funds cannot go below zero"));
+        }
+
+        let held = to.held - from.held;
+        if held > Decimal::ZERO {
+            let tx = next_tx()?;
+            transactions.push(Transaction::deposit(client, tx, held).map_err(amount_error)?);
+            transactions.push(Transaction::dispute(client, tx));
+        }
+        let available = to.available - from.available;
+        if available > Decimal::ZERO {
+            transactions
+                .push(Transaction::deposit(client, next_tx()?, available).map_err(amount_error)?);
+        } else if available < Decimal::ZERO {
+            transactions.push(
+                Transaction::withdrawal(client, next_tx()?, -available).map_err(amount_error)?,
+            );
+        }
+        if to.locked {
+            let tx = next_tx()?;
+            transactions
+                .push(Transaction::deposit(client, tx, LOCKING_AMOUNT).map_err(amount_error)?);
+            transactions.push(Transaction::dispute(client, tx));
+            transactions.push(Transaction::chargeback(client, tx));
+        }
+    }
+    Ok(transactions)
+}
+
+/// Writes `transactions` as an input CSV with a `type,client,tx,amount`
+/// header.
+pub fn write_transactions<W: Write>(
+    writer: W,
+    transactions: &[Transaction],
+) -> Result<(), EngineError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["type", "client", "tx", "amount"])?;
+    for transaction in transactions {
+        let amount = transaction
+            .amount
+            .map(|amount| format_decimal(amount.value()))
+            .unwrap_or_default();
+        writer.write_record([
+            transaction.tx_type.as_str(),
+            &transaction.client.to_string(),
+            &transaction.tx.to_string(),
+            &amount,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn accounts(csv: &str) -> BTreeMap<ClientId, AccountBalances> {
+        read_accounts(csv.as_bytes()).unwrap()
+    }
+
+    fn replay(engine: &mut Engine, transactions: &[Transaction]) -> String {
+        let mut input = Vec::new();
+        write_transactions(&mut input, transactions).unwrap();
+        engine.process(input.as_slice()).unwrap();
+        let mut output = Vec::new();
+        engine.write_accounts(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn replaying_the_diff_reproduces_the_after_accounts() {
+        let before = accounts(
+            "client,available,held,total,locked\n\
+             1,10.0,0.0,10.0,false\n\
+             2,5.0,1.0,6.0,false\n\
+             3,1.0,0.0,1.0,false\n",
+        );
+        let after_csv = "client,available,held,total,locked\n\
+             1,4.5,2.0,6.5,false\n\
+             2,5.0,1.0,6.0,true\n\
+             3,0.0,0.0,0.0,false\n\
+             4,7.25,0.0,7.25,false\n";
+        let after = accounts(after_csv);
+
+        let mut engine = Engine::new();
+        let loaded = diff_accounts(&BTreeMap::new(), &before, TxId(1)).unwrap();
+        replay(&mut engine, &loaded);
+        let changes = diff_accounts(&before, &after, TxId(100)).unwrap();
+        assert_eq!(accounts(&replay(&mut engine, &changes)), after);
+        assert_eq!(changes.first().map(|tx| tx.tx), Some(TxId(100)));
+        assert_eq!(engine.row_counts().rejected, 0);
+    }
+
+    #[test]
+    fn irreversible_changes_are_refused() {
+        let before = accounts("client,available,held,total,locked\n1,1.0,2.0,3.0,false\n");
+        for after in [
+            "client,available,held,total,locked\n1,1.0,1.0,2.0,false\n",
+            "client,available,held,total,locked\n1,-1.0,2.0,1.0,false\n",
+        ] {
+            assert!(matches!(
+                diff_accounts(&before, &accounts(after), TxId(1)),
+                Err(EngineError::IrreversibleChange {
+                    client: ClientId(1),
+                    ..
+                })
+            ));
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Operator corrections to an engine's accounts, each returning the audit
+//! entries that record it. Operations on named clients reload them first
+//! if an eviction policy spilled them, as transactions do.
+
+use rust_decimal::Decimal;
+use std::{fmt, str::FromStr};
+
+use crate::{
+    Engine,
+    audit::{AuditAction, AuditEntry},
+    client::{Capability, Client},
+    errors::{ClientTransactionError, EngineError},
+    ids::{ClientId, TxId},
+    numeric::{Balance, Numeric},
+};
+
+/// Takes back a deposit that should never have been applied, as if it had
+/// not been: its amount leaves the balances and the lifetime deposit
+/// figures.
+pub fn reverse_deposit<B: Balance>(
+    engine: &mut Engine<B>,
+    client_id: ClientId,
+    tx: TxId,
+) -> Result<AuditEntry, EngineError> {
+    engine.touch(client_id)?;
+    let client = engine
+        .clients
+        .get_mut(client_id)
+        .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+    let amount = client.reverse_deposit(tx)?;
+    let entry = AuditEntry::new(AuditAction::ReverseDeposit, client, tx, Some(amount));
+    engine.mark_changed(client_id);
+    Ok(entry)
+}
+
+pub fn force_resolve<B: Balance>(
+    engine: &mut Engine<B>,
+    client_id: ClientId,
+    tx: TxId,
+) -> Result<AuditEntry, EngineError> {
+    engine.touch(client_id)?;
+    let client = engine
+        .clients
+        .get_mut(client_id)
+        .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+    let amount = client.force_resolve(tx)?;
+    let entry = AuditEntry::new(AuditAction::ForceResolve, client, tx, Some(amount));
+    engine.mark_changed(client_id);
+    Ok(entry)
+}
+
+/// Closes every open dispute of a client in its favour, with one audit entry
+/// per released dispute.
+pub fn resolve_all<B: Balance>(
+    engine: &mut Engine<B>,
+    client_id: ClientId,
+) -> Result<Vec<AuditEntry>, EngineError> {
+    engine.touch(client_id)?;
+    let client = engine
+        .clients
+        .get_mut(client_id)
+        .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+    let released = client.resolve_all()?;
+    let entries = released
+        .into_iter()
+        .map(|(tx, amount)| AuditEntry::new(AuditAction::ResolveAll, client, tx, Some(amount)))
+        .collect();
+    engine.mark_changed(client_id);
+    engine.requeue_open_disputes();
+    Ok(entries)
+}
+
+/// Merges `from` into `into` (see [`crate::client::Client::merge`]) and
+/// drops `from`, along with its balance history, whose balances would not
+/// be `into`'s. `from` stays in `Engine::changed_clients`, so delta loaders
+/// can tell it went away. On failure neither client is changed.
+pub fn merge_clients<B: Balance>(
+    engine: &mut Engine<B>,
+    from: ClientId,
+    into: ClientId,
+) -> Result<AuditEntry, EngineError> {
+    if from == into {
+        return Err(ClientTransactionError::MergeIntoSelf { client_id: into }.into());
+    }
+    engine.touch(from)?;
+    engine.touch(into)?;
+    let source = engine
+        .clients
+        .get(from)
+        .cloned()
+        .ok_or(ClientTransactionError::UnknownClient { client_id: from })?;
+    let client = engine
+        .clients
+        .get_mut(into)
+        .ok_or(ClientTransactionError::UnknownClient { client_id: into })?;
+    client.merge(&source)?;
+    let entry = AuditEntry::new(AuditAction::MergeClients, client, TxId(0), None)
+        .with_reason(format!("merged client {from}"));
+    engine.clients.remove(from);
+    engine.last_touched.remove(&from);
+    if let Some(history) = &mut engine.history {
+        history.remove(from);
+    }
+    engine.changed.insert(from);
+    engine.mark_changed(into);
+    engine.requeue_open_disputes();
+    Ok(entry)
+}
+
+/// Switches one capability of an account on or off; see
+/// [`crate::client::Capabilities`].
+pub fn set_capability<B: Balance>(
+    engine: &mut Engine<B>,
+    client_id: ClientId,
+    capability: Capability,
+    allowed: bool,
+) -> Result<AuditEntry, EngineError> {
+    engine.touch(client_id)?;
+    let client = engine
+        .clients
+        .get_mut(client_id)
+        .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+    client.set_capability(capability, allowed);
+    let entry = AuditEntry::new(AuditAction::SetCapability, client, TxId(0), None)
+        .with_reason(format!("{capability}={allowed}"));
+    engine.mark_changed(client_id);
+    Ok(entry)
+}
+
+/// Erases a client's transaction history, for deletion requests: its
+/// remembered deposits, its balance history but for the latest point, and
+/// the engine's unwritten audit entries and review queue entries about it,
+/// since those carry its balances and transaction ids. Balances stay, so totals
+/// across accounts still add up, and the returned entry is the tombstone
+/// recording that the history was erased. Fails while the client has open
+/// disputes or withdrawal holds.
+///
+/// Audit trails and dead-letter files already written are not touched.
+pub fn forget_client<B: Balance>(
+    engine: &mut Engine<B>,
+    client_id: ClientId,
+) -> Result<AuditEntry, EngineError> {
+    engine.touch(client_id)?;
+    let client = engine
+        .clients
+        .get_mut(client_id)
+        .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+    let forgotten = client.forget_history()?;
+    let entry = AuditEntry::new(AuditAction::ForgetClient, client, TxId(0), None)
+        .with_reason(format!("erased history of {forgotten} deposit(s)"));
+    if let Some(history) = &mut engine.history {
+        history.forget(client_id);
+    }
+    engine.audit.retain(|entry| entry.client != client_id);
+    engine.review_queue.retain(|item| item.client != client_id);
+    engine.mark_changed(client_id);
+    engine.requeue_open_disputes();
+    Ok(entry)
+}
+
+/// An account whose `total` is not `available + held + pending`, as older
+/// versions or hand-edited snapshots can leave behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BalanceMismatch {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub pending: Decimal,
+    pub total: Decimal,
+}
+
+impl BalanceMismatch {
+    fn of<B: Balance>(client: &Client<B>) -> Option<Self> {
+        if client.balances_add_up() {
+            return None;
+        }
+        Some(BalanceMismatch {
+            client: client.id,
+            available: client.available.to_decimal(),
+            held: client.held.into(),
+            pending: client.pending.into(),
+            total: client.total.to_decimal(),
+        })
+    }
+
+    pub fn expected_total(&self) -> Decimal {
+        self.available + self.held + self.pending
+    }
+}
+
+impl fmt::Display for BalanceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client {}: total {} but available {} + held {} + pending {} = {}",
+            self.client,
+            self.total,
+            self.available,
+            self.held,
+            self.pending,
+            self.expected_total()
+        )
+    }
+}
+
+/// How [`repair_balances`] fixes a [`BalanceMismatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepairStrategy {
+    /// Trust the component balances and set `total` to their sum.
+    RecomputeTotal,
+    /// Leave the balances alone for an investigation, and lock the account
+    /// so nothing moves in the meantime.
+    Quarantine,
+}
+
+impl FromStr for RepairStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "recompute-total" => Ok(RepairStrategy::RecomputeTotal),
+            "quarantine" => Ok(RepairStrategy::Quarantine),
+            other => Err(format!(
+                "unknown repair strategy {other}, expected recompute-total or quarantine"
+            )),
+        }
+    }
+}
+
+/// Every resident account with inconsistent balances, in client id order.
+pub fn find_balance_mismatches<B: Balance>(engine: &Engine<B>) -> Vec<BalanceMismatch> {
+    let mut mismatches: Vec<BalanceMismatch> = engine
+        .clients
+        .values()
+        .filter_map(BalanceMismatch::of)
+        .collect();
+    mismatches.sort_unstable_by_key(|mismatch| mismatch.client);
+    mismatches
+}
+
+/// Applies `strategy` to every account [`find_balance_mismatches`] reports,
+/// with one audit entry per account that names the mismatch.
+pub fn repair_balances<B: Balance>(
+    engine: &mut Engine<B>,
+    strategy: RepairStrategy,
+) -> Vec<AuditEntry> {
+    let mut entries = Vec::new();
+    for mismatch in find_balance_mismatches(engine) {
+        let Some(client) = engine.clients.get_mut(mismatch.client) else {
+            continue;
+        };
+        let expected = Numeric::checked_add(client.available, client.held.value())
+            .and_then(|sum| Numeric::checked_add(sum, client.pending.value()));
+        let action = match (strategy, expected) {
+            (RepairStrategy::RecomputeTotal, Some(total)) => {
+                client.total = total;
+                AuditAction::RepairTotal
+            }
+            // A sum `B` cannot hold is quarantined instead.
+            (RepairStrategy::RecomputeTotal, None) | (RepairStrategy::Quarantine, _) => {
+                client.locked = true;
+                AuditAction::Quarantine
+            }
+        };
+        entries
+            .push(AuditEntry::new(action, client, TxId(0), None).with_reason(mismatch.to_string()));
+        engine.mark_changed(mismatch.client);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::EngineConfig,
+        dispute_expiry::{DisputeDeadline, DisputeExpiry, ExpiryOutcome},
+        eviction::{EvictionPolicy, MemoryStore},
+        history::PointInTime,
+        transaction::TransactionType,
+    };
+    use rust_decimal::dec;
+    use std::{io::Cursor, time::Duration};
+
+    fn engine_from_raw_csv(csv: &str) -> Engine {
+        let mut engine = Engine::new();
+        engine.process(Cursor::new(csv.as_bytes())).unwrap();
+        engine
+    }
+
+    #[test]
+    fn admin_operations_reload_evicted_clients() {
+        let mut engine = engine_from_raw_csv(
+            "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,2.0\ndispute,1,1,\ndeposit,2,3,1.0\n",
+        );
+        engine.set_eviction(EvictionPolicy {
+            idle_for: Duration::ZERO,
+            store: Box::new(MemoryStore::default()),
+        });
+        assert_eq!(engine.evict_idle().unwrap(), 2);
+
+        reverse_deposit(&mut engine, ClientId(1), TxId(2)).unwrap();
+        engine.evict_idle().unwrap();
+        force_resolve(&mut engine, ClientId(1), TxId(1)).unwrap();
+        engine.evict_idle().unwrap();
+        set_capability(&mut engine, ClientId(1), Capability::Withdraw, false).unwrap();
+        engine.evict_idle().unwrap();
+        merge_clients(&mut engine, ClientId(2), ClientId(1)).unwrap();
+
+        let client = engine.client(ClientId(1)).unwrap();
+        assert_eq!((client.available, client.total), (dec!(6), dec!(6)));
+        assert_eq!(
+            (client.lifetime_deposits(), client.deposit_count()),
+            (dec!(6), 2)
+        );
+        assert!(!client.capabilities().can_withdraw);
+    }
+
+    #[test]
+    fn merged_disputes_still_expire() {
+        let mut engine = Engine::with_config(EngineConfig {
+            dispute_expiry: Some(DisputeExpiry {
+                deadline: DisputeDeadline::Sequence(2),
+                outcome: ExpiryOutcome::Chargeback,
+            }),
+            ..Default::default()
+        });
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,1.0\ndispute,2,2,\n",
+            ))
+            .unwrap();
+
+        merge_clients(&mut engine, ClientId(2), ClientId(1)).unwrap();
+        for tx in 3..6 {
+            engine
+                .push(
+                    TransactionType::Deposit,
+                    ClientId(3),
+                    TxId(tx),
+                    Some(dec!(1)),
+                )
+                .unwrap();
+        }
+
+        let client = engine.client(ClientId(1)).unwrap();
+        assert_eq!((client.held.value(), client.total), (dec!(0), dec!(5)));
+        assert!(client.locked);
+    }
+
+    #[test]
+    fn forget_client_drops_its_review_queue_entries() {
+        let mut engine =
+            engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,1.0\n");
+        engine
+            .add_review_note(ClientId(1), "asked to be forgotten")
+            .unwrap();
+        engine.add_review_note(ClientId(2), "unrelated").unwrap();
+
+        forget_client(&mut engine, ClientId(1)).unwrap();
+
+        let queued: Vec<ClientId> = engine
+            .review_queue()
+            .iter()
+            .map(|item| item.client)
+            .collect();
+        assert_eq!(queued, [ClientId(2)]);
+    }
+
+    #[test]
+    fn merge_clients_keeps_the_source_changed_and_drops_its_history() {
+        let mut engine = Engine::new();
+        engine.enable_balance_history();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,1.0\n".as_bytes(),
+            ))
+            .unwrap();
+        let mut engine = Engine::from_snapshot(engine.snapshot().unwrap());
+
+        merge_clients(&mut engine, ClientId(2), ClientId(1)).unwrap();
+
+        let mut changed: Vec<ClientId> = engine.changed_clients().collect();
+        changed.sort_unstable();
+        assert_eq!(changed, [ClientId(1), ClientId(2)]);
+        let history = engine.balance_history().unwrap();
+        let latest = PointInTime::Seq(history.last_seq());
+        assert_eq!(history.balance_at(ClientId(2), latest), None);
+        assert_eq!(
+            history
+                .balance_at(ClientId(1), latest)
+                .map(|point| point.total),
+            Some(dec!(6))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use rust_decimal::Decimal;
+use std::{collections::HashSet, fmt};
+
+use crate::client::Client;
+use crate::ids::ClientId;
+use crate::numeric::Balance;
+
+/// Limits that raise an [`Alert`] while rows are being processed. Each alert
+/// fires when its limit is crossed, not again for every row beyond it; it
+/// re-arms once the value is back on the safe side.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AlertThresholds {
+    /// Alert when a client's available balance drops below this.
+    pub min_available: Option<Decimal>,
+    /// Alert when a client's held balance rises above this.
+    pub max_held: Option<Decimal>,
+    /// Alert when the total of all locked accounts rises above this.
+    pub max_locked_total: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alert {
+    LowAvailable {
+        client: ClientId,
+        available: Decimal,
+        threshold: Decimal,
+    },
+    HighHeld {
+        client: ClientId,
+        held: Decimal,
+        threshold: Decimal,
+    },
+    LockedTotalExceeded {
+        locked_total: Decimal,
+        threshold: Decimal,
+    },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::LowAvailable {
+                client,
+                available,
+                threshold,
+            } => write!(
+                f,
+                "Client {client}: available {available} dropped below {threshold}"
+            ),
+            Alert::HighHeld {
+                client,
+                held,
+                threshold,
+            } => write!(f, "Client {client}: held {held} rose above {threshold}"),
+            Alert::LockedTotalExceeded {
+                locked_total,
+                threshold,
+            } => write!(
+                f,
+                "Locked accounts now total {locked_total}, above {threshold}"
+            ),
+        }
+    }
+}
+
+type AlertSink = Box<dyn Fn(&Alert) + Send + Sync>;
+
+/// Watches client balances against [`AlertThresholds`] and hands every alert
+/// to a sink: a logger, a metrics counter, or the sending half of a channel.
+pub(crate) struct AlertMonitor {
+    thresholds: AlertThresholds,
+    sink: AlertSink,
+    low_available: HashSet<ClientId>,
+    high_held: HashSet<ClientId>,
+    locked_total: Decimal,
+    locked_alerted: bool,
+}
+
+impl AlertMonitor {
+    pub(crate) fn new<'a, B: Balance>(
+        thresholds: AlertThresholds,
+        sink: AlertSink,
+        clients: impl Iterator<Item = &'a Client<B>>,
+    ) -> Self {
+        let mut monitor = AlertMonitor {
+            thresholds,
+            sink,
+            low_available: HashSet::new(),
+            high_held: HashSet::new(),
+            locked_total: Decimal::ZERO,
+            locked_alerted: false,
+        };
+        for client in clients {
+            monitor.observe(None, client);
+        }
+        monitor
+    }
+
+    /// Re-checks `client` after a change; `locked_before` is the total it
+    /// contributed to the locked sum before the change, if any.
+    pub(crate) fn observe<B: Balance>(
+        &mut self,
+        locked_before: Option<Decimal>,
+        client: &Client<B>,
+    ) {
+        let thresholds = &self.thresholds;
+        if let Some(threshold) = thresholds.min_available {
+            let available = client.available.to_decimal();
+            let low = available < threshold;
+            if low && self.low_available.insert(client.id) {
+                (self.sink)(&Alert::LowAvailable {
+                    client: client.id,
+                    available,
+                    threshold,
+                });
+            } else if !low {
+                self.low_available.remove(&client.id);
+            }
+        }
+        if let Some(threshold) = thresholds.max_held {
+            let held = Decimal::from(client.held);
+            let high = held > threshold;
+            if high && self.high_held.insert(client.id) {
+                (self.sink)(&Alert::HighHeld {
+                    client: client.id,
+                    held,
+                    threshold,
+                });
+            } else if !high {
+                self.high_held.remove(&client.id);
+            }
+        }
+        self.locked_total -= locked_before.unwrap_or_default();
+        self.locked_total += locked_contribution(client).unwrap_or_default();
+        if let Some(threshold) = thresholds.max_locked_total {
+            let exceeded = self.locked_total > threshold;
+            if exceeded && !self.locked_alerted {
+                (self.sink)(&Alert::LockedTotalExceeded {
+                    locked_total: self.locked_total,
+                    threshold,
+                });
+            }
+            self.locked_alerted = exceeded;
+        }
+    }
+}
+
+/// What `client` adds to the locked total.
+pub(crate) fn locked_contribution<B: Balance>(client: &Client<B>) -> Option<Decimal> {
+    client.locked.then(|| client.total.to_decimal())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use crate::money::Money;
+    use rust_decimal::dec;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn alerts_fire_once_per_crossing() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&alerts);
+        let mut monitor = AlertMonitor::new(
+            AlertThresholds {
+                min_available: Some(dec!(10)),
+                ..AlertThresholds::default()
+            },
+            Box::new(move |alert| sink.lock().unwrap().push(alert.clone())),
+            std::iter::empty::<&Client>(),
+        );
+        let mut client = Client::new(ClientId(1));
+        client
+            .deposit(TxId(1), Money::new(dec!(20)).unwrap())
+            .unwrap();
+        monitor.observe(None, &client);
+        for _ in 0..2 {
+            client.withdraw(Money::new(dec!(6)).unwrap()).unwrap();
+            monitor.observe(None, &client);
+        }
+        client
+            .deposit(TxId(2), Money::new(dec!(20)).unwrap())
+            .unwrap();
+        monitor.observe(None, &client);
+        client.withdraw(Money::new(dec!(25)).unwrap()).unwrap();
+        monitor.observe(None, &client);
+
+        let alerts = alerts.lock().unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(
+            alerts[0].to_string(),
+            "Client 1: available 8 dropped below 10"
+        );
+    }
+}
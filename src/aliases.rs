@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::{collections::HashMap, io::Read};
+
+use crate::errors::EngineError;
+use crate::ids::ClientId;
+
+pub const ALIAS_HEADER: [&str; 2] = ["external_id", "client"];
+
+#[derive(Deserialize)]
+struct AliasRow {
+    external_id: String,
+    client: ClientId,
+}
+
+/// External customer ids (UUIDs and the like) mapped one to one onto client
+/// ids, so integrators can feed and read the engine in their own ids. With
+/// `EngineConfig::client_aliases` set, the `client` column of CSV input may
+/// hold either form, and `EngineConfig::output_external_ids` keys account
+/// output by external id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientAliases {
+    clients: HashMap<String, ClientId>,
+    external_ids: HashMap<ClientId, String>,
+}
+
+impl ClientAliases {
+    /// Reads a CSV mapping with an `external_id,client` header.
+    pub fn load<R: Read>(reader: R) -> Result<Self, EngineError> {
+        let mut aliases = ClientAliases::default();
+        for row in csv::Reader::from_reader(reader).deserialize::<AliasRow>() {
+            let row = row?;
+            aliases.insert(row.external_id, row.client)?;
+        }
+        Ok(aliases)
+    }
+
+    /// Fails if either id is already mapped, since output keyed by external
+    /// id could not be read back otherwise.
+    pub fn insert(
+        &mut self,
+        external_id: impl Into<String>,
+        client: ClientId,
+    ) -> Result<(), EngineError> {
+        let external_id = external_id.into().trim().to_string();
+        if external_id.is_empty() {
+            return Err(EngineError::ClientAlias(format!(
+                "client {client} has an empty external id"
+            )));
+        }
+        if let Some(existing) = self.clients.get(&external_id) {
+            return Err(EngineError::ClientAlias(format!(
+                "{external_id} maps to both client {existing} and client {client}"
+            )));
+        }
+        if let Some(existing) = self.external_ids.get(&client) {
+            return Err(EngineError::ClientAlias(format!(
+                "client {client} has two external ids, {existing} and {external_id}"
+            )));
+        }
+        self.clients.insert(external_id.clone(), client);
+        self.external_ids.insert(client, external_id);
+        Ok(())
+    }
+
+    pub fn client(&self, external_id: &str) -> Option<ClientId> {
+        self.clients.get(external_id).copied()
+    }
+
+    pub fn external_id(&self, client: ClientId) -> Option<&str> {
+        self.external_ids.get(&client).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The client id a `client` input field stands for: a mapped external
+    /// id, or else a plain numeric id, which passes through unchanged.
+    pub(crate) fn resolve<'f>(&self, field: &'f str) -> Result<Option<ClientId>, &'f str> {
+        let field = field.trim();
+        match self.client(field) {
+            Some(client) => Ok(Some(client)),
+            None if field.parse::<ClientId>().is_ok() => Ok(None),
+            None => Err(field),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappings_load_and_must_be_one_to_one() {
+        let aliases = ClientAliases::load(
+            "external_id,client\n8f14e45f-ceea-467f-a0e6-2f1c1b7c9d0e,7\ncus_42,42\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(aliases.client("cus_42"), Some(ClientId(42)));
+        assert_eq!(
+            aliases.external_id(ClientId(7)),
+            Some("8f14e45f-ceea-467f-a0e6-2f1c1b7c9d0e")
+        );
+        assert_eq!(aliases.resolve("cus_42"), Ok(Some(ClientId(42))));
+        assert_eq!(aliases.resolve("9"), Ok(None));
+        assert_eq!(aliases.resolve("cus_9"), Err("cus_9"));
+
+        for duplicated in [
+            "external_id,client\na,1\na,2\n",
+            "external_id,client\na,1\nb,1\n",
+        ] {
+            assert!(matches!(
+                ClientAliases::load(duplicated.as_bytes()),
+                Err(EngineError::ClientAlias(_))
+            ));
+        }
+    }
+}
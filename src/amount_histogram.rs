@@ -0,0 +1,202 @@
+use rust_decimal::{Decimal, dec};
+use serde::Serialize;
+use std::{collections::BTreeMap, io::Write, str::FromStr};
+
+pub use crate::segments::{ALL_CLIENTS, ClientSegments};
+use crate::{errors::EngineError, format::Format, ids::ClientId, transaction::TransactionType};
+
+pub const HISTOGRAM_HEADER: [&str; 5] = ["segment", "type", "above", "up_to", "count"];
+
+/// Upper bounds of amount buckets, strictly increasing. An amount falls in
+/// the first bucket whose bound it does not exceed, or in a last, open
+/// bucket above every bound.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmountBuckets(Vec<Decimal>);
+
+impl AmountBuckets {
+    pub fn new(bounds: Vec<Decimal>) -> Result<Self, String> {
+        if bounds.is_empty() {
+            return Err("at least one bucket bound is needed".to_string());
+        }
+        if let Some(pair) = bounds.windows(2).find(|pair| pair[0] >= pair[1]) {
+            return Err(format!(
+                "bucket bounds must increase, but {} comes after {}",
+                pair[1], pair[0]
+            ));
+        }
+        Ok(AmountBuckets(bounds))
+    }
+
+    pub fn bounds(&self) -> &[Decimal] {
+        &self.0
+    }
+
+    fn index(&self, amount: Decimal) -> usize {
+        self.0.partition_point(|&bound| bound < amount)
+    }
+}
+
+/// Bounds around the usual cash reporting threshold, so deposits kept just
+/// under it stand out.
+impl Default for AmountBuckets {
+    fn default() -> Self {
+        AmountBuckets(vec![
+            dec!(100),
+            dec!(1000),
+            dec!(5000),
+            dec!(9000),
+            dec!(10000),
+        ])
+    }
+}
+
+/// Comma-separated bounds in plain notation, such as `100,1000,10000`.
+impl FromStr for AmountBuckets {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let bounds = value
+            .split(',')
+            .map(|bound| {
+                Decimal::from_str(bound.trim()).map_err(|_| format!("invalid bucket bound {bound}"))
+            })
+            .collect::<Result<_, _>>()?;
+        AmountBuckets::new(bounds)
+    }
+}
+
+/// One bucket of [`AmountHistogram`] output. The first bucket has no lower
+/// bound and the last no upper one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HistogramRow<'h> {
+    pub segment: &'h str,
+    #[serde(rename = "type")]
+    pub tx_type: &'static str,
+    pub above: Option<Decimal>,
+    pub up_to: Option<Decimal>,
+    pub count: u64,
+}
+
+/// Deposit and withdrawal amounts counted into buckets, over the whole run
+/// and per client segment, as input for structuring detection. Only applied
+/// transactions are counted. Enable with `Engine::enable_amount_histogram`.
+#[derive(Clone, Debug, Default)]
+pub struct AmountHistogram {
+    buckets: AmountBuckets,
+    segments: ClientSegments,
+    counts: BTreeMap<(String, &'static str), Vec<u64>>,
+}
+
+impl AmountHistogram {
+    pub fn new(buckets: AmountBuckets, segments: ClientSegments) -> Self {
+        AmountHistogram {
+            buckets,
+            segments,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, client: ClientId, tx_type: TransactionType, amount: Decimal) {
+        let tx_type = match tx_type {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            _ => return,
+        };
+        let bucket = self.buckets.index(amount);
+        let size = self.buckets.bounds().len() + 1;
+        for segment in [ALL_CLIENTS]
+            .into_iter()
+            .chain(self.segments.segments(client))
+        {
+            self.counts
+                .entry((segment.to_string(), tx_type))
+                .or_insert_with(|| vec![0; size])[bucket] += 1;
+        }
+    }
+
+    /// Every bucket of every segment and type seen, empty buckets included,
+    /// ordered by segment, type and amount.
+    pub fn rows(&self) -> impl Iterator<Item = HistogramRow<'_>> {
+        let bounds = self.buckets.bounds();
+        self.counts
+            .iter()
+            .flat_map(move |((segment, tx_type), counts)| {
+                counts
+                    .iter()
+                    .enumerate()
+                    .map(move |(bucket, &count)| HistogramRow {
+                        segment,
+                        tx_type,
+                        above: bucket.checked_sub(1).map(|below| bounds[below]),
+                        up_to: bounds.get(bucket).copied(),
+                        count,
+                    })
+            })
+    }
+
+    /// Writes [`AmountHistogram::rows`] as CSV with [`HISTOGRAM_HEADER`], or
+    /// as one JSON object per line.
+    pub fn write<W: Write>(&self, mut writer: W, format: Format) -> Result<(), EngineError> {
+        match format {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record(HISTOGRAM_HEADER)?;
+                for row in self.rows() {
+                    let bound = |bound: Option<Decimal>| bound.map(|b| b.to_string());
+                    writer.write_record([
+                        row.segment,
+                        row.tx_type,
+                        bound(row.above).as_deref().unwrap_or(""),
+                        bound(row.up_to).as_deref().unwrap_or(""),
+                        &row.count.to_string(),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+            Format::Json => {
+                for row in self.rows() {
+                    serde_json::to_writer(&mut writer, &row)?;
+                    writer.write_all(b"\n")?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_counted_per_run_and_per_segment() {
+        let mut segments = ClientSegments::default();
+        segments.insert(ClientId(2), "business");
+        let mut histogram = AmountHistogram::new("100,1000".parse().unwrap(), segments);
+        histogram.record(ClientId(1), TransactionType::Deposit, dec!(100));
+        histogram.record(ClientId(2), TransactionType::Deposit, dec!(999.99));
+        histogram.record(ClientId(2), TransactionType::Withdrawal, dec!(5000));
+        histogram.record(ClientId(2), TransactionType::Dispute, dec!(1));
+
+        let mut csv = Vec::new();
+        histogram.write(&mut csv, Format::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "segment,type,above,up_to,count\n\
+             all,deposit,,100,1\n\
+             all,deposit,100,1000,1\n\
+             all,deposit,1000,,0\n\
+             all,withdrawal,,100,0\n\
+             all,withdrawal,100,1000,0\n\
+             all,withdrawal,1000,,1\n\
+             business,deposit,,100,0\n\
+             business,deposit,100,1000,1\n\
+             business,deposit,1000,,0\n\
+             business,withdrawal,,100,0\n\
+             business,withdrawal,100,1000,0\n\
+             business,withdrawal,1000,,1\n"
+        );
+        assert!("100,100".parse::<AmountBuckets>().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Write};
+
+use crate::{
+    client::Client,
+    errors::EngineError,
+    formatting::format_decimal,
+    ids::{ClientId, TxId},
+    money::Money,
+    numeric::Balance,
+    redaction::Redaction,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ReverseDeposit,
+    ForceResolve,
+    ResolveAll,
+    RuleFlagged,
+    RuleDenied,
+    ManualCredit,
+    ManualDebit,
+    MergeClients,
+    ExpiredResolve,
+    ExpiredChargeback,
+    RepairTotal,
+    Quarantine,
+    SetCapability,
+    ForgetClient,
+    ZeroAmountIgnored,
+    BalanceLimitExceeded,
+    NegativeBalanceReview,
+    AccruedInterest,
+    AccruedPenalty,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::ReverseDeposit => "reverse_deposit",
+            AuditAction::ForceResolve => "force_resolve",
+            AuditAction::ResolveAll => "resolve_all",
+            AuditAction::RuleFlagged => "rule_flagged",
+            AuditAction::RuleDenied => "rule_denied",
+            AuditAction::ManualCredit => "manual_credit",
+            AuditAction::ManualDebit => "manual_debit",
+            AuditAction::MergeClients => "merge_clients",
+            AuditAction::ExpiredResolve => "expired_resolve",
+            AuditAction::ExpiredChargeback => "expired_chargeback",
+            AuditAction::RepairTotal => "repair_total",
+            AuditAction::Quarantine => "quarantine",
+            AuditAction::SetCapability => "set_capability",
+            AuditAction::ForgetClient => "forget_client",
+            AuditAction::ZeroAmountIgnored => "zero_amount_ignored",
+            AuditAction::BalanceLimitExceeded => "balance_limit_exceeded",
+            AuditAction::NegativeBalanceReview => "negative_balance_review",
+            AuditAction::AccruedInterest => "accrued_interest",
+            AuditAction::AccruedPenalty => "accrued_penalty",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single operator-visible change to an account, recorded with the balances
+/// the account was left with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub action: AuditAction,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub amount: Option<Decimal>,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// Sequence number of the accepted transaction this entry belongs to;
+    /// see `Engine::last_sequence`. `None` for rejected rows and operator
+    /// actions.
+    #[serde(default)]
+    pub sequence: Option<u64>,
+}
+
+impl AuditEntry {
+    pub fn new<B: Balance>(
+        action: AuditAction,
+        client: &Client<B>,
+        tx: TxId,
+        amount: Option<Money<B>>,
+    ) -> Self {
+        AuditEntry {
+            action,
+            client: client.id,
+            tx,
+            amount: amount.map(Decimal::from),
+            available: client.available.to_decimal(),
+            held: client.held.into(),
+            total: client.total.to_decimal(),
+            locked: client.locked,
+            reason: None,
+            reference: None,
+            sequence: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_reference(mut self, reference: Option<String>) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: Option<u64>) -> Self {
+        self.sequence = sequence;
+        self
+    }
+}
+
+pub const AUDIT_HEADER: [&str; 11] = [
+    "action",
+    "client",
+    "tx",
+    "amount",
+    "available",
+    "held",
+    "total",
+    "locked",
+    "reason",
+    "reference",
+    "sequence",
+];
+
+pub fn write_audit_entries<W: Write>(
+    entries: &[AuditEntry],
+    writer: W,
+    include_header: bool,
+) -> Result<(), EngineError> {
+    write_entries(entries, None, writer, include_header)
+}
+
+/// Like `write_audit_entries`, with client ids hashed, amounts disguised,
+/// ids and amounts masked in reasons, and partner references left out.
+pub fn write_redacted_audit_entries<W: Write>(
+    entries: &[AuditEntry],
+    redaction: &Redaction,
+    writer: W,
+    include_header: bool,
+) -> Result<(), EngineError> {
+    write_entries(entries, Some(redaction), writer, include_header)
+}
+
+fn write_entries<W: Write>(
+    entries: &[AuditEntry],
+    redaction: Option<&Redaction>,
+    writer: W,
+    include_header: bool,
+) -> Result<(), EngineError> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    if include_header {
+        csv_writer.write_record(AUDIT_HEADER)?;
+    }
+
+    let amount = |value: Decimal| match redaction {
+        Some(redaction) => redaction.amount(value),
+        None => format_decimal(value),
+    };
+    for entry in entries {
+        let (client, reason, reference) = match redaction {
+            Some(redaction) => (
+                redaction.client(entry.client),
+                entry.reason.as_deref().map(|reason| redaction.text(reason)),
+                None,
+            ),
+            None => (
+                entry.client.to_string(),
+                entry.reason.clone(),
+                entry.reference.clone(),
+            ),
+        };
+        csv_writer.write_record(&[
+            entry.action.to_string(),
+            client,
+            entry.tx.to_string(),
+            entry.amount.map(amount).unwrap_or_default(),
+            amount(entry.available),
+            amount(entry.held),
+            amount(entry.total),
+            entry.locked.to_string(),
+            reason.unwrap_or_default(),
+            reference.unwrap_or_default(),
+            entry
+                .sequence
+                .map(|seq| seq.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
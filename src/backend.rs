@@ -0,0 +1,161 @@
+use rust_decimal::Decimal;
+use std::io::{Read, Write};
+
+use crate::{
+    Engine, RowCounts,
+    config::EngineConfig,
+    engine::{Rejection, ingest::read_input},
+    errors::{ClientTransactionError, EngineError},
+    ids::{ClientId, TxId},
+    snapshot::Snapshot,
+    transaction::TransactionType,
+};
+
+/// The accounting side of the engine, separated from input parsing so a
+/// downstream crate can keep balances elsewhere (a database, a distributed
+/// ledger) and still use this crate's readers through [`process_into`].
+/// [`Engine`] is the in-memory implementation.
+pub trait PaymentsEngine {
+    /// Applies one transaction, returning why it was rejected, if it was.
+    /// Errors are reserved for failures of the backend itself.
+    fn push(
+        &mut self,
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+    ) -> Result<Option<Rejection>, EngineError>;
+
+    /// Called once every transaction has been pushed, to write the final
+    /// account states.
+    fn finalize(&mut self, writer: &mut dyn Write) -> Result<(), EngineError>;
+
+    fn snapshot(&self) -> Result<Snapshot, EngineError>;
+}
+
+impl PaymentsEngine for Engine {
+    fn push(
+        &mut self,
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+    ) -> Result<Option<Rejection>, EngineError> {
+        Engine::push(self, tx_type, client, tx, amount)
+    }
+
+    fn finalize(&mut self, writer: &mut dyn Write) -> Result<(), EngineError> {
+        self.write_accounts(writer).map(drop)
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, EngineError> {
+        Engine::snapshot(self)
+    }
+}
+
+/// Reads `source` as `config` describes it and pushes every parsed row to
+/// `backend`. Rows that cannot be parsed, or whose transaction id does not
+/// fit a `u32`, are counted as rejected without reaching the backend.
+///
+/// Only the input settings of `config` apply here; the rest are up to the
+/// backend.
+pub fn process_into<B: PaymentsEngine + ?Sized, R: Read>(
+    backend: &mut B,
+    source: R,
+    config: &EngineConfig,
+) -> Result<RowCounts, EngineError> {
+    let mut rows = RowCounts::default();
+    let (_, transactions) = read_input(source, config, &[], false)?;
+    for transaction in transactions {
+        let Ok(transaction) = transaction else {
+            rows.record(true);
+            continue;
+        };
+        let Ok(tx) = u32::try_from(transaction.tx).map(TxId) else {
+            let err = ClientTransactionError::InvalidTransactionId {
+                client_id: transaction.client,
+                tx: transaction.tx,
+            };
+            config.rejection_logging.log(
+                err.name(),
+                format_args!("Error parsing CSV row {}: {err}", transaction.row),
+            );
+            rows.record(true);
+            continue;
+        };
+        let rejection = backend.push(
+            transaction.tx_type,
+            transaction.client,
+            tx,
+            transaction.amount,
+        )?;
+        rows.record(rejection.is_some());
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A backend that only counts what reaches it.
+    #[derive(Default)]
+    struct Counting {
+        pushed: Vec<(TransactionType, ClientId, TxId)>,
+        finalized: bool,
+    }
+
+    impl PaymentsEngine for Counting {
+        fn push(
+            &mut self,
+            tx_type: TransactionType,
+            client: ClientId,
+            tx: TxId,
+            _amount: Option<Decimal>,
+        ) -> Result<Option<Rejection>, EngineError> {
+            self.pushed.push((tx_type, client, tx));
+            Ok(None)
+        }
+
+        fn finalize(&mut self, _writer: &mut dyn Write) -> Result<(), EngineError> {
+            self.finalized = true;
+            Ok(())
+        }
+
+        fn snapshot(&self) -> Result<Snapshot, EngineError> {
+            Engine::new().snapshot()
+        }
+    }
+
+    const INPUT: &str =
+        "type,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,-2,1.0\nbogus\ndispute,1,1,\n";
+
+    #[test]
+    fn parsed_rows_reach_any_backend() {
+        let mut backend = Counting::default();
+        let rows = process_into(&mut backend, INPUT.as_bytes(), &EngineConfig::default()).unwrap();
+        assert_eq!((rows.read, rows.rejected), (4, 2));
+        assert_eq!(
+            backend.pushed,
+            [
+                (TransactionType::Deposit, ClientId(1), TxId(1)),
+                (TransactionType::Dispute, ClientId(1), TxId(1))
+            ]
+        );
+        backend.finalize(&mut Vec::new()).unwrap();
+        assert!(backend.finalized);
+
+        let mut engine = Engine::new();
+        let backend: &mut dyn PaymentsEngine = &mut engine;
+        process_into(backend, INPUT.as_bytes(), &EngineConfig::default()).unwrap();
+        let mut via_trait = Vec::new();
+        backend.finalize(&mut via_trait).unwrap();
+
+        let mut direct = Vec::new();
+        let mut engine = Engine::new();
+        engine.process(Cursor::new(INPUT)).unwrap();
+        engine.write_accounts(&mut direct).unwrap();
+        assert_eq!(via_trait, direct);
+    }
+}
@@ -0,0 +1,79 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::HashMap, io::Read};
+
+use crate::errors::EngineError;
+use crate::ids::ClientId;
+
+/// `client` value of the row that sets the limit of unlisted clients.
+pub const DEFAULT_LIMIT_CLIENT: &str = "*";
+
+#[derive(Deserialize)]
+struct LimitRow {
+    client: String,
+    limit: Decimal,
+}
+
+/// Ceilings on an account's total balance, such as the stored-value limits
+/// of e-money regulation. A deposit that would take a client's total above
+/// its limit is rejected with `ClientTransactionError::BalanceLimitExceeded`
+/// and audited as `AuditAction::BalanceLimitExceeded`. Set through
+/// `EngineConfig::balance_limits`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BalanceLimits {
+    default: Option<Decimal>,
+    clients: HashMap<ClientId, Decimal>,
+}
+
+impl BalanceLimits {
+    /// Limits for clients without one of their own.
+    pub fn with_default(limit: Decimal) -> Self {
+        BalanceLimits {
+            default: Some(limit),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Reads a CSV with a `client,limit` header. A client of
+    /// [`DEFAULT_LIMIT_CLIENT`] sets the limit of every unlisted client.
+    pub fn load<R: Read>(reader: R) -> Result<Self, EngineError> {
+        let mut limits = BalanceLimits::default();
+        for row in csv::Reader::from_reader(reader).deserialize::<LimitRow>() {
+            let row = row?;
+            let client = row.client.trim();
+            if client == DEFAULT_LIMIT_CLIENT {
+                limits.default = Some(row.limit);
+                continue;
+            }
+            let client = client.parse().map_err(|_| {
+                EngineError::Usage(format!("balance limits: invalid client {client}"))
+            })?;
+            limits.insert(client, row.limit);
+        }
+        Ok(limits)
+    }
+
+    pub fn insert(&mut self, client: ClientId, limit: Decimal) {
+        self.clients.insert(client, limit);
+    }
+
+    /// `client`'s ceiling, if it has one.
+    pub fn limit(&self, client: ClientId) -> Option<Decimal> {
+        self.clients.get(&client).copied().or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    #[test]
+    fn listed_clients_override_the_default() {
+        let limits = BalanceLimits::load("client,limit\n*,150\n7,1000\n".as_bytes()).unwrap();
+        assert_eq!(limits.limit(ClientId(7)), Some(dec!(1000)));
+        assert_eq!(limits.limit(ClientId(8)), Some(dec!(150)));
+        assert_eq!(BalanceLimits::default().limit(ClientId(8)), None);
+        assert!(BalanceLimits::load("client,limit\nseven,1\n".as_bytes()).is_err());
+    }
+}
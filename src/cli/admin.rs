@@ -0,0 +1,68 @@
+use rust_payments_engine::Engine;
+use rust_payments_engine::admin;
+use rust_payments_engine::errors::EngineError;
+
+use super::{Args, load_snapshot, save_snapshot, write_audit_trail};
+
+const USAGE: &str = "Usage: cargo run -- admin <reverse-deposit|force-resolve> --snapshot <state.json> --client <id> --tx <id> [--audit <audit.csv>]\n       cargo run -- admin resolve-all --snapshot <state.json> --client <id> [--audit <audit.csv>]\n       cargo run -- admin forget --snapshot <state.json> --client <id> [--audit <audit.csv>]\n       cargo run -- admin merge --snapshot <state.json> --from <id> --into <id> [--audit <audit.csv>]\n       cargo run -- admin set-capability --snapshot <state.json> --client <id> --capability <can_withdraw|can_deposit|dispute_allowed> --allowed <true|false> [--audit <audit.csv>]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &[
+            "--snapshot",
+            "--client",
+            "--tx",
+            "--from",
+            "--into",
+            "--capability",
+            "--allowed",
+            "--audit",
+        ],
+        &[],
+        USAGE,
+    )?;
+    let [action] = args.positional() else {
+        return Err(args.usage_error());
+    };
+
+    let snapshot_path = args.required("--snapshot")?;
+    let snapshot = load_snapshot(snapshot_path)?;
+    let mut engine = Engine::from_snapshot(snapshot);
+
+    let entries = match action.as_str() {
+        "reverse-deposit" => vec![admin::reverse_deposit(
+            &mut engine,
+            args.parse_required("--client")?,
+            args.parse_required("--tx")?,
+        )?],
+        "force-resolve" => vec![admin::force_resolve(
+            &mut engine,
+            args.parse_required("--client")?,
+            args.parse_required("--tx")?,
+        )?],
+        "resolve-all" => admin::resolve_all(&mut engine, args.parse_required("--client")?)?,
+        "forget" => vec![admin::forget_client(
+            &mut engine,
+            args.parse_required("--client")?,
+        )?],
+        "merge" => vec![admin::merge_clients(
+            &mut engine,
+            args.parse_required("--from")?,
+            args.parse_required("--into")?,
+        )?],
+        "set-capability" => vec![admin::set_capability(
+            &mut engine,
+            args.parse_required("--client")?,
+            args.option("--capability")
+                .ok_or_else(|| args.usage_error())?
+                .parse()
+                .map_err(EngineError::Usage)?,
+            args.parse_required("--allowed")?,
+        )?],
+        _ => return Err(args.usage_error()),
+    };
+
+    save_snapshot(&engine.snapshot()?, snapshot_path)?;
+    write_audit_trail(args.option("--audit"), &entries, None)
+}
@@ -0,0 +1,45 @@
+use std::io::BufWriter;
+use std::time::{Duration, UNIX_EPOCH};
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::history::{PointInTime, write_balance_point};
+use rust_payments_engine::ids::ClientId;
+
+use super::{Args, load_snapshot};
+
+const USAGE: &str = "Usage: cargo run -- balance-at --snapshot <state.json> --client <id> (--seq <n> | --at <unix-seconds>)";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--snapshot", "--client", "--seq", "--at"],
+        &[],
+        USAGE,
+    )?;
+    if !args.positional().is_empty() {
+        return Err(args.usage_error());
+    }
+    let client: ClientId = args.parse_required("--client")?;
+    let point = match (args.parse_option("--seq")?, args.parse_option("--at")?) {
+        (Some(seq), None) => PointInTime::Seq(seq),
+        (None, Some(seconds)) => PointInTime::At(UNIX_EPOCH + Duration::from_secs(seconds)),
+        _ => return Err(args.usage_error()),
+    };
+
+    let snapshot = load_snapshot(args.required("--snapshot")?)?;
+    let engine = Engine::from_snapshot(snapshot);
+    if engine.balance_history().is_none() {
+        return Err(EngineError::Usage(
+            "the snapshot has no balance history; record it with --balance-history".to_string(),
+        ));
+    }
+    let Some(balance) = engine.balance_at(client, point) else {
+        return Err(EngineError::Usage(format!(
+            "client {client} has no balance at that point"
+        )));
+    };
+
+    let stdout = std::io::stdout();
+    write_balance_point(client, balance, BufWriter::new(stdout.lock()))
+}
@@ -0,0 +1,35 @@
+use log::info;
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::history::Retention;
+
+use super::{Args, load_snapshot, save_snapshot};
+
+const USAGE: &str = "Usage: cargo run -- compact --snapshot <state.json> --retain <days>d|<seconds>s [--save-snapshot <state.json>]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--snapshot", "--retain", "--save-snapshot"],
+        &[],
+        USAGE,
+    )?;
+    if !args.positional().is_empty() {
+        return Err(args.usage_error());
+    }
+    let retention: Retention = args
+        .required("--retain")?
+        .parse()
+        .map_err(EngineError::Usage)?;
+
+    let snapshot_path = args.required("--snapshot")?;
+    let mut engine = Engine::from_snapshot(load_snapshot(snapshot_path)?);
+    let dropped = engine.compact(retention);
+    save_snapshot(
+        &engine.snapshot()?,
+        args.option("--save-snapshot").unwrap_or(snapshot_path),
+    )?;
+    info!("Dropped {dropped} balance history point(s) older than the retention");
+    Ok(())
+}
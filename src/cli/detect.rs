@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::detect::detect_input;
+use rust_payments_engine::errors::EngineError;
+
+use super::Args;
+
+const USAGE: &str = "Usage: cargo run -- detect [--no-header] [--strict-columns] [--input-encoding <label>] <input>...";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--input-encoding"],
+        &["--no-header", "--strict-columns"],
+        USAGE,
+    )?;
+    let inputs = args.positional();
+    if inputs.is_empty() {
+        return Err(args.usage_error());
+    }
+    let config = EngineConfig {
+        has_headers: !args.flag("--no-header"),
+        strict_columns: args.flag("--strict-columns"),
+        input_encoding: args
+            .option("--input-encoding")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default(),
+        ..EngineConfig::default()
+    };
+
+    let mut failed = 0;
+    for input in inputs {
+        match File::open(input)
+            .map_err(EngineError::from)
+            .and_then(|file| detect_input(BufReader::new(file), &config))
+        {
+            Ok(detected) => println!("{input}: {detected}"),
+            Err(err) => {
+                println!("{input}: {err}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(EngineError::UnsupportedInput(format!(
+            "{failed} of {} input(s) could not be read",
+            inputs.len()
+        )));
+    }
+    Ok(())
+}
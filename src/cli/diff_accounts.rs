@@ -0,0 +1,30 @@
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter};
+
+use log::info;
+
+use rust_payments_engine::account_diff::{diff_accounts, read_accounts, write_transactions};
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::ids::TxId;
+
+use super::{Args, open_file};
+
+const USAGE: &str = "Usage: cargo run -- diff-accounts [--before <accounts.csv>] [--first-tx <id>] <after_accounts.csv>";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &["--before", "--first-tx"], &[], USAGE)?;
+    let [after] = args.positional() else {
+        return Err(args.usage_error());
+    };
+    let before = match args.option("--before") {
+        Some(path) => read_accounts(BufReader::new(open_file(path)?))?,
+        None => BTreeMap::new(),
+    };
+    let after = read_accounts(BufReader::new(open_file(after)?))?;
+    let first_tx = args.parse_option("--first-tx")?.unwrap_or(TxId(1));
+
+    let transactions = diff_accounts(&before, &after, first_tx)?;
+    write_transactions(BufWriter::new(std::io::stdout().lock()), &transactions)?;
+    info!("Wrote {} transaction(s)", transactions.len());
+    Ok(())
+}
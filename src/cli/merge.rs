@@ -0,0 +1,38 @@
+use log::info;
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::engine::ClientConflict;
+use rust_payments_engine::errors::EngineError;
+
+use super::{Args, load_snapshot, save_snapshot};
+
+const USAGE: &str = "Usage: cargo run -- merge --save-snapshot <state.json> [--on-shared-client <reject|combine>] <shard.json> <shard.json>...";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &["--save-snapshot", "--on-shared-client"], &[], USAGE)?;
+    let [first, rest @ ..] = args.positional() else {
+        return Err(args.usage_error());
+    };
+    if rest.is_empty() {
+        return Err(args.usage_error());
+    }
+    let output = args.required("--save-snapshot")?;
+    let conflicts: ClientConflict = args
+        .option("--on-shared-client")
+        .map(str::parse)
+        .transpose()
+        .map_err(EngineError::Usage)?
+        .unwrap_or_default();
+
+    let mut engine = Engine::from_snapshot(load_snapshot(first)?);
+    for path in rest {
+        engine.merge(Engine::from_snapshot(load_snapshot(path)?), conflicts)?;
+    }
+    save_snapshot(&engine.snapshot()?, output)?;
+    info!(
+        "Merged {} snapshots with {} client(s)",
+        rest.len() + 1,
+        engine.resident_clients()
+    );
+    Ok(())
+}
@@ -0,0 +1,417 @@
+pub mod admin;
+pub mod balance_at;
+pub mod compact;
+pub mod detect;
+pub mod diff_accounts;
+pub mod merge;
+pub mod repair;
+pub mod report;
+pub mod review_queue;
+pub mod run;
+pub mod sample;
+pub mod settle;
+pub mod statement;
+pub mod stress;
+pub mod verify;
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rust_payments_engine::RowCounts;
+use rust_payments_engine::audit::{AuditEntry, write_audit_entries, write_redacted_audit_entries};
+use rust_payments_engine::digest::sha256_hex;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::redaction::Redaction;
+use rust_payments_engine::rejection_log::RejectionLogging;
+use rust_payments_engine::snapshot::Snapshot;
+#[cfg(feature = "encryption")]
+use rust_payments_engine::snapshot::{ENCRYPTED_MAGIC, SnapshotKey};
+
+/// Environment variable holding the salt for `--redact`, kept off the
+/// command line so it does not show up in shell history or `ps`.
+pub const REDACT_SALT_VAR: &str = "PAYMENTS_REDACT_SALT";
+
+/// Environment variable with per-category rejection log levels, such as
+/// `NotInDispute=silent,*=warn`, for when `--rejection-log` is not given.
+pub const REJECTION_LOG_VAR: &str = "PAYMENTS_REJECTION_LOG";
+
+/// Environment variables holding the snapshot encryption key as 64 hex
+/// digits, or the path of a file holding it. With either set, every snapshot
+/// and checkpoint is written encrypted.
+pub const SNAPSHOT_KEY_VAR: &str = "PAYMENTS_SNAPSHOT_KEY";
+pub const SNAPSHOT_KEY_FILE_VAR: &str = "PAYMENTS_SNAPSHOT_KEY_FILE";
+
+/// How a successful run went, as far as the exit code is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Clean,
+    RowsSkipped,
+    ErrorRateExceeded,
+    QualityBelowThreshold,
+}
+
+impl Outcome {
+    /// `max_error_rate` is the share of rejected rows (0 to 1) above which a
+    /// run counts as failed rather than partially successful.
+    pub fn from_rows(rows: RowCounts, max_error_rate: Option<f64>) -> Self {
+        if max_error_rate.is_some_and(|max| rows.error_rate() > max) {
+            Outcome::ErrorRateExceeded
+        } else if rows.rejected > 0 {
+            Outcome::RowsSkipped
+        } else {
+            Outcome::Clean
+        }
+    }
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Outcome::Clean => 0,
+            Outcome::RowsSkipped => 2,
+            Outcome::ErrorRateExceeded => 3,
+            Outcome::QualityBelowThreshold => 5,
+        }
+    }
+}
+
+/// Exit code for a run that failed outright: 4 when input or output could
+/// not be read or written, 130 when interrupted, 75 when stopped by a run
+/// budget (so a scheduler can run the rest later), 1 otherwise.
+pub fn error_exit_code(err: &EngineError) -> u8 {
+    match err {
+        EngineError::Io(_)
+        | EngineError::File { .. }
+        | EngineError::Csv(_)
+        | EngineError::Json(_) => 4,
+        EngineError::Interrupted { .. } => 130,
+        EngineError::BudgetExhausted { .. } => 75,
+        _ => 1,
+    }
+}
+
+/// Flag every command accepts, making `main` write fatal errors as JSON.
+pub const JSON_ERRORS_FLAG: &str = "--json-errors";
+
+/// Whether `Args::parse` saw [`JSON_ERRORS_FLAG`] in flag position, not as
+/// the value of an option such as `--note`.
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn json_errors() -> bool {
+    JSON_ERRORS.load(Ordering::Relaxed)
+}
+
+pub struct Args {
+    usage: &'static str,
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(
+        args: &[String],
+        options: &[&str],
+        flags: &[&str],
+        usage: &'static str,
+    ) -> Result<Self, EngineError> {
+        let mut parsed = Args {
+            usage,
+            positional: Vec::new(),
+            options: HashMap::new(),
+            flags: Vec::new(),
+        };
+
+        // Reads every argument even past a usage error, so a later
+        // `--json-errors` still applies to reporting it.
+        let mut valid = true;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == JSON_ERRORS_FLAG {
+                JSON_ERRORS.store(true, Ordering::Relaxed);
+            } else if flags.contains(&arg.as_str()) {
+                parsed.flags.push(arg.clone());
+            } else if options.contains(&arg.as_str()) {
+                match iter.next() {
+                    Some(value) => {
+                        parsed.options.insert(arg.clone(), value.clone());
+                    }
+                    None => valid = false,
+                }
+            } else if arg.starts_with("--") {
+                valid = false;
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+
+        if !valid {
+            return Err(parsed.usage_error());
+        }
+        Ok(parsed)
+    }
+
+    /// SHA-256 over every option and flag except `except`, independent of
+    /// their order on the command line.
+    pub fn config_digest(&self, except: &[&str]) -> String {
+        let mut settings: Vec<String> = self
+            .options
+            .iter()
+            .filter(|(name, _)| !except.contains(&name.as_str()))
+            .map(|(name, value)| format!("{name}={value}"))
+            .chain(
+                self.flags
+                    .iter()
+                    .filter(|flag| !except.contains(&flag.as_str()))
+                    .cloned(),
+            )
+            .collect();
+        settings.sort();
+        settings.dedup();
+        sha256_hex(settings.join("\n").as_bytes()).expect("hashing a slice cannot fail")
+    }
+
+    pub fn usage_error(&self) -> EngineError {
+        EngineError::Usage(self.usage.to_string())
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    /// Names of every option and flag given, in no particular order.
+    pub fn given(&self) -> impl Iterator<Item = &str> {
+        self.options.keys().chain(&self.flags).map(String::as_str)
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, EngineError> {
+        self.option(name).ok_or_else(|| self.usage_error())
+    }
+
+    pub fn parse_option<T: FromStr>(&self, name: &str) -> Result<Option<T>, EngineError> {
+        self.option(name)
+            .map(|value| value.parse().map_err(|_| self.usage_error()))
+            .transpose()
+    }
+
+    pub fn parse_required<T: FromStr>(&self, name: &str) -> Result<T, EngineError> {
+        self.parse_option(name)?.ok_or_else(|| self.usage_error())
+    }
+
+    /// Redaction settings from `--redact` and `--redact-amounts`, with the
+    /// salt read from [`REDACT_SALT_VAR`].
+    pub fn redaction(&self) -> Result<Option<Redaction>, EngineError> {
+        if !self.flag("--redact") {
+            return Ok(None);
+        }
+        let salt = env_var(REDACT_SALT_VAR).ok_or_else(|| {
+            EngineError::Usage(format!("--redact needs a salt in {REDACT_SALT_VAR}"))
+        })?;
+        let amounts = self
+            .option("--redact-amounts")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default();
+        Ok(Some(Redaction::new(salt, amounts)))
+    }
+
+    /// Rejection log levels from `--rejection-log`, or else from
+    /// [`REJECTION_LOG_VAR`].
+    pub fn rejection_logging(&self) -> Result<RejectionLogging, EngineError> {
+        let spec = self
+            .option("--rejection-log")
+            .map(str::to_string)
+            .or_else(|| env_var(REJECTION_LOG_VAR));
+        spec.map(|spec| spec.parse())
+            .transpose()
+            .map_err(|err| EngineError::Usage(format!("--rejection-log: {err}")))
+            .map(Option::unwrap_or_default)
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(feature = "encryption")]
+fn snapshot_key() -> Result<Option<SnapshotKey>, EngineError> {
+    match (env_var(SNAPSHOT_KEY_VAR), env_var(SNAPSHOT_KEY_FILE_VAR)) {
+        (None, None) => Ok(None),
+        (Some(hex), None) => SnapshotKey::from_hex(&hex).map(Some),
+        (None, Some(path)) => SnapshotKey::from_file(path).map(Some),
+        (Some(_), Some(_)) => Err(EngineError::Usage(format!(
+            "set only one of {SNAPSHOT_KEY_VAR} and {SNAPSHOT_KEY_FILE_VAR}"
+        ))),
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+fn snapshot_key() -> Result<Option<std::convert::Infallible>, EngineError> {
+    if env_var(SNAPSHOT_KEY_VAR).is_some() || env_var(SNAPSHOT_KEY_FILE_VAR).is_some() {
+        return Err(EngineError::Usage(
+            "snapshot encryption needs the `encryption` feature".to_string(),
+        ));
+    }
+    Ok(None)
+}
+
+/// Loads the snapshot at `path`, decrypting it when a key is configured. A
+/// plaintext snapshot still loads, so existing state can be migrated; it is
+/// encrypted the next time it is saved.
+pub fn load_snapshot(path: impl AsRef<Path>) -> Result<Snapshot, EngineError> {
+    let path = path.as_ref();
+    let Some(key) = snapshot_key()? else {
+        return Snapshot::load(BufReader::new(open_file(path)?));
+    };
+    #[cfg(feature = "encryption")]
+    {
+        let bytes = std::fs::read(path).map_err(|source| file_error(path, source))?;
+        if bytes.starts_with(ENCRYPTED_MAGIC) {
+            Snapshot::load_encrypted(bytes.as_slice(), &key)
+        } else {
+            log::warn!(
+                "Snapshot {} is not encrypted; it will be when saved",
+                path.display()
+            );
+            Snapshot::load(bytes.as_slice())
+        }
+    }
+    #[cfg(not(feature = "encryption"))]
+    match key {}
+}
+
+/// Claims an output path for one run with an advisory lock on a
+/// `<path>.lock` file, so two runs aimed at the same file cannot interleave
+/// their writes. The operating system releases the lock when the process
+/// ends, however it ends, so a killed run leaves nothing to clean up. The
+/// file stays, holding the PID of the last run that claimed the path; it
+/// is not deleted because a run could be opening it at that moment.
+pub struct OutputLock {
+    _file: File,
+}
+
+impl OutputLock {
+    pub fn acquire(path: impl AsRef<Path>) -> Result<Self, EngineError> {
+        let path = path.as_ref();
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|source| file_error(&lock_path, source))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(EngineError::OutputLocked {
+                    path: path.display().to_string(),
+                });
+            }
+            Err(TryLockError::Error(source)) => return Err(file_error(&lock_path, source)),
+        }
+        file.set_len(0)?;
+        writeln!(&file, "{}", std::process::id())?;
+        Ok(OutputLock { _file: file })
+    }
+}
+
+/// `File::open`, naming `path` in the error.
+pub fn open_file(path: impl AsRef<Path>) -> Result<File, EngineError> {
+    let path = path.as_ref();
+    File::open(path).map_err(|source| file_error(path, source))
+}
+
+/// `File::create`, naming `path` in the error.
+pub fn create_file(path: impl AsRef<Path>) -> Result<File, EngineError> {
+    let path = path.as_ref();
+    File::create(path).map_err(|source| file_error(path, source))
+}
+
+/// An I/O error on `path`, so `--json-errors` can report which file failed.
+pub fn file_error(path: &Path, source: io::Error) -> EngineError {
+    EngineError::File {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// Saves `snapshot` to `path`, encrypted when a key is configured. It is
+/// written to `<path>.partial` in the same directory and renamed over
+/// `path` once synced, so a run killed mid-write leaves the previous
+/// snapshot or checkpoint intact.
+pub fn save_snapshot(snapshot: &Snapshot, path: impl AsRef<Path>) -> Result<(), EngineError> {
+    let path = path.as_ref();
+    let key = snapshot_key()?;
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let write = || -> Result<(), EngineError> {
+        let mut writer = BufWriter::new(create_file(&partial)?);
+        match key {
+            #[cfg(feature = "encryption")]
+            Some(key) => snapshot.save_encrypted(&mut writer, &key)?,
+            None => snapshot.save(&mut writer)?,
+        }
+        writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()
+            .map_err(|source| file_error(&partial, source))?;
+        Ok(())
+    };
+    if let Err(err) = write() {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+    fs::rename(&partial, path).map_err(|source| file_error(path, source))?;
+    Ok(())
+}
+
+/// Appends audit entries to `path`, writing the header only when the file is
+/// new, or prints them to stdout when no path is given.
+pub fn write_audit_trail(
+    path: Option<&str>,
+    entries: &[AuditEntry],
+    redaction: Option<&Redaction>,
+) -> Result<(), EngineError> {
+    let write = |writer: &mut dyn Write, include_header| match redaction {
+        Some(redaction) => write_redacted_audit_entries(entries, redaction, writer, include_header),
+        None => write_audit_entries(entries, writer, include_header),
+    };
+    match path {
+        Some(path) => {
+            let is_new = !Path::new(path).exists();
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|source| file_error(Path::new(path), source))?;
+            let mut writer = BufWriter::new(file);
+            write(&mut writer, is_new)?;
+            writer.flush()?;
+            Ok(())
+        }
+        None => {
+            let stdout = std::io::stdout();
+            let mut handle = stdout.lock();
+            write(&mut handle, true)?;
+            handle.flush()?;
+            Ok(())
+        }
+    }
+}
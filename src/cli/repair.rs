@@ -0,0 +1,40 @@
+use rust_payments_engine::Engine;
+use rust_payments_engine::admin::{find_balance_mismatches, repair_balances};
+use rust_payments_engine::errors::EngineError;
+
+use super::{Args, load_snapshot, save_snapshot, write_audit_trail};
+
+const USAGE: &str = "Usage: cargo run -- repair --snapshot <state.json> [--strategy <recompute-total|quarantine> [--audit <audit.csv>]]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &["--snapshot", "--strategy", "--audit"], &[], USAGE)?;
+    if !args.positional().is_empty() {
+        return Err(args.usage_error());
+    }
+    let strategy = args
+        .option("--strategy")
+        .map(str::parse)
+        .transpose()
+        .map_err(EngineError::Usage)?;
+
+    let snapshot_path = args.required("--snapshot")?;
+    let mut engine = Engine::from_snapshot(load_snapshot(snapshot_path)?);
+    let mismatches = find_balance_mismatches(&engine);
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+
+    match strategy {
+        None if args.option("--audit").is_some() => Err(args.usage_error()),
+        None if mismatches.is_empty() => Ok(()),
+        None => Err(EngineError::InconsistentAccounts(mismatches.len())),
+        Some(strategy) => {
+            let entries = repair_balances(&mut engine, strategy);
+            if entries.is_empty() {
+                return Ok(());
+            }
+            save_snapshot(&engine.snapshot()?, snapshot_path)?;
+            write_audit_trail(args.option("--audit"), &entries, None)
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{BufReader, BufWriter};
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::report::{
+    segment_stats, write_html_report_with_segments, write_segment_stats,
+};
+use rust_payments_engine::segments::ClientSegments;
+
+use super::{Args, create_file, load_snapshot, open_file};
+
+const USAGE: &str = "Usage: cargo run -- report [<transactions.csv>] [--snapshot <state.json>] --html <report.html> [--client-segments <segments.csv> [--segment-stats <stats.csv>]] [--no-header] [--redact [--redact-amounts <bucket:width|scale:factor>]]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &[
+            "--snapshot",
+            "--html",
+            "--client-segments",
+            "--segment-stats",
+            "--redact-amounts",
+        ],
+        &["--no-header", "--redact"],
+        USAGE,
+    )?;
+    let input = match args.positional() {
+        [] if args.option("--snapshot").is_some() => None,
+        [input] => Some(input),
+        _ => return Err(args.usage_error()),
+    };
+    let output = args.required("--html")?;
+    let segments = match args.option("--client-segments") {
+        Some(path) => Some(ClientSegments::load(BufReader::new(open_file(path)?))?),
+        None if args.option("--segment-stats").is_some() => return Err(args.usage_error()),
+        None => None,
+    };
+
+    let mut engine = match args.option("--snapshot") {
+        Some(path) => Engine::from_snapshot(load_snapshot(path)?),
+        None => Engine::new(),
+    };
+    engine.set_config(EngineConfig {
+        has_headers: !args.flag("--no-header"),
+        redaction: args.redaction()?,
+        ..EngineConfig::default()
+    });
+    engine.enable_rejection_breakdown();
+    if let Some(input) = input {
+        engine.process(BufReader::new(open_file(input)?))?;
+    }
+
+    if let (Some(segments), Some(path)) = (&segments, args.option("--segment-stats")) {
+        write_segment_stats(
+            &segment_stats(&engine, segments)?,
+            engine.config().redaction.as_ref(),
+            BufWriter::new(create_file(path)?),
+        )?;
+    }
+    write_html_report_with_segments(
+        &engine,
+        segments.as_ref(),
+        BufWriter::new(create_file(output)?),
+    )
+}
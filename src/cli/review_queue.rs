@@ -0,0 +1,51 @@
+use std::io::BufWriter;
+
+use log::info;
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::review::write_review_queue;
+
+use super::{Args, create_file, load_snapshot, save_snapshot};
+
+const USAGE: &str = "Usage: cargo run -- review-queue --snapshot <state.json> [--client <id> --note <text>] [--output <review.csv>] [--clear]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--snapshot", "--client", "--note", "--output"],
+        &["--clear"],
+        USAGE,
+    )?;
+    if !args.positional().is_empty() {
+        return Err(args.usage_error());
+    }
+    let snapshot_path = args.required("--snapshot")?;
+    let mut engine = Engine::from_snapshot(load_snapshot(snapshot_path)?);
+
+    let note = args.option("--note");
+    if let Some(note) = note {
+        engine.add_review_note(args.parse_required("--client")?, note)?;
+    } else if args.option("--client").is_some() {
+        return Err(args.usage_error());
+    }
+
+    match args.option("--output") {
+        Some(path) => {
+            write_review_queue(engine.review_queue(), BufWriter::new(create_file(path)?))?
+        }
+        None => write_review_queue(
+            engine.review_queue(),
+            BufWriter::new(std::io::stdout().lock()),
+        )?,
+    }
+    info!("Exported {} review item(s)", engine.review_queue().len());
+
+    if args.flag("--clear") {
+        engine.take_review_queue();
+    }
+    if note.is_some() || args.flag("--clear") {
+        save_snapshot(&engine.snapshot()?, snapshot_path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,691 @@
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use rust_decimal::Decimal;
+use rust_payments_engine::alerts::AlertThresholds;
+use rust_payments_engine::aliases::ClientAliases;
+use rust_payments_engine::amount_histogram::{AmountBuckets, AmountHistogram, ClientSegments};
+use rust_payments_engine::balance_limits::BalanceLimits;
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::dispute_expiry::DisputeExpiry;
+use rust_payments_engine::engine::priority::DEFAULT_PRIORITY_WINDOW;
+use rust_payments_engine::errors::{AmountError, EngineError};
+use rust_payments_engine::eviction::{DirectoryStore, EvictionPolicy};
+use rust_payments_engine::format::Format;
+use rust_payments_engine::formatting::{FormattingOptions, parse_amount};
+use rust_payments_engine::hold_accrual::HoldAccrual;
+use rust_payments_engine::manifest::{InputFile, RunManifest};
+use rust_payments_engine::metrics::TypeMetrics;
+use rust_payments_engine::money::Money;
+use rust_payments_engine::output::{ChecksumWriter, TrailerMode};
+use rust_payments_engine::prelink::{PrelinkMode, check_dispute_links};
+use rust_payments_engine::quality::QualityThresholds;
+use rust_payments_engine::rules::max_withdrawal_per_run;
+use rust_payments_engine::sort::ExternalSort;
+use rust_payments_engine::tenant::TenantEngines;
+use rust_payments_engine::{Engine, RowCounts};
+
+use super::{
+    Args, Outcome, OutputLock, create_file, file_error, load_snapshot, open_file, save_snapshot,
+    write_audit_trail,
+};
+
+const USAGE: &str = "Usage: cargo run -- <transactions.csv> [--sort-by timestamp <more.csv>...] [--snapshot <state.json>] [--save-snapshot <state.json>] [--tenant <id>] [--tenant-output <column|files> [--output-dir <dir>]] [--no-header] [--strict-columns] [--lenient-csv] [--reject-unexpected-amounts] [--strict-tx-order] [--priority [--priority-window <rows>]] [--catch-row-panics] [--prelink <warn|fail>] [--zero-amounts <reject|ignore>] [--rejection-log <category=error|warn|silent,...>] [--audit <audit.csv> [--redact [--redact-amounts <bucket:width|scale:factor>]]] [--client-aliases <aliases.csv> [--output-external-ids]] [--max-withdrawal-per-run <amount>] [--withdrawal-policy <available|projected|freeze-on-open-dispute>] [--balance-limits <limits.csv>] [--non-negative-balances [--review-output <review.csv>]] [--review-after-disputes <n>] [--expire-disputes-after <days>d|<seconds>s|<n>tx [--expired-dispute-outcome <resolve|chargeback>]] [--accrue-holds-after <days>d|<seconds>s|<n>tx --hold-accrual-rate <fraction> [--hold-accrual <interest|penalty>]] [--input-format <csv|tsv|json|auto>] [--input-encoding <label>] [--output-format <csv|json>] [--json-amounts <string|number>] [--output-schema <v1|v2>] [--risk-score] [--idempotent] [--balance-history] [--output <accounts.csv>] [--output-trailer <comment|sidecar>] [--changed-only [--full-output <accounts.csv>]] [--dead-letter <rejected.csv>] [--manifest <manifest.json>] [--metrics <metrics.json>] [--amount-histogram <histogram.csv> [--histogram-buckets <bound,...>] [--histogram-format <csv|json>] [--client-segments <segments.csv>]] [--on-interrupt <checkpoint|discard>] [--checkpoint <state.json>] [--max-runtime <seconds>] [--max-rows <n>] [--max-memory <bytes> [--on-memory-limit <abort|spill|drop-history>] [--spill-dir <dir>]] [--max-error-rate <fraction>] [--quality-report <quality.json>] [--quality-thresholds <parse|validation|unknown|duplicates|score=fraction,...>] [--alert-min-available <amount>] [--alert-max-held <amount>] [--alert-max-locked <amount>] [--decimal-separator <dot|comma>] [--thousands-separator <none|comma|dot|space|apostrophe>] [--places <n>] [--rounding <truncate|half-up>] [--quote <necessary|always|non-numeric|never>] [--line-ending <lf|crlf>] [--fixed-width <width,...>]";
+
+pub fn run(args: &[String], interrupt: Arc<AtomicBool>) -> Result<Outcome, EngineError> {
+    let started = Instant::now();
+    let args = Args::parse(
+        args,
+        &[
+            "--snapshot",
+            "--save-snapshot",
+            "--tenant",
+            "--tenant-output",
+            "--output-dir",
+            "--audit",
+            "--redact-amounts",
+            "--client-aliases",
+            "--max-withdrawal-per-run",
+            "--withdrawal-policy",
+            "--balance-limits",
+            "--review-output",
+            "--review-after-disputes",
+            "--prelink",
+            "--zero-amounts",
+            "--rejection-log",
+            "--expire-disputes-after",
+            "--expired-dispute-outcome",
+            "--accrue-holds-after",
+            "--hold-accrual-rate",
+            "--hold-accrual",
+            "--input-format",
+            "--input-encoding",
+            "--output-format",
+            "--json-amounts",
+            "--output-schema",
+            "--decimal-separator",
+            "--thousands-separator",
+            "--places",
+            "--rounding",
+            "--quote",
+            "--line-ending",
+            "--fixed-width",
+            "--sort-by",
+            "--priority-window",
+            "--dead-letter",
+            "--manifest",
+            "--metrics",
+            "--amount-histogram",
+            "--histogram-buckets",
+            "--histogram-format",
+            "--client-segments",
+            "--on-interrupt",
+            "--checkpoint",
+            "--max-runtime",
+            "--max-rows",
+            "--output",
+            "--output-trailer",
+            "--full-output",
+            "--max-memory",
+            "--on-memory-limit",
+            "--spill-dir",
+            "--max-error-rate",
+            "--quality-report",
+            "--quality-thresholds",
+            "--alert-min-available",
+            "--alert-max-held",
+            "--alert-max-locked",
+        ],
+        &[
+            "--no-header",
+            "--strict-columns",
+            "--lenient-csv",
+            "--reject-unexpected-amounts",
+            "--strict-tx-order",
+            "--catch-row-panics",
+            "--idempotent",
+            "--priority",
+            "--changed-only",
+            "--balance-history",
+            "--redact",
+            "--output-external-ids",
+            "--risk-score",
+            "--non-negative-balances",
+        ],
+        USAGE,
+    )?;
+    let sorted;
+    let input = match (args.option("--sort-by"), args.positional()) {
+        (None, [input]) => Path::new(input),
+        (Some("timestamp"), inputs) if !inputs.is_empty() => {
+            if args.flag("--no-header") || args.option("--input-format").is_some_and(|f| f != "csv")
+            {
+                return Err(EngineError::Usage(
+                    "--sort-by timestamp needs CSV input with a header row".to_string(),
+                ));
+            }
+            sorted = sort_by_timestamp(inputs)?;
+            sorted.0.as_path()
+        }
+        _ => return Err(args.usage_error()),
+    };
+    let max_error_rate: Option<f64> = args.parse_option("--max-error-rate")?;
+    let quality_thresholds = args
+        .option("--quality-thresholds")
+        .map(str::parse::<QualityThresholds>)
+        .transpose()
+        .map_err(EngineError::Usage)?;
+    let trailer: Option<TrailerMode> = args
+        .option("--output-trailer")
+        .map(str::parse)
+        .transpose()
+        .map_err(EngineError::Usage)?;
+    if trailer == Some(TrailerMode::Sidecar) && args.option("--output").is_none() {
+        return Err(EngineError::Usage(
+            "--output-trailer sidecar needs --output".to_string(),
+        ));
+    }
+    let checkpoint = match args.option("--on-interrupt").unwrap_or("checkpoint") {
+        "checkpoint" => true,
+        "discard" => false,
+        _ => return Err(args.usage_error()),
+    };
+    let dispute_expiry = match (
+        args.option("--expire-disputes-after")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?,
+        args.parse_option("--expired-dispute-outcome")?,
+    ) {
+        (Some(deadline), outcome) => Some(DisputeExpiry {
+            deadline,
+            outcome: outcome.unwrap_or_default(),
+        }),
+        (None, None) => None,
+        (None, Some(_)) => return Err(args.usage_error()),
+    };
+    let hold_accrual = match (
+        args.option("--accrue-holds-after")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?,
+        args.parse_option::<Decimal>("--hold-accrual-rate")?,
+        args.parse_option("--hold-accrual")?,
+    ) {
+        (Some(grace), Some(rate), kind) if !rate.is_sign_negative() => Some(HoldAccrual {
+            grace,
+            rate,
+            kind: kind.unwrap_or_default(),
+        }),
+        (None, None, None) => None,
+        _ => return Err(args.usage_error()),
+    };
+    let client_aliases = match args.option("--client-aliases") {
+        Some(path) => Some(Arc::new(ClientAliases::load(BufReader::new(open_file(
+            path,
+        )?))?)),
+        None if args.flag("--output-external-ids") => return Err(args.usage_error()),
+        None => None,
+    };
+
+    if args.option("--review-output").is_some() && !args.flag("--non-negative-balances") {
+        return Err(args.usage_error());
+    }
+
+    let priority_window = match args.parse_option("--priority-window")? {
+        Some(window) if args.flag("--priority") => Some(window),
+        Some(_) => return Err(args.usage_error()),
+        None => args.flag("--priority").then_some(DEFAULT_PRIORITY_WINDOW),
+    };
+
+    let balance_limits = match args.option("--balance-limits") {
+        Some(path) => Some(Arc::new(BalanceLimits::load(BufReader::new(open_file(
+            path,
+        )?))?)),
+        None => None,
+    };
+
+    // TSV is CSV with another delimiter, and `auto` is settled per input.
+    let (input_format, input_delimiter) = match args.option("--input-format") {
+        Some("tsv") => (Format::Csv, b'\t'),
+        Some("auto") => (Format::Csv, b','),
+        _ => (
+            args.parse_option("--input-format")?.unwrap_or_default(),
+            b',',
+        ),
+    };
+    let config = EngineConfig {
+        has_headers: !args.flag("--no-header"),
+        strict_columns: args.flag("--strict-columns"),
+        lenient_csv: args.flag("--lenient-csv"),
+        input_format,
+        input_delimiter,
+        detect_input_format: args.option("--input-format") == Some("auto"),
+        input_encoding: args
+            .option("--input-encoding")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default(),
+        output_format: args.parse_option("--output-format")?.unwrap_or_default(),
+        amount_encoding: args.parse_option("--json-amounts")?.unwrap_or_default(),
+        output_schema: args.parse_option("--output-schema")?.unwrap_or_default(),
+        formatting: FormattingOptions {
+            places: args.parse_option("--places")?.unwrap_or(4),
+            rounding: args.parse_option("--rounding")?.unwrap_or_default(),
+            decimal_separator: args
+                .parse_option("--decimal-separator")?
+                .unwrap_or_default(),
+            thousands_separator: args
+                .parse_option("--thousands-separator")?
+                .unwrap_or_default(),
+            quoting: args.parse_option("--quote")?.unwrap_or_default(),
+            line_ending: args
+                .option("--line-ending")
+                .map(str::parse)
+                .transpose()
+                .map_err(EngineError::Usage)?
+                .unwrap_or_default(),
+            fixed_width: args
+                .option("--fixed-width")
+                .map(str::parse)
+                .transpose()
+                .map_err(EngineError::Usage)?,
+        },
+        max_memory_bytes: args.parse_option("--max-memory")?,
+        max_runtime: args.parse_option("--max-runtime")?.map(Duration::from_secs),
+        max_rows: args.parse_option("--max-rows")?,
+        memory_policy: args.parse_option("--on-memory-limit")?.unwrap_or_default(),
+        redaction: args.redaction()?,
+        dispute_expiry,
+        hold_accrual,
+        reject_unexpected_amounts: args.flag("--reject-unexpected-amounts"),
+        strict_tx_order: args.flag("--strict-tx-order"),
+        catch_row_panics: args.flag("--catch-row-panics"),
+        zero_amounts: args
+            .option("--zero-amounts")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default(),
+        rejection_logging: args.rejection_logging()?,
+        balance_limits,
+        non_negative_balances: args.flag("--non-negative-balances"),
+        review_after_disputes: args
+            .parse_option("--review-after-disputes")?
+            .filter(|&disputes| disputes > 0),
+        priority_window,
+        withdrawal_policy: args
+            .option("--withdrawal-policy")
+            .map(str::parse)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default(),
+        client_aliases,
+        output_external_ids: args.flag("--output-external-ids"),
+        output_risk_score: args.flag("--risk-score"),
+    };
+    if let Err(err @ AmountError::ConflictingSeparators(_)) = parse_amount(
+        "0",
+        config.formatting.decimal_separator,
+        config.formatting.thousands_separator,
+    ) {
+        return Err(EngineError::Usage(err.to_string()));
+    }
+    if trailer == Some(TrailerMode::Comment) && !config.writes_output_header() {
+        return Err(EngineError::Usage(
+            "--output-trailer comment needs CSV output".to_string(),
+        ));
+    }
+
+    if let Some(mode) = args.option("--tenant-output") {
+        let rows = run_multi_tenant(&args, config, input, mode, interrupt)?;
+        return Ok(Outcome::from_rows(rows, max_error_rate));
+    }
+
+    let _output_lock = args
+        .option("--output")
+        .map(OutputLock::acquire)
+        .transpose()?;
+
+    let mut engine = match (args.option("--snapshot"), args.option("--tenant")) {
+        (Some(path), _) => Engine::from_snapshot(load_snapshot(path)?),
+        (None, Some(tenant)) => Engine::with_tenant(tenant),
+        (None, None) => Engine::new(),
+    };
+    engine.set_config(config);
+
+    if let Some(limit) = args.parse_option::<Decimal>("--max-withdrawal-per-run")? {
+        let limit = Money::new(limit).map_err(|_| args.usage_error())?;
+        engine
+            .rules_mut()
+            .register("max_withdrawal_per_run", max_withdrawal_per_run(limit));
+    }
+
+    if let Some(dir) = args.option("--spill-dir") {
+        engine.set_eviction(EvictionPolicy {
+            idle_for: Duration::MAX,
+            store: Box::new(DirectoryStore::new(dir)?),
+        });
+    }
+
+    let thresholds = AlertThresholds {
+        min_available: args.parse_option("--alert-min-available")?,
+        max_held: args.parse_option("--alert-max-held")?,
+        max_locked_total: args.parse_option("--alert-max-locked")?,
+    };
+    if thresholds != AlertThresholds::default() {
+        engine.set_alerts(thresholds, |alert| warn!("Alert: {alert}"));
+    }
+
+    if args.flag("--balance-history") {
+        engine.enable_balance_history();
+    }
+
+    if args.option("--manifest").is_some() {
+        engine.enable_rejection_breakdown();
+    }
+
+    if args.option("--quality-report").is_some() || quality_thresholds.is_some() {
+        engine.enable_quality_report();
+    }
+
+    if args.option("--amount-histogram").is_some() {
+        let buckets = args
+            .option("--histogram-buckets")
+            .map(str::parse::<AmountBuckets>)
+            .transpose()
+            .map_err(EngineError::Usage)?
+            .unwrap_or_default();
+        let segments = match args.option("--client-segments") {
+            Some(path) => ClientSegments::load(BufReader::new(open_file(path)?))?,
+            None => ClientSegments::default(),
+        };
+        engine.enable_amount_histogram(AmountHistogram::new(buckets, segments));
+    } else if [
+        "--histogram-buckets",
+        "--histogram-format",
+        "--client-segments",
+    ]
+    .iter()
+    .any(|option| args.option(option).is_some())
+    {
+        return Err(args.usage_error());
+    }
+
+    let metrics = args
+        .option("--metrics")
+        .map(|_| Arc::new(TypeMetrics::new()));
+    if let Some(metrics) = &metrics {
+        engine.set_metrics(metrics.clone());
+    }
+
+    if let Some(mode) = args
+        .option("--prelink")
+        .map(str::parse::<PrelinkMode>)
+        .transpose()
+        .map_err(EngineError::Usage)?
+    {
+        let report = check_dispute_links(&engine, BufReader::new(open_file(input)?))?;
+        for unlinked in &report.unlinked {
+            warn!("Unlinked reference at {unlinked}");
+        }
+        info!(
+            "Prelink: {} of {} dispute, resolve and chargeback rows name unknown deposits",
+            report.unlinked.len(),
+            report.references
+        );
+        if mode == PrelinkMode::Fail && !report.unlinked.is_empty() {
+            return Err(EngineError::UnlinkedReferences(report.unlinked.len()));
+        }
+    }
+
+    engine.set_interrupt_flag(interrupt);
+    if let Some(path) = args.option("--dead-letter") {
+        engine.set_dead_letter(BufWriter::new(create_file(path)?));
+    }
+    if let Some(path) = args.option("--review-output") {
+        engine.set_review_output(BufWriter::new(create_file(path)?));
+    }
+    let csv_file = open_file(input)?;
+    let sorted_input = args.option("--sort-by").map(|_| input);
+    let processed = if args.flag("--idempotent") {
+        engine.process_once(BufReader::new(csv_file)).map(drop)
+    } else {
+        engine.process(BufReader::new(csv_file))
+    };
+    match processed {
+        Err(EngineError::Interrupted { row }) if checkpoint => {
+            return write_checkpoint(
+                &args,
+                &engine,
+                sorted_input,
+                EngineError::Interrupted { row },
+            );
+        }
+        Err(err @ EngineError::BudgetExhausted { .. }) => {
+            return write_checkpoint(&args, &engine, sorted_input, err);
+        }
+        processed => processed?,
+    }
+
+    if engine.tx_order_violations() > 0 {
+        warn!(
+            "{} rows had a reused or out-of-order transaction id",
+            engine.tx_order_violations()
+        );
+    }
+
+    if let Some(path) = args.option("--audit") {
+        write_audit_trail(
+            Some(path),
+            engine.audit_entries(),
+            engine.config().redaction.as_ref(),
+        )?;
+    }
+
+    if let Some(path) = args.option("--save-snapshot") {
+        save_snapshot(&engine.snapshot()?, path)?;
+    }
+
+    let mut writer = ChecksumWriter::new(account_writer(&args)?);
+    let rows = if !args.flag("--changed-only") {
+        engine.write_accounts(&mut writer)?
+    } else {
+        if let Some(path) = args.option("--full-output") {
+            engine.write_accounts(BufWriter::new(create_file(path)?))?;
+        }
+        engine.write_changed_accounts(&mut writer)?
+    };
+    let (mut writer, checksum) = writer.finish(rows);
+    match (trailer, args.option("--output")) {
+        (Some(TrailerMode::Comment), _) => {
+            write!(writer, "# {checksum}")?;
+            writer.write_all(engine.config().formatting.line_ending.as_bytes())?;
+            writer.flush()?;
+        }
+        (Some(TrailerMode::Sidecar), Some(path)) => {
+            let sidecar = format!("{path}.sha256");
+            fs::write(&sidecar, format!("{checksum}\n"))
+                .map_err(|source| file_error(Path::new(&sidecar), source))?;
+        }
+        _ => {}
+    }
+
+    if let Some(path) = args.option("--manifest") {
+        let inputs = match args.positional() {
+            [] => Vec::new(),
+            inputs => inputs
+                .iter()
+                .map(InputFile::hash)
+                .collect::<Result<_, _>>()?,
+        };
+        RunManifest::new(
+            &engine,
+            inputs,
+            started.elapsed(),
+            args.config_digest(&["--manifest"]),
+        )
+        .write(BufWriter::new(create_file(path)?))?;
+    }
+    if let (Some(path), Some(histogram)) =
+        (args.option("--amount-histogram"), engine.amount_histogram())
+    {
+        histogram.write(
+            BufWriter::new(create_file(path)?),
+            args.parse_option("--histogram-format")?.unwrap_or_default(),
+        )?;
+    }
+    if let (Some(path), Some(metrics)) = (args.option("--metrics"), &metrics) {
+        let mut writer = BufWriter::new(create_file(path)?);
+        serde_json::to_writer_pretty(&mut writer, &metrics.stats())?;
+        writer.flush()?;
+    }
+    if let Some(report) = engine.quality_report() {
+        info!("Data quality: {report}");
+        if let Some(path) = args.option("--quality-report") {
+            let mut writer = BufWriter::new(create_file(path)?);
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        let breaches = quality_thresholds
+            .map(|thresholds| thresholds.breaches(&report))
+            .unwrap_or_default();
+        if !breaches.is_empty() {
+            for breach in &breaches {
+                warn!("Data quality below threshold: {breach}");
+            }
+            return Ok(Outcome::QualityBelowThreshold);
+        }
+    }
+    Ok(Outcome::from_rows(engine.row_counts(), max_error_rate))
+}
+
+/// Where account output goes: the `--output` file, else stdout.
+fn account_writer(args: &Args) -> Result<Box<dyn Write>, EngineError> {
+    Ok(match args.option("--output") {
+        Some(path) => Box::new(BufWriter::new(create_file(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    })
+}
+
+/// Saves the state reached so far as a snapshot and writes the partial
+/// accounts to the account output, then reports why the run `stopped`. Resume by
+/// loading the checkpoint with `--snapshot` and feeding only the rows after
+/// `row`.
+///
+/// Under `--sort-by`, `row` counts rows of the merged input, which is only
+/// a temporary file, so `sorted_input` is kept as `<checkpoint>.input.csv`
+/// to resume from.
+fn write_checkpoint(
+    args: &Args,
+    engine: &Engine,
+    sorted_input: Option<&Path>,
+    stopped: EngineError,
+) -> Result<Outcome, EngineError> {
+    let path = match args.option("--checkpoint") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(format!("{}.checkpoint.json", args.positional()[0])),
+    };
+    save_snapshot(&engine.snapshot()?, &path)?;
+    engine.write_accounts(account_writer(args)?)?;
+    match sorted_input {
+        Some(sorted_input) => {
+            let mut kept = path.as_os_str().to_owned();
+            kept.push(".input.csv");
+            let kept = PathBuf::from(kept);
+            fs::copy(sorted_input, &kept).map_err(|source| file_error(&kept, source))?;
+            warn!(
+                "{stopped}; partial accounts written, checkpoint saved to {}; resume with the rows of {} after that row",
+                path.display(),
+                kept.display()
+            );
+        }
+        None => warn!(
+            "{stopped}; partial accounts written, checkpoint saved to {}",
+            path.display()
+        ),
+    }
+    Err(stopped)
+}
+
+/// Options and flags `--tenant-output` honours: those read into the
+/// `EngineConfig` every tenant's engine shares, the input and output ones,
+/// and `--max-error-rate`. Anything else is rejected rather than ignored.
+const TENANT_OPTIONS: [&str; 38] = [
+    "--tenant",
+    "--tenant-output",
+    "--output-dir",
+    "--sort-by",
+    "--client-aliases",
+    "--withdrawal-policy",
+    "--balance-limits",
+    "--zero-amounts",
+    "--rejection-log",
+    "--expire-disputes-after",
+    "--expired-dispute-outcome",
+    "--accrue-holds-after",
+    "--hold-accrual-rate",
+    "--hold-accrual",
+    "--input-format",
+    "--input-encoding",
+    "--output-format",
+    "--json-amounts",
+    "--output-schema",
+    "--decimal-separator",
+    "--thousands-separator",
+    "--places",
+    "--rounding",
+    "--quote",
+    "--line-ending",
+    "--fixed-width",
+    "--priority-window",
+    "--max-error-rate",
+    "--no-header",
+    "--strict-columns",
+    "--lenient-csv",
+    "--reject-unexpected-amounts",
+    "--strict-tx-order",
+    "--catch-row-panics",
+    "--priority",
+    "--output-external-ids",
+    "--risk-score",
+    "--non-negative-balances",
+];
+
+fn run_multi_tenant(
+    args: &Args,
+    config: EngineConfig,
+    input: &Path,
+    mode: &str,
+    interrupt: Arc<AtomicBool>,
+) -> Result<RowCounts, EngineError> {
+    let mut unsupported: Vec<&str> = args
+        .given()
+        .filter(|name| !TENANT_OPTIONS.contains(name))
+        .collect();
+    if !unsupported.is_empty() {
+        unsupported.sort_unstable();
+        return Err(EngineError::Usage(format!(
+            "{} cannot be combined with --tenant-output",
+            unsupported.join(", ")
+        )));
+    }
+
+    let mut engines =
+        TenantEngines::with_config(args.option("--tenant").unwrap_or("default"), config);
+    engines.set_interrupt_flag(interrupt);
+    engines.process(BufReader::new(open_file(input)?))?;
+
+    match mode {
+        "column" => {
+            let stdout = std::io::stdout();
+            engines.write_accounts(BufWriter::new(stdout.lock()))?;
+        }
+        "files" => {
+            let dir = Path::new(args.required("--output-dir")?);
+            fs::create_dir_all(dir)?;
+            for (tenant, engine) in engines.tenants() {
+                if tenant.is_empty() || tenant.contains(['/', '\\']) || tenant.starts_with('.') {
+                    return Err(EngineError::Usage(format!(
+                        "Tenant {tenant:?} cannot be used as an output file name"
+                    )));
+                }
+                let file = create_file(dir.join(format!("{tenant}.csv")))?;
+                engine.write_accounts(BufWriter::new(file))?;
+            }
+        }
+        _ => return Err(args.usage_error()),
+    }
+    Ok(engines.row_counts())
+}
+
+/// Merged, chronologically ordered copy of the inputs, deleted when dropped.
+struct SortedInput(PathBuf);
+
+impl Drop for SortedInput {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn sort_by_timestamp(inputs: &[String]) -> Result<SortedInput, EngineError> {
+    let dir = std::env::temp_dir();
+    let sorted = SortedInput(dir.join(format!(
+        "rust-payments-engine-sorted-{}.csv",
+        std::process::id()
+    )));
+    let files = inputs
+        .iter()
+        .map(|input| Ok(BufReader::new(open_file(input)?)))
+        .collect::<Result<Vec<_>, EngineError>>()?;
+    ExternalSort::new(dir).sort(files, BufWriter::new(create_file(&sorted.0)?))?;
+    Ok(sorted)
+}
@@ -0,0 +1,44 @@
+use std::io::{BufReader, BufWriter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::sample::sample_clients;
+
+use super::{Args, open_file};
+
+const USAGE: &str = "Usage: cargo run -- sample --rate <fraction> [--seed <n>] <transactions.csv>";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &["--rate", "--seed"], &[], USAGE)?;
+    let [input] = args.positional() else {
+        return Err(args.usage_error());
+    };
+    let rate: f64 = args
+        .parse_option("--rate")?
+        .ok_or_else(|| args.usage_error())?;
+    if !(rate > 0.0 && rate <= 1.0) {
+        return Err(EngineError::Usage(format!(
+            "--rate must be in (0, 1], got {rate}"
+        )));
+    }
+    let seed = match args.parse_option("--seed")? {
+        Some(seed) => seed,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+    };
+
+    let stats = sample_clients(
+        BufReader::new(open_file(input)?),
+        BufWriter::new(std::io::stdout().lock()),
+        rate,
+        seed,
+    )?;
+    info!(
+        "Sampled {} client(s), {} of {} row(s), with seed {seed}",
+        stats.clients, stats.rows_written, stats.rows_read
+    );
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::io::{BufReader, BufWriter};
+
+use rust_decimal::Decimal;
+use rust_payments_engine::Engine;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::money::Money;
+use rust_payments_engine::settlement::{settle, write_settlement_report};
+
+use super::{Args, load_snapshot, open_file};
+
+const USAGE: &str = "Usage: cargo run -- settle (<transactions.csv> | --snapshot <state.json>) [--min-payout <amount>] [--format <csv|json>] [--json-amounts <string|number>]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--snapshot", "--min-payout", "--format", "--json-amounts"],
+        &[],
+        USAGE,
+    )?;
+
+    let engine = match (args.positional(), args.option("--snapshot")) {
+        ([input], None) => {
+            let mut engine = Engine::new();
+            engine.process(BufReader::new(open_file(input)?))?;
+            engine
+        }
+        ([], Some(path)) => Engine::from_snapshot(load_snapshot(path)?),
+        _ => return Err(args.usage_error()),
+    };
+
+    let min_payout = args
+        .parse_option::<Decimal>("--min-payout")?
+        .map(Money::new)
+        .transpose()
+        .map_err(|_| args.usage_error())?
+        .unwrap_or(Money::ZERO);
+
+    let stdout = std::io::stdout();
+    write_settlement_report(
+        &settle(&engine, min_payout)?,
+        args.parse_option("--format")?.unwrap_or_default(),
+        args.parse_option("--json-amounts")?.unwrap_or_default(),
+        BufWriter::new(stdout.lock()),
+    )
+}
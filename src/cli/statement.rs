@@ -0,0 +1,41 @@
+use std::io::{BufReader, BufWriter};
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::statement::write_statement;
+
+use super::{Args, open_file};
+
+const USAGE: &str = "Usage: cargo run -- statement --client <id> --input <transactions.csv> [--format <text|csv>] [--no-header]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(
+        args,
+        &["--client", "--input", "--format"],
+        &["--no-header"],
+        USAGE,
+    )?;
+    if !args.positional().is_empty() {
+        return Err(args.usage_error());
+    }
+    let client = args
+        .parse_option("--client")?
+        .ok_or_else(|| args.usage_error())?;
+
+    let mut engine = Engine::with_config(EngineConfig {
+        has_headers: !args.flag("--no-header"),
+        ..EngineConfig::default()
+    });
+    let lines = engine.statement(
+        BufReader::new(open_file(args.required("--input")?)?),
+        client,
+    )?;
+
+    let stdout = std::io::stdout();
+    write_statement(
+        &lines,
+        args.parse_option("--format")?.unwrap_or_default(),
+        BufWriter::new(stdout.lock()),
+    )
+}
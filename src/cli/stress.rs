@@ -0,0 +1,51 @@
+use std::io::BufReader;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::stress::stress_test;
+
+use super::{Args, open_file};
+
+const USAGE: &str =
+    "Usage: cargo run -- stress [--seed <n>] [--runs <n>] [--strict-tx-order] <transactions.csv>";
+
+/// Reorderings tried when `--runs` is not given.
+const DEFAULT_RUNS: usize = 10;
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &["--seed", "--runs"], &["--strict-tx-order"], USAGE)?;
+    let [input] = args.positional() else {
+        return Err(args.usage_error());
+    };
+    let runs = args.parse_option("--runs")?.unwrap_or(DEFAULT_RUNS);
+    let seed = match args.parse_option("--seed")? {
+        Some(seed) => seed,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+    };
+    let config = EngineConfig {
+        strict_tx_order: args.flag("--strict-tx-order"),
+        ..EngineConfig::default()
+    };
+
+    let failures = stress_test(BufReader::new(open_file(input)?), &config, seed, runs)?;
+    info!(
+        "{} of {runs} reordering(s) changed the accounts, with seed {seed}",
+        failures.len()
+    );
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let mut mismatches = 0;
+    for failure in &failures {
+        for mismatch in &failure.mismatches {
+            println!("seed {}: {mismatch}", failure.seed);
+        }
+        mismatches += failure.mismatches.len();
+    }
+    Err(EngineError::VerificationFailed(mismatches))
+}
@@ -0,0 +1,33 @@
+use std::io::BufReader;
+
+use rust_payments_engine::Engine;
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::errors::EngineError;
+use rust_payments_engine::verify::verify_accounts;
+
+use super::{Args, open_file};
+
+const USAGE: &str = "Usage: cargo run -- verify <transactions.csv> <expected_accounts.csv> [--no-header] [--strict-columns]";
+
+pub fn run(args: &[String]) -> Result<(), EngineError> {
+    let args = Args::parse(args, &[], &["--no-header", "--strict-columns"], USAGE)?;
+    let [input, expected] = args.positional() else {
+        return Err(args.usage_error());
+    };
+
+    let mut engine = Engine::with_config(EngineConfig {
+        has_headers: !args.flag("--no-header"),
+        strict_columns: args.flag("--strict-columns"),
+        ..EngineConfig::default()
+    });
+    engine.process(BufReader::new(open_file(input)?))?;
+
+    let mismatches = verify_accounts(&engine, BufReader::new(open_file(expected)?))?;
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    Err(EngineError::VerificationFailed(mismatches.len()))
+}
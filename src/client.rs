@@ -1,57 +1,451 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+use core::str::FromStr;
 use rust_decimal::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime};
 
-use crate::errors::ClientTransactionError;
+#[cfg(feature = "std")]
+use crate::clock::{Clock, SystemClock};
+use crate::errors::{ClientTransactionError, MoneyError};
+use crate::ids::{ClientId, TxId};
+use crate::money::Money;
+use crate::numeric::{Balance, Numeric};
+use crate::withdrawal_policy::WithdrawalPolicy;
 
-pub struct Client {
-    pub id: u16,
-    pub available: Decimal,
-    pub held: Decimal,
-    pub total: Decimal,
+/// A client's transactions by id. Without `std` there is no randomly seeded
+/// hasher, so they are kept in order instead.
+#[cfg(feature = "std")]
+type TxMap<V> = std::collections::HashMap<TxId, V>;
+#[cfg(not(feature = "std"))]
+type TxMap<V> = alloc::collections::BTreeMap<TxId, V>;
+
+/// One leg of a multi-leg operation for [`Client::apply_batch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation<B = Decimal> {
+    Deposit { tx: TxId, amount: Money<B> },
+    Withdraw { amount: Money<B> },
+    Credit { amount: Money<B> },
+    Dispute { tx: TxId },
+    Resolve { tx: TxId },
+    Chargeback { tx: TxId },
+    HoldWithdrawal { tx: TxId, amount: Money<B> },
+    SettleWithdrawal { tx: TxId },
+    CancelWithdrawal { tx: TxId },
+}
+
+/// One of the narrow restrictions in [`Capabilities`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    Withdraw,
+    Deposit,
+    Dispute,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Withdraw => "can_withdraw",
+            Capability::Deposit => "can_deposit",
+            Capability::Dispute => "dispute_allowed",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "can_withdraw" => Ok(Capability::Withdraw),
+            "can_deposit" => Ok(Capability::Deposit),
+            "dispute_allowed" => Ok(Capability::Dispute),
+            other => Err(format!(
+                "unknown capability {other}, expected can_withdraw, can_deposit or dispute_allowed"
+            )),
+        }
+    }
+}
+
+/// What an account may do, so risk can switch off one kind of transaction
+/// without locking the account outright. Everything is allowed by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Withdrawals and withdrawal holds.
+    pub can_withdraw: bool,
+    pub can_deposit: bool,
+    /// Opening disputes; resolves and chargebacks of open ones still apply.
+    pub dispute_allowed: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities {
+            can_withdraw: true,
+            can_deposit: true,
+            dispute_allowed: true,
+        }
+    }
+}
+
+impl Capabilities {
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Withdraw => self.can_withdraw,
+            Capability::Deposit => self.can_deposit,
+            Capability::Dispute => self.dispute_allowed,
+        }
+    }
+
+    pub fn set(&mut self, capability: Capability, allowed: bool) {
+        match capability {
+            Capability::Withdraw => self.can_withdraw = allowed,
+            Capability::Deposit => self.can_deposit = allowed,
+            Capability::Dispute => self.dispute_allowed = allowed,
+        }
+    }
+}
+
+/// An account and the transactions it can still dispute, with balances in
+/// the [`Balance`] type `B`: `Decimal` by default, or `i128` minor units or
+/// [`crate::numeric::MinorUnits`] for embedders with other precision or
+/// speed needs, in a `Client` of their own or an `Engine<B>`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "B: Balance")]
+pub struct Client<B = Decimal> {
+    pub id: ClientId,
+    pub available: B,
+    pub held: Money<B>,
+    pub total: B,
     pub locked: bool,
-    deposit_transactions: HashMap<u32, Decimal>,
-    disputed_transactions: HashMap<u32, Decimal>,
+    /// Funds held for withdrawals the payout provider has not confirmed yet.
+    /// They are out of `available` but still part of `total`.
+    #[serde(default)]
+    pub pending: Money<B>,
+    deposit_transactions: TxMap<Money<B>>,
+    disputed_transactions: TxMap<Money<B>>,
+    #[cfg(feature = "std")]
+    #[serde(default)]
+    dispute_opened_at: TxMap<SystemTime>,
+    #[serde(default)]
+    dispute_opened_seq: TxMap<u64>,
+    #[serde(default)]
+    withdrawal_holds: TxMap<Money<B>>,
+    #[serde(default)]
+    lifetime_deposits: B,
+    #[serde(default)]
+    lifetime_withdrawals: B,
+    #[serde(default)]
+    chargeback_count: u32,
+    #[serde(default)]
+    deposit_count: u32,
+    #[serde(default)]
+    dispute_count: u32,
+    #[serde(default)]
+    capabilities: Capabilities,
 }
-impl Client {
-    pub fn new(id: u16) -> Self {
+impl<B: Balance> Client<B> {
+    pub fn new(id: ClientId) -> Self {
         Client {
             id,
-            available: dec!(0),
-            held: dec!(0),
-            total: dec!(0),
+            available: B::ZERO,
+            held: Money::ZERO,
+            total: B::ZERO,
             locked: false,
-            deposit_transactions: HashMap::new(),
-            disputed_transactions: HashMap::new(),
+            pending: Money::ZERO,
+            deposit_transactions: TxMap::new(),
+            disputed_transactions: TxMap::new(),
+            #[cfg(feature = "std")]
+            dispute_opened_at: TxMap::new(),
+            dispute_opened_seq: TxMap::new(),
+            withdrawal_holds: TxMap::new(),
+            lifetime_deposits: B::ZERO,
+            lifetime_withdrawals: B::ZERO,
+            chargeback_count: 0,
+            deposit_count: 0,
+            dispute_count: 0,
+            capabilities: Capabilities::default(),
         }
     }
 
-    pub fn deposit(&mut self, tx_id: u32, amount: Decimal) -> Result<(), ClientTransactionError> {
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    pub fn set_capability(&mut self, capability: Capability, allowed: bool) {
+        self.capabilities.set(capability, allowed);
+    }
+
+    fn require(&self, capability: Capability) -> Result<(), ClientTransactionError> {
+        if self.capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(ClientTransactionError::CapabilityRevoked {
+                client_id: self.id,
+                capability,
+            })
+        }
+    }
+
+    /// Fails as an operation needing `capability` would on this account,
+    /// without changing it, for rows accepted but not applied.
+    pub(crate) fn check_allowed(
+        &self,
+        capability: Option<Capability>,
+    ) -> Result<(), ClientTransactionError> {
         if self.locked {
             return Err(ClientTransactionError::AccountLocked { client_id: self.id });
         }
-        self.available += amount;
-        self.total += amount;
+        capability.map_or(Ok(()), |capability| self.require(capability))
+    }
+
+    pub fn deposit(&mut self, tx_id: TxId, amount: Money<B>) -> Result<(), ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountLocked { client_id: self.id });
+        }
+        self.require(Capability::Deposit)?;
+        let available = self.plus(self.available, amount)?;
+        let total = self.plus(self.total, amount)?;
+        let lifetime_deposits = self.plus(self.lifetime_deposits, amount)?;
+        self.available = available;
+        self.total = total;
+        self.lifetime_deposits = lifetime_deposits;
+        self.deposit_count += 1;
         self.deposit_transactions.insert(tx_id, amount);
         Ok(())
     }
 
-    pub fn withdraw(&mut self, amount: Decimal) -> Result<(), ClientTransactionError> {
+    pub fn credit(&mut self, amount: Money<B>) -> Result<(), ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountLocked { client_id: self.id });
+        }
+        let available = self.plus(self.available, amount)?;
+        let total = self.plus(self.total, amount)?;
+        self.available = available;
+        self.total = total;
+        Ok(())
+    }
+
+    /// Takes funds outright, such as a fee, even if that leaves available
+    /// funds below zero.
+    pub fn debit(&mut self, amount: Money<B>) -> Result<(), ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountLocked { client_id: self.id });
+        }
+        let available = self.minus(self.available, amount)?;
+        let total = self.minus(self.total, amount)?;
+        self.available = available;
+        self.total = total;
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, amount: Money<B>) -> Result<(), ClientTransactionError> {
+        self.withdraw_under(amount, WithdrawalPolicy::Available)
+    }
+
+    /// `withdraw`, checking funds the way `policy` says.
+    pub fn withdraw_under(
+        &mut self,
+        amount: Money<B>,
+        policy: WithdrawalPolicy,
+    ) -> Result<(), ClientTransactionError> {
+        self.check_withdrawal(amount, policy)?;
+        let available = self.minus(self.available, amount)?;
+        let total = self.minus(self.total, amount)?;
+        let lifetime_withdrawals = self.plus(self.lifetime_withdrawals, amount)?;
+        self.available = available;
+        self.total = total;
+        self.lifetime_withdrawals = lifetime_withdrawals;
+
+        Ok(())
+    }
+
+    /// First phase of a two-phase withdrawal: moves `amount` from
+    /// `available` to `pending` until the payout provider confirms
+    /// (`settle_withdrawal`) or fails (`cancel_withdrawal`) it, so the funds
+    /// cannot be spent twice in the meantime.
+    pub fn hold_withdrawal(
+        &mut self,
+        tx_id: TxId,
+        amount: Money<B>,
+    ) -> Result<(), ClientTransactionError> {
+        self.hold_withdrawal_under(tx_id, amount, WithdrawalPolicy::Available)
+    }
+
+    /// `hold_withdrawal`, checking funds the way `policy` says.
+    pub fn hold_withdrawal_under(
+        &mut self,
+        tx_id: TxId,
+        amount: Money<B>,
+        policy: WithdrawalPolicy,
+    ) -> Result<(), ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountLocked { client_id: self.id });
+        }
+        if self.withdrawal_holds.contains_key(&tx_id) {
+            return Err(ClientTransactionError::WithdrawalAlreadyHeld {
+                client_id: self.id,
+                tx_id,
+            });
+        }
+        self.check_withdrawal(amount, policy)?;
+        let available = self.minus(self.available, amount)?;
+        self.pending = self
+            .pending
+            .checked_add(amount)
+            .map_err(|source| self.arithmetic_error(source))?;
+        self.available = available;
+        self.withdrawal_holds.insert(tx_id, amount);
+        Ok(())
+    }
+
+    /// The payout went out: the held funds leave the account. Allowed on a
+    /// locked account, since the money has already been paid.
+    pub fn settle_withdrawal(&mut self, tx_id: TxId) -> Result<Money<B>, ClientTransactionError> {
+        let amount = self.withdrawal_hold(tx_id)?;
+        let total = self.minus(self.total, amount)?;
+        let lifetime_withdrawals = self.plus(self.lifetime_withdrawals, amount)?;
+        self.release_hold(tx_id, amount)?;
+        self.total = total;
+        self.lifetime_withdrawals = lifetime_withdrawals;
+        Ok(amount)
+    }
+
+    /// The payout failed: the held funds return to `available`.
+    pub fn cancel_withdrawal(&mut self, tx_id: TxId) -> Result<Money<B>, ClientTransactionError> {
+        let amount = self.withdrawal_hold(tx_id)?;
+        let available = self.plus(self.available, amount)?;
+        self.release_hold(tx_id, amount)?;
+        self.available = available;
+        Ok(amount)
+    }
+
+    /// Applies `operations` in order, all or nothing: if one fails, the
+    /// client is restored to how it was before the batch and the failing
+    /// operation's index comes back with the error. For partner files that
+    /// encode multi-leg operations which must never apply partially.
+    pub fn apply_batch(
+        &mut self,
+        operations: &[Operation<B>],
+    ) -> Result<(), (usize, ClientTransactionError)> {
+        let original = self.clone();
+        for (index, operation) in operations.iter().enumerate() {
+            let result = match *operation {
+                Operation::Deposit { tx, amount } => self.deposit(tx, amount),
+                Operation::Withdraw { amount } => self.withdraw(amount),
+                Operation::Credit { amount } => self.credit(amount),
+                Operation::Dispute { tx } => self.dispute(tx),
+                Operation::Resolve { tx } => self.resolve(tx),
+                Operation::Chargeback { tx } => self.chargeback(tx),
+                Operation::HoldWithdrawal { tx, amount } => self.hold_withdrawal(tx, amount),
+                Operation::SettleWithdrawal { tx } => self.settle_withdrawal(tx).map(drop),
+                Operation::CancelWithdrawal { tx } => self.cancel_withdrawal(tx).map(drop),
+            };
+            if let Err(err) = result {
+                *self = original;
+                return Err((index, err));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn withdrawal_holds(&self) -> impl Iterator<Item = (TxId, Money<B>)> + '_ {
+        self.withdrawal_holds
+            .iter()
+            .map(|(tx_id, amount)| (*tx_id, *amount))
+    }
+
+    fn check_withdrawal(
+        &self,
+        amount: Money<B>,
+        policy: WithdrawalPolicy,
+    ) -> Result<(), ClientTransactionError> {
         if self.locked {
             return Err(ClientTransactionError::AccountLocked { client_id: self.id });
         }
-        if self.available < amount {
-            return Err(ClientTransactionError::InsufficientAvailableFunds { client_id: self.id });
+        self.require(Capability::Withdraw)?;
+        let spendable = match policy {
+            WithdrawalPolicy::Available => self.available,
+            // Funds past what `B` holds cover any amount.
+            WithdrawalPolicy::Projected => {
+                Numeric::checked_add(self.available, self.held.value()).unwrap_or(B::MAX_MONEY)
+            }
+            WithdrawalPolicy::FreezeOnOpenDispute if !self.disputed_transactions.is_empty() => {
+                return Err(ClientTransactionError::WithdrawalsFrozen {
+                    client_id: self.id,
+                    disputes: self.disputed_transactions.len(),
+                });
+            }
+            WithdrawalPolicy::FreezeOnOpenDispute => self.available,
+        };
+        if spendable < amount.value() {
+            return Err(ClientTransactionError::InsufficientAvailableFunds {
+                client_id: self.id,
+                amount: amount.value().to_decimal(),
+                available: self.available.to_decimal(),
+                held: self.held.value().to_decimal(),
+            });
         }
-        self.available -= amount;
-        self.total -= amount;
+        Ok(())
+    }
 
+    fn withdrawal_hold(&self, tx_id: TxId) -> Result<Money<B>, ClientTransactionError> {
+        self.withdrawal_holds.get(&tx_id).copied().ok_or(
+            ClientTransactionError::UnknownWithdrawalHold {
+                client_id: self.id,
+                tx_id,
+            },
+        )
+    }
+
+    fn release_hold(
+        &mut self,
+        tx_id: TxId,
+        amount: Money<B>,
+    ) -> Result<(), ClientTransactionError> {
+        self.pending = self
+            .pending
+            .checked_sub(amount)
+            .map_err(|source| self.arithmetic_error(source))?;
+        self.withdrawal_holds.remove(&tx_id);
         Ok(())
     }
 
-    pub fn dispute(&mut self, tx_id: u32) -> Result<(), ClientTransactionError> {
+    #[cfg(feature = "std")]
+    pub fn dispute(&mut self, tx_id: TxId) -> Result<(), ClientTransactionError> {
+        self.dispute_at(tx_id, SystemClock.now())
+    }
+
+    /// Without `std` there is no clock, so disputes carry no opening time.
+    #[cfg(not(feature = "std"))]
+    pub fn dispute(&mut self, tx_id: TxId) -> Result<(), ClientTransactionError> {
+        self.open_dispute(tx_id)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn dispute_at(
+        &mut self,
+        tx_id: TxId,
+        opened_at: SystemTime,
+    ) -> Result<(), ClientTransactionError> {
+        self.open_dispute(tx_id)?;
+        self.dispute_opened_at.insert(tx_id, opened_at);
+        Ok(())
+    }
+
+    fn open_dispute(&mut self, tx_id: TxId) -> Result<(), ClientTransactionError> {
         if self.locked {
             return Err(ClientTransactionError::AccountLocked { client_id: self.id });
         }
+        self.require(Capability::Dispute)?;
         if self.disputed_transactions.contains_key(&tx_id) {
             return Err(ClientTransactionError::AlreadyInDispute {
                 client_id: self.id,
@@ -65,13 +459,13 @@ impl Client {
             },
         )?;
 
-        self.available -= amount;
-        self.held += amount;
+        self.hold(amount)?;
         self.disputed_transactions.insert(tx_id, amount);
+        self.dispute_count += 1;
         Ok(())
     }
 
-    pub fn resolve(&mut self, tx_id: u32) -> Result<(), ClientTransactionError> {
+    pub fn resolve(&mut self, tx_id: TxId) -> Result<(), ClientTransactionError> {
         if self.locked {
             return Err(ClientTransactionError::AccountLocked { client_id: self.id });
         }
@@ -82,23 +476,121 @@ impl Client {
             },
         )?;
 
-        if self.held < amount {
-            return Err(ClientTransactionError::InsufficientHeldFunds {
+        let available = self.plus(self.available, amount)?;
+        self.release_held("resolve", amount)?;
+        self.available = available;
+        self.close_dispute(tx_id);
+        Ok(())
+    }
+
+    pub fn chargeback(&mut self, tx_id: TxId) -> Result<(), ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountAlreadyLocked { client_id: self.id });
+        }
+        let amount = self.disputed_transactions.get(&tx_id).cloned().ok_or(
+            ClientTransactionError::NotInDispute {
+                client_id: self.id,
+                tx_id,
+            },
+        )?;
+
+        let total = self.minus(self.total, amount)?;
+        self.release_held("chargeback", amount)?;
+        self.total = total;
+        self.locked = true;
+        self.chargeback_count += 1;
+        self.close_dispute(tx_id);
+        Ok(())
+    }
+
+    pub fn reverse_deposit(&mut self, tx_id: TxId) -> Result<Money<B>, ClientTransactionError> {
+        if self.disputed_transactions.contains_key(&tx_id) {
+            return Err(ClientTransactionError::AlreadyInDispute {
                 client_id: self.id,
-                action: "resolve",
+                tx_id,
             });
         }
+        let amount = self.deposit_transactions.get(&tx_id).copied().ok_or(
+            ClientTransactionError::UnknownTransaction {
+                client_id: self.id,
+                tx_id,
+            },
+        )?;
 
-        self.held -= amount;
-        self.available += amount;
-        self.disputed_transactions.remove(&tx_id);
-        Ok(())
+        let available = self.minus(self.available, amount)?;
+        let total = self.minus(self.total, amount)?;
+        let lifetime_deposits = self.minus(self.lifetime_deposits, amount)?;
+        self.deposit_transactions.remove(&tx_id);
+        self.available = available;
+        self.total = total;
+        self.lifetime_deposits = lifetime_deposits;
+        self.deposit_count = self.deposit_count.saturating_sub(1);
+        Ok(amount)
     }
 
-    pub fn chargeback(&mut self, tx_id: u32) -> Result<(), ClientTransactionError> {
-        if self.locked {
-            return Err(ClientTransactionError::AccountAlreadyLocked { client_id: self.id });
+    /// Folds `other`, a duplicate record of the same customer, into this
+    /// account: balances and lifetime totals add up, deposits, open disputes
+    /// and withdrawal holds move over, and the result is locked if either
+    /// account was. A transaction id known to both accounts cannot be told
+    /// apart afterwards, so it fails the merge and leaves both unchanged.
+    pub fn merge(&mut self, other: &Client<B>) -> Result<(), ClientTransactionError> {
+        let collision = other
+            .deposit_transactions
+            .keys()
+            .chain(other.withdrawal_holds.keys())
+            .find(|tx_id| {
+                self.deposit_transactions.contains_key(tx_id)
+                    || self.withdrawal_holds.contains_key(tx_id)
+            });
+        if let Some(tx_id) = collision {
+            return Err(ClientTransactionError::MergeCollision {
+                client_id: self.id,
+                from: other.id,
+                tx_id: *tx_id,
+            });
         }
+        let held = self
+            .held
+            .checked_add(other.held)
+            .map_err(|source| self.arithmetic_error(source))?;
+        let pending = self
+            .pending
+            .checked_add(other.pending)
+            .map_err(|source| self.arithmetic_error(source))?;
+        let available = self.sum(self.available, other.available)?;
+        let total = self.sum(self.total, other.total)?;
+        let lifetime_deposits = self.sum(self.lifetime_deposits, other.lifetime_deposits)?;
+        let lifetime_withdrawals =
+            self.sum(self.lifetime_withdrawals, other.lifetime_withdrawals)?;
+
+        self.available = available;
+        self.held = held;
+        self.pending = pending;
+        self.total = total;
+        self.locked |= other.locked;
+        self.capabilities = Capabilities {
+            can_withdraw: self.capabilities.can_withdraw && other.capabilities.can_withdraw,
+            can_deposit: self.capabilities.can_deposit && other.capabilities.can_deposit,
+            dispute_allowed: self.capabilities.dispute_allowed
+                && other.capabilities.dispute_allowed,
+        };
+        self.lifetime_deposits = lifetime_deposits;
+        self.lifetime_withdrawals = lifetime_withdrawals;
+        self.chargeback_count += other.chargeback_count;
+        self.deposit_count += other.deposit_count;
+        self.dispute_count += other.dispute_count;
+        self.deposit_transactions
+            .extend(&other.deposit_transactions);
+        self.disputed_transactions
+            .extend(&other.disputed_transactions);
+        #[cfg(feature = "std")]
+        self.dispute_opened_at.extend(&other.dispute_opened_at);
+        self.dispute_opened_seq.extend(&other.dispute_opened_seq);
+        self.withdrawal_holds.extend(&other.withdrawal_holds);
+        Ok(())
+    }
+
+    pub fn force_resolve(&mut self, tx_id: TxId) -> Result<Money<B>, ClientTransactionError> {
         let amount = self.disputed_transactions.get(&tx_id).cloned().ok_or(
             ClientTransactionError::NotInDispute {
                 client_id: self.id,
@@ -106,59 +598,467 @@ impl Client {
             },
         )?;
 
-        if self.held < amount {
-            return Err(ClientTransactionError::InsufficientHeldFunds {
+        let available = self.plus(self.available, amount)?;
+        self.release_held("force resolve", amount)?;
+        self.available = available;
+        self.close_dispute(tx_id);
+        Ok(amount)
+    }
+
+    /// Resolves every open dispute at once, releasing all held funds back to
+    /// `available` one dispute at a time. Returns the released disputes in
+    /// transaction order. On failure the account is unchanged.
+    pub fn resolve_all(&mut self) -> Result<Vec<(TxId, Money<B>)>, ClientTransactionError> {
+        if self.locked {
+            return Err(ClientTransactionError::AccountLocked { client_id: self.id });
+        }
+        let mut released: Vec<(TxId, Money<B>)> = self.open_disputes().collect();
+        released.sort_unstable_by_key(|(tx_id, _)| *tx_id);
+
+        let (available, held) = released.iter().try_fold(
+            (self.available, self.held),
+            |(available, held), (_, amount)| {
+                let held = self.without_held(held, "resolve all", *amount)?;
+                Ok((self.plus(available, *amount)?, held))
+            },
+        )?;
+        self.available = available;
+        self.held = held;
+        for (tx_id, _) in &released {
+            self.close_dispute(*tx_id);
+        }
+        Ok(released)
+    }
+
+    /// How long `tx_id` has been under dispute as of `now`, if it is open.
+    #[cfg(feature = "std")]
+    pub fn dispute_age(&self, tx_id: TxId, now: SystemTime) -> Option<Duration> {
+        if !self.disputed_transactions.contains_key(&tx_id) {
+            return None;
+        }
+        let opened_at = self.dispute_opened_at.get(&tx_id)?;
+        Some(now.duration_since(*opened_at).unwrap_or_default())
+    }
+
+    /// When the dispute on `tx_id` was opened, if it is open and the time
+    /// was recorded (snapshots from older versions do not carry it).
+    #[cfg(feature = "std")]
+    pub fn dispute_opened(&self, tx_id: TxId) -> Option<SystemTime> {
+        self.dispute_opened_at.get(&tx_id).copied()
+    }
+
+    /// Sequence number of the transaction that opened the dispute on `tx_id`,
+    /// if it is open and was opened through the engine.
+    pub fn dispute_sequence(&self, tx_id: TxId) -> Option<u64> {
+        self.dispute_opened_seq.get(&tx_id).copied()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn set_dispute_sequence(&mut self, tx_id: TxId, sequence: u64) {
+        if self.disputed_transactions.contains_key(&tx_id) {
+            self.dispute_opened_seq.insert(tx_id, sequence);
+        }
+    }
+
+    /// Moves the sequence numbers of open disputes `offset` later, for a
+    /// client whose engine is merged after another one.
+    #[cfg(feature = "std")]
+    pub(crate) fn shift_dispute_sequences(&mut self, offset: u64) {
+        for sequence in self.dispute_opened_seq.values_mut() {
+            *sequence += offset;
+        }
+    }
+
+    pub fn open_disputes(&self) -> impl Iterator<Item = (TxId, Money<B>)> + '_ {
+        self.disputed_transactions
+            .iter()
+            .map(|(tx_id, amount)| (*tx_id, *amount))
+    }
+
+    /// Sum of every deposit applied, including ones later charged back but
+    /// not ones reversed by an operator.
+    pub fn lifetime_deposits(&self) -> B {
+        self.lifetime_deposits
+    }
+
+    /// Sum of every withdrawal applied, counting holds once settled.
+    pub fn lifetime_withdrawals(&self) -> B {
+        self.lifetime_withdrawals
+    }
+
+    pub fn chargeback_count(&self) -> u32 {
+        self.chargeback_count
+    }
+
+    /// Deposits applied, including ones later charged back but not ones
+    /// reversed by an operator.
+    pub fn deposit_count(&self) -> u32 {
+        self.deposit_count
+    }
+
+    /// Disputes ever opened, however they were settled.
+    pub fn dispute_count(&self) -> u32 {
+        self.dispute_count
+    }
+
+    /// Deposits the client still remembers, in no particular order.
+    pub fn deposits(&self) -> impl Iterator<Item = (TxId, Money<B>)> + '_ {
+        self.deposit_transactions
+            .iter()
+            .map(|(tx_id, amount)| (*tx_id, *amount))
+    }
+
+    /// Whether `total == available + held + pending`, as every operation
+    /// here keeps it. `false` when the sum does not fit `B`.
+    pub(crate) fn balances_add_up(&self) -> bool {
+        Numeric::checked_add(self.available, self.held.value())
+            .and_then(|sum| Numeric::checked_add(sum, self.pending.value()))
+            == Some(self.total)
+    }
+
+    /// Rough heap and inline footprint, for memory budgeting. Map entries are
+    /// counted by capacity, with a word of hashing overhead each.
+    #[cfg(feature = "std")]
+    pub fn approximate_size(&self) -> usize {
+        const OVERHEAD: usize = size_of::<usize>();
+        let money_entry = size_of::<TxId>() + size_of::<Money<B>>() + OVERHEAD;
+        size_of::<Self>()
+            + (self.deposit_transactions.capacity()
+                + self.disputed_transactions.capacity()
+                + self.withdrawal_holds.capacity())
+                * money_entry
+            + self.dispute_opened_at.capacity()
+                * (size_of::<TxId>() + size_of::<SystemTime>() + OVERHEAD)
+            + self.dispute_opened_seq.capacity() * (size_of::<TxId>() + size_of::<u64>() + OVERHEAD)
+    }
+
+    /// Forgets undisputed deposits with a tx id below `cutoff`, which can no
+    /// longer be disputed afterwards. Returns how many were dropped.
+    #[cfg(feature = "std")]
+    pub(crate) fn forget_deposits_before(&mut self, cutoff: TxId) -> usize {
+        let before = self.deposit_transactions.len();
+        let disputed = &self.disputed_transactions;
+        self.deposit_transactions
+            .retain(|tx_id, _| *tx_id >= cutoff || disputed.contains_key(tx_id));
+        self.deposit_transactions.shrink_to_fit();
+        before - self.deposit_transactions.len()
+    }
+
+    /// Forgets every deposit the client remembers, keeping balances and
+    /// lifetime totals. Refused while disputes or withdrawal holds are open,
+    /// since those still need their transactions. Returns how many deposits
+    /// were dropped.
+    #[cfg(feature = "std")]
+    pub(crate) fn forget_history(&mut self) -> Result<usize, ClientTransactionError> {
+        let open = self.disputed_transactions.len() + self.withdrawal_holds.len();
+        if open > 0 {
+            return Err(ClientTransactionError::OpenTransactions {
                 client_id: self.id,
-                action: "chargeback",
+                open,
             });
         }
+        let forgotten = self.deposit_transactions.len();
+        self.deposit_transactions = TxMap::new();
+        Ok(forgotten)
+    }
 
-        self.held -= amount;
-        self.total -= amount;
-        self.locked = true;
+    fn close_dispute(&mut self, tx_id: TxId) {
         self.disputed_transactions.remove(&tx_id);
+        #[cfg(feature = "std")]
+        self.dispute_opened_at.remove(&tx_id);
+        self.dispute_opened_seq.remove(&tx_id);
+    }
+
+    /// Moves `amount` from `available` into `held` for a dispute. With
+    /// [`Client::release_held`], the only way dispute bookkeeping touches
+    /// `held`, so it always stays a valid [`Money`]: never negative and
+    /// never more than four decimal places.
+    fn hold(&mut self, amount: Money<B>) -> Result<(), ClientTransactionError> {
+        let available = self.minus(self.available, amount)?;
+        self.held = self
+            .held
+            .checked_add(amount)
+            .map_err(|source| self.arithmetic_error(source))?;
+        self.available = available;
         Ok(())
     }
+
+    /// Takes `amount` out of `held`, leaving the caller to say where it goes.
+    /// Fails without changing anything when less than `amount` is held.
+    fn release_held(
+        &mut self,
+        action: &'static str,
+        amount: Money<B>,
+    ) -> Result<(), ClientTransactionError> {
+        self.held = self.without_held(self.held, action, amount)?;
+        Ok(())
+    }
+
+    /// `held - amount`, failing `action` when `held` does not cover it.
+    fn without_held(
+        &self,
+        held: Money<B>,
+        action: &'static str,
+        amount: Money<B>,
+    ) -> Result<Money<B>, ClientTransactionError> {
+        held.checked_sub(amount).map_err(|source| match source {
+            MoneyError::Negative(_) => self.insufficient_held_funds(action, amount.value()),
+            source => self.arithmetic_error(source),
+        })
+    }
+
+    /// `balance + amount`, or an error when `B` cannot hold the result.
+    fn plus(&self, balance: B, amount: Money<B>) -> Result<B, ClientTransactionError> {
+        self.sum(balance, amount.value())
+    }
+
+    fn minus(&self, balance: B, amount: Money<B>) -> Result<B, ClientTransactionError> {
+        Numeric::checked_sub(balance, amount.value())
+            .ok_or_else(|| self.arithmetic_error(MoneyError::TooLarge(amount.value().to_decimal())))
+    }
+
+    fn sum(&self, balance: B, other: B) -> Result<B, ClientTransactionError> {
+        Numeric::checked_add(balance, other)
+            .ok_or_else(|| self.arithmetic_error(MoneyError::TooLarge(other.to_decimal())))
+    }
+
+    fn insufficient_held_funds(&self, action: &'static str, amount: B) -> ClientTransactionError {
+        ClientTransactionError::InsufficientHeldFunds {
+            client_id: self.id,
+            action,
+            amount: amount.to_decimal(),
+            available: self.available.to_decimal(),
+            held: self.held.value().to_decimal(),
+        }
+    }
+
+    fn arithmetic_error(&self, source: MoneyError) -> ClientTransactionError {
+        ClientTransactionError::Arithmetic {
+            client_id: self.id,
+            source,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::errors::ClientTransactionError;
+    use crate::errors::{ClientTransactionError, RowError};
+    use crate::money::MAX_MAGNITUDE;
+    use crate::numeric::MinorUnits;
+
+    fn money(value: Decimal) -> Money {
+        Money::new(value).unwrap()
+    }
+
+    #[test]
+    fn balances_past_the_range_of_the_type_fail_without_changing_the_account() {
+        let near_limit = Money::new(MinorUnits::MAX_MONEY).unwrap();
+        let mut client: Client<MinorUnits> = Client::new(ClientId(1));
+        client.deposit(TxId(1), near_limit).unwrap();
+
+        assert!(matches!(
+            client.deposit(TxId(2), near_limit),
+            Err(ClientTransactionError::Arithmetic { .. })
+        ));
+        assert!(matches!(
+            client.credit(Money::new(MinorUnits::from_minor(1)).unwrap()),
+            Err(ClientTransactionError::Arithmetic { .. })
+        ));
+        assert_eq!(
+            (client.available, client.total, client.lifetime_deposits()),
+            (near_limit.value(), near_limit.value(), near_limit.value())
+        );
+        client.withdraw(near_limit).unwrap();
+        assert_eq!(client.total, MinorUnits::ZERO);
+    }
+
+    #[test]
+    fn withdrawal_policies_decide_whether_held_funds_count() {
+        let disputed = || {
+            let mut client = Client::new(ClientId(1));
+            client.deposit(TxId(1), money(dec!(10))).unwrap();
+            client.deposit(TxId(2), money(dec!(4))).unwrap();
+            client.dispute(TxId(1)).unwrap();
+            client
+        };
+
+        let mut client = disputed();
+        assert!(matches!(
+            client.withdraw_under(money(dec!(6)), WithdrawalPolicy::Available),
+            Err(ClientTransactionError::InsufficientAvailableFunds { .. })
+        ));
+
+        client
+            .withdraw_under(money(dec!(12)), WithdrawalPolicy::Projected)
+            .unwrap();
+        assert_eq!(
+            (client.available, client.held.value(), client.total),
+            (dec!(-8), dec!(10), dec!(2))
+        );
+        assert!(
+            client
+                .withdraw_under(money(dec!(3)), WithdrawalPolicy::Projected)
+                .is_err()
+        );
+        client.resolve(TxId(1)).unwrap();
+        assert_eq!(client.available, dec!(2));
+
+        let mut client = disputed();
+        assert_eq!(
+            client.hold_withdrawal_under(
+                TxId(2),
+                money(dec!(1)),
+                WithdrawalPolicy::FreezeOnOpenDispute
+            ),
+            Err(ClientTransactionError::WithdrawalsFrozen {
+                client_id: ClientId(1),
+                disputes: 1
+            })
+        );
+        client.resolve(TxId(1)).unwrap();
+        client
+            .withdraw_under(money(dec!(1)), WithdrawalPolicy::FreezeOnOpenDispute)
+            .unwrap();
+    }
+
+    #[test]
+    fn projected_withdrawals_draw_on_held_funds_while_a_dispute_is_open() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        client.deposit(TxId(2), money(dec!(4))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+
+        client
+            .hold_withdrawal_under(TxId(3), money(dec!(14)), WithdrawalPolicy::Projected)
+            .unwrap();
+        assert_eq!(
+            (
+                client.available,
+                client.held.value(),
+                client.pending.value(),
+                client.total
+            ),
+            (dec!(-10), dec!(10), dec!(14), dec!(14))
+        );
+        assert!(matches!(
+            client.withdraw_under(money(dec!(0.0001)), WithdrawalPolicy::Projected),
+            Err(ClientTransactionError::InsufficientAvailableFunds { .. })
+        ));
+        assert_eq!((client.available, client.total), (dec!(-10), dec!(14)));
+
+        client.settle_withdrawal(TxId(3)).unwrap();
+        client.chargeback(TxId(1)).unwrap();
+        assert_eq!(
+            (client.available, client.held.value(), client.total),
+            (dec!(-10), dec!(0), dec!(-10))
+        );
+        assert!(client.locked);
+    }
+
+    #[test]
+    fn frozen_withdrawals_leave_the_account_untouched_until_disputes_close() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        client.deposit(TxId(2), money(dec!(4))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.dispute(TxId(2)).unwrap();
+        client.deposit(TxId(3), money(dec!(5))).unwrap();
+
+        assert_eq!(
+            client.withdraw_under(money(dec!(1)), WithdrawalPolicy::FreezeOnOpenDispute),
+            Err(ClientTransactionError::WithdrawalsFrozen {
+                client_id: ClientId(1),
+                disputes: 2
+            })
+        );
+        assert_eq!(
+            (client.available, client.held.value(), client.total),
+            (dec!(5), dec!(14), dec!(19))
+        );
+
+        client.resolve(TxId(1)).unwrap();
+        assert_eq!(
+            client.withdraw_under(money(dec!(1)), WithdrawalPolicy::FreezeOnOpenDispute),
+            Err(ClientTransactionError::WithdrawalsFrozen {
+                client_id: ClientId(1),
+                disputes: 1
+            })
+        );
+
+        client.resolve(TxId(2)).unwrap();
+        client
+            .withdraw_under(money(dec!(19)), WithdrawalPolicy::FreezeOnOpenDispute)
+            .unwrap();
+        assert_eq!((client.available, client.total), (dec!(0), dec!(0)));
+    }
 
     #[test]
     fn successful_deposit_and_stores_transaction() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(10.5)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10.5))).unwrap();
 
         assert_eq!(client.available, dec!(10.5));
         assert_eq!(client.total, dec!(10.5));
         assert_eq!(client.held, dec!(0));
         assert!(!client.locked);
-        assert!(client.deposit_transactions.contains_key(&1));
+        assert!(client.deposit_transactions.contains_key(&TxId(1)));
     }
 
     #[test]
     fn deposit_rejected_when_account_locked() {
-        let mut client = Client::new(1);
+        let mut client = Client::new(ClientId(1));
         client.locked = true;
 
-        let result = client.deposit(1, dec!(5));
+        let result = client.deposit(TxId(1), money(dec!(5)));
 
         assert!(matches!(
             result,
-            Err(ClientTransactionError::AccountLocked { client_id: 1 })
+            Err(ClientTransactionError::AccountLocked {
+                client_id: ClientId(1)
+            })
         ));
         assert_eq!(client.available, dec!(0));
         assert_eq!(client.total, dec!(0));
         assert!(client.deposit_transactions.is_empty());
     }
 
+    #[test]
+    fn withdrawal_hold_reserves_funds_until_settled_or_cancelled() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        client.hold_withdrawal(TxId(2), money(dec!(6))).unwrap();
+        client.hold_withdrawal(TxId(3), money(dec!(3))).unwrap();
+
+        assert!(matches!(
+            client.withdraw(money(dec!(2))),
+            Err(ClientTransactionError::InsufficientAvailableFunds { .. })
+        ));
+        assert_eq!(
+            (client.available, client.pending, client.total),
+            (dec!(1), money(dec!(9)), dec!(10))
+        );
+
+        assert_eq!(client.settle_withdrawal(TxId(2)), Ok(money(dec!(6))));
+        assert_eq!(client.cancel_withdrawal(TxId(3)), Ok(money(dec!(3))));
+        assert_eq!(
+            (client.available, client.pending, client.total),
+            (dec!(4), Money::ZERO, dec!(4))
+        );
+        assert_eq!(
+            client.settle_withdrawal(TxId(2)),
+            Err(ClientTransactionError::UnknownWithdrawalHold {
+                client_id: ClientId(1),
+                tx_id: TxId(2)
+            })
+        );
+    }
+
     #[test]
     fn successful_withdraw_deducts_available_balance() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(10)).unwrap();
-        let result = client.withdraw(dec!(4));
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        let result = client.withdraw(money(dec!(4)));
 
         assert!(result.is_ok());
         assert_eq!(client.available, dec!(6));
@@ -168,29 +1068,49 @@ mod tests {
 
     #[test]
     fn withdraw_rejected_insufficiente_funds() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(5)).unwrap();
-        let result = client.withdraw(dec!(7));
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        let result = client.withdraw(money(dec!(7)));
 
         assert!(matches!(
             result,
-            Err(ClientTransactionError::InsufficientAvailableFunds { client_id: 1 })
+            Err(ClientTransactionError::InsufficientAvailableFunds {
+                client_id: ClientId(1),
+                ..
+            })
         ));
         assert_eq!(client.available, dec!(5));
         assert_eq!(client.total, dec!(5));
     }
 
+    #[test]
+    fn rejected_withdrawal_reports_amount_balances_and_row() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.deposit(TxId(2), money(dec!(2))).unwrap();
+        client.dispute(TxId(2)).unwrap();
+
+        let source = client.withdraw(money(dec!(7))).unwrap_err();
+
+        assert_eq!(
+            RowError { row: 4, source }.to_string(),
+            "Row 4: Client 1: insufficient available funds to withdraw 7 (available 5, held 2)"
+        );
+    }
+
     #[test]
     fn withdraw_rejected_when_account_locked() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(6)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(6))).unwrap();
         client.locked = true;
 
-        let result = client.withdraw(dec!(2));
+        let result = client.withdraw(money(dec!(2)));
 
         assert!(matches!(
             result,
-            Err(ClientTransactionError::AccountLocked { client_id: 1 })
+            Err(ClientTransactionError::AccountLocked {
+                client_id: ClientId(1)
+            })
         ));
         assert_eq!(client.available, dec!(6));
         assert_eq!(client.total, dec!(6));
@@ -198,58 +1118,60 @@ mod tests {
 
     #[test]
     fn dispute_moves_deposit_to_held_balance() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(9)).unwrap();
-        let result = client.dispute(1);
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(9))).unwrap();
+        let result = client.dispute(TxId(1));
 
         assert!(result.is_ok());
         assert_eq!(client.available, dec!(0));
         assert_eq!(client.held, dec!(9));
         assert_eq!(client.total, dec!(9));
-        assert!(client.disputed_transactions.contains_key(&1));
+        assert!(client.disputed_transactions.contains_key(&TxId(1)));
     }
 
     #[test]
     fn dispute_rejected_unknown_transactions() {
-        let mut client = Client::new(1);
-        let result = client.dispute(999);
+        let mut client: Client = Client::new(ClientId(1));
+        let result = client.dispute(TxId(999));
 
         assert!(matches!(
             result,
             Err(ClientTransactionError::UnknownTransaction {
-                client_id: 1,
-                tx_id: 999
+                client_id: ClientId(1),
+                tx_id: TxId(999)
             })
         ));
     }
 
     #[test]
     fn dispute_supports_multiple_transactions_in_parallel() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(6)).unwrap();
-        client.deposit(2, dec!(4)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(6))).unwrap();
+        client.deposit(TxId(2), money(dec!(4))).unwrap();
 
-        client.dispute(1).unwrap();
-        client.dispute(2).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.dispute(TxId(2)).unwrap();
 
         assert_eq!(client.available, dec!(0));
         assert_eq!(client.held, dec!(10));
         assert_eq!(client.total, dec!(10));
-        assert!(client.disputed_transactions.contains_key(&1));
-        assert!(client.disputed_transactions.contains_key(&2));
+        assert!(client.disputed_transactions.contains_key(&TxId(1)));
+        assert!(client.disputed_transactions.contains_key(&TxId(2)));
     }
 
     #[test]
     fn dispute_rejected_when_account_locked() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(6)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(6))).unwrap();
         client.locked = true;
 
-        let result = client.dispute(1);
+        let result = client.dispute(TxId(1));
 
         assert!(matches!(
             result,
-            Err(ClientTransactionError::AccountLocked { client_id: 1 })
+            Err(ClientTransactionError::AccountLocked {
+                client_id: ClientId(1)
+            })
         ));
         assert!(client.disputed_transactions.is_empty());
         assert_eq!(client.held, dec!(0));
@@ -257,11 +1179,11 @@ mod tests {
 
     #[test]
     fn dispute_reallocates_funds_when_available_balance_is_negative() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(5)).unwrap();
-        client.withdraw(dec!(4)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.withdraw(money(dec!(4))).unwrap();
 
-        let result = client.dispute(1);
+        let result = client.dispute(TxId(1));
 
         assert!(result.is_ok());
         assert_eq!(client.available, dec!(-4));
@@ -271,134 +1193,392 @@ mod tests {
 
     #[test]
     fn resolve_releases_held_funds_back_to_available() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(8)).unwrap();
-        client.dispute(1).unwrap();
-        let result = client.resolve(1);
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(8))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        let result = client.resolve(TxId(1));
 
         assert!(result.is_ok());
         assert_eq!(client.available, dec!(8));
         assert_eq!(client.held, dec!(0));
         assert_eq!(client.total, dec!(8));
-        assert!(!client.disputed_transactions.contains_key(&1));
+        assert!(!client.disputed_transactions.contains_key(&TxId(1)));
+    }
+
+    #[test]
+    fn resolve_all_releases_every_open_dispute() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(3), money(dec!(2))).unwrap();
+        client.deposit(TxId(1), money(dec!(8))).unwrap();
+        client.deposit(TxId(2), money(dec!(1))).unwrap();
+        client.dispute(TxId(3)).unwrap();
+        client.dispute(TxId(1)).unwrap();
+
+        let released = client.resolve_all().unwrap();
+
+        assert_eq!(
+            released,
+            vec![(TxId(1), money(dec!(8))), (TxId(3), money(dec!(2)))]
+        );
+        assert_eq!(client.available, dec!(11));
+        assert_eq!(client.held, dec!(0));
+        assert_eq!(client.open_disputes().count(), 0);
+    }
+
+    #[test]
+    fn resolve_all_reports_the_dispute_held_funds_do_not_cover() {
+        let most = Money::new(MinorUnits::from_minor(i64::MAX / 5 * 3)).unwrap();
+        let mut client: Client<MinorUnits> = Client::new(ClientId(1));
+        client.deposit(TxId(1), most).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        // A second dispute that no deposit backs, as a hand-edited snapshot
+        // could hold; both together exceed what the type can count.
+        client.disputed_transactions.insert(TxId(2), most);
+
+        match client.resolve_all() {
+            Err(ClientTransactionError::InsufficientHeldFunds { amount, .. }) => {
+                assert_eq!(amount, most.value().to_decimal())
+            }
+            other => panic!("expected insufficient held funds, got {other:?}"),
+        }
+        assert_eq!(client.held, most);
+        assert_eq!(client.open_disputes().count(), 2);
     }
 
     #[test]
     fn resolve_fails_transactions_not_in_dispute() {
-        let mut client = Client::new(1);
-        let result = client.resolve(999);
+        let mut client: Client = Client::new(ClientId(1));
+        let result = client.resolve(TxId(999));
 
         assert!(matches!(
             result,
             Err(ClientTransactionError::NotInDispute {
-                client_id: 1,
-                tx_id: 999
+                client_id: ClientId(1),
+                tx_id: TxId(999)
             })
         ));
     }
 
     #[test]
     fn resolve_rejected_when_account_locked() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(8)).unwrap();
-        client.dispute(1).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(8))).unwrap();
+        client.dispute(TxId(1)).unwrap();
         client.locked = true;
 
-        let result = client.resolve(1);
+        let result = client.resolve(TxId(1));
 
         assert!(matches!(
             result,
-            Err(ClientTransactionError::AccountLocked { client_id: 1 })
+            Err(ClientTransactionError::AccountLocked {
+                client_id: ClientId(1)
+            })
         ));
         assert_eq!(client.held, dec!(8));
-        assert!(client.disputed_transactions.contains_key(&1));
+        assert!(client.disputed_transactions.contains_key(&TxId(1)));
     }
 
     #[test]
     fn resolve_rejected_when_held_balance_is_insufficient() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(5)).unwrap();
-        client.dispute(1).unwrap();
-        client.held = dec!(1);
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.held = money(dec!(1));
 
-        let result = client.resolve(1);
+        let result = client.resolve(TxId(1));
 
         assert!(matches!(
             result,
             Err(ClientTransactionError::InsufficientHeldFunds {
-                client_id: 1,
-                action: "resolve"
+                client_id: ClientId(1),
+                action: "resolve",
+                ..
             })
         ));
-        assert!(client.disputed_transactions.contains_key(&1));
+        assert!(client.disputed_transactions.contains_key(&TxId(1)));
+    }
+
+    #[test]
+    fn every_release_path_fails_cleanly_when_too_little_is_held() {
+        type Release = fn(&mut Client) -> Result<(), ClientTransactionError>;
+        let releases: [(&str, Release); 4] = [
+            ("resolve", |client| client.resolve(TxId(1))),
+            ("chargeback", |client| client.chargeback(TxId(1))),
+            ("force resolve", |client| {
+                client.force_resolve(TxId(1)).map(drop)
+            }),
+            ("resolve all", |client| client.resolve_all().map(drop)),
+        ];
+        for (name, release) in releases {
+            let mut client = Client::new(ClientId(1));
+            client.deposit(TxId(1), money(dec!(5))).unwrap();
+            client.dispute(TxId(1)).unwrap();
+            client.held = money(dec!(4.9999));
+            let before = (client.available, client.held, client.total, client.locked);
+
+            let result = release(&mut client);
+
+            assert!(
+                matches!(
+                    result,
+                    Err(ClientTransactionError::InsufficientHeldFunds { action, .. })
+                        if action == name
+                ),
+                "{name}: {result:?}"
+            );
+            assert_eq!(
+                (client.available, client.held, client.total, client.locked),
+                before,
+                "{name}"
+            );
+            assert_eq!(client.open_disputes().count(), 1, "{name}");
+        }
+    }
+
+    #[test]
+    fn adversarial_sequences_keep_held_equal_to_open_disputes() {
+        // A fixed linear congruential generator, so failures reproduce.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: u64| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) % bound
+        };
+        for _ in 0..200 {
+            let mut client = Client::new(ClientId(1));
+            for _ in 0..100 {
+                let tx = next(6) as u32;
+                let amount = money(Decimal::new(next(100_000) as i64, 4));
+                let _ = match next(11) {
+                    0 | 1 => client.deposit(TxId(tx), amount),
+                    2 => client.withdraw(amount),
+                    3 | 4 => client.dispute(TxId(tx)),
+                    5 => client.resolve(TxId(tx)),
+                    6 => client.chargeback(TxId(tx)),
+                    7 => client.force_resolve(TxId(tx)).map(drop),
+                    8 => client.resolve_all().map(drop),
+                    9 => client.reverse_deposit(TxId(tx)).map(drop),
+                    _ => client
+                        .hold_withdrawal(TxId(tx), amount)
+                        .and_then(|()| client.cancel_withdrawal(TxId(tx)).map(drop)),
+                };
+
+                let disputed: Decimal = client.open_disputes().map(|(_, a)| a.value()).sum();
+                assert_eq!(client.held, disputed);
+                assert!(!client.held.value().is_sign_negative());
+                assert!(client.held.value().normalize().scale() <= crate::money::MAX_SCALE);
+                assert_eq!(
+                    client.total,
+                    client.available + client.held.value() + client.pending.value()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn batches_apply_all_or_nothing() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+
+        let transfer = [
+            Operation::Withdraw {
+                amount: money(dec!(4)),
+            },
+            Operation::Deposit {
+                tx: TxId(2),
+                amount: money(dec!(1)),
+            },
+        ];
+        client.apply_batch(&transfer).unwrap();
+        assert_eq!(client.available, dec!(7));
+
+        let failing = [
+            Operation::Deposit {
+                tx: TxId(3),
+                amount: money(dec!(5)),
+            },
+            Operation::Dispute { tx: TxId(1) },
+            Operation::Withdraw {
+                amount: money(dec!(100)),
+            },
+        ];
+        let result = client.apply_batch(&failing);
+
+        assert!(matches!(
+            result,
+            Err((2, ClientTransactionError::InsufficientAvailableFunds { .. }))
+        ));
+        assert_eq!((client.available, client.total), (dec!(7), dec!(7)));
+        assert_eq!(client.held, dec!(0));
+        assert_eq!(client.open_disputes().count(), 0);
+        assert!(!client.deposit_transactions.contains_key(&TxId(3)));
+        assert_eq!(client.lifetime_deposits(), dec!(11));
     }
 
     #[test]
     fn chargeback_sets_account_locked_and_removes_funds() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(12)).unwrap();
-        client.dispute(1).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(12))).unwrap();
+        client.dispute(TxId(1)).unwrap();
 
         assert_eq!(client.available, dec!(0));
         assert_eq!(client.held, dec!(12));
         assert_eq!(client.total, dec!(12));
-        assert!(client.disputed_transactions.contains_key(&1));
+        assert!(client.disputed_transactions.contains_key(&TxId(1)));
 
-        let result = client.chargeback(1);
+        let result = client.chargeback(TxId(1));
 
         assert!(result.is_ok());
         assert_eq!(client.available, dec!(0));
         assert_eq!(client.held, dec!(0));
         assert_eq!(client.total, dec!(0));
         assert!(client.locked);
-        assert!(!client.disputed_transactions.contains_key(&1));
+        assert!(!client.disputed_transactions.contains_key(&TxId(1)));
     }
 
     #[test]
     fn chargeback_rejected_when_not_in_dispute() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(5)).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
 
-        let result = client.chargeback(999);
+        let result = client.chargeback(TxId(999));
 
         assert!(matches!(
             result,
             Err(ClientTransactionError::NotInDispute {
-                client_id: 1,
-                tx_id: 999
+                client_id: ClientId(1),
+                tx_id: TxId(999)
             })
         ));
     }
 
     #[test]
     fn chargeback_rejected_when_account_already_locked() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(10)).unwrap();
-        client.dispute(1).unwrap();
-        client.chargeback(1).unwrap();
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.chargeback(TxId(1)).unwrap();
 
-        let result = client.chargeback(1);
+        let result = client.chargeback(TxId(1));
         assert!(matches!(
             result,
-            Err(ClientTransactionError::AccountAlreadyLocked { client_id: 1 })
+            Err(ClientTransactionError::AccountAlreadyLocked {
+                client_id: ClientId(1)
+            })
         ));
     }
 
     #[test]
     fn chargeback_rejected_when_held_balance_is_insufficient() {
-        let mut client = Client::new(1);
-        client.deposit(1, dec!(9)).unwrap();
-        client.dispute(1).unwrap();
-        client.held = dec!(1);
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(9))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.held = money(dec!(1));
 
-        let result = client.chargeback(1);
+        let result = client.chargeback(TxId(1));
 
         assert!(matches!(
             result,
             Err(ClientTransactionError::InsufficientHeldFunds {
-                client_id: 1,
-                action: "chargeback"
+                client_id: ClientId(1),
+                action: "chargeback",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reverse_deposit_removes_funds_and_forgets_transaction() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.deposit(TxId(2), money(dec!(5))).unwrap();
+
+        let result = client.reverse_deposit(TxId(2));
+
+        assert_eq!(result, Ok(money(dec!(5))));
+        assert_eq!(client.available, dec!(5));
+        assert_eq!(client.total, dec!(5));
+        assert!(!client.deposit_transactions.contains_key(&TxId(2)));
+    }
+
+    #[test]
+    fn reverse_deposit_rejected_when_transaction_in_dispute() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+
+        let result = client.reverse_deposit(TxId(1));
+
+        assert!(matches!(
+            result,
+            Err(ClientTransactionError::AlreadyInDispute {
+                client_id: ClientId(1),
+                tx_id: TxId(1)
             })
         ));
+        assert_eq!(client.held, dec!(5));
+    }
+
+    #[test]
+    fn force_resolve_releases_funds_on_locked_account() {
+        let mut client = Client::new(ClientId(1));
+        client.deposit(TxId(1), money(dec!(4))).unwrap();
+        client.deposit(TxId(2), money(dec!(6))).unwrap();
+        client.dispute(TxId(1)).unwrap();
+        client.dispute(TxId(2)).unwrap();
+        client.chargeback(TxId(1)).unwrap();
+
+        let result = client.force_resolve(TxId(2));
+
+        assert_eq!(result, Ok(money(dec!(6))));
+        assert!(client.locked);
+        assert_eq!(client.available, dec!(6));
+        assert_eq!(client.held, dec!(0));
+        assert_eq!(client.total, dec!(6));
+    }
+
+    #[test]
+    fn dispute_age_is_measured_from_when_the_dispute_opened() {
+        let mut client = Client::new(ClientId(1));
+        let opened_at = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        client.deposit(TxId(1), money(dec!(5))).unwrap();
+        client.dispute_at(TxId(1), opened_at).unwrap();
+
+        let now = opened_at + Duration::from_secs(30);
+        assert_eq!(
+            client.dispute_age(TxId(1), now),
+            Some(Duration::from_secs(30))
+        );
+
+        client.resolve(TxId(1)).unwrap();
+        assert_eq!(client.dispute_age(TxId(1), now), None);
+        assert!(client.dispute_opened_at.is_empty());
+    }
+
+    #[test]
+    fn every_balance_type_runs_the_same_dispute_logic() {
+        fn settle<B: Balance>() -> (Decimal, Decimal, Decimal, bool) {
+            let amount = |value| Money::<B>::try_from(value).unwrap();
+            let mut client = Client::<B>::new(ClientId(1));
+            client.deposit(TxId(1), amount(dec!(10.5))).unwrap();
+            client.deposit(TxId(2), amount(dec!(0.0001))).unwrap();
+            client.withdraw(amount(dec!(3.25))).unwrap();
+            client.dispute(TxId(1)).unwrap();
+            assert!(client.withdraw(amount(dec!(0.01))).is_err());
+            client.chargeback(TxId(1)).unwrap();
+            (
+                client.available.to_decimal(),
+                client.held.into(),
+                client.total.to_decimal(),
+                client.locked,
+            )
+        }
+
+        let decimal = settle::<Decimal>();
+        assert_eq!(decimal, (dec!(-3.2499), dec!(0), dec!(-3.2499), true));
+        assert_eq!(settle::<i128>(), decimal);
+        assert_eq!(settle::<MinorUnits>(), decimal);
+        assert!(Money::<MinorUnits>::try_from(MAX_MAGNITUDE).is_err());
     }
 }
@@ -0,0 +1,178 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    aliases::ClientAliases,
+    balance_limits::BalanceLimits,
+    dispute_expiry::DisputeExpiry,
+    encoding::InputEncoding,
+    format::{AmountEncoding, Format},
+    formatting::FormattingOptions,
+    hold_accrual::HoldAccrual,
+    ids::ClientId,
+    memory::MemoryPolicy,
+    output::OutputSchema,
+    redaction::Redaction,
+    rejection_log::RejectionLogging,
+    withdrawal_policy::WithdrawalPolicy,
+    zero_amount::ZeroAmountPolicy,
+};
+
+/// Run-time options for an `Engine`. Everything defaults to the behaviour of
+/// a plain `process_transactions` call.
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    /// Whether the first input row is a header. Headerless files are read
+    /// positionally as `type,client,tx,amount[,tenant]`.
+    pub has_headers: bool,
+    /// Reject headers with columns the engine does not know about instead of
+    /// ignoring them, for partners bound to a fixed schema.
+    pub strict_columns: bool,
+    /// Recover from malformed CSV quoting row by row: an unterminated quoted
+    /// field rejects the line it starts on instead of swallowing the rest of
+    /// the input. See [`crate::engine::lenient`].
+    pub lenient_csv: bool,
+    pub input_format: Format,
+    /// Field delimiter of CSV input, `b'\t'` for TSV.
+    pub input_delimiter: u8,
+    /// Work out the format, delimiter and compression of each input from
+    /// its first bytes instead of taking `input_format` and
+    /// `input_delimiter`. See [`crate::detect`].
+    pub detect_input_format: bool,
+    /// Character encoding of the input; a UTF-8 byte order mark is always
+    /// skipped.
+    pub input_encoding: InputEncoding,
+    pub output_format: Format,
+    /// Whether JSON output writes amounts as strings or as numbers.
+    pub amount_encoding: AmountEncoding,
+    /// Decimal separator of CSV input amounts, and precision, rounding and
+    /// quoting of CSV account output.
+    pub formatting: FormattingOptions,
+    /// Columns of CSV account output.
+    pub output_schema: OutputSchema,
+    /// Budget for the engine's approximate memory use, checked every
+    /// [`crate::memory::MEMORY_CHECK_INTERVAL`] rows and at the end of each input.
+    pub max_memory_bytes: Option<usize>,
+    pub memory_policy: MemoryPolicy,
+    /// Stop each `process` call with `EngineError::BudgetExhausted` once it
+    /// has run this long, or read this many rows, so a scheduler can resume
+    /// the rest later.
+    pub max_runtime: Option<Duration>,
+    pub max_rows: Option<usize>,
+    /// Disguise client ids and amounts in audit trails and reports meant for
+    /// sharing. Account output is not affected.
+    pub redaction: Option<Redaction>,
+    /// Resolve or charge back disputes left open past a deadline, as card
+    /// schemes do when a party does not respond.
+    pub dispute_expiry: Option<DisputeExpiry>,
+    /// Credit interest, or debit a penalty, on funds a dispute held past a
+    /// grace period when the dispute is resolved, by a row or on expiry.
+    /// Each accrual is audited as `accrued_interest` or `accrued_penalty`.
+    pub hold_accrual: Option<HoldAccrual>,
+    /// Reject dispute, resolve, chargeback and other amountless rows that
+    /// carry an amount anyway, as likely malformed, instead of ignoring it.
+    pub reject_unexpected_amounts: bool,
+    /// Reject deposits, withdrawals and withdrawal holds whose transaction id
+    /// is not above every id seen before it, for sources that promise
+    /// increasing ids: a reused or out-of-order id points at a corrupt file.
+    pub strict_tx_order: bool,
+    /// Turn a panic while applying a row into a rejection of that row,
+    /// logged as `EngineError::RowPanic`, and carry on with the next one.
+    /// The client the row touched may be left half-updated, so check its
+    /// balances afterwards.
+    pub catch_row_panics: bool,
+    /// Apply rows with a higher `priority` column before earlier rows of the
+    /// same client, looking this many rows ahead. See
+    /// [`crate::engine::priority`].
+    pub priority_window: Option<usize>,
+    /// Reject zero amounts where an amount is required, or accept such rows
+    /// as audited no-ops.
+    pub zero_amounts: ZeroAmountPolicy,
+    /// Log level of each category of rejected rows; everything is logged as
+    /// an error by default.
+    pub rejection_logging: RejectionLogging,
+    /// Ceilings on each account's total, enforced on deposits.
+    pub balance_limits: Option<Arc<BalanceLimits>>,
+    /// Reject, for manual review, any transaction that would take a
+    /// client's available or total balance further below zero, such as a
+    /// dispute of funds already withdrawn. The row is rejected with
+    /// `ClientTransactionError::NegativeBalance` and audited as
+    /// `AuditAction::NegativeBalanceReview`.
+    pub non_negative_balances: bool,
+    /// Lifetime disputes at which a client joins `Engine::review_queue`;
+    /// `None`, the default, never queues it for disputes. Each client is
+    /// queued once, when it reaches the count.
+    pub review_after_disputes: Option<u32>,
+    /// Whether withdrawals may count held funds, or are blocked outright,
+    /// while disputes are open.
+    pub withdrawal_policy: WithdrawalPolicy,
+    /// External customer ids the CSV `client` column may use instead of
+    /// client ids.
+    pub client_aliases: Option<Arc<ClientAliases>>,
+    /// Key account output by external id, for clients that have one.
+    pub output_external_ids: bool,
+    /// Append each client's `Client::risk_score` to account output.
+    pub output_risk_score: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            has_headers: true,
+            strict_columns: false,
+            lenient_csv: false,
+            input_format: Format::Csv,
+            input_delimiter: b',',
+            detect_input_format: false,
+            input_encoding: InputEncoding::Utf8,
+            output_format: Format::Csv,
+            amount_encoding: AmountEncoding::String,
+            formatting: FormattingOptions::default(),
+            output_schema: OutputSchema::V1,
+            max_memory_bytes: None,
+            memory_policy: MemoryPolicy::Abort,
+            max_runtime: None,
+            max_rows: None,
+            redaction: None,
+            dispute_expiry: None,
+            hold_accrual: None,
+            reject_unexpected_amounts: false,
+            strict_tx_order: false,
+            priority_window: None,
+            zero_amounts: ZeroAmountPolicy::Reject,
+            catch_row_panics: false,
+            rejection_logging: RejectionLogging::default(),
+            balance_limits: None,
+            non_negative_balances: false,
+            review_after_disputes: None,
+            withdrawal_policy: WithdrawalPolicy::Available,
+            client_aliases: None,
+            output_external_ids: false,
+            output_risk_score: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Columns of CSV account output.
+    pub(crate) fn account_header(&self) -> Vec<&'static str> {
+        let mut header = self.output_schema.header();
+        if self.output_risk_score {
+            header.push("risk_score");
+        }
+        header
+    }
+
+    /// Whether account output starts with a header line: CSV does, JSON and
+    /// fixed-width output do not.
+    pub fn writes_output_header(&self) -> bool {
+        self.output_format == Format::Csv && self.formatting.fixed_width.is_none()
+    }
+
+    /// The external id account output should show for `client`, if any.
+    pub(crate) fn output_id(&self, client: ClientId) -> Option<&str> {
+        if !self.output_external_ids {
+            return None;
+        }
+        self.client_aliases.as_ref()?.external_id(client)
+    }
+}
@@ -0,0 +1,134 @@
+use rust_decimal::Decimal;
+use std::{collections::HashMap, io::Write};
+
+use crate::{Engine, errors::EngineError, formatting::format_decimal};
+
+/// Exchange rates for one run, supplied by the caller.
+pub trait RateProvider {
+    /// How many units of `to` one unit of `from` is worth, if known.
+    fn rate(&self, from: &str, to: &str) -> Option<Decimal>;
+}
+
+/// Rates quoted against a single base currency.
+#[derive(Clone, Debug, Default)]
+pub struct FixedRates {
+    base: String,
+    to_base: HashMap<String, Decimal>,
+}
+
+impl FixedRates {
+    pub fn new(base: impl Into<String>) -> Self {
+        FixedRates {
+            base: base.into(),
+            to_base: HashMap::new(),
+        }
+    }
+
+    /// One unit of `currency` is worth `rate` units of the base currency.
+    pub fn with_rate(mut self, currency: impl Into<String>, rate: Decimal) -> Self {
+        self.to_base.insert(currency.into(), rate);
+        self
+    }
+}
+
+impl RateProvider for FixedRates {
+    fn rate(&self, from: &str, to: &str) -> Option<Decimal> {
+        let to_base = |currency: &str| {
+            if currency == self.base {
+                Some(Decimal::ONE)
+            } else {
+                self.to_base.get(currency).copied()
+            }
+        };
+        let (from, to) = (to_base(from)?, to_base(to)?);
+        from.checked_div(to)
+    }
+}
+
+pub const CONVERTED_HEADER: [&str; 10] = [
+    "client",
+    "currency",
+    "available",
+    "held",
+    "total",
+    "locked",
+    "base_currency",
+    "available_base",
+    "held_base",
+    "total_base",
+];
+
+/// Writes `engine`'s accounts, all held in `currency`, with both native and
+/// `base`-currency amounts. The engine does not track a currency per
+/// account, so a mixed book is modelled as one engine per currency (for
+/// example one tenant each).
+pub fn write_converted_accounts<W: Write>(
+    engine: &Engine,
+    currency: &str,
+    base: &str,
+    rates: &dyn RateProvider,
+    writer: W,
+) -> Result<(), EngineError> {
+    let rate = rates
+        .rate(currency, base)
+        .ok_or_else(|| EngineError::MissingRate {
+            from: currency.to_string(),
+            to: base.to_string(),
+        })?;
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(CONVERTED_HEADER)?;
+    engine.visit_clients(|client| {
+        let amounts = [client.available, client.held.value(), client.total];
+        let record = [client.id.to_string(), currency.to_string()]
+            .into_iter()
+            .chain(amounts.map(format_decimal))
+            .chain([client.locked.to_string(), base.to_string()])
+            .chain(amounts.map(|amount| format_decimal(amount * rate)));
+        csv_writer.write_record(record)?;
+        Ok(())
+    })?;
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    #[test]
+    fn cross_rates_go_through_the_base_currency() {
+        let rates = FixedRates::new("USD")
+            .with_rate("EUR", dec!(1.10))
+            .with_rate("GBP", dec!(1.32));
+
+        assert_eq!(rates.rate("EUR", "USD"), Some(dec!(1.10)));
+        assert_eq!(rates.rate("GBP", "EUR"), Some(dec!(1.2)));
+        assert_eq!(rates.rate("JPY", "USD"), None);
+    }
+
+    #[test]
+    fn writes_native_and_converted_columns() {
+        let mut engine = Engine::new();
+        engine
+            .process(Cursor::new("type,client,tx,amount\ndeposit,1,1,10.0\n"))
+            .unwrap();
+        let mut output = Vec::new();
+
+        write_converted_accounts(
+            &engine,
+            "EUR",
+            "USD",
+            &FixedRates::new("USD").with_rate("EUR", dec!(1.10)),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,currency,available,held,total,locked,base_currency,available_base,held_base,total_base\n\
+             1,EUR,10.0000,0.0000,10.0000,false,USD,11.0000,0.0000,11.0000\n"
+        );
+    }
+}
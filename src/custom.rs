@@ -0,0 +1,49 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+use crate::{
+    client::Client,
+    errors::ClientTransactionError,
+    transaction::{CustomType, Transaction},
+};
+
+pub(crate) type Handler<B> =
+    Box<dyn Fn(&mut Client<B>, &Transaction) -> Result<(), ClientTransactionError> + Send + Sync>;
+
+pub(crate) struct CustomHandler<B> {
+    pub(crate) requires_amount: bool,
+    pub(crate) handler: Handler<B>,
+}
+
+/// Handlers for embedder-defined transaction types such as `bonus` or
+/// `fee_reversal`, so private extensions do not need new
+/// `TransactionType` variants. A handler gets the client and the validated
+/// transaction (positive four-place amount when required, `u32` tx id) after
+/// rules have run. Rows for locked accounts are rejected before it is
+/// called, and a handler that returns an error or leaves
+/// `total != available + held + pending` has its changes rolled back.
+pub struct CustomTypes<B = Decimal> {
+    handlers: HashMap<CustomType, CustomHandler<B>>,
+}
+
+impl<B> Default for CustomTypes<B> {
+    fn default() -> Self {
+        CustomTypes {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<B> CustomTypes<B> {
+    pub(crate) fn insert(&mut self, name: CustomType, handler: CustomHandler<B>) {
+        self.handlers.insert(name, handler);
+    }
+
+    pub(crate) fn get(&self, name: &CustomType) -> Option<&CustomHandler<B>> {
+        self.handlers.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(CustomType::as_str)
+    }
+}
@@ -0,0 +1,93 @@
+use csv::{ByteRecord, StringRecord};
+use std::io::Write;
+
+use crate::{engine::Rejection, errors::EngineError};
+
+/// An input row the engine skipped, with the fields as they were read.
+pub(crate) struct RejectedRow {
+    /// 1-based position in the input; `0` until `read_input` numbers it.
+    pub(crate) row: usize,
+    /// Kept as bytes so rows that are not valid UTF-8 are written back as
+    /// they were.
+    pub(crate) raw: ByteRecord,
+    pub(crate) reason: String,
+}
+
+/// A row the engine skipped or rejected, as sent to the channel set with
+/// `Engine::set_rejection_sender`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedTransaction {
+    /// 1-based position in the input.
+    pub row: usize,
+    /// The fields as they were read; a JSON line is one field. Bytes that
+    /// are not valid UTF-8 come out as U+FFFD.
+    pub fields: Vec<String>,
+    pub reason: String,
+    /// Why a row that parsed was not applied. `None` for rows that could not
+    /// be parsed.
+    pub rejection: Option<Rejection>,
+}
+
+impl RejectedTransaction {
+    pub(crate) fn new(rejected: &RejectedRow, rejection: Option<Rejection>) -> Self {
+        RejectedTransaction {
+            row: rejected.row,
+            fields: rejected
+                .raw
+                .iter()
+                .map(|field| String::from_utf8_lossy(field).into_owned())
+                .collect(),
+            reason: rejected.reason.clone(),
+            rejection,
+        }
+    }
+}
+
+/// CSV sink for rows that were skipped or rejected. Each row is written
+/// verbatim, followed by a `reason` column, so it can be corrected and
+/// resubmitted on its own. The header mirrors the input's (plus `reason`) and
+/// is omitted for headerless input; JSON lines are written in a `line` column.
+pub struct DeadLetter {
+    writer: csv::Writer<Box<dyn Write + Send>>,
+    header_written: bool,
+}
+
+impl DeadLetter {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        DeadLetter {
+            writer: csv::WriterBuilder::new()
+                .flexible(true)
+                .from_writer(Box::new(writer)),
+            header_written: false,
+        }
+    }
+
+    pub(crate) fn write_header(
+        &mut self,
+        header: Option<&StringRecord>,
+    ) -> Result<(), EngineError> {
+        if let Some(header) = header
+            && !self.header_written
+        {
+            self.writer
+                .write_record(header.iter().chain(std::iter::once("reason")))?;
+        }
+        self.header_written = true;
+        Ok(())
+    }
+
+    pub(crate) fn write(&mut self, rejected: &RejectedRow) -> Result<(), EngineError> {
+        self.writer.write_record(
+            rejected
+                .raw
+                .iter()
+                .chain(std::iter::once(rejected.reason.as_bytes())),
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), EngineError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,377 @@
+use csv::StringRecord;
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Cursor, Read},
+};
+
+use crate::{
+    config::EngineConfig, encoding::decode, errors::EngineError, format::Format,
+    header::validate_header,
+};
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// Most bytes read looking for the first line.
+const MAX_FIRST_LINE: u64 = 64 * 1024;
+
+/// What an input turned out to be, under `EngineConfig::detect_input_format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DetectedInput {
+    pub format: Format,
+    /// Field delimiter of CSV input: `b','`, or `b'\t'` for TSV.
+    pub delimiter: u8,
+    /// Whether the input was gzip-compressed.
+    pub gzip: bool,
+}
+
+impl DetectedInput {
+    /// `config` set up to read this input.
+    pub fn apply(&self, config: &EngineConfig) -> EngineConfig {
+        EngineConfig {
+            input_format: self.format,
+            input_delimiter: self.delimiter,
+            detect_input_format: false,
+            ..config.clone()
+        }
+    }
+}
+
+impl fmt::Display for DetectedInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.format, self.delimiter) {
+            (Format::Json, _) => f.write_str("json")?,
+            (Format::Csv, b'\t') => f.write_str("tsv")?,
+            (Format::Csv, _) => f.write_str("csv")?,
+        }
+        if self.gzip {
+            f.write_str(" (gzip)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Works out what `source` is from its first bytes and first line: gzip
+/// (decompressed first), then newline-delimited JSON when the line opens an
+/// object, otherwise CSV or TSV by whichever of comma and tab it has more
+/// of. The header of CSV and TSV input is checked as `process` would. The
+/// input is decoded as `config.input_encoding` says.
+///
+/// Fails with `EngineError::UnsupportedInput` for archives, binary data,
+/// JSON arrays and lines with neither delimiter, or, without the `gzip`
+/// feature, for gzip input.
+pub fn detect_input<R: Read>(
+    source: R,
+    config: &EngineConfig,
+) -> Result<DetectedInput, EngineError> {
+    sniff(source, config).map(|(detected, _)| detected)
+}
+
+/// As [`detect_input`], also returning the decompressed and decoded input,
+/// from its first byte, ready for `read_input`.
+pub(crate) fn sniff<'a, R: Read + 'a>(
+    source: R,
+    config: &EngineConfig,
+) -> Result<(DetectedInput, Box<dyn Read + 'a>), EngineError> {
+    let mut source = BufReader::new(source);
+    let start = source.fill_buf()?;
+    if start.starts_with(ZIP_MAGIC) {
+        return Err(unsupported(
+            "input is a zip archive, such as a spreadsheet; export it as CSV",
+        ));
+    }
+    let gzip = start.starts_with(GZIP_MAGIC);
+    let source: Box<dyn Read + 'a> = if gzip {
+        gunzip(source)?
+    } else {
+        Box::new(source)
+    };
+    let mut source = BufReader::new(decode(source, config.input_encoding)?);
+
+    let mut start = Vec::new();
+    let first_line = loop {
+        let line_start = start.len();
+        if (&mut source)
+            .take(MAX_FIRST_LINE)
+            .read_until(b'\n', &mut start)?
+            == 0
+        {
+            return Err(unsupported("input is empty"));
+        }
+        let line = start[line_start..].trim_ascii();
+        if !line.is_empty() {
+            break line.to_vec();
+        }
+    };
+    let source = Box::new(Cursor::new(start).chain(source));
+
+    if first_line.contains(&0) {
+        return Err(unsupported("input is binary data, not text"));
+    }
+    let detected = |format, delimiter| DetectedInput {
+        format,
+        delimiter,
+        gzip,
+    };
+    if first_line.starts_with(b"{") {
+        return Ok((detected(Format::Json, b','), source));
+    }
+    if first_line.starts_with(b"[") {
+        return Err(unsupported(
+            "input is a JSON array; JSON input must have one object per line",
+        ));
+    }
+    let count = |delimiter| first_line.iter().filter(|byte| **byte == delimiter).count();
+    let delimiter = match (count(b','), count(b'\t')) {
+        (0, 0) => {
+            return Err(unsupported(&format!(
+                "first line {:?} is not a JSON object and has no comma or tab separated columns",
+                String::from_utf8_lossy(&first_line)
+            )));
+        }
+        (commas, tabs) if tabs > commas => b'\t',
+        _ => b',',
+    };
+    if config.has_headers {
+        let header = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(delimiter)
+            .from_reader(first_line.as_slice())
+            .records()
+            .next()
+            .transpose()?
+            .unwrap_or_else(StringRecord::new);
+        validate_header(&header, config.strict_columns)?;
+    }
+    Ok((detected(Format::Csv, delimiter), source))
+}
+
+fn unsupported(reason: &str) -> EngineError {
+    EngineError::UnsupportedInput(reason.to_string())
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip<'a, R: BufRead + 'a>(_source: R) -> Result<Box<dyn Read + 'a>, EngineError> {
+    Err(unsupported(
+        "input is gzip-compressed, which needs the `gzip` feature; decompress it first",
+    ))
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip<'a, R: BufRead + 'a>(source: R) -> Result<Box<dyn Read + 'a>, EngineError> {
+    Ok(Box::new(gzip::GzipReader::new(source)?))
+}
+
+#[cfg(feature = "gzip")]
+mod gzip {
+    use miniz_oxide::{
+        DataFormat, MZError, MZFlush, MZStatus,
+        inflate::stream::{InflateState, inflate},
+    };
+    use std::io::{self, BufRead, Read};
+
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    const CRC_TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut index = 0;
+        while index < 256 {
+            let mut crc = index as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xEDB8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[index] = crc;
+            index += 1;
+        }
+        table
+    };
+
+    fn invalid(reason: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("gzip input: {reason}"))
+    }
+
+    /// Streams the first member of a gzip file, checking its CRC-32 and
+    /// length at the end.
+    pub(super) struct GzipReader<R> {
+        source: R,
+        state: Box<InflateState>,
+        crc: u32,
+        len: u32,
+        done: bool,
+    }
+
+    impl<R: BufRead> GzipReader<R> {
+        pub(super) fn new(mut source: R) -> io::Result<Self> {
+            let mut header = [0; 10];
+            source.read_exact(&mut header)?;
+            if header[2] != 8 {
+                return Err(invalid("unknown compression method"));
+            }
+            let flags = header[3];
+            if flags & FEXTRA != 0 {
+                let mut len = [0; 2];
+                source.read_exact(&mut len)?;
+                io::copy(
+                    &mut (&mut source).take(u16::from_le_bytes(len).into()),
+                    &mut io::sink(),
+                )?;
+            }
+            for flag in [FNAME, FCOMMENT] {
+                if flags & flag != 0 {
+                    source.read_until(0, &mut Vec::new())?;
+                }
+            }
+            if flags & FHCRC != 0 {
+                source.read_exact(&mut [0; 2])?;
+            }
+            Ok(GzipReader {
+                source,
+                state: InflateState::new_boxed(DataFormat::Raw),
+                crc: !0,
+                len: 0,
+                done: false,
+            })
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            let mut trailer = [0; 8];
+            self.source.read_exact(&mut trailer)?;
+            let [crc, len] = [&trailer[..4], &trailer[4..]]
+                .map(|field| u32::from_le_bytes(field.try_into().unwrap_or_default()));
+            if crc != !self.crc || len != self.len {
+                return Err(invalid("checksum mismatch"));
+            }
+            self.done = true;
+            Ok(())
+        }
+    }
+
+    impl<R: BufRead> Read for GzipReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            loop {
+                let input = self.source.fill_buf()?;
+                let at_end = input.is_empty();
+                let result = inflate(&mut self.state, input, buf, MZFlush::None);
+                self.source.consume(result.bytes_consumed);
+                let written = &buf[..result.bytes_written];
+                for byte in written {
+                    self.crc = CRC_TABLE[((self.crc ^ u32::from(*byte)) & 0xFF) as usize]
+                        ^ (self.crc >> 8);
+                }
+                self.len = self.len.wrapping_add(written.len() as u32);
+                match result.status {
+                    Ok(MZStatus::StreamEnd) => {
+                        self.finish()?;
+                        return Ok(written.len());
+                    }
+                    Ok(_) | Err(MZError::Buf) if !written.is_empty() => {
+                        return Ok(written.len());
+                    }
+                    Ok(_) | Err(MZError::Buf) if !at_end => {}
+                    Ok(_) | Err(MZError::Buf) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "gzip input ends mid-stream",
+                        ));
+                    }
+                    Err(_) => return Err(invalid("corrupt compressed data")),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(input: &[u8]) -> Result<String, EngineError> {
+        detect_input(input, &EngineConfig::default()).map(|detected| detected.to_string())
+    }
+
+    #[test]
+    fn formats_are_told_apart_by_their_first_line() {
+        assert_eq!(detect(b"type,client,tx,amount\n").unwrap(), "csv");
+        assert_eq!(
+            detect(b"\xEF\xBB\xBF\ntype\tclient\ttx\tamount\n").unwrap(),
+            "tsv"
+        );
+        assert_eq!(detect(b"{\"type\":\"deposit\"}\n").unwrap(), "json");
+
+        for (input, reason) in [
+            (&b"PK\x03\x04..."[..], "zip archive"),
+            (b"[{\"type\":\"deposit\"}]", "JSON array"),
+            (b"type;client;tx;amount\n", "no comma or tab"),
+            (b"", "empty"),
+        ] {
+            let Err(EngineError::UnsupportedInput(message)) = detect(input) else {
+                panic!("{input:?} was accepted");
+            };
+            assert!(message.contains(reason), "{message}");
+        }
+        assert!(matches!(
+            detect(b"deposit,1,1,1.0\n"),
+            Err(EngineError::InvalidHeader { .. })
+        ));
+
+        let mut engine = crate::Engine::with_config(EngineConfig {
+            detect_input_format: true,
+            ..EngineConfig::default()
+        });
+        engine
+            .process("type\tclient\ttx\tamount\ndeposit\t1\t1\t2.5\n".as_bytes())
+            .unwrap();
+        assert_eq!(
+            engine.client(crate::ids::ClientId(1)).unwrap().available,
+            rust_decimal::dec!(2.5)
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_input_is_decompressed_and_checked() {
+        let text = b"{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":\"1\"}\n";
+        let mut input = b"\x1f\x8b\x08\x08\0\0\0\0\0\xffin.json\0".to_vec();
+        input.extend(miniz_oxide::deflate::compress_to_vec(text, 6));
+        let crc = text
+            .iter()
+            .fold(!0u32, |crc, byte| gzip_crc_step(crc, *byte));
+        input.extend((!crc).to_le_bytes());
+        input.extend((text.len() as u32).to_le_bytes());
+
+        let (detected, mut source) = sniff(input.as_slice(), &EngineConfig::default()).unwrap();
+        assert_eq!(detected.to_string(), "json (gzip)");
+        let mut output = Vec::new();
+        source.read_to_end(&mut output).unwrap();
+        assert_eq!(output, text);
+        drop(source);
+
+        let len = input.len();
+        input[len - 5] ^= 1;
+        let result = sniff(input.as_slice(), &EngineConfig::default())
+            .and_then(|(_, mut source)| Ok(source.read_to_end(&mut Vec::new())?));
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    fn gzip_crc_step(crc: u32, byte: u8) -> u32 {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            }
+        })
+    }
+}
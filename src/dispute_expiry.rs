@@ -0,0 +1,89 @@
+use std::{str::FromStr, time::Duration};
+
+/// When an open dispute counts as stale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisputeDeadline {
+    /// Held for at least this long by the engine's clock.
+    Age(Duration),
+    /// At least this many transactions accepted since the one that opened it;
+    /// see `Engine::last_sequence`. Unlike an age, it gives the same result
+    /// however fast a file is replayed.
+    Sequence(u64),
+}
+
+impl FromStr for DisputeDeadline {
+    type Err = String;
+
+    /// `<n>d` for days, `<n>s` for seconds or `<n>tx` for sequence units.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("invalid dispute deadline {value}, expected <days>d, <seconds>s or <n>tx");
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (number, unit) = value.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        match unit {
+            "d" => Ok(DisputeDeadline::Age(Duration::from_secs(
+                number.checked_mul(24 * 60 * 60).ok_or_else(invalid)?,
+            ))),
+            "s" => Ok(DisputeDeadline::Age(Duration::from_secs(number))),
+            "tx" => Ok(DisputeDeadline::Sequence(number)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// What happens to a dispute nobody settled in time. Card schemes default
+/// one way or the other when a party does not respond.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExpiryOutcome {
+    /// Release the held funds, as if the partner had sent a resolve.
+    #[default]
+    Resolve,
+    /// Reverse the deposit and lock the account, as a chargeback would.
+    Chargeback,
+}
+
+impl FromStr for ExpiryOutcome {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "resolve" => Ok(ExpiryOutcome::Resolve),
+            "chargeback" => Ok(ExpiryOutcome::Chargeback),
+            _ => Err(format!(
+                "invalid expired dispute outcome {value}, expected resolve or chargeback"
+            )),
+        }
+    }
+}
+
+/// Settles stale disputes while processing, before each row; see
+/// `EngineConfig::dispute_expiry`. Every settlement is audited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisputeExpiry {
+    pub deadline: DisputeDeadline,
+    pub outcome: ExpiryOutcome,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_parse_with_units() {
+        assert_eq!(
+            "30d".parse(),
+            Ok(DisputeDeadline::Age(Duration::from_secs(30 * 86_400)))
+        );
+        assert_eq!(
+            "90s".parse(),
+            Ok(DisputeDeadline::Age(Duration::from_secs(90)))
+        );
+        assert_eq!("500tx".parse(), Ok(DisputeDeadline::Sequence(500)));
+        for invalid in ["30", "d", "30w", "-3d", "99999999999999999d"] {
+            assert!(invalid.parse::<DisputeDeadline>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    str::FromStr,
+};
+
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Character encoding of the input file. Anything other than UTF-8 is
+/// transcoded to UTF-8 before parsing, which needs the `encoding` feature.
+/// A leading UTF-8 byte order mark is dropped in every case.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputEncoding {
+    #[default]
+    Utf8,
+    #[cfg(feature = "encoding")]
+    Legacy(&'static encoding_rs::Encoding),
+}
+
+impl InputEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            InputEncoding::Utf8 => "UTF-8",
+            #[cfg(feature = "encoding")]
+            InputEncoding::Legacy(encoding) => encoding.name(),
+        }
+    }
+}
+
+impl fmt::Debug for InputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    /// Accepts the WHATWG labels, such as `utf-8`, `windows-1252` or `latin1`.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        if label.eq_ignore_ascii_case("utf-8") || label.eq_ignore_ascii_case("utf8") {
+            return Ok(InputEncoding::Utf8);
+        }
+        #[cfg(feature = "encoding")]
+        {
+            match encoding_rs::Encoding::for_label(label.as_bytes()) {
+                Some(encoding) if encoding == encoding_rs::UTF_8 => Ok(InputEncoding::Utf8),
+                Some(encoding) => Ok(InputEncoding::Legacy(encoding)),
+                None => Err(format!("unknown input encoding {label}")),
+            }
+        }
+        #[cfg(not(feature = "encoding"))]
+        Err(format!(
+            "input encoding {label} needs the `encoding` feature"
+        ))
+    }
+}
+
+/// Wraps `source` so it reads as UTF-8 without a byte order mark.
+pub(crate) fn decode<'a, R: Read + 'a>(
+    source: R,
+    encoding: InputEncoding,
+) -> io::Result<Box<dyn Read + 'a>> {
+    match encoding {
+        InputEncoding::Utf8 => {
+            let mut reader = BufReader::new(source);
+            if reader.fill_buf()?.starts_with(UTF8_BOM) {
+                reader.consume(UTF8_BOM.len());
+            }
+            Ok(Box::new(reader))
+        }
+        #[cfg(feature = "encoding")]
+        InputEncoding::Legacy(encoding) => Ok(Box::new(Transcoder::new(source, encoding))),
+    }
+}
+
+#[cfg(feature = "encoding")]
+const CHUNK: usize = 8 * 1024;
+
+/// Decodes `source` chunk by chunk. Byte sequences the encoding cannot map
+/// come out as U+FFFD, so a bad byte spoils one field rather than the file.
+/// A byte order mark overrides the configured encoding, as browsers do.
+#[cfg(feature = "encoding")]
+struct Transcoder<R> {
+    source: R,
+    decoder: encoding_rs::Decoder,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> Transcoder<R> {
+    fn new(source: R, encoding: &'static encoding_rs::Encoding) -> Self {
+        let decoder = encoding.new_decoder();
+        let capacity = decoder
+            .max_utf8_buffer_length(CHUNK)
+            .expect("chunk size fits in usize");
+        Transcoder {
+            source,
+            decoder,
+            input: vec![0; CHUNK],
+            output: Vec::with_capacity(capacity),
+            position: 0,
+            finished: false,
+        }
+    }
+}
+
+#[cfg(feature = "encoding")]
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.output.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            let read = self.source.read(&mut self.input)?;
+            self.finished = read == 0;
+            self.output.resize(self.output.capacity(), 0);
+            let (_, _, written, _) =
+                self.decoder
+                    .decode_to_utf8(&self.input[..read], &mut self.output, self.finished);
+            self.output.truncate(written);
+            self.position = 0;
+        }
+        let count = buf.len().min(self.output.len() - self.position);
+        buf[..count].copy_from_slice(&self.output[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(input: &[u8], encoding: InputEncoding) -> String {
+        let mut output = String::new();
+        decode(input, encoding)
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn utf8_byte_order_mark_is_dropped() {
+        assert_eq!(
+            read_all(b"\xEF\xBB\xBFtype,client", InputEncoding::Utf8),
+            "type,client"
+        );
+        assert_eq!(read_all(b"type,client", InputEncoding::Utf8), "type,client");
+        assert_eq!(read_all(b"", InputEncoding::Utf8), "");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn windows_1252_is_transcoded() {
+        let encoding: InputEncoding = "windows-1252".parse().unwrap();
+        assert_eq!(encoding.name(), "windows-1252");
+        assert_eq!(read_all(b"caf\xE9,\x80 5", encoding), "café,€ 5");
+        assert_eq!(read_all(b"\xEF\xBB\xBFcaf\xC3\xA9", encoding), "café");
+        assert!("no-such-encoding".parse::<InputEncoding>().is_err());
+    }
+
+    #[cfg(not(feature = "encoding"))]
+    #[test]
+    fn other_encodings_need_the_feature() {
+        assert_eq!("UTF-8".parse(), Ok(InputEncoding::Utf8));
+        assert!("windows-1252".parse::<InputEncoding>().is_err());
+    }
+}
@@ -0,0 +1,859 @@
+use log::info;
+use rust_decimal::Decimal;
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+use super::Engine;
+use super::expiry::OpenDispute;
+use super::ingest::InputTransaction;
+use crate::{
+    alerts::locked_contribution,
+    audit::{AuditAction, AuditEntry},
+    client::{Capability, Client},
+    custom,
+    errors::{ClientTransactionError, EngineError, RowError},
+    hold_accrual::resolve_accruing,
+    ids::{ClientId, TxId},
+    money::Money,
+    numeric::Balance,
+    review::{ReviewItem, ReviewReason},
+    rules::RuleOutcome,
+    transaction::{Transaction, TransactionType},
+    zero_amount::ZeroAmountPolicy,
+};
+
+/// Why [`Engine::push`] (or an input row) was not applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// The account refused the transaction: insufficient funds, an unknown
+    /// transaction, a locked account and so on.
+    Client(ClientTransactionError),
+    /// Anything else, such as a tenant mismatch, a rule denial or an unknown
+    /// custom type.
+    Other(String),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Client(err) => err.fmt(f),
+            Rejection::Other(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// Runs a custom type's handler on a locked-checked client, rolling back
+/// anything it did if it fails or breaks the balance invariant.
+fn apply_custom<B: Balance>(
+    client: &mut Client<B>,
+    transaction: &Transaction,
+    handler: &custom::Handler<B>,
+) -> Result<(), ClientTransactionError> {
+    if client.locked {
+        return Err(ClientTransactionError::AccountLocked {
+            client_id: client.id,
+        });
+    }
+    let original = client.clone();
+    let result = handler(client, transaction).and_then(|()| {
+        if client.balances_add_up() {
+            Ok(())
+        } else {
+            Err(ClientTransactionError::InconsistentBalances {
+                client_id: client.id,
+            })
+        }
+    });
+    if result.is_err() {
+        *client = original;
+    }
+    result
+}
+
+/// The capability a row of `tx_type` needs from the account, if any.
+fn required_capability(tx_type: TransactionType) -> Option<Capability> {
+    match tx_type {
+        TransactionType::Deposit => Some(Capability::Deposit),
+        TransactionType::Withdrawal | TransactionType::WithdrawalHold => Some(Capability::Withdraw),
+        TransactionType::Dispute => Some(Capability::Dispute),
+        _ => None,
+    }
+}
+
+/// Everything the account output shows, to detect which clients a row changed.
+pub(crate) fn balances<B: Balance>(client: &Client<B>) -> (B, Money<B>, Money<B>, B, bool) {
+    (
+        client.available,
+        client.held,
+        client.pending,
+        client.total,
+        client.locked,
+    )
+}
+
+enum ValidatedTransaction<B> {
+    WithAmount { tx: TxId, amount: Money<B> },
+    NoAmount { tx: TxId },
+}
+
+impl<B: Balance> ValidatedTransaction<B> {
+    fn tx(&self) -> TxId {
+        match self {
+            ValidatedTransaction::WithAmount { tx, .. } | ValidatedTransaction::NoAmount { tx } => {
+                *tx
+            }
+        }
+    }
+
+    fn amount(&self) -> Option<Money<B>> {
+        match self {
+            ValidatedTransaction::WithAmount { amount, .. } => Some(*amount),
+            ValidatedTransaction::NoAmount { .. } => None,
+        }
+    }
+}
+
+fn validate_transaction<B: Balance>(
+    tx_type: TransactionType,
+    requires_amount: bool,
+    client_id: ClientId,
+    tx: i64,
+    amount: Option<Decimal>,
+) -> Result<ValidatedTransaction<B>, ClientTransactionError> {
+    if tx < 0 {
+        return Err(ClientTransactionError::InvalidTransactionId { client_id, tx });
+    }
+
+    let tx_id = u32::try_from(tx)
+        .map(TxId)
+        .map_err(|_| ClientTransactionError::InvalidTransactionId { client_id, tx })?;
+
+    if !requires_amount {
+        return Ok(ValidatedTransaction::NoAmount { tx: tx_id });
+    }
+    match amount {
+        Some(value) if value.is_zero() => Err(ClientTransactionError::ZeroAmount {
+            client_id,
+            tx_type,
+            tx: tx_id,
+        }),
+        Some(value) if value > Decimal::ZERO => Money::try_from(value)
+            .map(|amount| ValidatedTransaction::WithAmount { tx: tx_id, amount })
+            .map_err(|_| ClientTransactionError::InvalidAmount {
+                client_id,
+                tx: tx_id,
+                amount: value,
+            }),
+        Some(value) => Err(ClientTransactionError::InvalidAmount {
+            client_id,
+            tx: tx_id,
+            amount: value,
+        }),
+        None => Err(ClientTransactionError::MissingAmount {
+            client_id,
+            tx_type,
+            tx: tx_id,
+        }),
+    }
+}
+
+impl<B: Balance> Engine<B> {
+    #[cfg(feature = "fault-injection")]
+    fn inject_faults(&self, mut transaction: InputTransaction) -> InputTransaction {
+        if let Some(faults) = &self.faults {
+            faults.maybe_delay();
+            if faults.should_corrupt_row() {
+                transaction.tx = -1;
+            }
+        }
+        transaction
+    }
+
+    /// [`Engine::apply`], turning a panic into a rejection of the row when
+    /// `EngineConfig::catch_row_panics` is set.
+    pub(crate) fn apply_guarded(
+        &mut self,
+        transaction: InputTransaction,
+    ) -> Result<Option<Rejection>, EngineError> {
+        if !self.config.catch_row_panics {
+            return self.apply(transaction);
+        }
+        let row = transaction.row;
+        match panic::catch_unwind(AssertUnwindSafe(|| self.apply(transaction))) {
+            Ok(applied) => applied,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown cause");
+                let err = EngineError::RowPanic { row };
+                self.config
+                    .rejection_logging
+                    .log("RowPanic", format_args!("{err}: {message}"));
+                Ok(Some(Rejection::Other(err.to_string())))
+            }
+        }
+    }
+
+    /// Applies one row. Returns why the row was rejected, if it was; the
+    /// reason has already been logged.
+    pub(crate) fn apply(
+        &mut self,
+        transaction: InputTransaction,
+    ) -> Result<Option<Rejection>, EngineError> {
+        #[cfg(feature = "fault-injection")]
+        let transaction = self.inject_faults(transaction);
+
+        if let (Some(expected), Some(tenant)) = (&self.tenant, &transaction.tenant)
+            && expected != tenant
+        {
+            let reason = format!("belongs to tenant {tenant}, not {expected}");
+            self.config.rejection_logging.log(
+                "TenantMismatch",
+                format_args!(
+                    "Client {}: transaction {} {reason}",
+                    transaction.client, transaction.tx
+                ),
+            );
+            return Ok(Some(Rejection::Other(reason)));
+        }
+
+        let InputTransaction {
+            tx_type,
+            client: client_id,
+            tx,
+            amount,
+            reference,
+            row,
+            ..
+        } = transaction;
+        let row_error = |source| RowError { row, source };
+
+        let requires_amount = match tx_type {
+            TransactionType::Custom(name) => match self.custom_types.get(&name) {
+                Some(custom) => custom.requires_amount,
+                None => {
+                    let reason = format!("unknown transaction type {}", name.as_str());
+                    self.config.rejection_logging.log(
+                        "UnknownType",
+                        format_args!("Error parsing CSV row {row}: {reason}"),
+                    );
+                    return Ok(Some(Rejection::Other(reason)));
+                }
+            },
+            tx_type => tx_type.requires_amount(),
+        };
+        let validated = match validate_transaction(tx_type, requires_amount, client_id, tx, amount)
+            .and_then(|validated| match amount {
+                Some(amount) if !requires_amount && self.config.reject_unexpected_amounts => {
+                    Err(ClientTransactionError::UnexpectedAmount {
+                        client_id,
+                        tx_type,
+                        tx: validated.tx(),
+                        amount,
+                    })
+                }
+                _ => Ok(validated),
+            }) {
+            Ok(value) => value,
+            // Checked like any other row, only applied as a no-op.
+            Err(ClientTransactionError::ZeroAmount { tx, .. })
+                if self.config.zero_amounts == ZeroAmountPolicy::Ignore =>
+            {
+                ValidatedTransaction::WithAmount {
+                    tx,
+                    amount: Money::ZERO,
+                }
+            }
+            Err(err) => {
+                self.config
+                    .rejection_logging
+                    .log(err.name(), format_args!("{}", row_error(err.clone())));
+                return Ok(Some(Rejection::Client(err)));
+            }
+        };
+
+        if self.config.strict_tx_order
+            && matches!(
+                tx_type,
+                TransactionType::Deposit
+                    | TransactionType::Withdrawal
+                    | TransactionType::WithdrawalHold
+            )
+        {
+            let tx = validated.tx();
+            if let Some(last) = self.last_tx.filter(|&last| tx <= last) {
+                let err = ClientTransactionError::TxIdOutOfOrder {
+                    client_id,
+                    tx,
+                    last,
+                };
+                self.config
+                    .rejection_logging
+                    .log(err.name(), format_args!("{}", row_error(err.clone())));
+                self.tx_order_violations += 1;
+                return Ok(Some(Rejection::Client(err)));
+            }
+            self.last_tx = Some(tx);
+        }
+
+        self.touch(client_id)?;
+        if !self.clients.contains_key(client_id) {
+            self.changed.insert(client_id);
+        }
+        let client = self
+            .clients
+            .get_or_insert_with(client_id, || Client::new(client_id));
+        let amount = validated.amount();
+        let zero_ignored = amount.is_some_and(Money::is_zero);
+        let balances_before = balances(client);
+        let available_before = client.available;
+        let locked_before = locked_contribution(client);
+
+        let transaction = Transaction {
+            tx_type,
+            client: client_id,
+            tx: validated.tx(),
+            amount: validated.amount().map(Money::to_decimal_money),
+            reference: reference.filter(|reference| !reference.is_empty()),
+        };
+        let flags = match self.rules.evaluate(client, &transaction) {
+            RuleOutcome::Allow { flags } => flags,
+            RuleOutcome::Deny(reason) => {
+                let rejection = format!("denied by rule {reason}");
+                self.config.rejection_logging.log(
+                    "RuleDenied",
+                    format_args!(
+                        "Client {client_id}: transaction {} denied by rule {reason}",
+                        transaction.tx
+                    ),
+                );
+                self.audit.push(
+                    AuditEntry::new(AuditAction::RuleDenied, client, transaction.tx, amount)
+                        .with_reason(reason)
+                        .with_reference(transaction.reference.clone()),
+                );
+                return Ok(Some(Rejection::Other(rejection)));
+            }
+        };
+
+        if let (TransactionType::Deposit, Some(limits), Some(deposit)) =
+            (tx_type, &self.config.balance_limits, transaction.amount)
+            && let Some(limit) = limits.limit(client_id)
+            && !client.locked
+            && !zero_ignored
+            && client.total.to_decimal() + deposit.value() > limit
+        {
+            let err = ClientTransactionError::BalanceLimitExceeded {
+                client_id,
+                tx: transaction.tx,
+                amount: deposit.value(),
+                total: client.total.to_decimal(),
+                limit,
+            };
+            self.config
+                .rejection_logging
+                .log(err.name(), format_args!("{}", row_error(err.clone())));
+            self.audit.push(
+                AuditEntry::new(
+                    AuditAction::BalanceLimitExceeded,
+                    client,
+                    transaction.tx,
+                    amount,
+                )
+                .with_reason(format!("balance limit {limit}"))
+                .with_reference(transaction.reference.clone()),
+            );
+            return Ok(Some(Rejection::Client(err)));
+        }
+
+        let original = self.config.non_negative_balances.then(|| client.clone());
+        let mut accrued = None;
+        let outcome = match (tx_type, validated) {
+            (tx_type, ValidatedTransaction::WithAmount { .. }) if zero_ignored => client
+                .check_allowed(required_capability(tx_type))
+                .map_err(|e| ("Error processing zero-amount transaction", e)),
+            (TransactionType::Deposit, ValidatedTransaction::WithAmount { tx, amount }) => client
+                .deposit(tx, amount)
+                .map_err(|e| ("Error processing deposit", e)),
+            (TransactionType::Withdrawal, ValidatedTransaction::WithAmount { tx: _, amount }) => {
+                client
+                    .withdraw_under(amount, self.config.withdrawal_policy)
+                    .map_err(|e| ("Error processing withdrawal", e))
+            }
+            (TransactionType::Dispute, ValidatedTransaction::NoAmount { tx }) => client
+                .dispute_at(tx, self.clock.now())
+                .map_err(|e| ("Partner's error processing dispute", e)),
+            (TransactionType::Resolve, ValidatedTransaction::NoAmount { tx }) => {
+                accrued = self.config.hold_accrual.and_then(|accrual| {
+                    let amount = accrual.accrued(client, tx, self.clock.now(), self.sequence)?;
+                    Some((accrual, amount))
+                });
+                resolve_accruing(client, tx, accrued)
+                    .map_err(|e| ("Partner's error processing resolve", e))
+            }
+            (TransactionType::Chargeback, ValidatedTransaction::NoAmount { tx }) => client
+                .chargeback(tx)
+                .map_err(|e| ("Partner's error processing chargeback", e)),
+            (TransactionType::WithdrawalHold, ValidatedTransaction::WithAmount { tx, amount }) => {
+                client
+                    .hold_withdrawal_under(tx, amount, self.config.withdrawal_policy)
+                    .map_err(|e| ("Error processing withdrawal hold", e))
+            }
+            (TransactionType::WithdrawalSettle, ValidatedTransaction::NoAmount { tx }) => client
+                .settle_withdrawal(tx)
+                .map(drop)
+                .map_err(|e| ("Error processing withdrawal settlement", e)),
+            (TransactionType::WithdrawalCancel, ValidatedTransaction::NoAmount { tx }) => client
+                .cancel_withdrawal(tx)
+                .map(drop)
+                .map_err(|e| ("Error processing withdrawal cancellation", e)),
+            (TransactionType::Custom(name), _) => {
+                let handler = self
+                    .custom_types
+                    .get(&name)
+                    .map(|custom| &custom.handler)
+                    .expect("custom types are looked up before validation");
+                apply_custom(client, &transaction, handler)
+                    .map_err(|e| ("Error processing custom transaction", e))
+            }
+            (tx_type, _) => {
+                self.config.rejection_logging.log(
+                    "ValidationMismatch",
+                    format_args!(
+                        "Validation mismatch for client {client_id} on transaction type {tx_type}"
+                    ),
+                );
+                return Ok(Some(Rejection::Other(format!(
+                    "validation mismatch on transaction type {tx_type}"
+                ))));
+            }
+        };
+        let outcome = match (outcome, original) {
+            (Ok(()), Some(original))
+                if (client.available.is_negative() && client.available < original.available)
+                    || (client.total.is_negative() && client.total < original.total) =>
+            {
+                let err = ClientTransactionError::NegativeBalance {
+                    client_id,
+                    tx_type,
+                    tx: transaction.tx,
+                    available: client.available.to_decimal(),
+                    total: client.total.to_decimal(),
+                };
+                let note = format!(
+                    "{tx_type} held: would leave available {} and total {}",
+                    client.available.to_decimal(),
+                    client.total.to_decimal()
+                );
+                *client = original;
+                self.review_queue.push(ReviewItem::new(
+                    ReviewReason::NegativeAvailable,
+                    client,
+                    Some(transaction.tx),
+                    note,
+                ));
+                self.audit.push(
+                    AuditEntry::new(
+                        AuditAction::NegativeBalanceReview,
+                        client,
+                        transaction.tx,
+                        amount,
+                    )
+                    .with_reason(format!("{tx_type} would leave a negative balance"))
+                    .with_reference(transaction.reference.clone()),
+                );
+                Err(("Held for manual review", err))
+            }
+            (outcome, _) => outcome,
+        };
+        let sequence = outcome.is_ok().then(|| {
+            self.sequence += 1;
+            self.sequence
+        });
+        if let (Some((accrual, amount)), Some(_)) = (accrued, sequence) {
+            self.audit.push(
+                AuditEntry::new(
+                    accrual.kind.audit_action(),
+                    client,
+                    transaction.tx,
+                    Some(amount),
+                )
+                .with_reason(accrual.reason())
+                .with_reference(transaction.reference.clone())
+                .with_sequence(sequence),
+            );
+        }
+        if zero_ignored && sequence.is_some() {
+            info!(
+                "Client {client_id}: ignoring zero-amount {tx_type} transaction {}",
+                transaction.tx
+            );
+            self.audit.push(
+                AuditEntry::new(
+                    AuditAction::ZeroAmountIgnored,
+                    client,
+                    transaction.tx,
+                    amount,
+                )
+                .with_reason(format!("zero-amount {tx_type}"))
+                .with_reference(transaction.reference.clone())
+                .with_sequence(sequence),
+            );
+            if let Some(history) = &mut self.history {
+                history.record(client, self.clock.now(), sequence);
+            }
+        }
+        if let (Some(histogram), Some(_), Some(amount)) =
+            (&mut self.amount_histogram, sequence, transaction.amount)
+            && !zero_ignored
+        {
+            histogram.record(client_id, tx_type, amount.value());
+        }
+        if let (TransactionType::Dispute, Some(sequence)) = (transaction.tx_type, sequence) {
+            client.set_dispute_sequence(transaction.tx, sequence);
+            if self.config.dispute_expiry.is_some() {
+                self.expiring.push_back(OpenDispute {
+                    sequence,
+                    client: client_id,
+                    tx: transaction.tx,
+                });
+            }
+        }
+        if balances(client) != balances_before {
+            self.changed.insert(client_id);
+            if let Some(history) = &mut self.history {
+                history.record(client, self.clock.now(), sequence);
+            }
+            if let Some(alerts) = &mut self.alerts {
+                alerts.observe(locked_before, client);
+            }
+        }
+        if sequence.is_some() && client.available.is_negative() && !available_before.is_negative() {
+            self.review_queue.push(
+                ReviewItem::new(
+                    ReviewReason::NegativeAvailable,
+                    client,
+                    Some(transaction.tx),
+                    format!("{tx_type} left available {}", client.available.to_decimal()),
+                )
+                .with_sequence(sequence),
+            );
+        }
+        if tx_type == TransactionType::Dispute
+            && sequence.is_some()
+            && self.config.review_after_disputes == Some(client.dispute_count())
+        {
+            self.review_queue.push(
+                ReviewItem::new(
+                    ReviewReason::RepeatedDisputes,
+                    client,
+                    Some(transaction.tx),
+                    format!("{} disputes", client.dispute_count()),
+                )
+                .with_sequence(sequence),
+            );
+        }
+        let rejection = outcome.err().map(|(context, e)| {
+            self.config.rejection_logging.log(
+                e.name(),
+                format_args!("{context}: {}", row_error(e.clone())),
+            );
+            Rejection::Client(e)
+        });
+
+        for reason in flags {
+            self.review_queue.push(
+                ReviewItem::new(
+                    ReviewReason::RuleFlagged,
+                    client,
+                    Some(transaction.tx),
+                    reason.clone(),
+                )
+                .with_sequence(sequence),
+            );
+            self.audit.push(
+                AuditEntry::new(AuditAction::RuleFlagged, client, transaction.tx, amount)
+                    .with_reason(reason)
+                    .with_reference(transaction.reference.clone())
+                    .with_sequence(sequence),
+            );
+        }
+        Ok(rejection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::EngineConfig,
+        dispute_expiry::{DisputeDeadline, DisputeExpiry, ExpiryOutcome},
+        hold_accrual::{AccrualKind, HoldAccrual},
+        numeric::MinorUnits,
+        rules::RuleDecision,
+    };
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    type Queued = (ReviewReason, ClientId, Option<TxId>, Option<u64>);
+
+    fn queued(engine: &mut Engine, rows: &str) -> Vec<Queued> {
+        engine
+            .process(Cursor::new(format!("type,client,tx,amount\n{rows}")))
+            .unwrap();
+        engine
+            .review_queue()
+            .iter()
+            .map(|item| (item.reason, item.client, item.tx, item.sequence))
+            .collect()
+    }
+
+    #[test]
+    fn rule_flags_are_queued_for_review() {
+        let mut engine = Engine::new();
+        engine.rules_mut().register("large", |_, transaction| {
+            match transaction.amount.map(|amount| amount.value()) {
+                Some(amount) if amount > dec!(100) => RuleDecision::Flag("large".to_string()),
+                _ => RuleDecision::Allow,
+            }
+        });
+
+        assert_eq!(
+            queued(&mut engine, "deposit,1,1,50\ndeposit,1,2,500\n"),
+            [(
+                ReviewReason::RuleFlagged,
+                ClientId(1),
+                Some(TxId(2)),
+                Some(2)
+            )]
+        );
+        assert_eq!(engine.review_queue()[0].note, "large: large");
+    }
+
+    #[test]
+    fn available_funds_going_negative_are_queued_once() {
+        let mut engine = Engine::new();
+
+        assert_eq!(
+            queued(
+                &mut engine,
+                "deposit,1,1,10\nwithdrawal,1,2,8\ndispute,1,1,\ndeposit,1,3,1\n"
+            ),
+            [(
+                ReviewReason::NegativeAvailable,
+                ClientId(1),
+                Some(TxId(1)),
+                Some(3)
+            )]
+        );
+        assert_eq!(engine.review_queue()[0].available, dec!(-8));
+    }
+
+    #[test]
+    fn transactions_held_for_negative_balances_are_queued_unsequenced() {
+        let mut engine = Engine::with_config(EngineConfig {
+            non_negative_balances: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            queued(
+                &mut engine,
+                "deposit,1,1,10\nwithdrawal,1,2,8\ndispute,1,1,\n"
+            ),
+            [(
+                ReviewReason::NegativeAvailable,
+                ClientId(1),
+                Some(TxId(1)),
+                None
+            )]
+        );
+        assert_eq!(engine.review_queue()[0].available, dec!(2));
+    }
+
+    #[test]
+    fn repeated_disputes_are_queued_only_when_configured() {
+        let disputes = "deposit,1,1,10\ndispute,1,1,\nresolve,1,1,\ndispute,1,1,\nresolve,1,1,\ndispute,1,1,\n";
+        assert!(queued(&mut Engine::new(), disputes).is_empty());
+
+        let mut engine = Engine::with_config(EngineConfig {
+            review_after_disputes: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            queued(&mut engine, disputes),
+            [(
+                ReviewReason::RepeatedDisputes,
+                ClientId(1),
+                Some(TxId(1)),
+                Some(4)
+            )]
+        );
+    }
+
+    #[test]
+    fn resolves_whose_accrual_overflows_are_rolled_back() {
+        // Interest of a tenth on 900 trillion held takes available past
+        // what `MinorUnits` holds.
+        let config = |dispute_expiry| EngineConfig {
+            hold_accrual: Some(HoldAccrual {
+                grace: DisputeDeadline::Sequence(0),
+                rate: dec!(0.1),
+                kind: AccrualKind::Interest,
+            }),
+            dispute_expiry,
+            ..Default::default()
+        };
+        let rows =
+            "type,client,tx,amount\ndeposit,1,1,900000000000000\ndispute,1,1,\ndeposit,2,2,1\n";
+        let held = |engine: &Engine<MinorUnits>| {
+            let client = engine.client(ClientId(1)).unwrap();
+            (
+                client.available,
+                client.held.value(),
+                client.open_disputes().count(),
+            )
+        };
+        let before = (
+            MinorUnits::from_minor(0),
+            MinorUnits::from_minor(9_000_000_000_000_000_000),
+            1,
+        );
+
+        let mut engine = Engine::<MinorUnits>::default();
+        engine.set_config(config(None));
+        engine.process(Cursor::new(rows)).unwrap();
+        let rejection = engine
+            .push(TransactionType::Resolve, ClientId(1), TxId(1), None)
+            .unwrap();
+        assert!(matches!(
+            rejection,
+            Some(Rejection::Client(ClientTransactionError::Arithmetic { .. }))
+        ));
+        assert_eq!(held(&engine), before);
+        assert_eq!(engine.last_sequence(), 3);
+
+        let mut expiring = Engine::<MinorUnits>::default();
+        expiring.set_config(config(Some(DisputeExpiry {
+            deadline: DisputeDeadline::Sequence(1),
+            outcome: ExpiryOutcome::Resolve,
+        })));
+        expiring.process(Cursor::new(rows)).unwrap();
+        assert_eq!(held(&expiring), before);
+        assert!(expiring.audit_entries().is_empty());
+    }
+
+    #[test]
+    fn custom_handlers_that_break_the_balances_are_rolled_back() {
+        let mut client = Client::new(ClientId(1));
+        client
+            .deposit(TxId(1), Money::new(dec!(5)).unwrap())
+            .unwrap();
+        let transaction = Transaction {
+            tx_type: TransactionType::from_name("bonus").unwrap(),
+            client: ClientId(1),
+            tx: TxId(2),
+            amount: None,
+            reference: None,
+        };
+        let unbalanced: custom::Handler<Decimal> = Box::new(|client, _| {
+            client.deposit(TxId(2), Money::new(dec!(2)).unwrap())?;
+            client.available += dec!(1);
+            Ok(())
+        });
+        let failing: custom::Handler<Decimal> = Box::new(|client, _| {
+            client.deposit(TxId(2), Money::new(dec!(2)).unwrap())?;
+            Err(ClientTransactionError::UnknownClient {
+                client_id: client.id,
+            })
+        });
+
+        assert_eq!(
+            apply_custom(&mut client, &transaction, &unbalanced),
+            Err(ClientTransactionError::InconsistentBalances {
+                client_id: ClientId(1)
+            })
+        );
+        assert!(apply_custom(&mut client, &transaction, &failing).is_err());
+        assert_eq!(
+            (client.available, client.total, client.deposit_count()),
+            (dec!(5), dec!(5), 1)
+        );
+        assert_eq!(client.deposits().count(), 1);
+    }
+
+    #[test]
+    fn unregistered_custom_types_are_rejected() {
+        let mut engine = Engine::new();
+        engine.enable_rejection_breakdown();
+        engine
+            .register_transaction_type("bonus", false, |_, _| Ok(()))
+            .unwrap();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,5\nmystery,1,2,1\nmystery,2,3,1\n",
+            ))
+            .unwrap();
+
+        assert_eq!(engine.row_counts().rejected, 2);
+        assert_eq!(
+            engine
+                .rejection_breakdown()
+                .unwrap()
+                .get("unknown transaction type mystery"),
+            Some(&2)
+        );
+        assert_eq!(engine.client(ClientId(1)).unwrap().total, dec!(5));
+        assert!(engine.client(ClientId(2)).is_none());
+    }
+
+    #[test]
+    fn transactions_are_validated_before_dispatch() {
+        let validate = |tx_type: TransactionType, tx, amount| {
+            validate_transaction::<Decimal>(
+                tx_type,
+                tx_type.requires_amount(),
+                ClientId(1),
+                tx,
+                amount,
+            )
+        };
+
+        assert!(matches!(
+            validate(TransactionType::Deposit, 1, Some(dec!(2.5))),
+            Ok(ValidatedTransaction::WithAmount { tx: TxId(1), .. })
+        ));
+        assert!(matches!(
+            validate(TransactionType::Dispute, 1, Some(dec!(2.5))),
+            Ok(ValidatedTransaction::NoAmount { tx: TxId(1) })
+        ));
+        assert!(matches!(
+            validate(TransactionType::Deposit, -1, Some(dec!(1))),
+            Err(ClientTransactionError::InvalidTransactionId { tx: -1, .. })
+        ));
+        assert!(matches!(
+            validate(
+                TransactionType::Deposit,
+                i64::from(u32::MAX) + 1,
+                Some(dec!(1))
+            ),
+            Err(ClientTransactionError::InvalidTransactionId { .. })
+        ));
+        assert!(matches!(
+            validate(TransactionType::Withdrawal, 2, Some(dec!(0))),
+            Err(ClientTransactionError::ZeroAmount { tx: TxId(2), .. })
+        ));
+        assert!(matches!(
+            validate(TransactionType::Withdrawal, 2, Some(dec!(-1))),
+            Err(ClientTransactionError::InvalidAmount { tx: TxId(2), .. })
+        ));
+        assert!(matches!(
+            validate(TransactionType::Withdrawal, 2, None),
+            Err(ClientTransactionError::MissingAmount { tx: TxId(2), .. })
+        ));
+    }
+}
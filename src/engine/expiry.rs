@@ -0,0 +1,150 @@
+use log::{info, warn};
+
+use super::Engine;
+use super::dispatch::balances;
+use crate::{
+    alerts::locked_contribution,
+    audit::{AuditAction, AuditEntry},
+    dispute_expiry::{DisputeDeadline, ExpiryOutcome},
+    errors::EngineError,
+    hold_accrual::resolve_accruing,
+    ids::{ClientId, TxId},
+    numeric::Balance,
+};
+
+/// A dispute waiting for its deadline. Entries are queued in the order the
+/// disputes were opened, so only the front can be due; ones settled in the
+/// meantime are skipped when they reach it.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct OpenDispute {
+    pub(crate) sequence: u64,
+    pub(crate) client: ClientId,
+    pub(crate) tx: TxId,
+}
+
+impl<B: Balance> Engine<B> {
+    /// Queues the open disputes of resident clients, oldest first, for an
+    /// expiry policy set after they were opened (typically on an engine
+    /// restored from a snapshot).
+    pub(crate) fn queue_open_disputes(&mut self) {
+        let mut open: Vec<OpenDispute> = self
+            .clients
+            .values()
+            .flat_map(|client| {
+                client.open_disputes().map(|(tx, _)| OpenDispute {
+                    sequence: client.dispute_sequence(tx).unwrap_or(0),
+                    client: client.id,
+                    tx,
+                })
+            })
+            .collect();
+        open.sort_unstable_by_key(|dispute| (dispute.sequence, dispute.client, dispute.tx));
+        self.expiring = open.into();
+    }
+
+    /// Requeues the open disputes after they moved between clients or
+    /// closed outside of a transaction, while an expiry policy is set.
+    pub(crate) fn requeue_open_disputes(&mut self) {
+        if self.config.dispute_expiry.is_some() {
+            self.queue_open_disputes();
+        }
+    }
+
+    /// Settles every queued dispute that is past `EngineConfig::dispute_expiry`
+    /// as of now, auditing each one.
+    pub(crate) fn expire_disputes(&mut self) -> Result<(), EngineError> {
+        let Some(expiry) = self.config.dispute_expiry else {
+            return Ok(());
+        };
+        let now = self.clock.now();
+        while let Some(&open) = self.expiring.front() {
+            if !self.clients.contains_key(open.client) {
+                self.touch(open.client)?;
+            }
+            let Some(client) = self.clients.get_mut(open.client) else {
+                self.expiring.pop_front();
+                continue;
+            };
+            let amount = client
+                .open_disputes()
+                .find(|(tx, _)| *tx == open.tx)
+                .map(|(_, amount)| amount);
+            if amount.is_none() || client.dispute_sequence(open.tx).unwrap_or(0) != open.sequence {
+                // Settled since, or disputed again under a later sequence.
+                self.expiring.pop_front();
+                continue;
+            }
+            let due = match expiry.deadline {
+                DisputeDeadline::Sequence(units) => {
+                    self.sequence.saturating_sub(open.sequence) >= units
+                }
+                DisputeDeadline::Age(age) => match client.dispute_age(open.tx, now) {
+                    Some(held) => held >= age,
+                    // Without an opening time it can never come due.
+                    None => {
+                        self.expiring.pop_front();
+                        continue;
+                    }
+                },
+            };
+            if !due {
+                return Ok(());
+            }
+            self.expiring.pop_front();
+
+            let balances_before = balances(client);
+            let locked_before = locked_contribution(client);
+            let accrued = match expiry.outcome {
+                ExpiryOutcome::Resolve => self.config.hold_accrual.and_then(|accrual| {
+                    let amount = accrual.accrued(client, open.tx, now, self.sequence)?;
+                    Some((accrual, amount))
+                }),
+                ExpiryOutcome::Chargeback => None,
+            };
+            let (action, result) = match expiry.outcome {
+                ExpiryOutcome::Resolve => (
+                    AuditAction::ExpiredResolve,
+                    resolve_accruing(client, open.tx, accrued),
+                ),
+                ExpiryOutcome::Chargeback => {
+                    (AuditAction::ExpiredChargeback, client.chargeback(open.tx))
+                }
+            };
+            if let Err(err) = result {
+                warn!(
+                    "Client {}: could not settle expired dispute on transaction {}, left open: {err}",
+                    open.client, open.tx
+                );
+                continue;
+            }
+            info!(
+                "Client {}: dispute on transaction {} expired, applied {action}",
+                open.client, open.tx
+            );
+            let reason = match expiry.deadline {
+                DisputeDeadline::Age(age) => format!("not settled within {}s", age.as_secs()),
+                DisputeDeadline::Sequence(units) => {
+                    format!("not settled within {units} transactions")
+                }
+            };
+            self.audit
+                .push(AuditEntry::new(action, client, open.tx, amount).with_reason(reason));
+            if let Some((accrual, accrued)) = accrued {
+                self.audit.push(
+                    AuditEntry::new(accrual.kind.audit_action(), client, open.tx, Some(accrued))
+                        .with_reason(accrual.reason()),
+                );
+            }
+            if balances(client) != balances_before {
+                self.changed.insert(open.client);
+                if let Some(history) = &mut self.history {
+                    history.record(client, now, None);
+                }
+                if let Some(alerts) = &mut self.alerts {
+                    alerts.observe(locked_before, client);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,402 @@
+use csv::{ByteRecord, Position, StringRecord};
+use log::warn;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+
+use super::lenient::LenientRecords;
+use super::priority::PriorityReorder;
+use crate::{
+    aliases::ClientAliases,
+    config::EngineConfig,
+    dead_letter::RejectedRow,
+    detect::sniff,
+    encoding::decode,
+    errors::{AmountError, EngineError},
+    format::{Format, read_json_transactions},
+    formatting::FormattingOptions,
+    header::{default_header, validate_header},
+    ids::ClientId,
+    preprocess::Preprocessor,
+    transaction::TransactionType,
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct InputTransaction {
+    #[serde(rename = "type")]
+    pub(crate) tx_type: TransactionType,
+    pub(crate) client: ClientId,
+    pub(crate) tx: i64,
+    pub(crate) amount: Option<Decimal>,
+    #[serde(default)]
+    pub(crate) tenant: Option<String>,
+    #[serde(default)]
+    pub(crate) reference: Option<String>,
+    /// Only read with `EngineConfig::priority_window` set.
+    #[serde(default)]
+    pub(crate) priority: Option<i32>,
+    /// 1-based position in the input, for error context.
+    #[serde(skip)]
+    pub(crate) row: usize,
+    /// The row as read, kept only while a dead-letter file is set.
+    #[serde(skip)]
+    pub(crate) raw: Option<StringRecord>,
+}
+
+/// Raw records of a CSV input. The strict reader parses each row into one
+/// reused record rather than a fresh one per row, which saves three
+/// allocations a row: in the `deposit-heavy` workload bench, from about four
+/// per row to one. It reads the bytes first, into a second reused buffer, so
+/// a row that is not valid UTF-8 can still be dead-lettered as it was. The
+/// lenient reader builds its records as it goes, so it gains nothing from the
+/// buffers.
+enum Records<R> {
+    Strict(csv::Reader<R>, ByteRecord),
+    Lenient(LenientRecords<BufReader<R>>),
+}
+
+impl<R: Read> Records<R> {
+    /// Reads the next row into `record`, or returns `None` at the end.
+    fn read_into(&mut self, record: &mut StringRecord) -> Option<Result<(), RejectedRow>> {
+        match self {
+            Records::Strict(reader, bytes) => {
+                let position = reader.position().clone();
+                match reader.read_byte_record(bytes) {
+                    Ok(true) => Some(decode_record(bytes, record, &position)),
+                    Ok(false) => None,
+                    Err(err) => Some(Err(RejectedRow {
+                        row: 0,
+                        raw: ByteRecord::new(),
+                        reason: err.to_string(),
+                    })),
+                }
+            }
+            Records::Lenient(records) => Some(records.next()?.map(|read| *record = read)),
+        }
+    }
+}
+
+/// Copies `bytes` into `record`, or rejects them whole, with the reason the
+/// csv reader gives, when a field is not valid UTF-8.
+fn decode_record(
+    bytes: &ByteRecord,
+    record: &mut StringRecord,
+    position: &Position,
+) -> Result<(), RejectedRow> {
+    record.clear();
+    for (field, value) in bytes.iter().enumerate() {
+        match std::str::from_utf8(value) {
+            Ok(value) => record.push_field(value),
+            Err(err) => {
+                return Err(RejectedRow {
+                    row: 0,
+                    raw: bytes.clone(),
+                    reason: format!(
+                        "CSV parse error: record {} (line {}, field: {field}, byte: {}): invalid utf-8: \
+                         invalid UTF-8 in field {field} near byte index {}",
+                        position.record(),
+                        position.line(),
+                        position.byte(),
+                        err.valid_up_to()
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) type InputRows<'a> =
+    Box<dyn Iterator<Item = Result<InputTransaction, RejectedRow>> + 'a>;
+
+/// Parsed rows, plus the input's own header for the dead-letter file. Rows
+/// that cannot be parsed are logged and come back as `Err`; their raw fields
+/// are only kept when `keep_raw` is set.
+fn read_transactions<'a, R: Read + 'a>(
+    source: R,
+    config: &EngineConfig,
+    preprocessors: &[Arc<dyn Preprocessor>],
+    keep_raw: bool,
+) -> Result<
+    (
+        Option<StringRecord>,
+        impl Iterator<Item = Result<InputTransaction, RejectedRow>> + use<'a, R>,
+    ),
+    EngineError,
+> {
+    let has_headers = config.has_headers;
+    let formatting = config.formatting.clone();
+    // Flexible so short and long rows can still be dead-lettered verbatim;
+    // their length is checked below instead.
+    let (input_header, mut records) = if config.lenient_csv {
+        let mut records = LenientRecords::new(BufReader::new(source), config.input_delimiter);
+        let header = if has_headers {
+            match records.next().transpose() {
+                Ok(header) => Some(header.unwrap_or_default()),
+                Err(malformed) => {
+                    return Err(EngineError::Io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unreadable header: {}", malformed.reason),
+                    )));
+                }
+            }
+        } else {
+            None
+        };
+        (header, Records::Lenient(records))
+    } else {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(has_headers)
+            .delimiter(config.input_delimiter)
+            .flexible(true)
+            .from_reader(source);
+        let header = has_headers.then(|| reader.headers().cloned()).transpose()?;
+        (header, Records::Strict(reader, ByteRecord::new()))
+    };
+    let header = match &input_header {
+        Some(header) => {
+            validate_header(header, config.strict_columns)?;
+            header.clone()
+        }
+        None => default_header(),
+    };
+    let amount_index = header.iter().position(|column| column == "amount");
+    let client_index = header.iter().position(|column| column == "client");
+    let aliases = config.client_aliases.clone();
+    let logging = config.rejection_logging.clone();
+    let preprocessors = preprocessors.to_vec();
+    let mut expected_len = has_headers.then(|| header.len());
+
+    let mut parse = move |row: usize, result: Result<&StringRecord, RejectedRow>| {
+        let reject = |raw: &ByteRecord, reason: String| {
+            logging.log(
+                "Parse",
+                format_args!("Error parsing CSV row {row}: {reason}"),
+            );
+            Some(Err(RejectedRow {
+                row,
+                raw: if keep_raw {
+                    raw.clone()
+                } else {
+                    ByteRecord::new()
+                },
+                reason,
+            }))
+        };
+        let record = match result {
+            Ok(record) => record,
+            Err(rejected) => return reject(&rejected.raw, rejected.reason),
+        };
+        let expected = *expected_len.get_or_insert(record.len());
+        if record.len() != expected {
+            return reject(
+                record.as_byte_record(),
+                format!(
+                    "found record with {} fields, but the previous record has {expected} fields",
+                    record.len()
+                ),
+            );
+        }
+        if has_headers && record.iter().eq(header.iter()) {
+            warn!("Skipping repeated header at CSV row {row}");
+            return None;
+        }
+        let mut preprocessed = None;
+        if !preprocessors.is_empty() {
+            let mut rewritten = record.clone();
+            for preprocessor in &preprocessors {
+                if let Err(reason) = preprocessor.preprocess(&header, &mut rewritten) {
+                    return reject(record.as_byte_record(), reason);
+                }
+            }
+            preprocessed = Some(rewritten);
+        }
+        let preprocessed = preprocessed.as_ref().unwrap_or(record);
+        let normalized = match normalize_amount(preprocessed, amount_index, &formatting) {
+            Ok(normalized) => normalized,
+            Err(err) => return reject(record.as_byte_record(), err.to_string()),
+        };
+        let normalized = normalized.as_ref().unwrap_or(preprocessed);
+        let aliased = match &aliases {
+            Some(aliases) => match resolve_client(normalized, client_index, aliases) {
+                Ok(aliased) => aliased,
+                Err(reason) => return reject(record.as_byte_record(), reason),
+            },
+            None => None,
+        };
+        match aliased
+            .as_ref()
+            .unwrap_or(normalized)
+            .deserialize::<InputTransaction>(Some(&header))
+        {
+            Ok(transaction) => Some(Ok(InputTransaction {
+                row,
+                raw: keep_raw.then(|| record.clone()),
+                ..transaction
+            })),
+            Err(err) => reject(record.as_byte_record(), err.to_string()),
+        }
+    };
+    let mut record = StringRecord::new();
+    let mut row = 0;
+    let transactions = std::iter::from_fn(move || {
+        loop {
+            let result = records.read_into(&mut record)?;
+            row += 1;
+            if let Some(parsed) = parse(row, result.map(|()| &record)) {
+                return Some(parsed);
+            }
+        }
+    });
+    Ok((input_header, transactions))
+}
+
+/// Rewrites a localized amount field (`3,50`, `1,234.56`) into the plain
+/// form serde expects, or returns `None` when the record can be used as is.
+/// Plain input skips this unless a field carries a comma, so `1,234` is
+/// reported as ambiguous instead of as an invalid number.
+fn normalize_amount(
+    record: &StringRecord,
+    amount_index: Option<usize>,
+    formatting: &FormattingOptions,
+) -> Result<Option<StringRecord>, AmountError> {
+    let Some(field) = amount_index.and_then(|index| record.get(index)) else {
+        return Ok(None);
+    };
+    if field.trim().is_empty() || (formatting.has_default_input() && !field.contains(',')) {
+        return Ok(None);
+    }
+    let amount = formatting.parse_amount(field)?.to_string();
+    Ok(Some(
+        record
+            .iter()
+            .enumerate()
+            .map(|(position, field)| {
+                if Some(position) == amount_index {
+                    amount.as_str()
+                } else {
+                    field
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Rewrites an external id in the `client` field into its client id, or
+/// returns `None` when the field already holds a client id.
+fn resolve_client(
+    record: &StringRecord,
+    client_index: Option<usize>,
+    aliases: &ClientAliases,
+) -> Result<Option<StringRecord>, String> {
+    let Some((index, field)) = client_index.and_then(|index| Some((index, record.get(index)?)))
+    else {
+        return Ok(None);
+    };
+    let client = match aliases.resolve(field) {
+        Ok(Some(client)) => client.to_string(),
+        Ok(None) => return Ok(None),
+        Err(unknown) => return Err(format!("unknown client {unknown}")),
+    };
+    Ok(Some(
+        record
+            .iter()
+            .enumerate()
+            .map(|(position, field)| {
+                if position == index {
+                    client.as_str()
+                } else {
+                    field
+                }
+            })
+            .collect(),
+    ))
+}
+
+pub(crate) fn read_input<'a, R: Read + 'a>(
+    source: R,
+    config: &EngineConfig,
+    preprocessors: &[Arc<dyn Preprocessor>],
+    keep_raw: bool,
+) -> Result<(Option<StringRecord>, InputRows<'a>), EngineError> {
+    let detected_config;
+    let (source, config) = if config.detect_input_format {
+        let (detected, source) = sniff(source, config)?;
+        detected_config = detected.apply(config);
+        (source, &detected_config)
+    } else {
+        (decode(source, config.input_encoding)?, config)
+    };
+    let (header, rows): (_, InputRows<'a>) = match config.input_format {
+        Format::Csv => {
+            let (header, rows) = read_transactions(source, config, preprocessors, keep_raw)?;
+            (header, Box::new(rows))
+        }
+        Format::Json => (
+            Some(StringRecord::from(vec!["line"])),
+            Box::new(read_json_transactions(
+                source,
+                keep_raw,
+                config.rejection_logging.clone(),
+            )),
+        ),
+    };
+    Ok(match config.priority_window {
+        Some(window) => (header, Box::new(PriorityReorder::new(rows, window))),
+        None => (header, rows),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::DecimalSeparator;
+    use rust_decimal::dec;
+
+    #[test]
+    fn rows_are_parsed_or_rejected_with_their_position() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,x,2,1\nwithdrawal,1,3,\n";
+        let (header, rows) =
+            read_input(input.as_bytes(), &EngineConfig::default(), &[], true).unwrap();
+        let rows: Vec<_> = rows.collect();
+
+        assert_eq!(header.unwrap().len(), 4);
+        assert_eq!(rows.len(), 3);
+        let Ok(deposit) = &rows[0] else {
+            panic!("deposit row was rejected");
+        };
+        assert_eq!(deposit.tx_type, TransactionType::Deposit);
+        assert_eq!(
+            (deposit.client, deposit.tx, deposit.row),
+            (ClientId(1), 1, 1)
+        );
+        assert_eq!(deposit.amount, Some(dec!(1.5)));
+        assert!(deposit.raw.is_some());
+        let Err(rejected) = &rows[1] else {
+            panic!("row with a bad client id was accepted");
+        };
+        assert_eq!(rejected.raw.get(1), Some(&b"x"[..]));
+        let Ok(withdrawal) = &rows[2] else {
+            panic!("withdrawal row was rejected");
+        };
+        assert_eq!((withdrawal.row, withdrawal.amount), (3, None));
+    }
+
+    #[test]
+    fn amounts_are_normalized_to_the_default_notation() {
+        let formatting = FormattingOptions {
+            decimal_separator: DecimalSeparator::Comma,
+            ..FormattingOptions::default()
+        };
+        let record = StringRecord::from(vec!["deposit", "1", "1", "2,5"]);
+
+        let normalized = normalize_amount(&record, Some(3), &formatting).unwrap();
+        assert_eq!(normalized.unwrap().get(3), Some("2.5"));
+        assert_eq!(normalize_amount(&record, None, &formatting).unwrap(), None);
+
+        let record = StringRecord::from(vec!["deposit", "1", "1", "1.5"]);
+        let default = FormattingOptions::default();
+        assert_eq!(normalize_amount(&record, Some(3), &default).unwrap(), None);
+    }
+}
@@ -0,0 +1,218 @@
+use csv::{ByteRecord, StringRecord};
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead},
+};
+
+use crate::dead_letter::RejectedRow;
+
+/// Most physical lines one quoted field may span in lenient mode. A quote
+/// still open after this many lines is taken to be malformed.
+pub const MAX_FIELD_LINES: usize = 16;
+
+/// Splits CSV input into logical records before parsing them, for
+/// `EngineConfig::lenient_csv`. Quoted fields may still span lines, but a
+/// quote that is never closed costs just the line it started on, where the
+/// plain reader would read the rest of the file into that one field. So does
+/// a quote that only closes by swallowing lines that are rows in their own
+/// right, or into a record with the wrong number of fields.
+pub(crate) struct LenientRecords<R> {
+    source: R,
+    lines: VecDeque<Vec<u8>>,
+    /// Field count of the first record (the header, when there is one).
+    expected_len: Option<usize>,
+    exhausted: bool,
+    delimiter: u8,
+}
+
+impl<R: BufRead> LenientRecords<R> {
+    pub(crate) fn new(source: R, delimiter: u8) -> Self {
+        LenientRecords {
+            source,
+            lines: VecDeque::new(),
+            expected_len: None,
+            exhausted: false,
+            delimiter,
+        }
+    }
+
+    /// Makes sure at least `count` lines are buffered, unless the input ends.
+    fn buffer_lines(&mut self, count: usize) -> io::Result<bool> {
+        while self.lines.len() < count && !self.exhausted {
+            let mut line = Vec::new();
+            if self.source.read_until(b'\n', &mut line)? == 0 {
+                self.exhausted = true;
+            } else {
+                self.lines.push_back(line);
+            }
+        }
+        Ok(self.lines.len() >= count)
+    }
+
+    /// Whether `line` reads as a whole row on its own. A quoted field that
+    /// would have to run through such a line is more likely a stray quote.
+    fn is_complete_row(&self, line: &[u8]) -> bool {
+        !ends_in_quoted_field(line, false, self.delimiter)
+            && parse_records(line, self.delimiter).is_ok_and(|records| {
+                records.len() == 1 && Some(records[0].len()) == self.expected_len
+            })
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Result<StringRecord, RejectedRow>>> {
+        loop {
+            if !self.buffer_lines(1)? {
+                return Ok(None);
+            }
+            let mut taken = 1;
+            let mut quoted = ends_in_quoted_field(&self.lines[0], false, self.delimiter);
+            while quoted && taken < MAX_FIELD_LINES && self.buffer_lines(taken + 1)? {
+                quoted = ends_in_quoted_field(&self.lines[taken], true, self.delimiter);
+                taken += 1;
+            }
+            let bytes: Vec<u8> = self.lines.range(..taken).flatten().copied().collect();
+            let parsed = parse_records(&bytes, self.delimiter);
+            let fits = |record: &StringRecord| {
+                taken == 1
+                    || (self.expected_len.is_none_or(|len| record.len() == len)
+                        && !self
+                            .lines
+                            .range(1..taken)
+                            .any(|line| self.is_complete_row(line)))
+            };
+            match parsed {
+                Ok(mut records) if !quoted && records.len() == 1 && fits(&records[0]) => {
+                    self.lines.drain(..taken);
+                    let record = records.remove(0);
+                    self.expected_len.get_or_insert(record.len());
+                    return Ok(Some(Ok(record)));
+                }
+                Ok(records) if !quoted && records.is_empty() => {
+                    // Blank line.
+                    self.lines.drain(..taken);
+                }
+                Err(reason) if !quoted && taken == 1 => {
+                    let line = self.lines.pop_front().unwrap_or_default();
+                    return Ok(Some(Err(malformed(&line, reason))));
+                }
+                _ => {
+                    let line = self.lines.pop_front().unwrap_or_default();
+                    return Ok(Some(Err(malformed(
+                        &line,
+                        "unterminated quoted field".to_string(),
+                    ))));
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LenientRecords<R> {
+    type Item = Result<StringRecord, RejectedRow>;
+
+    /// A read error ends the input after it is reported.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().unwrap_or_else(|err| {
+            self.exhausted = true;
+            self.lines.clear();
+            Some(Err(RejectedRow {
+                row: 0,
+                raw: ByteRecord::new(),
+                reason: err.to_string(),
+            }))
+        })
+    }
+}
+
+/// Whether a quoted field is still open at the end of `line`. Only a quote
+/// at the start of a field opens one; elsewhere quotes are literal, as in the
+/// csv reader.
+fn ends_in_quoted_field(line: &[u8], mut quoted: bool, delimiter: u8) -> bool {
+    let mut field_start = !quoted;
+    let mut bytes = line.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if quoted {
+            if byte == b'"' && bytes.next_if_eq(&&b'"').is_none() {
+                quoted = false;
+            }
+        } else if byte == b'"' && field_start {
+            quoted = true;
+        }
+        field_start = !quoted && byte == delimiter;
+    }
+    quoted
+}
+
+fn parse_records(bytes: &[u8], delimiter: u8) -> Result<Vec<StringRecord>, String> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .flexible(true)
+        .buffer_capacity(bytes.len() + 1)
+        .from_reader(bytes)
+        .into_records()
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())
+}
+
+/// The line a malformed record started on, kept whole as one field so the dead-letter file shows
+/// it exactly as it was read.
+fn malformed(mut line: &[u8], reason: String) -> RejectedRow {
+    while let [rest @ .., b'\r' | b'\n'] = line {
+        line = rest;
+    }
+    RejectedRow {
+        row: 0,
+        raw: ByteRecord::from(vec![line]),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &str) -> Vec<Result<Vec<String>, String>> {
+        LenientRecords::new(input.as_bytes(), b',')
+            .map(|record| match record {
+                Ok(record) => Ok(record.iter().map(str::to_string).collect()),
+                Err(malformed) => Err(String::from_utf8_lossy(&malformed.raw[0]).into_owned()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quoted_fields_may_span_lines() {
+        let records = read("a,b,memo\n1,2,\"x, \"\"y\"\"\nz\"\n\n3,4,5\n");
+        assert_eq!(
+            records,
+            [
+                Ok(vec!["a".into(), "b".into(), "memo".into()]),
+                Ok(vec!["1".into(), "2".into(), "x, \"y\"\nz".into()]),
+                Ok(vec!["3".into(), "4".into(), "5".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_unclosed_quote_costs_only_its_line() {
+        let records = read("a,b,memo\n1,2,\"open\n3,4,5\n6,7,8\n");
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1], Err("1,2,\"open".to_string()));
+        assert_eq!(records[2], Ok(vec!["3".into(), "4".into(), "5".into()]));
+        assert_eq!(records[3], Ok(vec!["6".into(), "7".into(), "8".into()]));
+    }
+
+    #[test]
+    fn quotes_do_not_pair_up_across_rows() {
+        let records = read("a,b,memo\n1,2,\"open\nmore\n6,7\",8\n9,10,11\n");
+        assert_eq!(records[1], Err("1,2,\"open".to_string()));
+        assert_eq!(records[2], Ok(vec!["more".into()]));
+        assert_eq!(records[3], Ok(vec!["6".into(), "7\"".into(), "8".into()]));
+        assert_eq!(records[4], Ok(vec!["9".into(), "10".into(), "11".into()]));
+
+        let records = read("a,b,memo\n1,2,\"open\n3,4,5\n6,7,x\"y\n");
+        assert_eq!(records[1], Err("1,2,\"open".to_string()));
+        assert_eq!(records[2], Ok(vec!["3".into(), "4".into(), "5".into()]));
+        assert_eq!(records[3], Ok(vec!["6".into(), "7".into(), "x\"y".into()]));
+    }
+}
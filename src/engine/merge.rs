@@ -0,0 +1,235 @@
+use std::{collections::HashSet, str::FromStr};
+
+use super::Engine;
+use crate::{errors::EngineError, numeric::Balance};
+
+/// What [`Engine::merge`] does with a client both engines have.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClientConflict {
+    /// Fail with `EngineError::MergeConflict` before anything changes.
+    /// Shards split by client never share one.
+    #[default]
+    Reject,
+    /// Fold the two accounts into one, as `admin merge` does (see
+    /// [`crate::client::Client::merge`]). A transaction id known to both
+    /// fails the merge.
+    Combine,
+}
+
+impl FromStr for ClientConflict {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "reject" => Ok(ClientConflict::Reject),
+            "combine" => Ok(ClientConflict::Combine),
+            other => Err(format!(
+                "unknown client conflict strategy {other}, expected reject or combine"
+            )),
+        }
+    }
+}
+
+impl<B: Balance> Engine<B> {
+    /// Folds `other` into this engine, for inputs sharded and processed on
+    /// separate machines, then combined. Clients, unwritten audit entries,
+    /// the review queue, balance history, applied-input digests and row
+    /// counts carry over. The sequence numbers of `other` continue after
+    /// this engine's, so they stay unique. Clients found in both are
+    /// handled by `conflicts`. Either engine's eviction store is read as
+    /// needed.
+    ///
+    /// Everything is checked before anything changes, so on failure this
+    /// engine is as it was. Engines of different tenants are never merged.
+    /// Metrics, amount histograms and quality scores belong to the run that
+    /// collected them and are not carried over.
+    pub fn merge(
+        &mut self,
+        other: Engine<B>,
+        conflicts: ClientConflict,
+    ) -> Result<(), EngineError> {
+        if self.tenant != other.tenant {
+            return Err(EngineError::MergeConflict(format!(
+                "tenant {:?} cannot be merged into tenant {:?}",
+                other.tenant, self.tenant
+            )));
+        }
+        let mut incoming = Vec::new();
+        other.visit_clients(|client| {
+            incoming.push(client.clone());
+            Ok(())
+        })?;
+
+        let offset = self.sequence;
+        let mut merged = Vec::with_capacity(incoming.len());
+        let mut combined = HashSet::new();
+        for mut client in incoming {
+            client.shift_dispute_sequences(offset);
+            self.touch(client.id)?;
+            match self.clients.get(client.id) {
+                None => merged.push(client),
+                Some(_) if conflicts == ClientConflict::Reject => {
+                    return Err(EngineError::MergeConflict(format!(
+                        "client {} is in both engines",
+                        client.id
+                    )));
+                }
+                Some(existing) => {
+                    let mut existing = existing.clone();
+                    existing.merge(&client)?;
+                    combined.insert(existing.id);
+                    merged.push(existing);
+                }
+            }
+        }
+
+        self.sequence += other.sequence;
+        let absorbed_history = match (&mut self.history, other.history) {
+            (Some(history), Some(theirs)) => {
+                history.absorb(theirs, offset, &combined);
+                true
+            }
+            _ => false,
+        };
+        for client in merged {
+            let client_id = client.id;
+            self.clients.insert(client);
+            self.touch(client_id)?;
+            if absorbed_history && !combined.contains(&client_id) {
+                self.changed.insert(client_id);
+            } else {
+                self.mark_changed(client_id);
+            }
+        }
+        self.audit.extend(other.audit.into_iter().map(|mut entry| {
+            entry.sequence = entry.sequence.map(|sequence| sequence + offset);
+            entry
+        }));
+        self.review_queue
+            .extend(other.review_queue.into_iter().map(|mut item| {
+                item.sequence = item.sequence.map(|sequence| sequence + offset);
+                item
+            }));
+        self.processed_inputs.extend(other.processed_inputs);
+        self.rows.read += other.rows.read;
+        self.rows.rejected += other.rows.rejected;
+        if let (Some(rejections), Some(theirs)) = (&mut self.rejections, other.rejections) {
+            for (reason, count) in theirs {
+                *rejections.entry(reason).or_default() += count;
+            }
+        }
+        self.last_tx = self.last_tx.max(other.last_tx);
+        self.tx_order_violations += other.tx_order_violations;
+        self.requeue_open_disputes();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::EngineConfig,
+        dispute_expiry::{DisputeDeadline, DisputeExpiry, ExpiryOutcome},
+        ids::{ClientId, TxId},
+        transaction::TransactionType,
+    };
+    use rust_decimal::dec;
+
+    fn engine(input: &str) -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .process(format!("type,client,tx,amount\n{input}").as_bytes())
+            .unwrap();
+        engine
+    }
+
+    fn accounts(engine: &Engine) -> String {
+        let mut output = Vec::new();
+        engine.write_accounts(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn shards_split_by_client_merge_into_the_unsharded_result() {
+        let odd = "deposit,1,1,10\ndeposit,3,3,5\ndispute,1,1,\n";
+        let even = "deposit,2,2,7\nwithdrawal,2,4,2\nwithdrawal,2,5,9\n";
+        let whole = engine(&format!("{odd}{even}"));
+
+        let mut merged = engine(odd);
+        merged.merge(engine(even), ClientConflict::Reject).unwrap();
+
+        assert_eq!(accounts(&merged), accounts(&whole));
+        assert_eq!(merged.row_counts(), whole.row_counts());
+        assert_eq!(merged.last_sequence(), whole.last_sequence());
+    }
+
+    #[test]
+    fn merged_disputes_expire_after_the_shifted_sequence() {
+        let mut merged = Engine::with_config(EngineConfig {
+            dispute_expiry: Some(DisputeExpiry {
+                deadline: DisputeDeadline::Sequence(3),
+                outcome: ExpiryOutcome::Resolve,
+            }),
+            ..Default::default()
+        });
+        merged
+            .process(
+                "type,client,tx,amount\ndeposit,1,1,1\ndeposit,1,2,1\ndeposit,1,3,1\n".as_bytes(),
+            )
+            .unwrap();
+        merged
+            .merge(
+                engine("deposit,2,10,5\ndispute,2,10,\n"),
+                ClientConflict::Reject,
+            )
+            .unwrap();
+        let held = |engine: &Engine| engine.client(ClientId(2)).unwrap().held;
+
+        assert_eq!(
+            merged
+                .client(ClientId(2))
+                .unwrap()
+                .dispute_sequence(TxId(10)),
+            Some(5)
+        );
+        for tx in 4..7 {
+            merged
+                .push(
+                    TransactionType::Deposit,
+                    ClientId(1),
+                    TxId(tx),
+                    Some(dec!(1)),
+                )
+                .unwrap();
+            assert_eq!(held(&merged), dec!(5));
+        }
+        merged
+            .push(
+                TransactionType::Deposit,
+                ClientId(1),
+                TxId(7),
+                Some(dec!(1)),
+            )
+            .unwrap();
+        assert_eq!(held(&merged), dec!(0));
+    }
+
+    #[test]
+    fn shared_clients_are_rejected_or_combined() {
+        let mut merged = engine("deposit,1,1,10\n");
+
+        let result = merged.merge(engine("deposit,1,2,5\n"), ClientConflict::Reject);
+        assert!(matches!(result, Err(EngineError::MergeConflict(_))));
+        assert_eq!(merged.client(ClientId(1)).unwrap().total, dec!(10));
+
+        let result = merged.merge(engine("deposit,1,1,5\n"), ClientConflict::Combine);
+        assert!(matches!(result, Err(EngineError::Admin(_))));
+        assert_eq!(merged.client(ClientId(1)).unwrap().total, dec!(10));
+
+        merged
+            .merge(engine("deposit,1,2,5\n"), ClientConflict::Combine)
+            .unwrap();
+        assert_eq!(merged.client(ClientId(1)).unwrap().total, dec!(15));
+    }
+}
@@ -0,0 +1,789 @@
+pub(crate) mod dispatch;
+mod expiry;
+pub(crate) mod ingest;
+pub mod lenient;
+mod merge;
+pub mod output;
+mod pipeline;
+pub mod priority;
+
+pub use dispatch::Rejection;
+use expiry::OpenDispute;
+use ingest::InputTransaction;
+pub use merge::ClientConflict;
+use pipeline::Pipeline;
+
+use log::{info, warn};
+use rust_decimal::Decimal;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    io::{Read, Seek, SeekFrom, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    alerts::{Alert, AlertMonitor, AlertThresholds},
+    amount_histogram::AmountHistogram,
+    audit::AuditEntry,
+    client::Client,
+    clock::{Clock, SystemClock},
+    config::EngineConfig,
+    custom::{CustomHandler, CustomTypes},
+    dead_letter::{DeadLetter, RejectedTransaction},
+    errors::{ClientTransactionError, EngineError},
+    eviction::EvictionPolicy,
+    guard::ClientGuard,
+    history::{BalanceHistory, BalancePoint, PointInTime, Retention},
+    ids::{ClientId, TxId},
+    memory::{LOW_WATER_PERCENT, MemoryPolicy},
+    metrics::{Metrics, MetricsRecorder},
+    money::Money,
+    numeric::Balance,
+    preprocess::Preprocessor,
+    quality::{QualityReport, QualityTracker},
+    registry::ClientRegistry,
+    report::reason_category,
+    review::{ReviewItem, ReviewReason},
+    rules::RuleSet,
+    snapshot::{SNAPSHOT_VERSION, Snapshot},
+    transaction::{Transaction, TransactionType},
+};
+
+/// How many input rows an engine has read, and how many of those were
+/// skipped as unparseable or rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    pub read: usize,
+    pub rejected: usize,
+}
+
+impl RowCounts {
+    /// Share of rows rejected, `0.0` when nothing was read.
+    pub fn error_rate(&self) -> f64 {
+        if self.read == 0 {
+            0.0
+        } else {
+            self.rejected as f64 / self.read as f64
+        }
+    }
+
+    pub(crate) fn record(&mut self, rejected: bool) {
+        self.read += 1;
+        self.rejected += usize::from(rejected);
+    }
+}
+
+/// Accounts and everything processing them. `B` is the type client
+/// balances are kept in (see [`Balance`]); rows, rules, custom handlers
+/// and reports still see amounts as [`Decimal`].
+pub struct Engine<B = Decimal> {
+    config: EngineConfig,
+    tenant: Option<String>,
+    pub(crate) clients: ClientRegistry<B>,
+    rules: RuleSet<B>,
+    pub(crate) audit: Vec<AuditEntry>,
+    clock: Arc<dyn Clock>,
+    eviction: Option<EvictionPolicy<B>>,
+    pub(crate) last_touched: HashMap<ClientId, SystemTime>,
+    processed_inputs: BTreeSet<String>,
+    interrupt: Option<Arc<AtomicBool>>,
+    dead_letter: Option<DeadLetter>,
+    review: Option<DeadLetter>,
+    pub(crate) review_queue: Vec<ReviewItem>,
+    rejection_sender: Option<Sender<RejectedTransaction>>,
+    /// Clients created or whose balances moved since this engine was built.
+    pub(crate) changed: HashSet<ClientId>,
+    rows: RowCounts,
+    rejections: Option<BTreeMap<String, usize>>,
+    /// Sequence number of the last accepted transaction.
+    sequence: u64,
+    /// Highest id of a row that opened a transaction, and how many rows
+    /// `EngineConfig::strict_tx_order` rejected.
+    last_tx: Option<TxId>,
+    tx_order_violations: usize,
+    /// Open disputes in opening order, while `EngineConfig::dispute_expiry`
+    /// is set.
+    expiring: VecDeque<OpenDispute>,
+    pub(crate) history: Option<BalanceHistory>,
+    alerts: Option<AlertMonitor>,
+    metrics: Option<MetricsRecorder>,
+    amount_histogram: Option<AmountHistogram>,
+    quality: Option<QualityTracker>,
+    custom_types: CustomTypes<B>,
+    pub(crate) preprocessors: Vec<Arc<dyn Preprocessor>>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<crate::fault::FaultInjector>>,
+}
+
+impl<B: Balance> Default for Engine<B> {
+    fn default() -> Self {
+        Engine {
+            config: EngineConfig::default(),
+            tenant: None,
+            clients: ClientRegistry::new(),
+            rules: RuleSet::default(),
+            audit: Vec::new(),
+            clock: Arc::new(SystemClock),
+            eviction: None,
+            last_touched: HashMap::new(),
+            processed_inputs: BTreeSet::new(),
+            interrupt: None,
+            dead_letter: None,
+            review: None,
+            review_queue: Vec::new(),
+            rejection_sender: None,
+            changed: HashSet::new(),
+            rows: RowCounts::default(),
+            rejections: None,
+            sequence: 0,
+            last_tx: None,
+            tx_order_violations: 0,
+            expiring: VecDeque::new(),
+            history: None,
+            alerts: None,
+            metrics: None,
+            amount_histogram: None,
+            quality: None,
+            custom_types: CustomTypes::default(),
+            preprocessors: Vec::new(),
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::default()
+    }
+
+    pub fn with_tenant(tenant: impl Into<String>) -> Self {
+        Engine {
+            tenant: Some(tenant.into()),
+            ..Engine::default()
+        }
+    }
+
+    pub fn with_config(config: EngineConfig) -> Self {
+        Engine {
+            config,
+            ..Engine::default()
+        }
+    }
+}
+
+impl<B: Balance> Engine<B> {
+    pub fn from_snapshot(snapshot: Snapshot<B>) -> Self {
+        let clients = snapshot
+            .clients
+            .into_iter()
+            .map(|client| (client.id, client))
+            .collect();
+        Engine {
+            tenant: snapshot.tenant,
+            clients,
+            processed_inputs: snapshot.processed_inputs,
+            sequence: snapshot.last_sequence,
+            history: snapshot.balance_history,
+            review_queue: snapshot.review_queue,
+            ..Engine::default()
+        }
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot<B>, EngineError> {
+        let mut clients = Vec::new();
+        self.visit_clients(|client| {
+            clients.push(client.clone());
+            Ok(())
+        })?;
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            tenant: self.tenant.clone(),
+            processed_inputs: self.processed_inputs.clone(),
+            clients,
+            last_sequence: self.sequence,
+            balance_history: self.history.clone(),
+            review_queue: self.review_queue.clone(),
+        })
+    }
+
+    /// Sequence number of the last accepted transaction, `0` before the
+    /// first. Every accepted row takes the next number, across runs resumed
+    /// from a snapshot. The audit trail and balance history carry it, so
+    /// reports can be lined up without timestamps.
+    pub fn last_sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Setting a dispute expiry policy queues the disputes resident
+    /// clients already have open, so they expire too.
+    pub fn set_config(&mut self, config: EngineConfig) {
+        self.config = config;
+        if self.config.dispute_expiry.is_some() {
+            self.queue_open_disputes();
+        } else {
+            self.expiring.clear();
+        }
+    }
+
+    /// Calls `sink` whenever a processed row takes a client, or the total
+    /// of locked accounts, across one of `thresholds`. Resident clients
+    /// already past a threshold alert straight away.
+    pub fn set_alerts(
+        &mut self,
+        thresholds: AlertThresholds,
+        sink: impl Fn(&Alert) + Send + Sync + 'static,
+    ) {
+        self.alerts = Some(AlertMonitor::new(
+            thresholds,
+            Box::new(sink),
+            self.clients.values(),
+        ));
+    }
+
+    /// Reports applied and rejected rows, and the time spent on each type,
+    /// to `metrics` from now on.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = Some(MetricsRecorder::new(metrics));
+    }
+
+    /// Counts the amounts of applied deposits and withdrawals into
+    /// `histogram` from now on.
+    pub fn enable_amount_histogram(&mut self, histogram: AmountHistogram) {
+        self.amount_histogram = Some(histogram);
+    }
+
+    pub fn amount_histogram(&self) -> Option<&AmountHistogram> {
+        self.amount_histogram.as_ref()
+    }
+
+    /// Scores every row read from now on for [`Engine::quality_report`].
+    /// Off by default because spotting duplicates keeps every deposit and
+    /// withdrawal id of the run.
+    pub fn enable_quality_report(&mut self) {
+        self.quality.get_or_insert_default();
+    }
+
+    pub fn quality_report(&self) -> Option<QualityReport> {
+        self.quality.as_ref().map(QualityTracker::report)
+    }
+
+    /// Handles rows whose `type` is `name` with `handler` (see
+    /// [`CustomTypes`]). `requires_amount` makes the amount mandatory;
+    /// otherwise it is ignored. Fails for built-in type names and names
+    /// longer than [`transaction::CUSTOM_TYPE_MAX_LEN`] bytes.
+    pub fn register_transaction_type<F>(
+        &mut self,
+        name: &str,
+        requires_amount: bool,
+        handler: F,
+    ) -> Result<(), EngineError>
+    where
+        F: Fn(&mut Client<B>, &Transaction) -> Result<(), ClientTransactionError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let Some(TransactionType::Custom(name)) = TransactionType::from_name(name) else {
+            return Err(EngineError::Usage(format!(
+                "{name:?} cannot be registered as a custom transaction type"
+            )));
+        };
+        self.custom_types.insert(
+            name,
+            CustomHandler {
+                requires_amount,
+                handler: Box::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn custom_types(&self) -> &CustomTypes<B> {
+        &self.custom_types
+    }
+
+    /// Rewrites every CSV record with `preprocessor` before it is parsed,
+    /// after the ones added before it.
+    pub fn add_preprocessor(&mut self, preprocessor: impl Preprocessor + 'static) {
+        self.preprocessors.push(Arc::new(preprocessor));
+    }
+
+    /// Starts recording every balance change, for [`Engine::balance_at`].
+    /// Clients that already exist are recorded as they are now.
+    pub fn enable_balance_history(&mut self) {
+        if self.history.is_some() {
+            return;
+        }
+        let mut history = BalanceHistory::default();
+        let now = self.clock.now();
+        for client in self.sorted_clients() {
+            history.record(client, now, None);
+        }
+        self.history = Some(history);
+    }
+
+    pub fn balance_history(&self) -> Option<&BalanceHistory> {
+        self.history.as_ref()
+    }
+
+    /// `client`'s balances as of `point`, from the balance history. `None`
+    /// when history is off or the client had no balance yet.
+    pub fn balance_at(&self, client: ClientId, point: PointInTime) -> Option<&BalancePoint> {
+        self.history.as_ref()?.balance_at(client, point)
+    }
+
+    /// Bounds the balance history, the one part of the state that grows with
+    /// every row, for long-lived deployments that snapshot and restart: points
+    /// older than `retention` on the engine clock are dropped, except each
+    /// client's latest, so `balance_at` still answers for any moment within
+    /// the retention. Returns the number of points dropped, `0` with history
+    /// off. Take a snapshot afterwards to persist the smaller state.
+    pub fn compact(&mut self, retention: Retention) -> usize {
+        let cutoff = self
+            .clock
+            .now()
+            .checked_sub(retention.0)
+            .unwrap_or(UNIX_EPOCH);
+        self.history
+            .as_mut()
+            .map_or(0, |history| history.compact(cutoff))
+    }
+
+    /// Marks `client_id` as changed for the changed-only output and, when
+    /// enabled, records its balances in the history.
+    pub(crate) fn mark_changed(&mut self, client_id: ClientId) {
+        self.changed.insert(client_id);
+        if let (Some(history), Some(client)) = (&mut self.history, self.clients.get(client_id)) {
+            history.record(client, self.clock.now(), None);
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    #[cfg(feature = "fault-injection")]
+    pub fn set_fault_injector(&mut self, faults: Arc<crate::fault::FaultInjector>) {
+        self.faults = Some(faults);
+    }
+
+    /// Open disputes that have been held for at least `age` according to the
+    /// engine's clock, as `(client, tx)` pairs in client order.
+    pub fn disputes_older_than(&self, age: Duration) -> Vec<(ClientId, TxId)> {
+        let now = self.clock.now();
+        let mut stale: Vec<(ClientId, TxId)> = self
+            .clients
+            .values()
+            .flat_map(|client| {
+                client
+                    .open_disputes()
+                    .filter(move |(tx, _)| {
+                        client.dispute_age(*tx, now).is_some_and(|held| held >= age)
+                    })
+                    .map(move |(tx, _)| (client.id, tx))
+            })
+            .collect();
+        stale.sort_unstable();
+        stale
+    }
+
+    /// Enables spilling of idle clients. Clients already resident count as
+    /// touched now.
+    pub fn set_eviction(&mut self, policy: EvictionPolicy<B>) {
+        let now = self.clock.now();
+        self.last_touched = self.clients.ids().map(|id| (id, now)).collect();
+        self.eviction = Some(policy);
+    }
+
+    /// Moves every client idle for longer than the eviction policy allows
+    /// into its store, returning how many were evicted.
+    pub fn evict_idle(&mut self) -> Result<usize, EngineError> {
+        let Some(policy) = &mut self.eviction else {
+            return Ok(0);
+        };
+        let now = self.clock.now();
+        let idle: Vec<ClientId> = self
+            .last_touched
+            .iter()
+            .filter(|(_, touched)| {
+                now.duration_since(**touched).unwrap_or_default() >= policy.idle_for
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for client_id in &idle {
+            // Stored before it is removed, so a failing store loses nothing.
+            if let Some(client) = self.clients.get(*client_id) {
+                policy.store.store(client)?;
+                self.clients.remove(*client_id);
+            }
+            self.last_touched.remove(client_id);
+        }
+        Ok(idle.len())
+    }
+
+    /// Rough size of the resident clients and their bookkeeping. Spilled
+    /// clients are not counted.
+    pub fn approximate_memory(&self) -> usize {
+        const ID_ENTRY: usize = size_of::<ClientId>() + size_of::<usize>();
+        self.clients
+            .values()
+            .map(Client::<B>::approximate_size)
+            .sum::<usize>()
+            + self.last_touched.capacity() * (ID_ENTRY + size_of::<SystemTime>())
+            + self.changed.capacity() * ID_ENTRY
+    }
+
+    /// Applies `config.memory_policy` if the engine is over
+    /// `config.max_memory_bytes`.
+    pub fn enforce_memory_limit(&mut self) -> Result<(), EngineError> {
+        let Some(limit) = self.config.max_memory_bytes else {
+            return Ok(());
+        };
+        let used = self.approximate_memory();
+        if used <= limit {
+            return Ok(());
+        }
+        let target = limit / 100 * LOW_WATER_PERCENT;
+        match self.config.memory_policy {
+            MemoryPolicy::Abort => {}
+            MemoryPolicy::Spill => self.spill_until(target)?,
+            MemoryPolicy::DropHistory => self.drop_history_until(target),
+        }
+        let used = self.approximate_memory();
+        if used > limit {
+            return Err(EngineError::MemoryLimitExceeded { used, limit });
+        }
+        Ok(())
+    }
+
+    fn spill_until(&mut self, target: usize) -> Result<(), EngineError> {
+        let Some(policy) = &mut self.eviction else {
+            return Err(EngineError::Usage(
+                "spilling over the memory limit needs an eviction store".to_string(),
+            ));
+        };
+        let mut by_age: Vec<(SystemTime, ClientId)> = self
+            .last_touched
+            .iter()
+            .map(|(id, touched)| (*touched, *id))
+            .collect();
+        by_age.sort_unstable();
+        let mut used = self
+            .clients
+            .values()
+            .map(Client::<B>::approximate_size)
+            .sum::<usize>();
+        let mut spilled = 0;
+        for (_, client_id) in by_age {
+            if used <= target {
+                break;
+            }
+            if let Some(client) = self.clients.get(client_id) {
+                policy.store.store(client)?;
+                used -= client.approximate_size();
+                self.clients.remove(client_id);
+                spilled += 1;
+            }
+            self.last_touched.remove(&client_id);
+        }
+        warn!("Over the memory limit: spilled {spilled} least recently used clients");
+        Ok(())
+    }
+
+    /// Forgets the oldest undisputed deposits in rounds, since freed map
+    /// capacity only roughly follows the number of entries removed.
+    fn drop_history_until(&mut self, target: usize) {
+        let entry = size_of::<TxId>() + size_of::<Money<B>>() + size_of::<usize>();
+        let mut dropped = 0;
+        loop {
+            let used = self.approximate_memory();
+            let mut history: Vec<TxId> = self
+                .clients
+                .values()
+                .flat_map(|client| {
+                    client
+                        .deposits()
+                        .filter(|(tx, _)| client.open_disputes().all(|(open, _)| open != *tx))
+                        .map(|(tx, _)| tx)
+                })
+                .collect();
+            if used <= target || history.is_empty() {
+                break;
+            }
+            let excess = (used - target).div_ceil(entry);
+            let cutoff = if excess >= history.len() {
+                TxId(u32::MAX)
+            } else {
+                *history.select_nth_unstable(excess).1
+            };
+            let forgotten: usize = self
+                .clients
+                .values_mut()
+                .map(|client| client.forget_deposits_before(cutoff))
+                .sum();
+            if forgotten == 0 {
+                break;
+            }
+            dropped += forgotten;
+        }
+        warn!(
+            "Over the memory limit: forgot the {dropped} oldest deposits, which can no longer be disputed"
+        );
+    }
+
+    pub fn resident_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub(crate) fn touch(&mut self, client_id: ClientId) -> Result<(), EngineError> {
+        let Some(policy) = &mut self.eviction else {
+            return Ok(());
+        };
+        if !self.clients.contains_key(client_id)
+            && let Some(client) = policy.store.load(client_id)?
+        {
+            policy.store.remove(client_id)?;
+            self.clients.insert(client);
+        }
+        self.last_touched.insert(client_id, self.clock.now());
+        Ok(())
+    }
+
+    /// Calls `f` for every client in id order, including clients currently
+    /// spilled by the eviction policy, which are loaded one at a time.
+    pub(crate) fn visit_clients(
+        &self,
+        mut f: impl FnMut(&Client<B>) -> Result<(), EngineError>,
+    ) -> Result<(), EngineError> {
+        let Some(policy) = &self.eviction else {
+            return self.sorted_clients().into_iter().try_for_each(f);
+        };
+
+        let mut ids: BTreeSet<ClientId> = self.clients.ids().collect();
+        ids.extend(policy.store.client_ids()?);
+        for client_id in ids {
+            match self.clients.get(client_id) {
+                Some(client) => f(client)?,
+                None => {
+                    if let Some(client) = policy.store.load(client_id)? {
+                        f(&client)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rules_mut(&mut self) -> &mut RuleSet<B> {
+        &mut self.rules
+    }
+
+    pub fn audit_entries(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    pub fn take_audit_entries(&mut self) -> Vec<AuditEntry> {
+        std::mem::take(&mut self.audit)
+    }
+
+    /// Accounts queued for an analyst: rule flags, available balances
+    /// driven below zero, repeated disputes and notes. The queue is kept
+    /// in snapshots until taken.
+    pub fn review_queue(&self) -> &[ReviewItem] {
+        &self.review_queue
+    }
+
+    pub fn take_review_queue(&mut self) -> Vec<ReviewItem> {
+        std::mem::take(&mut self.review_queue)
+    }
+
+    /// Queues `client_id` for review with a free-text note.
+    pub fn add_review_note(
+        &mut self,
+        client_id: ClientId,
+        note: impl Into<String>,
+    ) -> Result<(), EngineError> {
+        self.touch(client_id)?;
+        let client = self
+            .clients
+            .get(client_id)
+            .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+        self.review_queue
+            .push(ReviewItem::new(ReviewReason::Note, client, None, note));
+        Ok(())
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    pub fn client(&self, client_id: ClientId) -> Option<&Client<B>> {
+        self.clients.get(client_id)
+    }
+
+    /// Guarded mutable access for embedder adjustments (promotions, manual
+    /// credits). Reloads the client first if it was evicted.
+    pub fn client_mut(&mut self, client_id: ClientId) -> Result<ClientGuard<'_, B>, EngineError> {
+        self.touch(client_id)?;
+        let client = self
+            .clients
+            .get_mut(client_id)
+            .ok_or(ClientTransactionError::UnknownClient { client_id })?;
+        self.changed.insert(client_id);
+        Ok(ClientGuard::new(client, &mut self.audit)
+            .with_history(self.history.as_mut(), self.clock.now()))
+    }
+
+    pub(crate) fn sorted_clients(&self) -> Vec<&Client<B>> {
+        let mut clients_sorted: Vec<&Client<B>> = self.clients.values().collect();
+        clients_sorted.sort_by_key(|client| client.id);
+        clients_sorted
+    }
+
+    /// Makes `process` stop before the next row once `flag` is set, for
+    /// example from a signal handler. Rows already applied stay applied.
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::SeqCst))
+    }
+
+    /// Writes every row skipped or rejected from now on to `writer`; see
+    /// [`DeadLetter`].
+    pub fn set_dead_letter(&mut self, writer: impl Write + Send + 'static) {
+        self.dead_letter = Some(DeadLetter::new(writer));
+    }
+
+    /// Writes every row held back under `EngineConfig::non_negative_balances`
+    /// from now on to `writer`, in the [`DeadLetter`] layout, as a queue for
+    /// manual review. They go to the dead-letter file too, if one is set.
+    pub fn set_review_output(&mut self, writer: impl Write + Send + 'static) {
+        self.review = Some(DeadLetter::new(writer));
+    }
+
+    /// Sends every input row skipped or rejected from now on to `sender`,
+    /// as it happens, for embedders that react to rejections in process.
+    /// Rows given to [`Engine::push`] are not sent; it returns the
+    /// rejection itself. Sending stops once the receiver is dropped.
+    pub fn set_rejection_sender(&mut self, sender: Sender<RejectedTransaction>) {
+        self.rejection_sender = Some(sender);
+    }
+
+    /// Applies every row of `source`. Fails with `EngineError::Interrupted`,
+    /// naming the last applied row, if the interrupt flag is raised midway,
+    /// and with `EngineError::BudgetExhausted` once `EngineConfig::max_rows`
+    /// or `max_runtime` is spent.
+    pub fn process<R: Read>(&mut self, source: R) -> Result<(), EngineError> {
+        Pipeline::new(self).run(source)
+    }
+
+    /// Applies one transaction that did not come from an input file, for
+    /// embedders that receive transactions one at a time. It is counted in
+    /// [`Engine::row_counts`] like an input row, but never dead-lettered
+    /// since there is no raw row to keep.
+    pub fn push(
+        &mut self,
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TxId,
+        amount: Option<Decimal>,
+    ) -> Result<Option<Rejection>, EngineError> {
+        self.expire_disputes()?;
+        let started = Instant::now();
+        let rejection = self.apply_guarded(InputTransaction {
+            tx_type,
+            client,
+            tx: tx.into(),
+            amount,
+            tenant: None,
+            reference: None,
+            priority: None,
+            row: self.rows.read + 1,
+            raw: None,
+        })?;
+        self.count_row(rejection.as_ref().map(Rejection::to_string).as_deref());
+        if let Some(quality) = &mut self.quality {
+            quality.record(Some((tx_type, tx.into())), rejection.as_ref());
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record(tx_type, rejection.is_some(), started);
+        }
+        Ok(rejection)
+    }
+
+    /// Records one row in the counts, and its reason in the rejection
+    /// breakdown when it was rejected.
+    fn count_row(&mut self, rejection: Option<&str>) {
+        self.rows.record(rejection.is_some());
+        if let (Some(rejections), Some(reason)) = (&mut self.rejections, rejection) {
+            *rejections.entry(reason_category(reason)).or_default() += 1;
+        }
+    }
+
+    fn flush_dead_letter(&mut self) -> Result<(), EngineError> {
+        for dead_letter in [&mut self.dead_letter, &mut self.review]
+            .into_iter()
+            .flatten()
+        {
+            dead_letter.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Rows read by `process` calls on this engine since it was built.
+    pub fn row_counts(&self) -> RowCounts {
+        self.rows
+    }
+
+    /// Counts rejected rows by reason from now on, with ids and amounts
+    /// masked so that rows failing the same way share a count. Off by
+    /// default because hostile input can make many distinct reasons.
+    pub fn enable_rejection_breakdown(&mut self) {
+        self.rejections.get_or_insert_default();
+    }
+
+    pub fn rejection_breakdown(&self) -> Option<&BTreeMap<String, usize>> {
+        self.rejections.as_ref()
+    }
+
+    /// Rows rejected under `EngineConfig::strict_tx_order` since this
+    /// engine was built.
+    pub fn tx_order_violations(&self) -> usize {
+        self.tx_order_violations
+    }
+
+    pub fn has_processed(&self, digest: &str) -> bool {
+        self.processed_inputs.contains(digest)
+    }
+
+    /// Processes `source` unless an input with the same SHA-256 was already
+    /// applied to this engine (or the snapshot it was restored from). Returns
+    /// whether the input was processed.
+    pub fn process_once<R: Read + Seek>(&mut self, mut source: R) -> Result<bool, EngineError> {
+        let digest = crate::digest::sha256_hex(&mut source)?;
+        if self.has_processed(&digest) {
+            warn!("Input with sha256 {digest} was already applied to this state, skipping");
+            return Ok(false);
+        }
+        source.seek(SeekFrom::Start(0))?;
+        self.process(source)?;
+        info!("Recorded input sha256 {digest} as applied");
+        self.processed_inputs.insert(digest);
+        Ok(true)
+    }
+}
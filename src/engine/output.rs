@@ -0,0 +1,165 @@
+use std::io::Write;
+
+use super::Engine;
+use crate::{
+    client::Client,
+    config::EngineConfig,
+    errors::EngineError,
+    format::{Format, write_json_account},
+    ids::ClientId,
+    numeric::Balance,
+    output::{AccountWriter, OutputSchema},
+};
+
+pub const ACCOUNT_HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+pub(crate) fn account_record<B: Balance>(client: &Client<B>, config: &EngineConfig) -> Vec<String> {
+    let options = &config.formatting;
+    let mut record = vec![
+        match config.output_id(client.id) {
+            Some(external_id) => external_id.to_string(),
+            None => client.id.to_string(),
+        },
+        options.format(client.available.to_decimal()),
+        options.format(client.held.into()),
+        options.format(client.total.to_decimal()),
+        client.locked.to_string(),
+    ];
+    if config.output_schema == OutputSchema::V2 {
+        record.extend([
+            client.open_disputes().count().to_string(),
+            options.format(client.lifetime_deposits().to_decimal()),
+            options.format(client.lifetime_withdrawals().to_decimal()),
+            client.chargeback_count().to_string(),
+        ]);
+    }
+    if config.output_risk_score {
+        record.push(client.risk_score().to_string());
+    }
+    record
+}
+
+impl<B: Balance> Engine<B> {
+    /// Clients created or whose balances (or lock) changed since this engine
+    /// was built or restored, in no particular order. Includes clients
+    /// `admin::merge_clients` merged away, which have no account left.
+    pub fn changed_clients(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.changed.iter().copied()
+    }
+
+    /// Writes every account in `EngineConfig::output_format`, returning how
+    /// many were written.
+    pub fn write_accounts<W: Write>(&self, writer: W) -> Result<u64, EngineError> {
+        self.write_selected_accounts(writer, false)
+    }
+
+    /// Like `write_accounts`, but only for [`Engine::changed_clients`], so a
+    /// run restored from a snapshot can emit a delta instead of every account.
+    pub fn write_changed_accounts<W: Write>(&self, writer: W) -> Result<u64, EngineError> {
+        self.write_selected_accounts(writer, true)
+    }
+
+    fn write_selected_accounts<W: Write>(
+        &self,
+        writer: W,
+        changed_only: bool,
+    ) -> Result<u64, EngineError> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = &self.faults {
+            return self.write_accounts_to(faults.wrap_writer(writer), changed_only);
+        }
+        self.write_accounts_to(writer, changed_only)
+    }
+
+    fn visit_output_clients(
+        &self,
+        changed_only: bool,
+        mut f: impl FnMut(&Client<B>) -> Result<(), EngineError>,
+    ) -> Result<(), EngineError> {
+        self.visit_clients(|client| {
+            if changed_only && !self.changed.contains(&client.id) {
+                return Ok(());
+            }
+            f(client)
+        })
+    }
+
+    fn write_accounts_to<W: Write>(
+        &self,
+        writer: W,
+        changed_only: bool,
+    ) -> Result<u64, EngineError> {
+        let mut rows = 0;
+        if self.config.output_format == Format::Json {
+            let mut writer = writer;
+            self.visit_output_clients(changed_only, |client| {
+                rows += 1;
+                write_json_account(
+                    &mut writer,
+                    None,
+                    client,
+                    self.config.output_id(client.id),
+                    self.config.output_risk_score.then(|| client.risk_score()),
+                    self.config.amount_encoding,
+                )
+            })?;
+            writer.flush()?;
+            return Ok(rows);
+        }
+
+        if !self.config.formatting.has_default_output()
+            || self.config.output_schema != OutputSchema::V1
+            || self.config.output_external_ids
+            || self.config.output_risk_score
+        {
+            let mut record_writer = self.config.formatting.record_writer(writer);
+            record_writer.write_header(&self.config.account_header())?;
+            self.visit_output_clients(changed_only, |client| {
+                rows += 1;
+                record_writer.write_record(account_record(client, &self.config))
+            })?;
+            record_writer.flush()?;
+            return Ok(rows);
+        }
+
+        let mut account_writer = AccountWriter::new(writer);
+        account_writer.write_header()?;
+        self.visit_output_clients(changed_only, |client| {
+            rows += 1;
+            account_writer.write_account(client)
+        })?;
+        account_writer.flush()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use crate::money::Money;
+    use rust_decimal::dec;
+
+    #[test]
+    fn v2_records_extend_the_v1_columns() {
+        let mut client = Client::new(ClientId(3));
+        client
+            .deposit(TxId(1), Money::new(dec!(5)).unwrap())
+            .unwrap();
+        client
+            .deposit(TxId(2), Money::new(dec!(1.25)).unwrap())
+            .unwrap();
+        client.dispute(TxId(2)).unwrap();
+
+        let v1 = account_record(&client, &EngineConfig::default());
+        assert_eq!(v1, ["3", "5.0000", "1.2500", "6.2500", "false"]);
+
+        let config = EngineConfig {
+            output_schema: OutputSchema::V2,
+            ..EngineConfig::default()
+        };
+        let v2 = account_record(&client, &config);
+        assert_eq!(v2[..ACCOUNT_HEADER.len()], v1[..]);
+        assert_eq!(v2[ACCOUNT_HEADER.len()..], ["1", "6.2500", "0.0000", "0"]);
+    }
+}
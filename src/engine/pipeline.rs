@@ -0,0 +1,201 @@
+use csv::StringRecord;
+use std::{io::Read, time::Instant};
+
+use super::ingest::read_input;
+use super::{Engine, Rejection};
+use crate::{
+    dead_letter::{RejectedRow, RejectedTransaction},
+    errors::{ClientTransactionError, EngineError},
+    memory::MEMORY_CHECK_INTERVAL,
+    numeric::Balance,
+};
+
+/// One pass over an input. Rows from the ingest stage go one at a time to
+/// the dispatch stage, and whatever is rejected goes to the dead-letter
+/// file. Between rows the pipeline honours the interrupt flag and the
+/// memory budget.
+pub(crate) struct Pipeline<'e, B> {
+    engine: &'e mut Engine<B>,
+}
+
+impl<'e, B: Balance> Pipeline<'e, B> {
+    pub(crate) fn new(engine: &'e mut Engine<B>) -> Self {
+        Pipeline { engine }
+    }
+
+    pub(crate) fn run<R: Read>(self, source: R) -> Result<(), EngineError> {
+        let (header, rows) = read_input(
+            source,
+            &self.engine.config,
+            &self.engine.preprocessors,
+            self.engine.dead_letter.is_some()
+                || self.engine.review.is_some()
+                || self.engine.rejection_sender.is_some(),
+        )?;
+        for dead_letter in [&mut self.engine.dead_letter, &mut self.engine.review]
+            .into_iter()
+            .flatten()
+        {
+            dead_letter.write_header(header.as_ref())?;
+        }
+        let (started, mut last_row) = (Instant::now(), 0);
+        for (index, row) in rows.enumerate() {
+            if index % MEMORY_CHECK_INTERVAL == MEMORY_CHECK_INTERVAL - 1 {
+                self.engine.enforce_memory_limit()?;
+            }
+            if self.engine.interrupted() {
+                self.engine.flush_dead_letter()?;
+                return Err(EngineError::Interrupted { row: last_row });
+            }
+            if let Some(budget) = self.exhausted_budget(index, started) {
+                self.engine.flush_dead_letter()?;
+                return Err(EngineError::BudgetExhausted {
+                    row: last_row,
+                    budget,
+                });
+            }
+            self.engine.expire_disputes()?;
+            let (rejected, rejection) = match row {
+                Ok(mut transaction) => {
+                    last_row = transaction.row;
+                    let row = transaction.row;
+                    let raw = transaction.raw.take();
+                    let (tx_type, started) = (transaction.tx_type, Instant::now());
+                    let tx = transaction.tx;
+                    let rejection = self.engine.apply_guarded(transaction)?;
+                    if let Some(quality) = &mut self.engine.quality {
+                        quality.record(Some((tx_type, tx)), rejection.as_ref());
+                    }
+                    if let Some(metrics) = &mut self.engine.metrics {
+                        metrics.record(tx_type, rejection.is_some(), started);
+                    }
+                    match rejection {
+                        Some(rejection) => (
+                            Some(RejectedRow {
+                                row,
+                                raw: raw.map(StringRecord::into_byte_record).unwrap_or_default(),
+                                reason: rejection.to_string(),
+                            }),
+                            Some(rejection),
+                        ),
+                        None => (None, None),
+                    }
+                }
+                Err(rejected) => {
+                    if let Some(quality) = &mut self.engine.quality {
+                        quality.record(None, None);
+                    }
+                    (Some(rejected), None)
+                }
+            };
+            self.engine
+                .count_row(rejected.as_ref().map(|rejected| rejected.reason.as_str()));
+            let Some(rejected) = rejected else {
+                continue;
+            };
+            if let (
+                Some(review),
+                Some(Rejection::Client(ClientTransactionError::NegativeBalance { .. })),
+            ) = (&mut self.engine.review, &rejection)
+            {
+                review.write(&rejected)?;
+            }
+            if let Some(sender) = &self.engine.rejection_sender
+                && sender
+                    .send(RejectedTransaction::new(&rejected, rejection))
+                    .is_err()
+            {
+                // Nobody is listening any more.
+                self.engine.rejection_sender = None;
+            }
+            if let Some(dead_letter) = &mut self.engine.dead_letter {
+                dead_letter.write(&rejected)?;
+            }
+        }
+        self.engine.flush_dead_letter()?;
+        if let Some(metrics) = &mut self.engine.metrics {
+            metrics.flush();
+        }
+        self.engine.evict_idle()?;
+        self.engine.enforce_memory_limit()
+    }
+}
+
+impl<B: Balance> Pipeline<'_, B> {
+    /// The budget of `EngineConfig` that is used up after `rows` rows, if
+    /// any.
+    fn exhausted_budget(&self, rows: usize, started: Instant) -> Option<&'static str> {
+        let config = &self.engine.config;
+        if config.max_rows.is_some_and(|max| rows >= max) {
+            Some("max_rows")
+        } else if config
+            .max_runtime
+            .is_some_and(|max| started.elapsed() >= max)
+        {
+            Some("max_runtime")
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ClientId;
+    use rust_decimal::Decimal;
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    const INPUT: &str = "type,client,tx,amount\ndeposit,1,1,2\nrefund,1,2,1\nwithdrawal,1,3,5\n";
+
+    #[test]
+    fn every_row_is_counted_and_accepted_rows_applied() {
+        let mut engine = Engine::new();
+
+        Pipeline::new(&mut engine).run(INPUT.as_bytes()).unwrap();
+
+        let rows = engine.row_counts();
+        assert_eq!((rows.read, rows.rejected), (3, 2));
+        assert_eq!(engine.client(ClientId(1)).unwrap().available, Decimal::TWO);
+    }
+
+    #[test]
+    fn a_raised_interrupt_flag_stops_before_the_next_row() {
+        let mut engine = Engine::new();
+        engine.set_interrupt_flag(Arc::new(AtomicBool::new(true)));
+
+        let result = Pipeline::new(&mut engine).run(INPUT.as_bytes());
+
+        assert!(matches!(result, Err(EngineError::Interrupted { row: 0 })));
+        assert_eq!(engine.row_counts().read, 0);
+    }
+
+    #[test]
+    fn a_spent_budget_stops_before_the_next_row() {
+        let mut engine = Engine::with_config(crate::config::EngineConfig {
+            max_rows: Some(2),
+            ..Default::default()
+        });
+
+        let result = Pipeline::new(&mut engine).run(INPUT.as_bytes());
+
+        assert!(matches!(
+            result,
+            Err(EngineError::BudgetExhausted {
+                row: 2,
+                budget: "max_rows"
+            })
+        ));
+        assert_eq!(engine.row_counts().read, 2);
+
+        let mut engine = Engine::with_config(crate::config::EngineConfig {
+            max_runtime: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        });
+        let result = Pipeline::new(&mut engine).run(INPUT.as_bytes());
+        assert!(matches!(
+            result,
+            Err(EngineError::BudgetExhausted { row: 0, .. })
+        ));
+    }
+}
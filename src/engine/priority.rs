@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use super::ingest::InputTransaction;
+use crate::dead_letter::RejectedRow;
+
+/// Rows held back under `--priority` unless `--priority-window` says otherwise.
+pub const DEFAULT_PRIORITY_WINDOW: usize = 64;
+
+type Row = Result<InputTransaction, RejectedRow>;
+
+/// Lets rows with a higher `priority` overtake earlier rows of the same
+/// client, up to `window` rows back, so a chargeback can beat a withdrawal
+/// sent ahead of it in the same file. A row never overtakes one with the
+/// same transaction id, so a dispute still follows its deposit. Rows of
+/// other clients, rows of equal priority and unparsable rows keep their
+/// input order; a missing priority counts as `0`.
+pub(crate) struct PriorityReorder<I> {
+    rows: I,
+    buffer: VecDeque<Row>,
+    window: usize,
+}
+
+impl<I: Iterator<Item = Row>> PriorityReorder<I> {
+    pub(crate) fn new(rows: I, window: usize) -> Self {
+        PriorityReorder {
+            rows,
+            buffer: VecDeque::with_capacity(window),
+            window: window.max(1),
+        }
+    }
+}
+
+fn priority(transaction: &InputTransaction) -> i32 {
+    transaction.priority.unwrap_or(0)
+}
+
+impl<I: Iterator<Item = Row>> Iterator for PriorityReorder<I> {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        while self.buffer.len() < self.window {
+            match self.rows.next() {
+                Some(row) => self.buffer.push_back(row),
+                None => break,
+            }
+        }
+        let Some(Ok(front)) = self.buffer.front() else {
+            return self.buffer.pop_front();
+        };
+        let (client, mut best, mut index) = (front.client, priority(front), 0);
+        for (position, row) in self.buffer.iter().enumerate().skip(1) {
+            if let Ok(transaction) = row
+                && transaction.client == client
+                && priority(transaction) > best
+                && !self.buffer.range(..position).any(|earlier| {
+                    earlier.as_ref().is_ok_and(|earlier| {
+                        earlier.client == client && earlier.tx == transaction.tx
+                    })
+                })
+            {
+                best = priority(transaction);
+                index = position;
+            }
+        }
+        self.buffer.remove(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ClientId;
+    use crate::{Engine, config::EngineConfig};
+
+    #[test]
+    fn higher_priority_rows_of_a_client_go_first_within_the_window() {
+        let input = "type,client,tx,amount,priority\n\
+            deposit,1,1,10,\n\
+            withdrawal,1,2,10,0\n\
+            deposit,2,3,1,\n\
+            dispute,1,1,,5\n\
+            chargeback,1,1,,5\n";
+
+        let mut engine = Engine::with_config(EngineConfig {
+            priority_window: Some(DEFAULT_PRIORITY_WINDOW),
+            ..EngineConfig::default()
+        });
+        engine.process(input.as_bytes()).unwrap();
+        let client = engine.client(ClientId(1)).unwrap();
+        assert!(client.locked);
+        assert_eq!(client.total, rust_decimal::Decimal::ZERO);
+        assert_eq!(engine.row_counts().rejected, 1);
+
+        let mut engine = Engine::with_config(EngineConfig {
+            priority_window: Some(2),
+            ..EngineConfig::default()
+        });
+        engine.process(input.as_bytes()).unwrap();
+        assert_eq!(
+            engine.client(ClientId(1)).unwrap().total,
+            rust_decimal::dec!(-10)
+        );
+        assert_eq!(engine.row_counts().rejected, 0);
+    }
+}
@@ -0,0 +1,33 @@
+use alloc::string::String;
+use thiserror::Error;
+
+/// Why an input amount could not be read with the configured separators.
+/// Positions are 1-based character offsets into the trimmed amount.
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum AmountError {
+    #[error("amount is empty")]
+    Empty,
+    #[error("decimal and thousands separators are both '{0}'")]
+    ConflictingSeparators(char),
+    #[error(
+        "amount {amount} is ambiguous: '{character}' at position {position} is neither the decimal nor the thousands separator"
+    )]
+    Ambiguous {
+        amount: String,
+        character: char,
+        position: usize,
+    },
+    #[error("amount {amount} has a misplaced thousands separator at position {position}")]
+    MisplacedThousandsSeparator { amount: String, position: usize },
+    #[error("amount {amount} has a second decimal separator at position {position}")]
+    RepeatedDecimalSeparator { amount: String, position: usize },
+    #[error("amount {amount} has an unexpected '{character}' at position {position}")]
+    UnexpectedCharacter {
+        amount: String,
+        character: char,
+        position: usize,
+    },
+    #[error("amount {amount} is invalid: {reason}")]
+    Invalid { amount: String, reason: String },
+}
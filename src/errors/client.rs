@@ -1,38 +1,192 @@
+use super::MoneyError;
+use crate::client::Capability;
+use crate::ids::{ClientId, TxId};
 use crate::transaction::TransactionType;
 use rust_decimal::Decimal;
 use thiserror::Error;
 
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ClientTransactionError {
     #[error("Client {client_id}: account is locked")]
-    AccountLocked { client_id: u16 },
+    AccountLocked { client_id: ClientId },
     #[error("Client {client_id}: account is already locked")]
-    AccountAlreadyLocked { client_id: u16 },
+    AccountAlreadyLocked { client_id: ClientId },
     #[error("Client {client_id}: invalid transaction id {tx}")]
-    InvalidTransactionId { client_id: u16, tx: i64 },
-    #[error("Client {client_id}: insufficient available funds")]
-    InsufficientAvailableFunds { client_id: u16 },
+    InvalidTransactionId { client_id: ClientId, tx: i64 },
+    #[error(
+        "Client {client_id}: insufficient available funds to withdraw {amount} (available {available}, held {held})"
+    )]
+    InsufficientAvailableFunds {
+        client_id: ClientId,
+        amount: Decimal,
+        available: Decimal,
+        held: Decimal,
+    },
+    #[error(
+        "Client {client_id}: deposit {tx} of {amount} would take the total of {total} above the limit of {limit}"
+    )]
+    BalanceLimitExceeded {
+        client_id: ClientId,
+        tx: TxId,
+        amount: Decimal,
+        total: Decimal,
+        limit: Decimal,
+    },
+    #[error(
+        "Client {client_id}: {tx_type} transaction {tx} would leave available {available} and total {total}, below zero"
+    )]
+    NegativeBalance {
+        client_id: ClientId,
+        tx_type: TransactionType,
+        tx: TxId,
+        available: Decimal,
+        total: Decimal,
+    },
     #[error("Client {client_id}: missing amount for {tx_type} transaction {tx}")]
     MissingAmount {
-        client_id: u16,
+        client_id: ClientId,
+        tx_type: TransactionType,
+        tx: TxId,
+    },
+    #[error("Client {client_id}: unexpected amount {amount} on {tx_type} transaction {tx}")]
+    UnexpectedAmount {
+        client_id: ClientId,
+        tx_type: TransactionType,
+        tx: TxId,
+        amount: Decimal,
+    },
+    #[error("Client {client_id}: transaction {tx} is not above the previous transaction id {last}")]
+    TxIdOutOfOrder {
+        client_id: ClientId,
+        tx: TxId,
+        last: TxId,
+    },
+    #[error("Client {client_id}: zero amount on {tx_type} transaction {tx}")]
+    ZeroAmount {
+        client_id: ClientId,
         tx_type: TransactionType,
-        tx: u32,
+        tx: TxId,
     },
     #[error("Client {client_id}: invalid amount {amount} for transaction {tx}")]
     InvalidAmount {
-        client_id: u16,
-        tx: u32,
+        client_id: ClientId,
+        tx: TxId,
         amount: Decimal,
     },
-    #[error("Client {client_id}: insufficient held funds for {action}")]
+    #[error(
+        "Client {client_id}: insufficient held funds for {action} of {amount} (available {available}, held {held})"
+    )]
     InsufficientHeldFunds {
-        client_id: u16,
+        client_id: ClientId,
         action: &'static str,
+        amount: Decimal,
+        available: Decimal,
+        held: Decimal,
     },
     #[error("Client {client_id}: transaction {tx_id} is unknown")]
-    UnknownTransaction { client_id: u16, tx_id: u32 },
+    UnknownTransaction { client_id: ClientId, tx_id: TxId },
     #[error("Client {client_id}: transaction {tx_id} is already in dispute")]
-    AlreadyInDispute { client_id: u16, tx_id: u32 },
+    AlreadyInDispute { client_id: ClientId, tx_id: TxId },
     #[error("Client {client_id}: transaction {tx_id} is not under dispute")]
-    NotInDispute { client_id: u16, tx_id: u32 },
+    NotInDispute { client_id: ClientId, tx_id: TxId },
+    #[error("Client {client_id}: withdrawal {tx_id} is already held")]
+    WithdrawalAlreadyHeld { client_id: ClientId, tx_id: TxId },
+    #[error("Client {client_id}: no withdrawal hold for transaction {tx_id}")]
+    UnknownWithdrawalHold { client_id: ClientId, tx_id: TxId },
+    #[error("Client {client_id}: {capability} is switched off for this account")]
+    CapabilityRevoked {
+        client_id: ClientId,
+        capability: Capability,
+    },
+    #[error("Client {client_id}: withdrawals are frozen while {disputes} dispute(s) are open")]
+    WithdrawalsFrozen {
+        client_id: ClientId,
+        disputes: usize,
+    },
+    #[error("Client {client_id}: cannot merge a client into itself")]
+    MergeIntoSelf { client_id: ClientId },
+    #[error("Client {client_id}: cannot merge client {from}, both know transaction {tx_id}")]
+    MergeCollision {
+        client_id: ClientId,
+        from: ClientId,
+        tx_id: TxId,
+    },
+    #[error(
+        "Client {client_id}: cannot forget history while {open} dispute(s) or hold(s) are open"
+    )]
+    OpenTransactions { client_id: ClientId, open: usize },
+    #[error("Client {client_id}: transaction left total != available + held + pending")]
+    InconsistentBalances { client_id: ClientId },
+    #[error("Client {client_id}: client is unknown")]
+    UnknownClient { client_id: ClientId },
+    #[error("Client {client_id}: {source}")]
+    Arithmetic {
+        client_id: ClientId,
+        source: MoneyError,
+    },
+}
+
+impl ClientTransactionError {
+    /// Names of the variants, as [`ClientTransactionError::name`] returns
+    /// them.
+    pub const NAMES: [&'static str; 25] = [
+        "AccountLocked",
+        "AccountAlreadyLocked",
+        "InvalidTransactionId",
+        "InsufficientAvailableFunds",
+        "BalanceLimitExceeded",
+        "NegativeBalance",
+        "MissingAmount",
+        "UnexpectedAmount",
+        "TxIdOutOfOrder",
+        "ZeroAmount",
+        "InvalidAmount",
+        "InsufficientHeldFunds",
+        "UnknownTransaction",
+        "AlreadyInDispute",
+        "NotInDispute",
+        "WithdrawalAlreadyHeld",
+        "UnknownWithdrawalHold",
+        "CapabilityRevoked",
+        "WithdrawalsFrozen",
+        "MergeIntoSelf",
+        "MergeCollision",
+        "OpenTransactions",
+        "InconsistentBalances",
+        "UnknownClient",
+        "Arithmetic",
+    ];
+
+    /// The variant name, such as `NotInDispute`, to group errors by.
+    pub fn name(&self) -> &'static str {
+        use ClientTransactionError::*;
+        match self {
+            AccountLocked { .. } => "AccountLocked",
+            AccountAlreadyLocked { .. } => "AccountAlreadyLocked",
+            InvalidTransactionId { .. } => "InvalidTransactionId",
+            InsufficientAvailableFunds { .. } => "InsufficientAvailableFunds",
+            BalanceLimitExceeded { .. } => "BalanceLimitExceeded",
+            NegativeBalance { .. } => "NegativeBalance",
+            MissingAmount { .. } => "MissingAmount",
+            UnexpectedAmount { .. } => "UnexpectedAmount",
+            TxIdOutOfOrder { .. } => "TxIdOutOfOrder",
+            ZeroAmount { .. } => "ZeroAmount",
+            InvalidAmount { .. } => "InvalidAmount",
+            InsufficientHeldFunds { .. } => "InsufficientHeldFunds",
+            UnknownTransaction { .. } => "UnknownTransaction",
+            AlreadyInDispute { .. } => "AlreadyInDispute",
+            NotInDispute { .. } => "NotInDispute",
+            WithdrawalAlreadyHeld { .. } => "WithdrawalAlreadyHeld",
+            UnknownWithdrawalHold { .. } => "UnknownWithdrawalHold",
+            CapabilityRevoked { .. } => "CapabilityRevoked",
+            WithdrawalsFrozen { .. } => "WithdrawalsFrozen",
+            MergeIntoSelf { .. } => "MergeIntoSelf",
+            MergeCollision { .. } => "MergeCollision",
+            OpenTransactions { .. } => "OpenTransactions",
+            InconsistentBalances { .. } => "InconsistentBalances",
+            UnknownClient { .. } => "UnknownClient",
+            Arithmetic { .. } => "Arithmetic",
+        }
+    }
 }
@@ -1,13 +1,192 @@
+use serde::Serialize;
 use std::io;
 
 use thiserror::Error;
 
+use super::ClientTransactionError;
+use crate::ids::ClientId;
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum EngineError {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
+    #[error("I/O error on {path}: {source}")]
+    File {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
     #[error("CSV error: {0}")]
     Csv(#[from] csv::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Admin operation failed: {0}")]
+    Admin(#[from] ClientTransactionError),
+    #[error(
+        "Invalid header: missing columns [{}], unexpected columns [{}], duplicated columns [{}]",
+        .missing.join(", "),
+        .unexpected.join(", "),
+        .duplicated.join(", ")
+    )]
+    InvalidHeader {
+        missing: Vec<String>,
+        unexpected: Vec<String>,
+        duplicated: Vec<String>,
+    },
+    #[error("Unsupported input: {0}")]
+    UnsupportedInput(String),
     #[error("{0}")]
     Usage(String),
+    #[error("Panic while applying input row {row}")]
+    RowPanic { row: usize },
+    #[error("Interrupted after input row {row}")]
+    Interrupted { row: usize },
+    #[error("Stopped at the {budget} budget after input row {row}")]
+    BudgetExhausted { row: usize, budget: &'static str },
+    #[error("No exchange rate from {from} to {to}")]
+    MissingRate { from: String, to: String },
+    #[error("Approximate memory use of {used} bytes exceeds the limit of {limit} bytes")]
+    MemoryLimitExceeded { used: usize, limit: usize },
+    #[error("Transaction ids are exhausted")]
+    TxIdsExhausted,
+    #[error("{0} account mismatch(es) against the expected output")]
+    VerificationFailed(usize),
+    #[error("{0} account(s) with total != available + held + pending")]
+    InconsistentAccounts(usize),
+    #[error("{0} dispute, resolve or chargeback row(s) name unknown deposits")]
+    UnlinkedReferences(usize),
+    #[error("{value:?} does not fit a fixed-width column of {width}")]
+    FieldTooWide { value: String, width: usize },
+    #[error("Client {client}: {reason}, which no transactions can do")]
+    IrreversibleChange {
+        client: ClientId,
+        reason: &'static str,
+    },
+    #[error("Snapshot encryption: {0}")]
+    Encryption(String),
+    #[error("{path} is being written by another run, which holds the lock on {path}.lock")]
+    OutputLocked { path: String },
+    #[error("Cannot merge engines: {0}")]
+    MergeConflict(String),
+    #[error("Client alias map: {0}")]
+    ClientAlias(String),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+impl EngineError {
+    /// The variant name, as a stable code for callers that branch on errors.
+    pub fn name(&self) -> &'static str {
+        use EngineError::*;
+        match self {
+            Io(_) => "Io",
+            File { .. } => "File",
+            Csv(_) => "Csv",
+            Json(_) => "Json",
+            Admin(_) => "Admin",
+            InvalidHeader { .. } => "InvalidHeader",
+            UnsupportedInput(_) => "UnsupportedInput",
+            Usage(_) => "Usage",
+            RowPanic { .. } => "RowPanic",
+            Interrupted { .. } => "Interrupted",
+            BudgetExhausted { .. } => "BudgetExhausted",
+            MissingRate { .. } => "MissingRate",
+            MemoryLimitExceeded { .. } => "MemoryLimitExceeded",
+            TxIdsExhausted => "TxIdsExhausted",
+            VerificationFailed(_) => "VerificationFailed",
+            InconsistentAccounts(_) => "InconsistentAccounts",
+            UnlinkedReferences(_) => "UnlinkedReferences",
+            FieldTooWide { .. } => "FieldTooWide",
+            IrreversibleChange { .. } => "IrreversibleChange",
+            Encryption(_) => "Encryption",
+            OutputLocked { .. } => "OutputLocked",
+            MergeConflict(_) => "MergeConflict",
+            ClientAlias(_) => "ClientAlias",
+            #[cfg(feature = "sqlite")]
+            Sqlite(_) => "Sqlite",
+        }
+    }
+
+    /// The input row the error stopped at, 1-based, or the line of a
+    /// malformed CSV record.
+    pub fn row(&self) -> Option<u64> {
+        match self {
+            EngineError::RowPanic { row }
+            | EngineError::Interrupted { row }
+            | EngineError::BudgetExhausted { row, .. } => Some(*row as u64),
+            EngineError::Csv(err) => err.position().map(|position| position.line()),
+            _ => None,
+        }
+    }
+
+    /// The file the error is about, for errors that name one.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            EngineError::OutputLocked { path } | EngineError::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// An `EngineError` as data, for callers that log or forward errors rather
+/// than read them; the CLI writes one as JSON under `--json-errors`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    /// `EngineError::name`.
+    pub code: &'static str,
+    pub message: String,
+    pub path: Option<String>,
+    pub row: Option<u64>,
+}
+
+impl From<&EngineError> for ErrorReport {
+    fn from(err: &EngineError) -> Self {
+        ErrorReport {
+            code: err.name(),
+            message: err.to_string(),
+            path: err.path().map(str::to_string),
+            row: err.row(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_carry_the_code_row_and_path() {
+        let report = ErrorReport::from(&EngineError::Interrupted { row: 12 });
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"code":"Interrupted","message":"Interrupted after input row 12","path":null,"row":12}"#
+        );
+
+        let csv_error = csv::Reader::from_reader("a,b\n1,2\n3\n".as_bytes())
+            .records()
+            .find_map(Result::err)
+            .unwrap();
+        let report = ErrorReport::from(&EngineError::from(csv_error));
+        assert_eq!((report.code, report.row), ("Csv", Some(3)));
+
+        let locked = EngineError::OutputLocked {
+            path: "accounts.csv".to_string(),
+        };
+        assert_eq!(
+            ErrorReport::from(&locked).path.as_deref(),
+            Some("accounts.csv")
+        );
+
+        let missing = EngineError::File {
+            path: "input.csv".to_string(),
+            source: io::Error::from(io::ErrorKind::NotFound),
+        };
+        let report = ErrorReport::from(&missing);
+        assert_eq!(
+            (report.code, report.path.as_deref()),
+            ("File", Some("input.csv"))
+        );
+    }
 }
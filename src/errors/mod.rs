@@ -1,5 +1,15 @@
+pub mod amount;
 pub mod client;
+#[cfg(feature = "std")]
 pub mod engine;
+pub mod money;
+pub mod row;
+pub mod validation;
 
+pub use amount::AmountError;
 pub use client::ClientTransactionError;
-pub use engine::EngineError;
+#[cfg(feature = "std")]
+pub use engine::{EngineError, ErrorReport};
+pub use money::MoneyError;
+pub use row::RowError;
+pub use validation::ValidationError;
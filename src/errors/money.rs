@@ -0,0 +1,13 @@
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum MoneyError {
+    #[error("amount {0} is negative")]
+    Negative(Decimal),
+    #[error("amount {0} has more than 4 decimal places")]
+    ScaleTooLarge(Decimal),
+    #[error("amount {0} exceeds the maximum supported magnitude")]
+    TooLarge(Decimal),
+}
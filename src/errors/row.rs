@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+use super::ClientTransactionError;
+
+/// A client error tagged with the input row that caused it (1-based, not
+/// counting the header), so one log line pinpoints the offending record.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Row {row}: {source}")]
+pub struct RowError {
+    pub row: usize,
+    #[source]
+    pub source: ClientTransactionError,
+}
@@ -0,0 +1,14 @@
+use rust_decimal::Decimal;
+use thiserror::Error;
+
+use super::MoneyError;
+
+/// Why a transaction could not be constructed.
+#[derive(Debug, Error, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ValidationError {
+    #[error("amount {0} must be positive")]
+    NonPositiveAmount(Decimal),
+    #[error(transparent)]
+    Amount(#[from] MoneyError),
+}
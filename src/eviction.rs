@@ -0,0 +1,190 @@
+use rust_decimal::Decimal;
+use std::{collections::HashMap, fs, marker::PhantomData, path::PathBuf, time::Duration};
+
+use crate::{client::Client, errors::EngineError, ids::ClientId, numeric::Balance};
+
+/// Somewhere to spill idle clients to. Loading must not remove the entry;
+/// the engine calls `remove` once a reloaded client is resident again.
+pub trait AccountStore<B = Decimal>: Send {
+    fn store(&mut self, client: &Client<B>) -> Result<(), EngineError>;
+    fn load(&self, client_id: ClientId) -> Result<Option<Client<B>>, EngineError>;
+    fn remove(&mut self, client_id: ClientId) -> Result<(), EngineError>;
+    fn client_ids(&self) -> Result<Vec<ClientId>, EngineError>;
+}
+
+/// Keeps evicted clients serialized in memory. Mostly useful in tests, but it
+/// still trades the live maps for a compact encoding.
+pub struct MemoryStore<B = Decimal> {
+    clients: HashMap<ClientId, Vec<u8>>,
+    balance: PhantomData<fn() -> B>,
+}
+
+impl<B> Default for MemoryStore<B> {
+    fn default() -> Self {
+        MemoryStore {
+            clients: HashMap::new(),
+            balance: PhantomData,
+        }
+    }
+}
+
+impl<B: Balance> AccountStore<B> for MemoryStore<B> {
+    fn store(&mut self, client: &Client<B>) -> Result<(), EngineError> {
+        self.clients.insert(client.id, serde_json::to_vec(client)?);
+        Ok(())
+    }
+
+    fn load(&self, client_id: ClientId) -> Result<Option<Client<B>>, EngineError> {
+        self.clients
+            .get(&client_id)
+            .map(|bytes| serde_json::from_slice(bytes).map_err(EngineError::from))
+            .transpose()
+    }
+
+    fn remove(&mut self, client_id: ClientId) -> Result<(), EngineError> {
+        self.clients.remove(&client_id);
+        Ok(())
+    }
+
+    fn client_ids(&self) -> Result<Vec<ClientId>, EngineError> {
+        Ok(self.clients.keys().copied().collect())
+    }
+}
+
+/// Stores each evicted client as `<dir>/<client>.json`.
+pub struct DirectoryStore<B = Decimal> {
+    dir: PathBuf,
+    balance: PhantomData<fn() -> B>,
+}
+
+impl<B> DirectoryStore<B> {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, EngineError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(DirectoryStore {
+            dir,
+            balance: PhantomData,
+        })
+    }
+
+    fn path(&self, client_id: ClientId) -> PathBuf {
+        self.dir.join(format!("{client_id}.json"))
+    }
+}
+
+impl<B: Balance> AccountStore<B> for DirectoryStore<B> {
+    fn store(&mut self, client: &Client<B>) -> Result<(), EngineError> {
+        fs::write(self.path(client.id), serde_json::to_vec(client)?)?;
+        Ok(())
+    }
+
+    fn load(&self, client_id: ClientId) -> Result<Option<Client<B>>, EngineError> {
+        match fs::read(self.path(client_id)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn remove(&mut self, client_id: ClientId) -> Result<(), EngineError> {
+        match fs::remove_file(self.path(client_id)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn client_ids(&self) -> Result<Vec<ClientId>, EngineError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Some(id) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Evict clients untouched for `idle_for` (per the engine clock) into
+/// `store`. Eviction runs at the end of every `Engine::process` call and on
+/// demand through `Engine::evict_idle`.
+pub struct EvictionPolicy<B = Decimal> {
+    pub idle_for: Duration,
+    pub store: Box<dyn AccountStore<B>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use crate::clock::ManualClock;
+    use crate::ids::TxId;
+    use crate::money::Money;
+    use rust_decimal::dec;
+    use std::{io::Cursor, sync::Arc};
+
+    /// A store whose disk is always full.
+    struct FailingStore;
+
+    impl AccountStore for FailingStore {
+        fn store(&mut self, _: &Client) -> Result<(), EngineError> {
+            Err(std::io::Error::other("disk full").into())
+        }
+
+        fn load(&self, _: ClientId) -> Result<Option<Client>, EngineError> {
+            Ok(None)
+        }
+
+        fn remove(&mut self, _: ClientId) -> Result<(), EngineError> {
+            Ok(())
+        }
+
+        fn client_ids(&self) -> Result<Vec<ClientId>, EngineError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn memory_store_round_trips_clients() {
+        let mut store = MemoryStore::default();
+        let mut client = Client::new(ClientId(7));
+        client
+            .deposit(TxId(1), Money::new(dec!(3)).unwrap())
+            .unwrap();
+
+        store.store(&client).unwrap();
+        assert_eq!(store.client_ids().unwrap(), [ClientId(7)]);
+        assert_eq!(store.load(ClientId(7)).unwrap().unwrap().available, dec!(3));
+
+        store.remove(ClientId(7)).unwrap();
+        assert!(store.load(ClientId(7)).unwrap().is_none());
+    }
+
+    #[test]
+    fn clients_stay_resident_when_the_store_fails() {
+        let clock = Arc::new(ManualClock::default());
+        let mut engine = Engine::new();
+        engine.set_clock(clock.clone());
+        engine.set_eviction(EvictionPolicy {
+            idle_for: Duration::from_secs(60),
+            store: Box::new(FailingStore),
+        });
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount
+deposit,1,1,5
+",
+            ))
+            .unwrap();
+        clock.advance(Duration::from_secs(60));
+
+        assert!(matches!(engine.evict_idle(), Err(EngineError::Io(_))));
+        assert_eq!(engine.client(ClientId(1)).unwrap().total, dec!(5));
+        assert_eq!(engine.write_accounts(Vec::new()).unwrap(), 1);
+    }
+}
@@ -0,0 +1,126 @@
+//! Error injection for testing services that embed the engine. Only built
+//! with the `fault-injection` feature; never enable it in production.
+
+use log::warn;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+/// Randomly fails account writes, delays row processing and corrupts input
+/// rows. Seeded, so a failing run can be replayed exactly.
+pub struct FaultInjector {
+    rng: Mutex<StdRng>,
+    write_failure_rate: f64,
+    corrupt_row_rate: f64,
+    delay_rate: f64,
+    delay: Duration,
+}
+
+impl FaultInjector {
+    pub fn new(seed: u64) -> Self {
+        FaultInjector {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            write_failure_rate: 0.0,
+            corrupt_row_rate: 0.0,
+            delay_rate: 0.0,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Fails this fraction of writes to the account output with an
+    /// `io::ErrorKind::Other` error.
+    pub fn fail_writes(mut self, rate: f64) -> Self {
+        self.write_failure_rate = rate;
+        self
+    }
+
+    /// Corrupts this fraction of input rows so they fail validation and are
+    /// skipped, as a malformed partner row would be.
+    pub fn corrupt_rows(mut self, rate: f64) -> Self {
+        self.corrupt_row_rate = rate;
+        self
+    }
+
+    /// Sleeps for `delay` before this fraction of input rows.
+    pub fn delay_operations(mut self, rate: f64, delay: Duration) -> Self {
+        self.delay_rate = rate;
+        self.delay = delay;
+        self
+    }
+
+    fn roll(&self, rate: f64) -> bool {
+        rate > 0.0 && self.rng.lock().unwrap().gen_bool(rate.min(1.0))
+    }
+
+    pub(crate) fn maybe_delay(&self) {
+        if self.roll(self.delay_rate) {
+            thread::sleep(self.delay);
+        }
+    }
+
+    pub(crate) fn should_corrupt_row(&self) -> bool {
+        let corrupt = self.roll(self.corrupt_row_rate);
+        if corrupt {
+            warn!("Fault injection: corrupting input row");
+        }
+        corrupt
+    }
+
+    pub(crate) fn wrap_writer<W: Write>(&self, writer: W) -> FaultyWriter<'_, W> {
+        FaultyWriter {
+            injector: self,
+            inner: writer,
+        }
+    }
+}
+
+pub(crate) struct FaultyWriter<'a, W> {
+    injector: &'a FaultInjector,
+    inner: W,
+}
+
+impl<W: Write> Write for FaultyWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.injector.roll(self.injector.write_failure_rate) {
+            return Err(io::Error::other("injected write failure"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_injects_the_same_faults() {
+        let rolls = |seed| {
+            let injector = FaultInjector::new(seed).corrupt_rows(0.5);
+            (0..32)
+                .map(|_| injector.should_corrupt_row())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rolls(7), rolls(7));
+        assert!(rolls(7).contains(&true) && rolls(7).contains(&false));
+    }
+
+    #[test]
+    fn writes_fail_at_full_rate() {
+        let injector = FaultInjector::new(1).fail_writes(1.0);
+        let mut writer = injector.wrap_writer(Vec::new());
+
+        assert_eq!(
+            writer.write(b"client").unwrap_err().kind(),
+            io::ErrorKind::Other
+        );
+    }
+}
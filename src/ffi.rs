@@ -0,0 +1,330 @@
+//! C interface for embedding the engine in C and C++ hosts. Only built with
+//! the `ffi` feature; the cdylib then exports the functions declared in
+//! `include/payments_engine.h`.
+//!
+//! Every function returns a [`PaymentsStatus`] code, with `0` for success.
+//! Panics are caught at the boundary and reported as
+//! [`PaymentsStatus::Internal`] rather than unwinding into C.
+
+use rust_decimal::Decimal;
+use std::{
+    ffi::{CStr, c_char},
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+use crate::{
+    Engine,
+    engine::Rejection,
+    errors::{ClientTransactionError, EngineError},
+    ids::{ClientId, TxId},
+    transaction::TransactionType,
+};
+
+/// Result codes. The values are part of the C ABI and never change meaning;
+/// new codes are only ever appended.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaymentsStatus {
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// A string was not UTF-8, or an amount or type could not be parsed.
+    InvalidArgument = 2,
+    /// An engine-level failure, such as an I/O error while evicting clients.
+    EngineFailure = 3,
+    /// Rejected for a reason other than the account's state, such as a rule.
+    Rejected = 4,
+    AccountLocked = 10,
+    InvalidTransactionId = 11,
+    InsufficientAvailableFunds = 12,
+    MissingAmount = 13,
+    InvalidAmount = 14,
+    InsufficientHeldFunds = 15,
+    UnknownTransaction = 16,
+    AlreadyInDispute = 17,
+    NotInDispute = 18,
+    WithdrawalAlreadyHeld = 19,
+    UnknownWithdrawalHold = 20,
+    InconsistentBalances = 21,
+    Arithmetic = 22,
+    WithdrawalsFrozen = 23,
+    UnexpectedAmount = 24,
+    CapabilityRevoked = 25,
+    TxIdOutOfOrder = 26,
+    OpenTransactions = 27,
+    ZeroAmount = 28,
+    /// Any other account error.
+    ClientError = 29,
+    BalanceLimitExceeded = 30,
+    NegativeBalance = 31,
+    Internal = 99,
+}
+
+impl From<&ClientTransactionError> for PaymentsStatus {
+    fn from(err: &ClientTransactionError) -> Self {
+        use ClientTransactionError::*;
+        match err {
+            AccountLocked { .. } | AccountAlreadyLocked { .. } => PaymentsStatus::AccountLocked,
+            InvalidTransactionId { .. } => PaymentsStatus::InvalidTransactionId,
+            InsufficientAvailableFunds { .. } => PaymentsStatus::InsufficientAvailableFunds,
+            MissingAmount { .. } => PaymentsStatus::MissingAmount,
+            InvalidAmount { .. } => PaymentsStatus::InvalidAmount,
+            InsufficientHeldFunds { .. } => PaymentsStatus::InsufficientHeldFunds,
+            UnknownTransaction { .. } => PaymentsStatus::UnknownTransaction,
+            AlreadyInDispute { .. } => PaymentsStatus::AlreadyInDispute,
+            NotInDispute { .. } => PaymentsStatus::NotInDispute,
+            WithdrawalAlreadyHeld { .. } => PaymentsStatus::WithdrawalAlreadyHeld,
+            UnknownWithdrawalHold { .. } => PaymentsStatus::UnknownWithdrawalHold,
+            InconsistentBalances { .. } => PaymentsStatus::InconsistentBalances,
+            Arithmetic { .. } => PaymentsStatus::Arithmetic,
+            WithdrawalsFrozen { .. } => PaymentsStatus::WithdrawalsFrozen,
+            UnexpectedAmount { .. } => PaymentsStatus::UnexpectedAmount,
+            CapabilityRevoked { .. } => PaymentsStatus::CapabilityRevoked,
+            TxIdOutOfOrder { .. } => PaymentsStatus::TxIdOutOfOrder,
+            OpenTransactions { .. } => PaymentsStatus::OpenTransactions,
+            ZeroAmount { .. } => PaymentsStatus::ZeroAmount,
+            BalanceLimitExceeded { .. } => PaymentsStatus::BalanceLimitExceeded,
+            NegativeBalance { .. } => PaymentsStatus::NegativeBalance,
+            MergeIntoSelf { .. } | MergeCollision { .. } | UnknownClient { .. } => {
+                PaymentsStatus::ClientError
+            }
+        }
+    }
+}
+
+impl From<&EngineError> for PaymentsStatus {
+    fn from(err: &EngineError) -> Self {
+        match err {
+            EngineError::Admin(err) => err.into(),
+            _ => PaymentsStatus::EngineFailure,
+        }
+    }
+}
+
+/// Bytes owned by Rust and lent to C. Release with [`payments_buffer_free`].
+#[repr(C)]
+pub struct PaymentsBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PaymentsBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let bytes = Box::leak(bytes.into_boxed_slice());
+        PaymentsBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        }
+    }
+}
+
+fn guarded(f: impl FnOnce() -> PaymentsStatus) -> PaymentsStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(PaymentsStatus::Internal)
+}
+
+/// # Safety
+/// `text` must be null or point to a NUL-terminated string.
+unsafe fn utf8<'a>(text: *const c_char) -> Result<&'a str, PaymentsStatus> {
+    if text.is_null() {
+        return Err(PaymentsStatus::NullPointer);
+    }
+    // SAFETY: non-null, and NUL-terminated per the caller's contract.
+    unsafe { CStr::from_ptr(text) }
+        .to_str()
+        .map_err(|_| PaymentsStatus::InvalidArgument)
+}
+
+/// Creates an engine with the default configuration and stores it in
+/// `*engine`. Free it with [`payments_engine_free`].
+///
+/// # Safety
+/// `engine` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn payments_engine_new(engine: *mut *mut Engine) -> PaymentsStatus {
+    if engine.is_null() {
+        return PaymentsStatus::NullPointer;
+    }
+    guarded(|| {
+        // SAFETY: checked non-null above; the caller guarantees it is writable.
+        unsafe { *engine = Box::into_raw(Box::new(Engine::new())) };
+        PaymentsStatus::Ok
+    })
+}
+
+/// # Safety
+/// `engine` must be null or come from [`payments_engine_new`], and must not
+/// be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn payments_engine_free(engine: *mut Engine) {
+    if !engine.is_null() {
+        // SAFETY: created by `Box::into_raw` in `payments_engine_new`.
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Applies one transaction. `tx_type` is a type name as in the CSV input,
+/// such as `"deposit"`; `amount` is a decimal string, or null for types
+/// without one. A rejected transaction returns the code for its reason and
+/// leaves the engine usable.
+///
+/// # Safety
+/// `engine` must come from [`payments_engine_new`]. `tx_type` and a non-null
+/// `amount` must be NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn payments_engine_push(
+    engine: *mut Engine,
+    tx_type: *const c_char,
+    client: u16,
+    tx: u32,
+    amount: *const c_char,
+) -> PaymentsStatus {
+    if engine.is_null() {
+        return PaymentsStatus::NullPointer;
+    }
+    guarded(|| {
+        // SAFETY: the caller passes an engine from `payments_engine_new`.
+        let engine = unsafe { &mut *engine };
+        // SAFETY: the caller passes NUL-terminated strings.
+        let tx_type = match unsafe { utf8(tx_type) }.map(TransactionType::from_name) {
+            Ok(Some(tx_type)) => tx_type,
+            Ok(None) => return PaymentsStatus::InvalidArgument,
+            Err(status) => return status,
+        };
+        let amount = if amount.is_null() {
+            None
+        } else {
+            // SAFETY: as above.
+            match unsafe { utf8(amount) }.map(str::parse::<Decimal>) {
+                Ok(Ok(amount)) => Some(amount),
+                Ok(Err(_)) => return PaymentsStatus::InvalidArgument,
+                Err(status) => return status,
+            }
+        };
+        match engine.push(tx_type, ClientId(client), TxId(tx), amount) {
+            Ok(None) => PaymentsStatus::Ok,
+            Ok(Some(Rejection::Client(err))) => (&err).into(),
+            Ok(Some(Rejection::Other(_))) => PaymentsStatus::Rejected,
+            Err(err) => (&err).into(),
+        }
+    })
+}
+
+/// Serializes every account as CSV, exactly as the CLI writes them, into
+/// `*buffer`. Free it with [`payments_buffer_free`].
+///
+/// # Safety
+/// `engine` must come from [`payments_engine_new`] and `buffer` must be valid
+/// for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn payments_engine_accounts(
+    engine: *const Engine,
+    buffer: *mut PaymentsBuffer,
+) -> PaymentsStatus {
+    if engine.is_null() || buffer.is_null() {
+        return PaymentsStatus::NullPointer;
+    }
+    guarded(|| {
+        // SAFETY: the caller passes an engine from `payments_engine_new`.
+        let engine = unsafe { &*engine };
+        let mut accounts = Vec::new();
+        if let Err(err) = engine.write_accounts(&mut accounts) {
+            return (&err).into();
+        }
+        // SAFETY: checked non-null above; the caller guarantees it is writable.
+        unsafe { *buffer = PaymentsBuffer::new(accounts) };
+        PaymentsStatus::Ok
+    })
+}
+
+/// # Safety
+/// `buffer` must come from this library and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn payments_buffer_free(buffer: PaymentsBuffer) {
+    if !buffer.data.is_null() {
+        // SAFETY: `data` and `len` describe a boxed slice leaked by
+        // `PaymentsBuffer::new`.
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+}
+
+/// A static, NUL-terminated description of `status`, for logs.
+#[unsafe(no_mangle)]
+pub extern "C" fn payments_status_message(status: PaymentsStatus) -> *const c_char {
+    let message: &'static CStr = match status {
+        PaymentsStatus::Ok => c"ok",
+        PaymentsStatus::NullPointer => c"null pointer",
+        PaymentsStatus::InvalidArgument => c"invalid argument",
+        PaymentsStatus::EngineFailure => c"engine failure",
+        PaymentsStatus::Rejected => c"transaction rejected",
+        PaymentsStatus::AccountLocked => c"account is locked",
+        PaymentsStatus::InvalidTransactionId => c"invalid transaction id",
+        PaymentsStatus::InsufficientAvailableFunds => c"insufficient available funds",
+        PaymentsStatus::MissingAmount => c"missing amount",
+        PaymentsStatus::InvalidAmount => c"invalid amount",
+        PaymentsStatus::InsufficientHeldFunds => c"insufficient held funds",
+        PaymentsStatus::UnknownTransaction => c"unknown transaction",
+        PaymentsStatus::AlreadyInDispute => c"transaction already in dispute",
+        PaymentsStatus::NotInDispute => c"transaction not under dispute",
+        PaymentsStatus::WithdrawalAlreadyHeld => c"withdrawal already held",
+        PaymentsStatus::UnknownWithdrawalHold => c"unknown withdrawal hold",
+        PaymentsStatus::InconsistentBalances => c"inconsistent balances",
+        PaymentsStatus::Arithmetic => c"arithmetic overflow",
+        PaymentsStatus::WithdrawalsFrozen => c"withdrawals frozen by an open dispute",
+        PaymentsStatus::UnexpectedAmount => c"amount given for a transaction without one",
+        PaymentsStatus::CapabilityRevoked => c"transaction type switched off for the account",
+        PaymentsStatus::TxIdOutOfOrder => c"transaction id out of order or reused",
+        PaymentsStatus::OpenTransactions => c"open disputes or withdrawal holds",
+        PaymentsStatus::ZeroAmount => c"zero amount",
+        PaymentsStatus::ClientError => c"account error",
+        PaymentsStatus::BalanceLimitExceeded => c"deposit would exceed the balance limit",
+        PaymentsStatus::NegativeBalance => c"transaction would leave a negative balance",
+        PaymentsStatus::Internal => c"internal error",
+    };
+    message.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transactions_round_trip_through_the_c_interface() {
+        let mut engine = ptr::null_mut();
+        unsafe {
+            assert_eq!(payments_engine_new(&mut engine), PaymentsStatus::Ok);
+            assert_eq!(
+                payments_engine_push(engine, c"deposit".as_ptr(), 1, 1, c"2.5".as_ptr()),
+                PaymentsStatus::Ok
+            );
+            assert_eq!(
+                payments_engine_push(engine, c"withdrawal".as_ptr(), 1, 2, c"9".as_ptr()),
+                PaymentsStatus::InsufficientAvailableFunds
+            );
+            assert_eq!(
+                payments_engine_push(engine, c"resolve".as_ptr(), 1, 1, ptr::null()),
+                PaymentsStatus::NotInDispute
+            );
+            assert_eq!(
+                payments_engine_push(engine, c"deposit".as_ptr(), 1, 3, c"abc".as_ptr()),
+                PaymentsStatus::InvalidArgument
+            );
+
+            let mut buffer = PaymentsBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(
+                payments_engine_accounts(engine, &mut buffer),
+                PaymentsStatus::Ok
+            );
+            let csv = std::slice::from_raw_parts(buffer.data, buffer.len);
+            assert_eq!(
+                csv,
+                b"client,available,held,total,locked\n1,2.5000,0.0000,2.5000,false\n"
+            );
+            payments_buffer_free(buffer);
+            payments_engine_free(engine);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Read, Write},
+    str::FromStr,
+};
+
+use crate::{
+    client::Client, dead_letter::RejectedRow, engine::ingest::InputTransaction,
+    errors::EngineError, formatting::format_decimal, ids::ClientId, numeric::Balance,
+    rejection_log::RejectionLogging,
+};
+use csv::StringRecord;
+use log::error;
+use rust_decimal::Decimal;
+
+/// Wire format for transaction input and account output. `Json` is newline
+/// delimited: one object per line, so files can be streamed like CSV.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            other => Err(format!("unknown format {other}")),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        })
+    }
+}
+
+/// How amounts are encoded in JSON output. `Number` writes the exact
+/// fixed-point text as a JSON number, never going through a float. JSON input
+/// accepts either encoding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AmountEncoding {
+    #[default]
+    String,
+    Number,
+}
+
+impl FromStr for AmountEncoding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "string" => Ok(AmountEncoding::String),
+            "number" => Ok(AmountEncoding::Number),
+            other => Err(format!("unknown amount encoding {other}")),
+        }
+    }
+}
+
+pub(crate) struct JsonAmount(pub(crate) Decimal, pub(crate) AmountEncoding);
+
+impl Serialize for JsonAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let text = format_decimal(self.0);
+        match self.1 {
+            AmountEncoding::String => serializer.serialize_str(&text),
+            AmountEncoding::Number => RawValue::from_string(text)
+                .map_err(serde::ser::Error::custom)?
+                .serialize(serializer),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonAccount<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<&'a str>,
+    client: ClientId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_id: Option<&'a str>,
+    available: JsonAmount,
+    held: JsonAmount,
+    total: JsonAmount,
+    locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    risk_score: Option<u8>,
+}
+
+pub(crate) fn read_json_transactions<R: Read>(
+    source: R,
+    keep_raw: bool,
+    logging: RejectionLogging,
+) -> impl Iterator<Item = Result<InputTransaction, RejectedRow>> {
+    BufReader::new(source)
+        .lines()
+        .map_while(|line| {
+            line.inspect_err(|err| error!("Error reading JSON input: {err}"))
+                .ok()
+        })
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(move |(row_index, line)| {
+            let raw = || {
+                if keep_raw {
+                    StringRecord::from(vec![line.as_str()])
+                } else {
+                    StringRecord::new()
+                }
+            };
+            match serde_json::from_str(&line) {
+                Ok(transaction) => Ok(InputTransaction {
+                    row: row_index + 1,
+                    raw: keep_raw.then(raw),
+                    ..transaction
+                }),
+                Err(err) => {
+                    logging.log(
+                        "Parse",
+                        format_args!("Error parsing JSON row {}: {}", row_index + 1, err),
+                    );
+                    Err(RejectedRow {
+                        row: row_index + 1,
+                        raw: raw().into_byte_record(),
+                        reason: err.to_string(),
+                    })
+                }
+            }
+        })
+}
+
+pub(crate) fn write_json_account<W: Write, B: Balance>(
+    mut writer: W,
+    tenant: Option<&str>,
+    client: &Client<B>,
+    external_id: Option<&str>,
+    risk_score: Option<u8>,
+    encoding: AmountEncoding,
+) -> Result<(), EngineError> {
+    let account = JsonAccount {
+        tenant,
+        client: client.id,
+        external_id,
+        available: JsonAmount(client.available.to_decimal(), encoding),
+        held: JsonAmount(client.held.into(), encoding),
+        total: JsonAmount(client.total.to_decimal(), encoding),
+        locked: client.locked,
+        risk_score,
+    };
+    serde_json::to_writer(&mut writer, &account)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+pub(crate) fn write_json_accounts<'a, W: Write>(
+    accounts: impl IntoIterator<Item = (Option<&'a str>, &'a Client)>,
+    encoding: AmountEncoding,
+    mut writer: W,
+) -> Result<(), EngineError> {
+    for (tenant, client) in accounts {
+        write_json_account(&mut writer, tenant, client, None, None, encoding)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use crate::money::Money;
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    fn client() -> Client {
+        let mut client = Client::new(ClientId(3));
+        client
+            .deposit(TxId(1), Money::new(dec!(1.5)).unwrap())
+            .unwrap();
+        client
+    }
+
+    fn render(encoding: AmountEncoding) -> String {
+        let client = client();
+        let mut output = Vec::new();
+        write_json_accounts([(None, &client)], encoding, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn string_encoding_quotes_amounts() {
+        assert_eq!(
+            render(AmountEncoding::String),
+            "{\"client\":3,\"available\":\"1.5000\",\"held\":\"0.0000\",\"total\":\"1.5000\",\"locked\":false}\n"
+        );
+    }
+
+    #[test]
+    fn number_encoding_writes_exact_fixed_point_numbers() {
+        assert_eq!(
+            render(AmountEncoding::Number),
+            "{\"client\":3,\"available\":1.5000,\"held\":0.0000,\"total\":1.5000,\"locked\":false}\n"
+        );
+    }
+
+    #[test]
+    fn json_input_accepts_string_and_number_amounts_and_skips_bad_lines() {
+        let input = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":\"2.5\"}\nnot json\n\n{\"type\":\"deposit\",\"client\":1,\"tx\":2,\"amount\":0.1}\n";
+        let rows: Vec<InputTransaction> =
+            read_json_transactions(Cursor::new(input), false, RejectionLogging::default())
+                .flatten()
+                .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].amount, Some(dec!(2.5)));
+        assert_eq!(rows[1].amount, Some(dec!(0.1)));
+    }
+}
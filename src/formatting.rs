@@ -0,0 +1,527 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::{io::Write, str::FromStr};
+
+use crate::errors::{AmountError, EngineError};
+
+/// Formats `value` with exactly four decimal places, truncating any further
+/// digits. This is the engine's default output precision.
+pub fn format_decimal(value: Decimal) -> String {
+    format!("{value:.4}")
+}
+
+/// Drops every digit past `places` without rounding.
+pub fn truncate_to(value: Decimal, places: u32) -> Decimal {
+    value.trunc_with_scale(places)
+}
+
+/// Rounds to `places`, with midpoints going away from zero (`1.005` becomes
+/// `1.01`, `-1.005` becomes `-1.01`).
+pub fn round_to(value: Decimal, places: u32) -> Decimal {
+    value.round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rounding {
+    #[default]
+    Truncate,
+    HalfUp,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "truncate" => Ok(Rounding::Truncate),
+            "half-up" => Ok(Rounding::HalfUp),
+            other => Err(format!("unknown rounding {other}")),
+        }
+    }
+}
+
+/// Formats `value` with exactly `places` decimal places.
+pub fn format_places(value: Decimal, places: u32, rounding: Rounding) -> String {
+    let value = match rounding {
+        Rounding::Truncate => truncate_to(value, places),
+        Rounding::HalfUp => round_to(value, places),
+    };
+    format!("{value:.0$}", places as usize)
+}
+
+/// Decimal separator used by input amounts. Some partners export `3,50`
+/// instead of `3.50`; with `Comma` such fields must be quoted in CSV.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+    #[default]
+    Dot,
+    Comma,
+}
+
+impl FromStr for DecimalSeparator {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "dot" => Ok(DecimalSeparator::Dot),
+            "comma" => Ok(DecimalSeparator::Comma),
+            other => Err(format!("unknown decimal separator {other}")),
+        }
+    }
+}
+
+impl DecimalSeparator {
+    fn mark(self) -> char {
+        match self {
+            DecimalSeparator::Dot => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+}
+
+/// Digit grouping accepted in input amounts, as in `1,234.56` or `1.234,56`.
+/// Groups must be three digits long, except the leading one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThousandsSeparator {
+    #[default]
+    None,
+    Comma,
+    Dot,
+    Space,
+    Apostrophe,
+}
+
+impl FromStr for ThousandsSeparator {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(ThousandsSeparator::None),
+            "comma" => Ok(ThousandsSeparator::Comma),
+            "dot" => Ok(ThousandsSeparator::Dot),
+            "space" => Ok(ThousandsSeparator::Space),
+            "apostrophe" => Ok(ThousandsSeparator::Apostrophe),
+            other => Err(format!("unknown thousands separator {other}")),
+        }
+    }
+}
+
+impl ThousandsSeparator {
+    fn mark(self) -> Option<char> {
+        match self {
+            ThousandsSeparator::None => None,
+            ThousandsSeparator::Comma => Some(','),
+            ThousandsSeparator::Dot => Some('.'),
+            ThousandsSeparator::Space => Some(' '),
+            ThousandsSeparator::Apostrophe => Some('\''),
+        }
+    }
+}
+
+/// Parses an amount written with `separator` and optional `thousands`
+/// grouping, independently of the host locale. A `.` or `,` that is neither
+/// separator is rejected as ambiguous rather than guessed at, so `1,234`
+/// never silently becomes `1.234` or `1234`.
+pub fn parse_amount(
+    text: &str,
+    separator: DecimalSeparator,
+    thousands: ThousandsSeparator,
+) -> Result<Decimal, AmountError> {
+    let amount = text.trim();
+    let decimal_mark = separator.mark();
+    let group_mark = thousands.mark();
+    if group_mark == Some(decimal_mark) {
+        return Err(AmountError::ConflictingSeparators(decimal_mark));
+    }
+    if amount.is_empty() {
+        return Err(AmountError::Empty);
+    }
+    let misplaced = |position| AmountError::MisplacedThousandsSeparator {
+        amount: amount.to_string(),
+        position,
+    };
+
+    let mut normalized = String::with_capacity(amount.len());
+    // Digits since the last separator, and where the last group mark was.
+    let mut run = 0;
+    let mut last_group = None;
+    let mut in_fraction = false;
+    for (index, character) in amount.chars().enumerate() {
+        let position = index + 1;
+        match character {
+            '0'..='9' => {
+                normalized.push(character);
+                run += 1;
+            }
+            '-' | '+' if index == 0 => normalized.push(character),
+            mark if mark == decimal_mark => {
+                if in_fraction {
+                    return Err(AmountError::RepeatedDecimalSeparator {
+                        amount: amount.to_string(),
+                        position,
+                    });
+                }
+                if let Some(group) = last_group
+                    && run != 3
+                {
+                    return Err(misplaced(group));
+                }
+                normalized.push('.');
+                in_fraction = true;
+                run = 0;
+            }
+            mark if Some(mark) == group_mark => {
+                let valid_run = match last_group {
+                    None => (1..=3).contains(&run),
+                    Some(_) => run == 3,
+                };
+                if in_fraction || !valid_run {
+                    return Err(misplaced(position));
+                }
+                last_group = Some(position);
+                run = 0;
+            }
+            '.' | ',' => {
+                return Err(AmountError::Ambiguous {
+                    amount: amount.to_string(),
+                    character,
+                    position,
+                });
+            }
+            _ => {
+                return Err(AmountError::UnexpectedCharacter {
+                    amount: amount.to_string(),
+                    character,
+                    position,
+                });
+            }
+        }
+    }
+    if !in_fraction
+        && let Some(group) = last_group
+        && run != 3
+    {
+        return Err(misplaced(group));
+    }
+
+    Decimal::from_str_exact(&normalized).map_err(|reason| AmountError::Invalid {
+        amount: amount.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// When CSV output fields are wrapped in quotes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quoting {
+    /// Only fields containing a delimiter, quote or newline.
+    #[default]
+    Necessary,
+    Always,
+    /// Every field that does not parse as a number.
+    NonNumeric,
+    /// Never, even if that makes the output ambiguous.
+    Never,
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "necessary" => Ok(Quoting::Necessary),
+            "always" => Ok(Quoting::Always),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            "never" => Ok(Quoting::Never),
+            other => Err(format!("unknown quoting {other}")),
+        }
+    }
+}
+
+impl Quoting {
+    fn quote_style(self) -> csv::QuoteStyle {
+        match self {
+            Quoting::Necessary => csv::QuoteStyle::Necessary,
+            Quoting::Always => csv::QuoteStyle::Always,
+            Quoting::NonNumeric => csv::QuoteStyle::NonNumeric,
+            Quoting::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+/// Line endings of CSV and fixed-width account output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::CrLf),
+            other => Err(format!("unknown line ending {other}, expected lf or crlf")),
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+
+    fn terminator(self) -> csv::Terminator {
+        match self {
+            LineEnding::Lf => csv::Terminator::Any(b'\n'),
+            LineEnding::CrLf => csv::Terminator::CRLF,
+        }
+    }
+}
+
+/// Column widths of fixed-width account output, one per output column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedWidths(Vec<usize>);
+
+impl FixedWidths {
+    pub fn new(widths: Vec<usize>) -> Result<Self, String> {
+        if widths.is_empty() || widths.contains(&0) {
+            return Err("fixed widths must be one or more positive numbers".to_string());
+        }
+        Ok(FixedWidths(widths))
+    }
+
+    pub fn widths(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+/// Comma-separated widths, such as `5,20,20,20,5`.
+impl FromStr for FixedWidths {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let widths = value
+            .split(',')
+            .map(|width| {
+                width
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid column width {width}"))
+            })
+            .collect::<Result<_, _>>()?;
+        FixedWidths::new(widths)
+    }
+}
+
+/// Per-engine amount formatting. Applies to amount parsing of CSV input and
+/// to CSV account output; the defaults reproduce [`format_decimal`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormattingOptions {
+    pub places: u32,
+    pub rounding: Rounding,
+    pub decimal_separator: DecimalSeparator,
+    pub thousands_separator: ThousandsSeparator,
+    pub quoting: Quoting,
+    pub line_ending: LineEnding,
+    /// Write account output as fixed-width columns, without a header or
+    /// delimiters, instead of CSV. Quoting does not apply.
+    pub fixed_width: Option<FixedWidths>,
+}
+
+impl Default for FormattingOptions {
+    fn default() -> Self {
+        FormattingOptions {
+            places: 4,
+            rounding: Rounding::Truncate,
+            decimal_separator: DecimalSeparator::Dot,
+            thousands_separator: ThousandsSeparator::None,
+            quoting: Quoting::Necessary,
+            line_ending: LineEnding::Lf,
+            fixed_width: None,
+        }
+    }
+}
+
+impl FormattingOptions {
+    pub fn format(&self, value: Decimal) -> String {
+        format_places(value, self.places, self.rounding)
+    }
+
+    /// Whether input amounts are plain `1234.56` that serde reads directly.
+    pub(crate) fn has_default_input(&self) -> bool {
+        self.decimal_separator == DecimalSeparator::Dot
+            && self.thousands_separator == ThousandsSeparator::None
+    }
+
+    pub(crate) fn parse_amount(&self, text: &str) -> Result<Decimal, AmountError> {
+        parse_amount(text, self.decimal_separator, self.thousands_separator)
+    }
+
+    /// Whether CSV account output is plain four-place truncation, which the
+    /// buffered `AccountWriter` produces directly.
+    pub(crate) fn has_default_output(&self) -> bool {
+        self.places == 4
+            && self.rounding == Rounding::Truncate
+            && self.quoting == Quoting::Necessary
+            && self.line_ending == LineEnding::Lf
+            && self.fixed_width.is_none()
+    }
+
+    pub(crate) fn record_writer<W: Write>(&self, writer: W) -> RecordWriter<W> {
+        match &self.fixed_width {
+            Some(widths) => RecordWriter::FixedWidth {
+                writer,
+                widths: widths.clone(),
+                line_ending: self.line_ending,
+            },
+            None => RecordWriter::Csv(Box::new(
+                csv::WriterBuilder::new()
+                    .quote_style(self.quoting.quote_style())
+                    .terminator(self.line_ending.terminator())
+                    .from_writer(writer),
+            )),
+        }
+    }
+}
+
+/// Account output as CSV or in fixed-width columns, as [`FormattingOptions`]
+/// selects.
+pub(crate) enum RecordWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    FixedWidth {
+        writer: W,
+        widths: FixedWidths,
+        line_ending: LineEnding,
+    },
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Starts CSV output with `header`. Fixed-width output has no header,
+    /// but needs a width for each of its columns.
+    pub(crate) fn write_header<T: AsRef<str>>(&mut self, header: &[T]) -> Result<(), EngineError> {
+        match self {
+            RecordWriter::Csv(writer) => writer.write_record(header.iter().map(AsRef::as_ref))?,
+            RecordWriter::FixedWidth { widths, .. } if widths.widths().len() != header.len() => {
+                return Err(EngineError::Usage(format!(
+                    "{} fixed widths given for {} output columns",
+                    widths.widths().len(),
+                    header.len()
+                )));
+            }
+            RecordWriter::FixedWidth { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Writes one record. In fixed-width output, numbers are right-aligned
+    /// and everything else left-aligned; a field wider than its column fails
+    /// rather than being cut.
+    pub(crate) fn write_record<I, T>(&mut self, record: I) -> Result<(), EngineError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str> + AsRef<[u8]>,
+    {
+        match self {
+            RecordWriter::Csv(writer) => writer.write_record(record)?,
+            RecordWriter::FixedWidth {
+                writer,
+                widths,
+                line_ending,
+            } => {
+                let mut line = String::new();
+                for (field, &width) in record.into_iter().zip(widths.widths()) {
+                    let field: &str = field.as_ref();
+                    if field.chars().count() > width {
+                        return Err(EngineError::FieldTooWide {
+                            value: field.to_string(),
+                            width,
+                        });
+                    }
+                    if Decimal::from_str(field).is_ok() {
+                        line.push_str(&format!("{field:>width$}"));
+                    } else {
+                        line.push_str(&format!("{field:<width$}"));
+                    }
+                }
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(line_ending.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), EngineError> {
+        match self {
+            RecordWriter::Csv(writer) => writer.flush()?,
+            RecordWriter::FixedWidth { writer, .. } => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    #[test]
+    fn truncation_and_rounding_differ_on_dropped_digits() {
+        assert_eq!(format_places(dec!(1.005), 2, Rounding::Truncate), "1.00");
+        assert_eq!(format_places(dec!(1.005), 2, Rounding::HalfUp), "1.01");
+        assert_eq!(format_places(dec!(-1.005), 2, Rounding::HalfUp), "-1.01");
+        assert_eq!(format_places(dec!(2), 3, Rounding::HalfUp), "2.000");
+        assert_eq!(
+            format_places(dec!(1.23456), 4, Rounding::Truncate),
+            format_decimal(dec!(1.23456))
+        );
+    }
+
+    #[test]
+    fn comma_separated_amounts_parse_without_locale() {
+        let comma = |text| parse_amount(text, DecimalSeparator::Comma, ThousandsSeparator::None);
+        let dot = |text| parse_amount(text, DecimalSeparator::Dot, ThousandsSeparator::None);
+
+        assert_eq!(comma("3,50"), Ok(dec!(3.50)));
+        assert_eq!(comma(" 12 "), Ok(dec!(12)));
+        assert!(comma("1.234,50").is_err());
+        assert!(dot("3,50").is_err());
+        assert_eq!(dot("3.50"), Ok(dec!(3.50)));
+    }
+
+    #[test]
+    fn grouped_amounts_parse_and_ambiguous_ones_are_rejected() {
+        let us = |text| parse_amount(text, DecimalSeparator::Dot, ThousandsSeparator::Comma);
+        let eu = |text| parse_amount(text, DecimalSeparator::Comma, ThousandsSeparator::Dot);
+
+        assert_eq!(us("1,234.56"), Ok(dec!(1234.56)));
+        assert_eq!(us("-12,345,678"), Ok(dec!(-12345678)));
+        assert_eq!(eu("1.234,56"), Ok(dec!(1234.56)));
+        assert_eq!(
+            us("1234,56").unwrap_err().to_string(),
+            "amount 1234,56 has a misplaced thousands separator at position 5"
+        );
+        assert_eq!(
+            us("1,23.4").unwrap_err().to_string(),
+            "amount 1,23.4 has a misplaced thousands separator at position 2"
+        );
+        assert_eq!(
+            parse_amount("1,234", DecimalSeparator::Dot, ThousandsSeparator::None)
+                .unwrap_err()
+                .to_string(),
+            "amount 1,234 is ambiguous: ',' at position 2 is neither the decimal nor the thousands separator"
+        );
+        assert!(matches!(
+            eu("1,2,3"),
+            Err(AmountError::RepeatedDecimalSeparator { position: 4, .. })
+        ));
+        assert_eq!(
+            parse_amount("1.0", DecimalSeparator::Dot, ThousandsSeparator::Dot),
+            Err(AmountError::ConflictingSeparators('.'))
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use log::error;
+use rust_decimal::Decimal;
+use std::{ops::Deref, time::SystemTime};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    client::Client,
+    engine::dispatch::balances,
+    errors::ClientTransactionError,
+    history::BalanceHistory,
+    ids::TxId,
+    money::Money,
+    numeric::Balance,
+};
+
+/// Mutable access to one client that only allows audited manual
+/// adjustments going through the accounting rules. On drop it re-checks
+/// `total == available + held + pending` and rolls the client (and any
+/// audit entries it produced) back if an adjustment left the account
+/// inconsistent. Surviving balance changes are recorded in the balance
+/// history, if the engine keeps one.
+pub struct ClientGuard<'a, B: Balance = Decimal> {
+    client: &'a mut Client<B>,
+    audit: &'a mut Vec<AuditEntry>,
+    original: Client<B>,
+    audit_len: usize,
+    history: Option<(&'a mut BalanceHistory, SystemTime)>,
+}
+
+impl<'a, B: Balance> ClientGuard<'a, B> {
+    pub(crate) fn new(client: &'a mut Client<B>, audit: &'a mut Vec<AuditEntry>) -> Self {
+        let original = client.clone();
+        let audit_len = audit.len();
+        ClientGuard {
+            client,
+            audit,
+            original,
+            audit_len,
+            history: None,
+        }
+    }
+
+    pub(crate) fn with_history(
+        mut self,
+        history: Option<&'a mut BalanceHistory>,
+        now: SystemTime,
+    ) -> Self {
+        self.history = history.map(|history| (history, now));
+        self
+    }
+
+    /// Adds funds that can never be disputed, such as a promotion.
+    pub fn credit(
+        &mut self,
+        tx_id: TxId,
+        amount: Money<B>,
+        reason: &str,
+    ) -> Result<(), ClientTransactionError> {
+        self.client.credit(amount)?;
+        self.record(AuditAction::ManualCredit, tx_id, amount, reason);
+        Ok(())
+    }
+
+    pub fn debit(
+        &mut self,
+        tx_id: TxId,
+        amount: Money<B>,
+        reason: &str,
+    ) -> Result<(), ClientTransactionError> {
+        self.client.withdraw(amount)?;
+        self.record(AuditAction::ManualDebit, tx_id, amount, reason);
+        Ok(())
+    }
+
+    fn record(&mut self, action: AuditAction, tx_id: TxId, amount: Money<B>, reason: &str) {
+        self.audit
+            .push(AuditEntry::new(action, self.client, tx_id, Some(amount)).with_reason(reason));
+    }
+}
+
+impl<B: Balance> Deref for ClientGuard<'_, B> {
+    type Target = Client<B>;
+
+    fn deref(&self) -> &Client<B> {
+        self.client
+    }
+}
+
+impl<B: Balance> Drop for ClientGuard<'_, B> {
+    fn drop(&mut self) {
+        if !self.client.balances_add_up() {
+            error!(
+                "Client {}: adjustment left total != available + held + pending, rolling back",
+                self.client.id
+            );
+            *self.client = self.original.clone();
+            self.audit.truncate(self.audit_len);
+        }
+        if let Some((history, now)) = &mut self.history
+            && balances(self.client) != balances(&self.original)
+        {
+            history.record(self.client, *now, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ClientId;
+    use rust_decimal::dec;
+
+    fn money(value: rust_decimal::Decimal) -> Money {
+        Money::new(value).unwrap()
+    }
+
+    #[test]
+    fn credit_adds_undisputable_funds_and_audits_them() {
+        let mut client = Client::new(ClientId(1));
+        let mut audit = Vec::new();
+        {
+            let mut guard = ClientGuard::new(&mut client, &mut audit);
+            guard
+                .credit(TxId(100), money(dec!(5)), "welcome bonus")
+                .unwrap();
+            assert_eq!(guard.available, dec!(5));
+        }
+
+        assert_eq!(client.total, dec!(5));
+        assert!(client.dispute(TxId(100)).is_err());
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].action, AuditAction::ManualCredit);
+        assert_eq!(audit[0].reason.as_deref(), Some("welcome bonus"));
+    }
+
+    #[test]
+    fn debit_follows_withdrawal_rules() {
+        let mut client = Client::new(ClientId(1));
+        let mut audit = Vec::new();
+        let mut guard = ClientGuard::new(&mut client, &mut audit);
+
+        assert_eq!(
+            guard.debit(TxId(1), money(dec!(1)), "fee"),
+            Err(ClientTransactionError::InsufficientAvailableFunds {
+                client_id: ClientId(1),
+                amount: dec!(1),
+                available: dec!(0),
+                held: dec!(0),
+            })
+        );
+    }
+
+    #[test]
+    fn inconsistent_client_is_rolled_back_on_drop() {
+        let mut client = Client::new(ClientId(1));
+        let mut audit = Vec::new();
+        {
+            let mut guard = ClientGuard::new(&mut client, &mut audit);
+            guard.credit(TxId(1), money(dec!(2)), "bonus").unwrap();
+            guard.client.total += dec!(1);
+        }
+
+        assert_eq!(client.available, dec!(0));
+        assert_eq!(client.total, dec!(0));
+        assert!(audit.is_empty());
+    }
+}
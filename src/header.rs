@@ -0,0 +1,121 @@
+use csv::StringRecord;
+
+use crate::errors::EngineError;
+
+pub const REQUIRED_COLUMNS: [&str; 3] = ["type", "client", "tx"];
+pub const OPTIONAL_COLUMNS: [&str; 3] = ["amount", "tenant", "reference"];
+/// Only order input: `timestamp` ahead of processing (see `sort`), and
+/// `priority` within a client under `EngineConfig::priority_window`. Not part
+/// of the headerless layout.
+pub const ORDERING_COLUMNS: [&str; 2] = ["timestamp", "priority"];
+
+fn is_known(column: &str) -> bool {
+    REQUIRED_COLUMNS
+        .iter()
+        .chain(OPTIONAL_COLUMNS.iter())
+        .chain(ORDERING_COLUMNS.iter())
+        .any(|known| *known == column)
+}
+
+pub fn default_header() -> StringRecord {
+    REQUIRED_COLUMNS
+        .iter()
+        .chain(OPTIONAL_COLUMNS.iter())
+        .collect()
+}
+
+/// Checks that every required column is present exactly once, in any order.
+/// Unrecognised columns are tolerated unless `strict` is set, but are always
+/// reported alongside missing ones since that usually means the file has no
+/// header row at all.
+pub fn validate_header(header: &StringRecord, strict: bool) -> Result<(), EngineError> {
+    let columns: Vec<&str> = header.iter().collect();
+
+    let missing: Vec<String> = REQUIRED_COLUMNS
+        .iter()
+        .filter(|required| !columns.contains(required))
+        .map(|column| column.to_string())
+        .collect();
+
+    let duplicated: Vec<String> = REQUIRED_COLUMNS
+        .iter()
+        .chain(OPTIONAL_COLUMNS.iter())
+        .chain(ORDERING_COLUMNS.iter())
+        .filter(|known| columns.iter().filter(|column| column == known).count() > 1)
+        .map(|column| column.to_string())
+        .collect();
+
+    let unexpected: Vec<String> = columns
+        .iter()
+        .filter(|column| !is_known(column))
+        .map(|column| column.to_string())
+        .collect();
+
+    if missing.is_empty() && duplicated.is_empty() && (!strict || unexpected.is_empty()) {
+        return Ok(());
+    }
+
+    Err(EngineError::InvalidHeader {
+        missing,
+        unexpected,
+        duplicated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(columns: &[&str]) -> StringRecord {
+        StringRecord::from(columns.to_vec())
+    }
+
+    #[test]
+    fn accepts_required_columns_in_any_order() {
+        assert!(validate_header(&header(&["tx", "amount", "client", "type"]), false).is_ok());
+        assert!(validate_header(&header(&["type", "client", "tx"]), true).is_ok());
+    }
+
+    #[test]
+    fn reports_missing_and_unexpected_columns_for_headerless_files() {
+        let result = validate_header(&header(&["deposit", "1", "1", "5.0"]), false);
+
+        match result {
+            Err(EngineError::InvalidHeader {
+                missing,
+                unexpected,
+                duplicated,
+            }) => {
+                assert_eq!(missing, ["type", "client", "tx"]);
+                assert_eq!(unexpected, ["deposit", "1", "1", "5.0"]);
+                assert!(duplicated.is_empty());
+            }
+            other => panic!("expected invalid header, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_duplicated_columns() {
+        let result = validate_header(
+            &header(&["type", "client", "tx", "amount", "amount"]),
+            false,
+        );
+
+        assert!(matches!(
+            result,
+            Err(EngineError::InvalidHeader { duplicated, .. }) if duplicated == ["amount"]
+        ));
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_columns() {
+        let columns = header(&["type", "client", "tx", "amount", "memo"]);
+
+        assert!(validate_header(&columns, false).is_ok());
+        assert!(matches!(
+            validate_header(&columns, true),
+            Err(EngineError::InvalidHeader { missing, unexpected, .. })
+                if missing.is_empty() && unexpected == ["memo"]
+        ));
+    }
+}
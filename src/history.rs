@@ -0,0 +1,212 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    client::Client, errors::EngineError, formatting::format_decimal, ids::ClientId,
+    numeric::Balance,
+};
+
+pub const BALANCE_AT_HEADER: [&str; 8] = [
+    "client",
+    "change",
+    "sequence",
+    "at",
+    "available",
+    "held",
+    "total",
+    "locked",
+];
+
+/// A client's balances right after one change, numbered in the order the
+/// engine made its changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalancePoint {
+    /// Number of the change in this history, counting operator adjustments
+    /// and merged histories; what `PointInTime::Seq` looks up.
+    #[serde(alias = "seq")]
+    pub change: u64,
+    /// Sequence number of the accepted transaction that made the change, as
+    /// in the audit trail. `None` for operator adjustments.
+    #[serde(default)]
+    pub sequence: Option<u64>,
+    pub at: SystemTime,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointInTime {
+    /// After the change with this number (and every earlier one); see
+    /// `BalancePoint::change`.
+    Seq(u64),
+    /// As of this moment on the engine's clock.
+    At(SystemTime),
+}
+
+/// How far back `Engine::compact` keeps balance history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retention(pub Duration);
+
+impl FromStr for Retention {
+    type Err = String;
+
+    /// `<n>d` for days or `<n>s` for seconds.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid retention {value}, expected <days>d or <seconds>s");
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (number, unit) = value.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        match unit {
+            "d" => Ok(Retention(Duration::from_secs(
+                number.checked_mul(24 * 60 * 60).ok_or_else(invalid)?,
+            ))),
+            "s" => Ok(Retention(Duration::from_secs(number))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Every balance change per client, so past balances can be looked up
+/// without replaying input. Costs one point per changing row, so it is only
+/// kept when enabled with `Engine::enable_balance_history`, and it travels
+/// with snapshots.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BalanceHistory {
+    last_seq: u64,
+    points: BTreeMap<ClientId, Vec<BalancePoint>>,
+}
+
+impl BalanceHistory {
+    /// Number of the latest change, `0` before the first one.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// `client`'s balances as of `point`, or `None` if it had no balance
+    /// change by then.
+    pub fn balance_at(&self, client: ClientId, point: PointInTime) -> Option<&BalancePoint> {
+        let points = self.points.get(&client)?;
+        let after = match point {
+            PointInTime::Seq(seq) => points.partition_point(|entry| entry.change <= seq),
+            PointInTime::At(at) => points.partition_point(|entry| entry.at <= at),
+        };
+        after.checked_sub(1).map(|index| &points[index])
+    }
+
+    /// Drops `client`'s past balances, keeping only the latest so lookups
+    /// after it still answer, without the change sequence or timestamps.
+    pub(crate) fn forget(&mut self, client: ClientId) {
+        if let Some(points) = self.points.get_mut(&client)
+            && let Some(mut last) = points.pop()
+        {
+            last.sequence = None;
+            *points = vec![last];
+        }
+    }
+
+    /// Drops all of `client`'s points, for a client that no longer exists.
+    pub(crate) fn remove(&mut self, client: ClientId) {
+        self.points.remove(&client);
+    }
+
+    /// Drops every point recorded before `cutoff` except each client's
+    /// latest one, which still holds the balances at the cutoff. Returns the
+    /// number of points dropped.
+    pub(crate) fn compact(&mut self, cutoff: SystemTime) -> usize {
+        let mut dropped = 0;
+        for points in self.points.values_mut() {
+            let before = points.partition_point(|point| point.at < cutoff);
+            let stale = before.saturating_sub(1);
+            points.drain(..stale);
+            dropped += stale;
+        }
+        dropped
+    }
+
+    /// Appends the points of `other`, a history kept by another engine,
+    /// numbered after this one's, with the transaction sequence numbers
+    /// moved up by `sequence_offset`. Points of the `skip` clients are
+    /// dropped.
+    pub(crate) fn absorb(
+        &mut self,
+        other: BalanceHistory,
+        sequence_offset: u64,
+        skip: &HashSet<ClientId>,
+    ) {
+        for (client, points) in other.points {
+            if skip.contains(&client) {
+                continue;
+            }
+            self.points
+                .entry(client)
+                .or_default()
+                .extend(points.into_iter().map(|point| BalancePoint {
+                    change: point.change + self.last_seq,
+                    sequence: point.sequence.map(|sequence| sequence + sequence_offset),
+                    ..point
+                }));
+        }
+        self.last_seq += other.last_seq;
+    }
+
+    pub(crate) fn record<B: Balance>(
+        &mut self,
+        client: &Client<B>,
+        at: SystemTime,
+        sequence: Option<u64>,
+    ) {
+        self.last_seq += 1;
+        self.points
+            .entry(client.id)
+            .or_default()
+            .push(BalancePoint {
+                change: self.last_seq,
+                sequence,
+                at,
+                available: client.available.to_decimal(),
+                held: client.held.into(),
+                total: client.total.to_decimal(),
+                locked: client.locked,
+            });
+    }
+}
+
+/// Writes one looked-up balance as CSV, with `at` in Unix seconds.
+pub fn write_balance_point<W: Write>(
+    client: ClientId,
+    point: &BalancePoint,
+    writer: W,
+) -> Result<(), EngineError> {
+    let at = point
+        .at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(BALANCE_AT_HEADER)?;
+    csv_writer.write_record([
+        client.to_string(),
+        point.change.to_string(),
+        point
+            .sequence
+            .map(|seq| seq.to_string())
+            .unwrap_or_default(),
+        at.to_string(),
+        format_decimal(point.available),
+        format_decimal(point.held),
+        format_decimal(point.total),
+        point.locked.to_string(),
+    ])?;
+    csv_writer.flush()?;
+    Ok(())
+}
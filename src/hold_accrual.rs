@@ -0,0 +1,194 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::{fmt, str::FromStr, time::SystemTime};
+
+use crate::{
+    audit::AuditAction,
+    client::Client,
+    dispute_expiry::DisputeDeadline,
+    errors::ClientTransactionError,
+    ids::TxId,
+    money::{MAX_SCALE, Money},
+    numeric::Balance,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Which way an accrual goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccrualKind {
+    /// Credited to the client, for funds held longer than agreed.
+    #[default]
+    Interest,
+    /// Debited from the client, even below zero available funds.
+    Penalty,
+}
+
+impl AccrualKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccrualKind::Interest => "interest",
+            AccrualKind::Penalty => "penalty",
+        }
+    }
+
+    pub(crate) fn audit_action(self) -> AuditAction {
+        match self {
+            AccrualKind::Interest => AuditAction::AccruedInterest,
+            AccrualKind::Penalty => AuditAction::AccruedPenalty,
+        }
+    }
+
+    pub(crate) fn apply<B: Balance>(
+        self,
+        client: &mut Client<B>,
+        amount: Money<B>,
+    ) -> Result<(), ClientTransactionError> {
+        match self {
+            AccrualKind::Interest => client.credit(amount),
+            AccrualKind::Penalty => client.debit(amount),
+        }
+    }
+}
+
+/// Resolves the dispute on `tx` and settles what it `accrued`, as one step:
+/// if the accrual fails, the resolve is rolled back with it.
+pub(crate) fn resolve_accruing<B: Balance>(
+    client: &mut Client<B>,
+    tx: TxId,
+    accrued: Option<(HoldAccrual, Money<B>)>,
+) -> Result<(), ClientTransactionError> {
+    let Some((accrual, amount)) = accrued else {
+        return client.resolve(tx);
+    };
+    let original = client.clone();
+    let result = client
+        .resolve(tx)
+        .and_then(|()| accrual.kind.apply(client, amount));
+    if result.is_err() {
+        *client = original;
+    }
+    result
+}
+
+impl fmt::Display for AccrualKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AccrualKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "interest" => Ok(AccrualKind::Interest),
+            "penalty" => Ok(AccrualKind::Penalty),
+            _ => Err(format!(
+                "invalid hold accrual {value}, expected interest or penalty"
+            )),
+        }
+    }
+}
+
+/// Interest or penalty on funds a dispute held past a grace period, settled
+/// when the dispute is resolved; see `EngineConfig::hold_accrual`. Accrual
+/// is simple: the held amount times `rate` for every day past `grace`, or
+/// for every transaction accepted past it when `grace` is a number of
+/// transactions. Chargebacks accrue nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HoldAccrual {
+    pub grace: DisputeDeadline,
+    /// Fraction of the held amount per day, or per transaction, past `grace`.
+    pub rate: Decimal,
+    pub kind: AccrualKind,
+}
+
+impl HoldAccrual {
+    /// What resolving the dispute on `tx` accrues as of `now`, `sequence`
+    /// being the last accepted sequence number, rounded to four places.
+    /// `None` when nothing accrues: the dispute is not open, was resolved
+    /// within the grace period, or its opening was not recorded.
+    pub fn accrued<B: Balance>(
+        &self,
+        client: &Client<B>,
+        tx: TxId,
+        now: SystemTime,
+        sequence: u64,
+    ) -> Option<Money<B>> {
+        let (_, held) = client.open_disputes().find(|(id, _)| *id == tx)?;
+        let periods = match self.grace {
+            DisputeDeadline::Age(grace) => {
+                let past = client.dispute_age(tx, now)?.checked_sub(grace)?;
+                Decimal::from(past.as_secs()) / Decimal::from(SECONDS_PER_DAY)
+            }
+            DisputeDeadline::Sequence(grace) => {
+                let elapsed = sequence.saturating_sub(client.dispute_sequence(tx)?);
+                Decimal::from(elapsed.checked_sub(grace)?)
+            }
+        };
+        let amount = held
+            .value()
+            .to_decimal()
+            .checked_mul(self.rate)?
+            .checked_mul(periods)?
+            .round_dp_with_strategy(MAX_SCALE, RoundingStrategy::MidpointAwayFromZero);
+        Money::try_from(amount)
+            .ok()
+            .filter(|amount| !amount.is_zero())
+    }
+
+    /// Audit reason of an accrual under this policy.
+    pub(crate) fn reason(&self) -> String {
+        let grace = match self.grace {
+            DisputeDeadline::Age(age) => format!("{}s", age.as_secs()),
+            DisputeDeadline::Sequence(units) => format!("{units} transactions"),
+        };
+        format!("{} at {} on funds held past {grace}", self.kind, self.rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ClientId;
+    use rust_decimal::dec;
+    use std::time::Duration;
+
+    #[test]
+    fn holds_accrue_per_day_or_transaction_past_the_grace_period() {
+        let opened = SystemTime::UNIX_EPOCH;
+        let mut client = Client::new(ClientId(1));
+        client
+            .deposit(TxId(1), Money::new(dec!(100)).unwrap())
+            .unwrap();
+        client.dispute_at(TxId(1), opened).unwrap();
+        client.set_dispute_sequence(TxId(1), 10);
+
+        let by_age = HoldAccrual {
+            grace: DisputeDeadline::Age(Duration::from_secs(2 * SECONDS_PER_DAY)),
+            rate: dec!(0.001),
+            kind: AccrualKind::Penalty,
+        };
+        let days = |days: u64| opened + Duration::from_secs(days * SECONDS_PER_DAY);
+        assert_eq!(by_age.accrued(&client, TxId(1), days(2), 0), None);
+        assert_eq!(
+            by_age.accrued(&client, TxId(1), days(5), 0),
+            Some(Money::new(dec!(0.3)).unwrap())
+        );
+        assert_eq!(
+            by_age.accrued(&client, TxId(1), days(2) + Duration::from_secs(3600), 0),
+            Some(Money::new(dec!(0.0042)).unwrap())
+        );
+        assert_eq!(by_age.accrued(&client, TxId(2), days(5), 0), None);
+
+        let by_sequence = HoldAccrual {
+            grace: DisputeDeadline::Sequence(3),
+            ..by_age
+        };
+        assert_eq!(by_sequence.accrued(&client, TxId(1), opened, 13), None);
+        assert_eq!(
+            by_sequence.accrued(&client, TxId(1), opened, 15),
+            Some(Money::new(dec!(0.2)).unwrap())
+        );
+    }
+}
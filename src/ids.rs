@@ -0,0 +1,94 @@
+use core::{fmt, num::ParseIntError, str::FromStr};
+use serde::{Deserialize, Serialize};
+
+/// A client (account) id, as in the `client` column. Client and transaction
+/// ids have their own types so they cannot be passed in each other's place.
+/// Both serialize as the bare number.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ClientId(pub u16);
+
+/// A transaction id, as in the `tx` column, unique across clients.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct TxId(pub u32);
+
+impl fmt::Display for ClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<u16> for ClientId {
+    fn from(id: u16) -> Self {
+        ClientId(id)
+    }
+}
+
+impl From<ClientId> for u16 {
+    fn from(id: ClientId) -> Self {
+        id.0
+    }
+}
+
+impl From<u32> for TxId {
+    fn from(id: u32) -> Self {
+        TxId(id)
+    }
+}
+
+impl From<TxId> for u32 {
+    fn from(id: TxId) -> Self {
+        id.0
+    }
+}
+
+impl From<TxId> for i64 {
+    fn from(id: TxId) -> Self {
+        id.0.into()
+    }
+}
+
+impl FromStr for ClientId {
+    type Err = ParseIntError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(ClientId)
+    }
+}
+
+impl FromStr for TxId {
+    type Err = ParseIntError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(TxId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_serialize_as_bare_numbers() {
+        let ids = (ClientId(7), TxId(42));
+        let json = serde_json::to_string(&ids).unwrap();
+        assert_eq!(json, "[7,42]");
+        assert_eq!(
+            serde_json::from_str::<(ClientId, TxId)>(&json).unwrap(),
+            ids
+        );
+        assert_eq!("7".parse(), Ok(ClientId(7)));
+        assert!("70000".parse::<ClientId>().is_err());
+    }
+}
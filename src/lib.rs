@@ -1,151 +1,127 @@
-pub mod client;
-pub mod errors;
-pub mod transaction;
-
-use client::Client;
-use errors::{ClientTransactionError, EngineError};
-use log::error;
-use rust_decimal::Decimal;
-use serde::Deserialize;
-use std::{
-    collections::HashMap,
-    io::{Read, Write},
-};
-
-use crate::transaction::TransactionType;
-
-#[derive(Deserialize)]
-struct InputTransaction {
-    #[serde(rename = "type")]
-    tx_type: TransactionType,
-    client: u16,
-    tx: i64,
-    amount: Option<Decimal>,
-}
-
-pub fn format_decimal(value: Decimal) -> String {
-    format!("{value:.4}")
-}
-
-enum ValidatedTransaction {
-    WithAmount { tx: u32, amount: Decimal },
-    NoAmount { tx: u32 },
-}
-
-fn validate_transaction(
-    tx_type: TransactionType,
-    client_id: u16,
-    tx: i64,
-    amount: Option<Decimal>,
-) -> Result<ValidatedTransaction, ClientTransactionError> {
-    if tx < 0 {
-        return Err(ClientTransactionError::InvalidTransactionId { client_id, tx });
-    }
+#![cfg_attr(not(feature = "std"), no_std)]
 
-    let tx_u32 = u32::try_from(tx)
-        .map_err(|_| ClientTransactionError::InvalidTransactionId { client_id, tx })?;
+extern crate alloc;
 
-    match tx_type {
-        TransactionType::Deposit | TransactionType::Withdrawal => match amount {
-            Some(value) if value > Decimal::ZERO => Ok(ValidatedTransaction::WithAmount {
-                tx: tx_u32,
-                amount: value,
-            }),
-            Some(value) => Err(ClientTransactionError::InvalidAmount {
-                client_id,
-                tx: tx_u32,
-                amount: value,
-            }),
-            None => Err(ClientTransactionError::MissingAmount {
-                client_id,
-                tx_type,
-                tx: tx_u32,
-            }),
-        },
-        _ => Ok(ValidatedTransaction::NoAmount { tx: tx_u32 }),
-    }
+/// Puts each item behind the `std` feature. Only the accounting core builds
+/// without it.
+macro_rules! std_only {
+    ($($item:item)*) => {
+        $(#[cfg(feature = "std")] $item)*
+    };
 }
 
-pub fn process_transactions<R: Read, W: Write>(source: R, writer: W) -> Result<(), EngineError> {
-    use transaction::TransactionType;
-    let mut reader = csv::Reader::from_reader(source);
-    let mut clients: HashMap<u16, Client> = HashMap::new();
-
-    for (row_index, result) in reader.deserialize().enumerate() {
-        let transaction: InputTransaction = match result {
-            Ok(record) => record,
-            Err(err) => {
-                error!("Error parsing CSV row {}: {}", row_index + 1, err);
-                continue;
-            }
-        };
-
-        let InputTransaction {
-            tx_type,
-            client: client_id,
-            tx,
-            amount,
-        } = transaction;
-
-        let validated = match validate_transaction(tx_type, client_id, tx, amount) {
-            Ok(value) => value,
-            Err(err) => {
-                error!("{err}");
-                continue;
-            }
-        };
-
-        let client = clients
-            .entry(client_id)
-            .or_insert_with(|| Client::new(client_id));
-        match (tx_type, validated) {
-            (TransactionType::Deposit, ValidatedTransaction::WithAmount { tx, amount }) => {
-                if let Err(e) = client.deposit(tx, amount) {
-                    error!("Error processing deposit: {e}");
-                }
-            }
-            (TransactionType::Withdrawal, ValidatedTransaction::WithAmount { tx: _, amount }) => {
-                if let Err(e) = client.withdraw(amount) {
-                    error!("Error processing withdrawal: {e}");
-                }
-            }
-            (TransactionType::Dispute, ValidatedTransaction::NoAmount { tx }) => {
-                if let Err(e) = client.dispute(tx) {
-                    error!("Partner's error processing dispute: {e}");
-                }
-            }
-            (TransactionType::Resolve, ValidatedTransaction::NoAmount { tx }) => {
-                if let Err(e) = client.resolve(tx) {
-                    error!("Partner's error processing resolve: {e}");
-                }
-            }
-            (TransactionType::Chargeback, ValidatedTransaction::NoAmount { tx }) => {
-                if let Err(e) = client.chargeback(tx) {
-                    error!("Partner's error processing chargeback: {e}");
-                }
-            }
-            (tx_type, _) => {
-                error!("Validation mismatch for client {client_id} on transaction type {tx_type}",);
-            }
-        }
+pub mod client;
+pub mod errors;
+pub mod ids;
+pub mod money;
+pub mod numeric;
+pub mod transaction;
+pub mod withdrawal_policy;
+
+std_only! {
+    pub mod account_diff;
+    pub mod admin;
+    pub mod alerts;
+    pub mod aliases;
+    pub mod amount_histogram;
+    pub mod audit;
+    pub mod backend;
+    pub mod balance_limits;
+    pub mod clock;
+    pub mod config;
+    pub mod currency;
+    pub mod custom;
+    pub mod dead_letter;
+    pub mod detect;
+    pub mod digest;
+    pub mod dispute_expiry;
+    pub mod encoding;
+    pub mod engine;
+    pub mod eviction;
+    #[cfg(feature = "fault-injection")]
+    pub mod fault;
+    #[cfg(feature = "ffi")]
+    pub mod ffi;
+    pub mod format;
+    pub mod formatting;
+    pub mod guard;
+    mod header;
+    pub mod history;
+    pub mod hold_accrual;
+    pub mod manifest;
+    pub mod memory;
+    pub mod metrics;
+    pub mod output;
+    pub mod prelink;
+    pub mod prelude;
+    pub mod preprocess;
+    #[cfg(feature = "python")]
+    mod python;
+    pub mod quality;
+    pub mod redaction;
+    pub mod registry;
+    pub mod rejection_log;
+    pub mod report;
+    pub mod review;
+    pub mod risk;
+    pub mod rules;
+    pub mod sample;
+    pub mod segments;
+    pub mod settlement;
+    pub mod snapshot;
+    pub mod sort;
+    #[cfg(feature = "sqlite")]
+    pub mod sqlite;
+    pub mod statement;
+    pub mod stress;
+    pub mod tenant;
+    pub mod testkit;
+    pub mod tx_id;
+    pub mod verify;
+    pub mod zero_amount;
+
+    pub use engine::{Engine, RowCounts, output::ACCOUNT_HEADER};
+    pub use formatting::format_decimal;
+
+    use dead_letter::RejectedTransaction;
+    use errors::EngineError;
+    use std::{
+        io::{Read, Write},
+        sync::mpsc::{self, Receiver},
+        thread::{self, JoinHandle},
+    };
+
+    pub fn process_transactions<R: Read, W: Write>(
+        source: R,
+        writer: W,
+    ) -> Result<(), EngineError> {
+        let mut engine = Engine::new();
+        engine.process(source)?;
+        engine.write_accounts(writer).map(drop)
     }
 
-    let mut csv_writer = csv::Writer::from_writer(writer);
-    csv_writer.write_record(["client", "available", "held", "total", "locked"])?;
-
-    let mut clients_sorted: Vec<&Client> = clients.values().collect();
-    clients_sorted.sort_by_key(|client| client.id);
-
-    for client in clients_sorted {
-        csv_writer.write_record(&[
-            client.id.to_string(),
-            format_decimal(client.available),
-            format_decimal(client.held),
-            format_decimal(client.total),
-            client.locked.to_string(),
-        ])?;
+    /// Like [`process_transactions`], but on a background thread, sending
+    /// every skipped or rejected row to the returned receiver as soon as it
+    /// happens. The receiver disconnects when processing ends; join the
+    /// handle for the outcome.
+    pub fn process_transactions_with_errors<R, W>(
+        source: R,
+        writer: W,
+    ) -> (
+        Receiver<RejectedTransaction>,
+        JoinHandle<Result<(), EngineError>>,
+    )
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut engine = Engine::new();
+            engine.set_rejection_sender(sender);
+            engine.process(source)?;
+            engine.write_accounts(writer).map(drop)
+        });
+        (receiver, handle)
     }
-
-    csv_writer.flush()?;
-    Ok(())
 }
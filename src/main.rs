@@ -1,24 +1,68 @@
+mod cli;
+
 use std::env;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::process::{self, ExitCode};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use rust_payments_engine::errors::EngineError;
-use rust_payments_engine::process_transactions;
+use log::warn;
 
-fn main() -> Result<(), EngineError> {
-    env_logger::init();
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        return Err(EngineError::Usage(
-            "Usage: cargo run -- <transactions.csv>".to_string(),
-        ));
+use cli::Outcome;
+use rust_payments_engine::errors::ErrorReport;
+
+/// Installs the SIGINT/SIGTERM handler, only for processing runs since the
+/// other commands never check it: the first signal asks the engine to stop
+/// after the current row, a second one exits immediately.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupt);
+    if let Err(err) = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+    }) {
+        warn!("Could not install the interrupt handler: {err}");
     }
+    interrupt
+}
 
-    let csv_file = File::open(&args[1])?;
-    let reader = BufReader::new(csv_file);
-    let stdout = std::io::stdout();
-    let handle = stdout.lock();
-    let writer = BufWriter::new(handle);
+fn main() -> ExitCode {
+    env_logger::init();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Given before the command, the flag cannot be an option's value. Later
+    // on, `Args::parse` picks it up where the command expects a flag.
+    let leading = args
+        .iter()
+        .take_while(|arg| *arg == cli::JSON_ERRORS_FLAG)
+        .count();
+    let json_errors = leading > 0;
+    args.drain(..leading);
 
-    process_transactions(reader, writer)
+    let result = match args.first().map(String::as_str) {
+        Some("admin") => cli::admin::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("compact") => cli::compact::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("detect") => cli::detect::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("diff-accounts") => cli::diff_accounts::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("balance-at") => cli::balance_at::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("merge") => cli::merge::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("repair") => cli::repair::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("report") => cli::report::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("review-queue") => cli::review_queue::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("sample") => cli::sample::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("settle") => cli::settle::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("statement") => cli::statement::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("stress") => cli::stress::run(&args[1..]).map(|()| Outcome::Clean),
+        Some("verify") => cli::verify::run(&args[1..]).map(|()| Outcome::Clean),
+        _ => cli::run::run(&args, interrupt_flag()),
+    };
+    match result {
+        Ok(outcome) => ExitCode::from(outcome.exit_code()),
+        Err(err) => {
+            match serde_json::to_string(&ErrorReport::from(&err)) {
+                Ok(json) if json_errors || cli::json_errors() => eprintln!("{json}"),
+                _ => eprintln!("Error: {err}"),
+            }
+            ExitCode::from(cli::error_exit_code(&err))
+        }
+    }
 }
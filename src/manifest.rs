@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Write},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{Engine, digest::sha256_hex, errors::EngineError, snapshot::SNAPSHOT_VERSION};
+
+/// Version of this crate, as recorded in run manifests.
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// One input file of a run, identified by content.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InputFile {
+    pub path: String,
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+impl InputFile {
+    pub fn hash(path: impl AsRef<Path>) -> Result<Self, EngineError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let bytes = file.metadata()?.len();
+        Ok(InputFile {
+            path: path.display().to_string(),
+            sha256: sha256_hex(BufReader::new(file))?,
+            bytes,
+        })
+    }
+}
+
+/// Provenance of one run, written next to its output so downstream
+/// pipelines can check what produced it without parsing logs.
+///
+/// `rejections` counts rejected rows by reason, with ids and amounts masked
+/// as in `Engine::rejection_breakdown`; it is empty unless the breakdown was
+/// enabled before processing.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RunManifest {
+    pub engine_version: &'static str,
+    pub snapshot_version: u32,
+    pub output_schema: &'static str,
+    pub inputs: Vec<InputFile>,
+    pub rows_read: usize,
+    pub rows_rejected: usize,
+    pub rejections: BTreeMap<String, usize>,
+    pub duration_ms: u128,
+    /// SHA-256 of the settings the run was configured with, so runs can be
+    /// grouped by configuration without storing it.
+    pub config_digest: String,
+}
+
+impl RunManifest {
+    pub fn new(
+        engine: &Engine,
+        inputs: Vec<InputFile>,
+        duration: Duration,
+        config_digest: String,
+    ) -> Self {
+        let rows = engine.row_counts();
+        RunManifest {
+            engine_version: ENGINE_VERSION,
+            snapshot_version: SNAPSHOT_VERSION,
+            output_schema: engine.config().output_schema.name(),
+            inputs,
+            rows_read: rows.read,
+            rows_rejected: rows.rejected,
+            rejections: engine.rejection_breakdown().cloned().unwrap_or_default(),
+            duration_ms: duration.as_millis(),
+            config_digest,
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), EngineError> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn manifest_records_counts_and_rejection_reasons() {
+        let mut engine = Engine::new();
+        engine.enable_rejection_breakdown();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,2,9.0\n",
+            ))
+            .unwrap();
+
+        let manifest = RunManifest::new(
+            &engine,
+            Vec::new(),
+            Duration::from_millis(12),
+            "abc".to_string(),
+        );
+        let mut json = Vec::new();
+        manifest.write(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["engine_version"], ENGINE_VERSION);
+        assert_eq!(json["output_schema"], "v1");
+        assert_eq!(
+            (json["rows_read"].as_u64(), json["rows_rejected"].as_u64()),
+            (Some(2), Some(1))
+        );
+        assert_eq!(json["rejections"].as_object().unwrap().len(), 1);
+        assert_eq!(json["duration_ms"], 12);
+    }
+}
@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+/// Rows between two memory checks while processing.
+pub const MEMORY_CHECK_INTERVAL: usize = 1024;
+
+/// Share of the limit, in percent, that spilling and dropping history aim for.
+pub(crate) const LOW_WATER_PERCENT: usize = 90;
+
+/// What the engine does when its approximate memory use goes over
+/// `EngineConfig::max_memory_bytes`. Spilling and dropping history both aim
+/// a little below the limit so the next check does not fire straight away.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryPolicy {
+    /// Fail the run with `EngineError::MemoryLimitExceeded`.
+    #[default]
+    Abort,
+    /// Move the least recently touched clients into the eviction store.
+    /// Needs `Engine::set_eviction`.
+    Spill,
+    /// Forget the oldest undisputed deposits (lowest tx ids first). They can
+    /// no longer be disputed; balances are unaffected.
+    DropHistory,
+}
+
+impl FromStr for MemoryPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "abort" => Ok(MemoryPolicy::Abort),
+            "spill" => Ok(MemoryPolicy::Spill),
+            "drop-history" => Ok(MemoryPolicy::DropHistory),
+            other => Err(format!("unknown memory policy {other}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Engine,
+        clock::ManualClock,
+        config::EngineConfig,
+        errors::EngineError,
+        eviction::{EvictionPolicy, MemoryStore},
+        ids::{ClientId, TxId},
+    };
+    use rust_decimal::dec;
+    use std::{io::Cursor, sync::Arc, time::Duration};
+
+    /// Ten clients with thirty deposits each, touched in id order, and the
+    /// first deposit of client 10 under dispute.
+    fn loaded(eviction: bool) -> Engine {
+        let clock = Arc::new(ManualClock::default());
+        let mut engine = Engine::new();
+        engine.set_clock(clock.clone());
+        if eviction {
+            engine.set_eviction(EvictionPolicy {
+                idle_for: Duration::MAX,
+                store: Box::new(MemoryStore::default()),
+            });
+        }
+        for client in 1..=10 {
+            let rows: String = (0..30)
+                .map(|n| format!("deposit,{client},{},1\n", client * 100 + n))
+                .collect();
+            engine
+                .process(Cursor::new(format!("type,client,tx,amount\n{rows}")))
+                .unwrap();
+            clock.advance(Duration::from_secs(1));
+        }
+        engine
+            .process(Cursor::new("type,client,tx,amount\ndispute,10,1000,\n"))
+            .unwrap();
+        engine
+    }
+
+    /// Caps `engine` at `percent` of what it uses now, returning the cap.
+    fn limit(engine: &mut Engine, percent: usize, memory_policy: MemoryPolicy) -> usize {
+        let limit = engine.approximate_memory() / 100 * percent;
+        engine.set_config(EngineConfig {
+            max_memory_bytes: Some(limit),
+            memory_policy,
+            ..Default::default()
+        });
+        limit
+    }
+
+    #[test]
+    fn engines_within_the_limit_are_left_alone() {
+        let mut engine = loaded(false);
+        limit(&mut engine, 200, MemoryPolicy::Abort);
+        engine.enforce_memory_limit().unwrap();
+        assert_eq!(engine.resident_clients(), 10);
+    }
+
+    #[test]
+    fn abort_fails_without_changing_the_engine() {
+        let mut engine = loaded(false);
+        let used = engine.approximate_memory();
+        let limit = limit(&mut engine, 50, MemoryPolicy::Abort);
+
+        match engine.enforce_memory_limit() {
+            Err(EngineError::MemoryLimitExceeded {
+                used: reported,
+                limit: reported_limit,
+            }) => assert_eq!((reported, reported_limit), (used, limit)),
+            other => panic!("expected the memory limit error, got {other:?}"),
+        }
+        assert_eq!(engine.approximate_memory(), used);
+        assert_eq!(engine.client(ClientId(1)).unwrap().deposits().count(), 30);
+    }
+
+    #[test]
+    fn spill_moves_the_least_recently_used_clients_to_the_store() {
+        let mut engine = loaded(true);
+        let limit = limit(&mut engine, 50, MemoryPolicy::Spill);
+
+        engine.enforce_memory_limit().unwrap();
+
+        assert!(engine.approximate_memory() <= limit);
+        assert!(engine.client(ClientId(1)).is_none());
+        assert!(engine.client(ClientId(10)).is_some());
+        assert_eq!(engine.write_accounts(Vec::new()).unwrap(), 10);
+    }
+
+    #[test]
+    fn spill_needs_an_eviction_store() {
+        let mut engine = loaded(false);
+        limit(&mut engine, 50, MemoryPolicy::Spill);
+        assert!(matches!(
+            engine.enforce_memory_limit(),
+            Err(EngineError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn drop_history_forgets_the_oldest_undisputed_deposits() {
+        let mut engine = loaded(false);
+        let limit = limit(&mut engine, 70, MemoryPolicy::DropHistory);
+
+        engine.enforce_memory_limit().unwrap();
+
+        assert!(engine.approximate_memory() <= limit);
+        let oldest = engine.client(ClientId(1)).unwrap();
+        assert_eq!(oldest.deposits().count(), 0);
+        assert_eq!(oldest.total, dec!(30));
+        let newest = engine.client(ClientId(10)).unwrap();
+        assert!(newest.deposits().any(|(tx, _)| tx == TxId(1000)));
+        assert_eq!(newest.held, dec!(1));
+    }
+}
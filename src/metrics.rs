@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::transaction::TransactionType;
+
+/// Rows per batch whose processing time is reported to [`Metrics::batch`].
+pub const METRICS_BATCH_ROWS: usize = 10_000;
+
+/// Receives per-type throughput figures while rows are processed, for
+/// export to a monitoring system. Register with `Engine::set_metrics`.
+///
+/// Only rows that parse far enough to have a type are counted: a row that
+/// is not valid CSV reaches neither `applied` nor `rejected`.
+pub trait Metrics: Send + Sync {
+    fn applied(&self, tx_type: &TransactionType);
+    fn rejected(&self, tx_type: &TransactionType);
+    /// Time spent applying the `rows` rows of `tx_type` in the last batch of
+    /// [`METRICS_BATCH_ROWS`] rows, or in the shorter batch that ends a
+    /// `process` call. Types absent from a batch are not reported.
+    fn batch(&self, tx_type: &TransactionType, rows: usize, elapsed: Duration);
+}
+
+/// Upper bounds of the [`DurationHistogram`] buckets, in milliseconds.
+pub const BATCH_BUCKETS_MS: [u64; 6] = [1, 5, 25, 100, 500, 2_500];
+
+/// Batch durations counted into [`BATCH_BUCKETS_MS`], with a last bucket
+/// for anything slower.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DurationHistogram {
+    pub buckets: [u64; BATCH_BUCKETS_MS.len() + 1],
+    pub total_ms: u128,
+}
+
+impl DurationHistogram {
+    pub fn record(&mut self, elapsed: Duration) {
+        let millis = elapsed.as_millis();
+        let bucket = BATCH_BUCKETS_MS
+            .iter()
+            .position(|&bound| millis <= u128::from(bound))
+            .unwrap_or(BATCH_BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+        self.total_ms += millis;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// What [`TypeMetrics`] has gathered for one transaction type.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TypeStats {
+    pub applied: u64,
+    pub rejected: u64,
+    pub rows_timed: u64,
+    pub batch_durations: DurationHistogram,
+}
+
+/// A [`Metrics`] implementation that keeps the figures in memory, keyed by
+/// type name, for the CLI's `--metrics` file or for polling from another
+/// thread.
+#[derive(Debug, Default)]
+pub struct TypeMetrics {
+    types: Mutex<BTreeMap<String, TypeStats>>,
+}
+
+impl TypeMetrics {
+    pub fn new() -> Self {
+        TypeMetrics::default()
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, TypeStats> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, TypeStats>> {
+        // A panicking sink cannot leave counters half-updated.
+        self.types
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, tx_type: &TransactionType, update: impl FnOnce(&mut TypeStats)) {
+        let mut types = self.lock();
+        match types.get_mut(tx_type.as_str()) {
+            Some(stats) => update(stats),
+            None => update(types.entry(tx_type.as_str().to_string()).or_default()),
+        }
+    }
+}
+
+impl Metrics for TypeMetrics {
+    fn applied(&self, tx_type: &TransactionType) {
+        self.update(tx_type, |stats| stats.applied += 1);
+    }
+
+    fn rejected(&self, tx_type: &TransactionType) {
+        self.update(tx_type, |stats| stats.rejected += 1);
+    }
+
+    fn batch(&self, tx_type: &TransactionType, rows: usize, elapsed: Duration) {
+        self.update(tx_type, |stats| {
+            stats.rows_timed += rows as u64;
+            stats.batch_durations.record(elapsed);
+        });
+    }
+}
+
+/// Times rows per type and hands each finished batch to the sink.
+pub(crate) struct MetricsRecorder {
+    sink: Arc<dyn Metrics>,
+    batch: HashMap<TransactionType, (usize, Duration)>,
+    batch_rows: usize,
+}
+
+impl MetricsRecorder {
+    pub(crate) fn new(sink: Arc<dyn Metrics>) -> Self {
+        MetricsRecorder {
+            sink,
+            batch: HashMap::new(),
+            batch_rows: 0,
+        }
+    }
+
+    pub(crate) fn record(&mut self, tx_type: TransactionType, rejected: bool, started: Instant) {
+        let elapsed = started.elapsed();
+        if rejected {
+            self.sink.rejected(&tx_type);
+        } else {
+            self.sink.applied(&tx_type);
+        }
+        let (rows, total) = self.batch.entry(tx_type).or_default();
+        *rows += 1;
+        *total += elapsed;
+        self.batch_rows += 1;
+        if self.batch_rows == METRICS_BATCH_ROWS {
+            self.flush();
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        for (tx_type, (rows, elapsed)) in self.batch.drain() {
+            self.sink.batch(&tx_type, rows, elapsed);
+        }
+        self.batch_rows = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_by_upper_bound() {
+        let mut histogram = DurationHistogram::default();
+        histogram.record(Duration::from_micros(300));
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_millis(6));
+        histogram.record(Duration::from_secs(10));
+        assert_eq!(histogram.buckets, [1, 1, 1, 0, 0, 0, 1]);
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.total_ms, 10_011);
+    }
+
+    #[test]
+    fn engine_reports_rows_per_type() {
+        let metrics = Arc::new(TypeMetrics::new());
+        let mut engine = crate::Engine::new();
+        engine.set_metrics(metrics.clone());
+        engine
+            .process(
+                "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,1.0\nwithdrawal,1,3,9.0\ndispute,1,1,\nbogus\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+
+        let stats = metrics.stats();
+        let counts: Vec<_> = stats
+            .iter()
+            .map(|(name, stats)| {
+                (
+                    name.as_str(),
+                    stats.applied,
+                    stats.rejected,
+                    stats.rows_timed,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("deposit", 2, 0, 2),
+                ("dispute", 1, 0, 1),
+                ("withdrawal", 0, 1, 1)
+            ]
+        );
+        assert!(
+            stats
+                .values()
+                .all(|stats| stats.batch_durations.count() == 1)
+        );
+    }
+}
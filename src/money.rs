@@ -0,0 +1,136 @@
+use core::fmt;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::MoneyError;
+use crate::numeric::{Balance, Numeric};
+
+pub const MAX_SCALE: u32 = 4;
+pub const MAX_MAGNITUDE: Decimal = dec!(1_000_000_000_000_000);
+
+/// A non-negative amount with at most four decimal places, held in the
+/// [`Balance`] type `B`. Every way of obtaining a `Money` goes through
+/// validation, so balances built from it can never silently pick up a
+/// negative or over-precise value.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "Decimal", into = "Decimal", bound = "B: Balance")]
+pub struct Money<B = Decimal>(B);
+
+impl<B: Balance> Money<B> {
+    pub const ZERO: Self = Money(B::ZERO);
+
+    pub fn new(value: B) -> Result<Self, MoneyError> {
+        if value.is_negative() {
+            return Err(MoneyError::Negative(value.to_decimal()));
+        }
+        if !value.within_scale() {
+            return Err(MoneyError::ScaleTooLarge(value.to_decimal()));
+        }
+        if value > B::MAX_MONEY {
+            return Err(MoneyError::TooLarge(value.to_decimal()));
+        }
+        Ok(Money(value))
+    }
+
+    pub fn value(self) -> B {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == B::ZERO
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, MoneyError> {
+        let sum = Numeric::checked_add(self.0, other.0)
+            .ok_or_else(|| MoneyError::TooLarge(self.0.to_decimal() + other.0.to_decimal()))?;
+        Money::new(sum)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, MoneyError> {
+        Money::new(self.0 - other.0)
+    }
+
+    /// The same amount as a `Decimal` `Money`, which every `Money` fits.
+    pub fn to_decimal_money(self) -> Money {
+        Money(self.0.to_decimal())
+    }
+}
+
+/// Fails as `Money::<Decimal>::new` would, or with `MoneyError::TooLarge`
+/// when `B` cannot hold the amount.
+impl<B: Balance> TryFrom<Decimal> for Money<B> {
+    type Error = MoneyError;
+
+    fn try_from(value: Decimal) -> Result<Self, Self::Error> {
+        Money::new(value)?;
+        B::from_decimal(value)
+            .ok_or(MoneyError::TooLarge(value))
+            .and_then(Money::new)
+    }
+}
+
+impl<B: Balance> From<Money<B>> for Decimal {
+    fn from(value: Money<B>) -> Self {
+        value.0.to_decimal()
+    }
+}
+
+impl<B: Balance> PartialEq<Decimal> for Money<B> {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.0.to_decimal() == *other
+    }
+}
+
+impl<B: Balance> fmt::Display for Money<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_non_negative_amounts_with_four_decimal_places() {
+        assert_eq!(Money::new(dec!(1.2345)).unwrap(), dec!(1.2345));
+        assert_eq!(Money::new(dec!(2.50000)).unwrap(), dec!(2.5));
+        assert!(Money::new(dec!(0)).unwrap().is_zero());
+    }
+
+    #[test]
+    fn rejects_negative_amounts() {
+        assert_eq!(
+            Money::new(dec!(-0.01)),
+            Err(MoneyError::Negative(dec!(-0.01)))
+        );
+    }
+
+    #[test]
+    fn rejects_amounts_with_too_many_decimal_places() {
+        assert_eq!(
+            Money::new(dec!(4.00309)),
+            Err(MoneyError::ScaleTooLarge(dec!(4.00309)))
+        );
+    }
+
+    #[test]
+    fn rejects_amounts_above_max_magnitude() {
+        let value = MAX_MAGNITUDE + dec!(1);
+        assert_eq!(Money::new(value), Err(MoneyError::TooLarge(value)));
+    }
+
+    #[test]
+    fn subtraction_below_zero_is_an_error() {
+        let small = Money::new(dec!(1)).unwrap();
+        let large = Money::new(dec!(3)).unwrap();
+
+        assert_eq!(large.checked_sub(small), Ok(Money::new(dec!(2)).unwrap()));
+        assert_eq!(
+            small.checked_sub(large),
+            Err(MoneyError::Negative(dec!(-2)))
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use alloc::{format, string::String};
+use core::{
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::money::{MAX_MAGNITUDE, MAX_SCALE};
+
+/// Minor units per whole unit: amounts carry exactly four decimal places.
+pub const MINOR_PER_UNIT: i64 = 10_000;
+
+/// Arithmetic the balance code needs from an amount type. Implemented for
+/// `Decimal` and for the cheaper [`MinorUnits`], so hot loops can be written
+/// once and run on either.
+pub trait Numeric: Copy + Ord + Default + fmt::Debug + fmt::Display {
+    const ZERO: Self;
+
+    /// `None` when `value` has more than four decimal places or is out of range.
+    fn from_decimal(value: Decimal) -> Option<Self>;
+    fn to_decimal(self) -> Decimal;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+
+    fn is_negative(self) -> bool {
+        self < Self::ZERO
+    }
+}
+
+impl Numeric for Decimal {
+    const ZERO: Self = Decimal::ZERO;
+
+    fn from_decimal(value: Decimal) -> Option<Self> {
+        (value.normalize().scale() <= MAX_SCALE).then_some(value)
+    }
+
+    fn to_decimal(self) -> Decimal {
+        self
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Decimal::checked_sub(self, other)
+    }
+}
+
+/// What a [`crate::client::Client`] keeps its balances in, chosen by the
+/// embedder: `Decimal`, which the engine uses, `i128` minor units (exact
+/// integer arithmetic with room to spare), or the cheaper, checked
+/// [`MinorUnits`]. Deposits, withdrawals and disputes work the same on each.
+///
+/// `+` and `-` panic on overflow, as they do for `Decimal`. `Client` only
+/// uses the checked operations, so a balance `B` cannot hold fails the
+/// transaction with `ClientTransactionError::Arithmetic` instead.
+pub trait Balance:
+    Numeric
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + AddAssign
+    + SubAssign
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    /// The largest [`crate::money::Money`] of this type: [`MAX_MAGNITUDE`],
+    /// or the largest value the type holds if that is less.
+    const MAX_MONEY: Self;
+
+    /// Whether the value has at most [`MAX_SCALE`] decimal places, as
+    /// fixed-point types always do.
+    fn within_scale(self) -> bool {
+        true
+    }
+}
+
+impl Balance for Decimal {
+    const MAX_MONEY: Self = MAX_MAGNITUDE;
+
+    fn within_scale(self) -> bool {
+        self.normalize().scale() <= MAX_SCALE
+    }
+}
+
+/// An `i128` balance is a count of minor units, 1/10000ths, so `Display`
+/// shows minor units too.
+impl Numeric for i128 {
+    const ZERO: Self = 0;
+
+    fn from_decimal(value: Decimal) -> Option<Self> {
+        if value.normalize().scale() > MAX_SCALE {
+            return None;
+        }
+        value.checked_mul(Decimal::from(MINOR_PER_UNIT))?.to_i128()
+    }
+
+    /// Saturates beyond `Decimal`'s range, which no `Money` reaches.
+    fn to_decimal(self) -> Decimal {
+        Decimal::try_from_i128_with_scale(self, MAX_SCALE).unwrap_or(if self < 0 {
+            Decimal::MIN
+        } else {
+            Decimal::MAX
+        })
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        i128::checked_add(self, other)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        i128::checked_sub(self, other)
+    }
+}
+
+impl Balance for i128 {
+    const MAX_MONEY: Self = 1_000_000_000_000_000 * MINOR_PER_UNIT as i128;
+}
+
+/// A signed amount stored as an `i64` count of 1/10000ths. Addition and
+/// subtraction are plain integer operations, and parsing reads the digits
+/// directly instead of building a `Decimal` first. The range is about
+/// ±922 trillion units, less than [`crate::money::MAX_MAGNITUDE`], so a
+/// `Money<MinorUnits>` is capped lower and a balance can overflow well
+/// before a `Decimal` one would.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "Decimal", into = "Decimal")]
+pub struct MinorUnits(i64);
+
+impl MinorUnits {
+    pub const fn from_minor(minor: i64) -> Self {
+        MinorUnits(minor)
+    }
+
+    pub const fn minor(self) -> i64 {
+        self.0
+    }
+}
+
+impl Numeric for MinorUnits {
+    const ZERO: Self = MinorUnits(0);
+
+    fn from_decimal(value: Decimal) -> Option<Self> {
+        if value.normalize().scale() > MAX_SCALE {
+            return None;
+        }
+        value
+            .checked_mul(Decimal::from(MINOR_PER_UNIT))?
+            .to_i64()
+            .map(MinorUnits)
+    }
+
+    fn to_decimal(self) -> Decimal {
+        Decimal::new(self.0, MAX_SCALE)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(MinorUnits)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(MinorUnits)
+    }
+}
+
+impl Balance for MinorUnits {
+    /// The whole `i64` range, about 922 trillion units, since that is below
+    /// [`MAX_MAGNITUDE`].
+    const MAX_MONEY: Self = MinorUnits(i64::MAX);
+}
+
+impl Add for MinorUnits {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Numeric::checked_add(self, other).expect("MinorUnits overflow")
+    }
+}
+
+impl Sub for MinorUnits {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Numeric::checked_sub(self, other).expect("MinorUnits overflow")
+    }
+}
+
+impl AddAssign for MinorUnits {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for MinorUnits {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl TryFrom<Decimal> for MinorUnits {
+    type Error = String;
+
+    fn try_from(value: Decimal) -> Result<Self, Self::Error> {
+        MinorUnits::from_decimal(value).ok_or_else(|| format!("{value} does not fit MinorUnits"))
+    }
+}
+
+impl From<MinorUnits> for Decimal {
+    fn from(value: MinorUnits) -> Self {
+        value.to_decimal()
+    }
+}
+
+impl FromStr for MinorUnits {
+    type Err = String;
+
+    /// Accepts the same plain amounts as the CSV reader: an optional sign,
+    /// digits, and at most four digits after a `.`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid amount {text:?}");
+        let (negative, digits) = match text.as_bytes() {
+            [b'-', rest @ ..] => (true, rest),
+            [b'+', rest @ ..] => (false, rest),
+            rest => (false, rest),
+        };
+        let (whole, fraction) = match digits.iter().position(|byte| *byte == b'.') {
+            Some(dot) => (&digits[..dot], &digits[dot + 1..]),
+            None => (digits, &digits[digits.len()..]),
+        };
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        if fraction.len() > MAX_SCALE as usize {
+            return Err(format!("{text:?} has more than {MAX_SCALE} decimal places"));
+        }
+        let mut minor: i64 = 0;
+        for &byte in whole.iter().chain(fraction) {
+            if !byte.is_ascii_digit() {
+                return Err(invalid());
+            }
+            minor = minor
+                .checked_mul(10)
+                .and_then(|minor| minor.checked_add(i64::from(byte - b'0')))
+                .ok_or_else(invalid)?;
+        }
+        minor = minor
+            .checked_mul(10_i64.pow(MAX_SCALE - fraction.len() as u32))
+            .ok_or_else(invalid)?;
+        Ok(MinorUnits(if negative { -minor } else { minor }))
+    }
+}
+
+impl fmt::Display for MinorUnits {
+    /// Always four decimal places, like the account output.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.unsigned_abs();
+        let per_unit = MINOR_PER_UNIT as u64;
+        write!(
+            f,
+            "{sign}{}.{:04}",
+            magnitude / per_unit,
+            magnitude % per_unit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    #[test]
+    fn parses_and_formats_four_decimal_places() {
+        let parse = |text: &str| text.parse::<MinorUnits>();
+
+        assert_eq!(parse("1.5"), Ok(MinorUnits::from_minor(15_000)));
+        assert_eq!(parse("-0.0001"), Ok(MinorUnits::from_minor(-1)));
+        assert_eq!(parse("42"), Ok(MinorUnits::from_minor(420_000)));
+        assert_eq!(parse(".25"), Ok(MinorUnits::from_minor(2_500)));
+        assert!(parse("1.00001").is_err());
+        assert!(parse("1,5").is_err());
+        assert!(parse("-").is_err());
+        assert_eq!(parse("-12.3").unwrap().to_string(), "-12.3000");
+        assert_eq!(MinorUnits::from_minor(-1).to_string(), "-0.0001");
+    }
+
+    #[test]
+    fn both_backends_agree() {
+        fn settle<N: Numeric>(amounts: &[Decimal]) -> Option<Decimal> {
+            let mut balance = N::ZERO;
+            for (index, amount) in amounts.iter().enumerate() {
+                let amount = N::from_decimal(*amount)?;
+                balance = if index % 3 == 2 {
+                    balance.checked_sub(amount)?
+                } else {
+                    balance.checked_add(amount)?
+                };
+            }
+            Some(balance.to_decimal())
+        }
+
+        let amounts = [dec!(10.5), dec!(0.0001), dec!(20.1234), dec!(3), dec!(0.7)];
+        assert_eq!(settle::<MinorUnits>(&amounts), settle::<Decimal>(&amounts));
+        assert_eq!(settle::<MinorUnits>(&amounts), Some(dec!(-5.9233)));
+        assert_eq!(settle::<MinorUnits>(&[dec!(0.00001)]), None);
+        assert_eq!(
+            MinorUnits::from_minor(i64::MAX).checked_add(MinorUnits::from_minor(1)),
+            None
+        );
+    }
+}
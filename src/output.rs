@@ -0,0 +1,468 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    ACCOUNT_HEADER, client::Client, digest::to_hex, errors::EngineError,
+    formatting::FormattingOptions, ids::ClientId, numeric::Balance,
+};
+
+/// Columns appended to [`ACCOUNT_HEADER`] by [`OutputSchema::V2`].
+pub const V2_COLUMNS: [&str; 4] = [
+    "open_disputes",
+    "lifetime_deposits",
+    "lifetime_withdrawals",
+    "chargeback_count",
+];
+
+/// Version of the CSV account output. New columns only ever arrive with a new
+/// version, so parsers written against one version keep working.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputSchema {
+    /// `client,available,held,total,locked`.
+    #[default]
+    V1,
+    /// V1 followed by [`V2_COLUMNS`].
+    V2,
+}
+
+impl FromStr for OutputSchema {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "v1" => Ok(OutputSchema::V1),
+            "v2" => Ok(OutputSchema::V2),
+            other => Err(format!("unknown output schema {other}")),
+        }
+    }
+}
+
+impl OutputSchema {
+    /// The name `FromStr` accepts for this version.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputSchema::V1 => "v1",
+            OutputSchema::V2 => "v2",
+        }
+    }
+
+    pub fn header(self) -> Vec<&'static str> {
+        match self {
+            OutputSchema::V1 => ACCOUNT_HEADER.to_vec(),
+            OutputSchema::V2 => ACCOUNT_HEADER.iter().chain(&V2_COLUMNS).copied().collect(),
+        }
+    }
+}
+
+/// One row of the accounts output as values, so `1.5` and `1.5000` compare
+/// equal. Embedders that keep accounts outside an `Engine` write them with
+/// [`write_summaries`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct AccountSummary {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+impl AccountSummary {
+    /// `total` is derived as `available + held`.
+    pub fn new(client: ClientId, available: Decimal, held: Decimal, locked: bool) -> Self {
+        AccountSummary {
+            client,
+            available,
+            held,
+            total: available + held,
+            locked,
+        }
+    }
+}
+
+impl<B: Balance> From<&Client<B>> for AccountSummary {
+    fn from(client: &Client<B>) -> Self {
+        AccountSummary {
+            client: client.id,
+            available: client.available.to_decimal(),
+            held: client.held.into(),
+            total: client.total.to_decimal(),
+            locked: client.locked,
+        }
+    }
+}
+
+/// The order of `Engine::write_accounts`, for [`write_summaries`].
+pub fn by_client(a: &AccountSummary, b: &AccountSummary) -> Ordering {
+    a.client.cmp(&b.client)
+}
+
+/// Writes `accounts` as [`ACCOUNT_HEADER`] CSV, sorted by `order` (stably,
+/// so equal accounts keep their order), with the same formatting the engine
+/// applies under `options`.
+pub fn write_summaries<'a, W: Write>(
+    accounts: impl IntoIterator<Item = &'a AccountSummary>,
+    writer: W,
+    options: &FormattingOptions,
+    mut order: impl FnMut(&AccountSummary, &AccountSummary) -> Ordering,
+) -> Result<(), EngineError> {
+    let mut accounts: Vec<&AccountSummary> = accounts.into_iter().collect();
+    accounts.sort_by(|a, b| order(a, b));
+
+    if options.has_default_output() {
+        let mut account_writer = AccountWriter::new(writer);
+        account_writer.write_header()?;
+        for account in accounts {
+            account_writer.write_summary(account)?;
+        }
+        return account_writer.flush();
+    }
+    let mut record_writer = options.record_writer(writer);
+    record_writer.write_header(&ACCOUNT_HEADER)?;
+    for account in accounts {
+        record_writer.write_record(vec![
+            account.client.to_string(),
+            options.format(account.available),
+            options.format(account.held),
+            options.format(account.total),
+            account.locked.to_string(),
+        ])?;
+    }
+    record_writer.flush()
+}
+
+/// Where an [`OutputChecksum`] goes, for transfer partners that verify
+/// files on arrival.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailerMode {
+    /// A last `# sha256=<hex> rows=<n>` line in the output itself.
+    Comment,
+    /// A `<output>.sha256` file next to the output.
+    Sidecar,
+}
+
+impl FromStr for TrailerMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "comment" => Ok(TrailerMode::Comment),
+            "sidecar" => Ok(TrailerMode::Sidecar),
+            other => Err(format!(
+                "unknown output trailer {other}, expected comment or sidecar"
+            )),
+        }
+    }
+}
+
+/// SHA-256 of an output body and the number of account rows in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputChecksum {
+    pub sha256: String,
+    pub rows: u64,
+}
+
+/// `sha256=<hex> rows=<n>`.
+impl fmt::Display for OutputChecksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256={} rows={}", self.sha256, self.rows)
+    }
+}
+
+/// Passes output through unchanged while hashing it.
+pub struct ChecksumWriter<W: Write> {
+    writer: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(writer: W) -> Self {
+        ChecksumWriter {
+            writer,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The underlying writer, to append a trailer to, and the checksum of
+    /// everything written so far. `rows` is the number of accounts written,
+    /// as returned by `Engine::write_accounts`; lines are not counted, since
+    /// a quoted field may span several.
+    pub fn finish(self, rows: u64) -> (W, OutputChecksum) {
+        let checksum = OutputChecksum {
+            sha256: to_hex(&self.hasher.finalize()),
+            rows,
+        };
+        (self.writer, checksum)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+const FLUSH_THRESHOLD: usize = 64 * 1024;
+const DECIMAL_PLACES: u32 = 4;
+
+/// CSV account writer for large runs. Records are formatted straight into a
+/// reusable byte buffer instead of allocating a `String` per field, and the
+/// buffer is handed to the underlying writer in large chunks. The output is
+/// byte-for-byte what `csv::Writer` produces for [`ACCOUNT_HEADER`] rows.
+pub struct AccountWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    integers: itoa::Buffer,
+}
+
+impl<W: Write> AccountWriter<W> {
+    pub fn new(writer: W) -> Self {
+        AccountWriter {
+            writer,
+            buffer: Vec::with_capacity(FLUSH_THRESHOLD + 256),
+            integers: itoa::Buffer::new(),
+        }
+    }
+
+    pub fn write_header(&mut self) -> Result<(), EngineError> {
+        self.buffer
+            .extend_from_slice(ACCOUNT_HEADER.join(",").as_bytes());
+        self.buffer.push(b'\n');
+        self.flush_if_full()
+    }
+
+    pub fn write_account<B: Balance>(&mut self, client: &Client<B>) -> Result<(), EngineError> {
+        self.write_summary(&AccountSummary::from(client))
+    }
+
+    pub fn write_summary(&mut self, account: &AccountSummary) -> Result<(), EngineError> {
+        self.buffer
+            .extend_from_slice(self.integers.format(account.client.0).as_bytes());
+        self.buffer.push(b',');
+        self.push_decimal(account.available);
+        self.buffer.push(b',');
+        self.push_decimal(account.held);
+        self.buffer.push(b',');
+        self.push_decimal(account.total);
+        self.buffer.push(b',');
+        self.buffer
+            .extend_from_slice(if account.locked { b"true" } else { b"false" });
+        self.buffer.push(b'\n');
+        self.flush_if_full()
+    }
+
+    pub fn flush(&mut self) -> Result<(), EngineError> {
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn flush_if_full(&mut self) -> Result<(), EngineError> {
+        if self.buffer.len() >= FLUSH_THRESHOLD {
+            self.writer.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Same digits as `format_decimal`: truncated (not rounded) to four
+    /// places, with the sign kept even on negative zero.
+    fn push_decimal(&mut self, value: Decimal) {
+        if value.is_sign_negative() {
+            self.buffer.push(b'-');
+        }
+        let mut mantissa = value.mantissa().unsigned_abs();
+        let mut scale = value.scale();
+        if scale > DECIMAL_PLACES {
+            mantissa /= 10u128.pow(scale - DECIMAL_PLACES);
+            scale = DECIMAL_PLACES;
+        }
+        // Realistic balances fit in a u64, where division is far cheaper.
+        let fraction = match u64::try_from(mantissa) {
+            Ok(mantissa) => {
+                let divisor = 10u64.pow(scale);
+                self.buffer
+                    .extend_from_slice(self.integers.format(mantissa / divisor).as_bytes());
+                mantissa % divisor
+            }
+            Err(_) => {
+                let divisor = 10u128.pow(scale);
+                self.buffer
+                    .extend_from_slice(self.integers.format(mantissa / divisor).as_bytes());
+                (mantissa % divisor) as u64
+            }
+        } * 10u64.pow(DECIMAL_PLACES - scale);
+        self.buffer.push(b'.');
+        let mut digits = [b'0'; DECIMAL_PLACES as usize];
+        let mut remaining = fraction;
+        for digit in digits.iter_mut().rev() {
+            *digit = b'0' + (remaining % 10) as u8;
+            remaining /= 10;
+        }
+        self.buffer.extend_from_slice(&digits);
+    }
+}
+
+impl<W: Write> Drop for AccountWriter<W> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            let _ = self.writer.write_all(&self.buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use crate::{engine::output::account_record, formatting::format_decimal, money::Money};
+    use rust_decimal::dec;
+
+    fn render(value: Decimal) -> String {
+        let mut output = Vec::new();
+        let mut writer = AccountWriter::new(&mut output);
+        writer.push_decimal(value);
+        writer.flush().unwrap();
+        drop(writer);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn checksums_cover_the_body_and_count_accounts_not_lines() {
+        let mut engine = crate::Engine::with_config(crate::config::EngineConfig {
+            client_aliases: Some(std::sync::Arc::new(
+                crate::aliases::ClientAliases::load(
+                    "external_id,client\n\"two\nlines\",1\n".as_bytes(),
+                )
+                .unwrap(),
+            )),
+            output_external_ids: true,
+            ..Default::default()
+        });
+        engine
+            .process("type,client,tx,amount\ndeposit,1,1,1\ndeposit,2,2,1\n".as_bytes())
+            .unwrap();
+        let mut writer = ChecksumWriter::new(Vec::new());
+        let rows = engine.write_accounts(&mut writer).unwrap();
+        let (body, checksum) = writer.finish(rows);
+
+        assert_eq!(body.iter().filter(|&&byte| byte == b'\n').count(), 4);
+        assert_eq!(checksum.rows, 2);
+        assert_eq!(
+            checksum.sha256,
+            crate::digest::sha256_hex(body.as_slice()).unwrap()
+        );
+        assert_eq!(
+            checksum.to_string(),
+            format!("sha256={} rows=2", checksum.sha256)
+        );
+    }
+
+    #[test]
+    fn decimals_match_format_decimal() {
+        for value in [
+            dec!(0),
+            dec!(-0.0000),
+            dec!(1.5),
+            dec!(-1.5),
+            dec!(0.0001),
+            dec!(12345678901234.9999),
+            dec!(-0.00009),
+            dec!(2.99999999),
+            dec!(100),
+        ] {
+            assert_eq!(render(value), format_decimal(value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn records_match_csv_writer_output() {
+        let mut client = Client::new(ClientId(7));
+        client
+            .deposit(TxId(1), Money::new(dec!(10.25)).unwrap())
+            .unwrap();
+        client
+            .deposit(TxId(2), Money::new(dec!(3)).unwrap())
+            .unwrap();
+        client.dispute(TxId(2)).unwrap();
+
+        let mut expected = csv::Writer::from_writer(Vec::new());
+        expected.write_record(ACCOUNT_HEADER).unwrap();
+        expected
+            .write_record(account_record(&client, &Default::default()))
+            .unwrap();
+        let expected = expected.into_inner().unwrap();
+
+        let mut output = Vec::new();
+        let mut writer = AccountWriter::new(&mut output);
+        writer.write_header().unwrap();
+        writer.write_account(&client).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn summaries_are_written_as_the_engine_writes_accounts() {
+        let mut engine = crate::Engine::new();
+        engine
+            .process(
+                "type,client,tx,amount
+deposit,2,1,1.5
+deposit,1,2,7
+"
+                .as_bytes(),
+            )
+            .unwrap();
+        let summaries = [
+            AccountSummary::new(ClientId(2), dec!(1.5), dec!(0), false),
+            AccountSummary::new(ClientId(1), dec!(7), dec!(0), false),
+        ];
+
+        for options in [
+            FormattingOptions::default(),
+            FormattingOptions {
+                places: 2,
+                ..FormattingOptions::default()
+            },
+        ] {
+            engine.set_config(crate::config::EngineConfig {
+                formatting: options.clone(),
+                ..Default::default()
+            });
+            let mut expected = Vec::new();
+            engine.write_accounts(&mut expected).unwrap();
+            let mut output = Vec::new();
+            write_summaries(&summaries, &mut output, &options, by_client).unwrap();
+            assert_eq!(output, expected);
+        }
+
+        let mut output = Vec::new();
+        write_summaries(
+            &summaries,
+            &mut output,
+            &FormattingOptions::default(),
+            |a, b| b.total.cmp(&a.total),
+        )
+        .unwrap();
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .ends_with("\n1,7.0000,0.0000,7.0000,false\n2,1.5000,0.0000,1.5000,false\n")
+        );
+    }
+}
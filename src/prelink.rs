@@ -0,0 +1,141 @@
+use std::{collections::HashSet, fmt, io::Read, str::FromStr};
+
+use crate::{
+    Engine,
+    engine::ingest::read_input,
+    errors::EngineError,
+    ids::{ClientId, TxId},
+    transaction::TransactionType,
+};
+
+/// What `run --prelink` does when [`check_dispute_links`] finds references
+/// to unknown deposits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrelinkMode {
+    /// Log each one and process the input anyway.
+    #[default]
+    Warn,
+    /// Stop with `EngineError::UnlinkedReferences` before any balance moves.
+    Fail,
+}
+
+impl FromStr for PrelinkMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "warn" => Ok(PrelinkMode::Warn),
+            "fail" => Ok(PrelinkMode::Fail),
+            other => Err(format!(
+                "unknown prelink mode {other}, expected warn or fail"
+            )),
+        }
+    }
+}
+
+/// A dispute, resolve or chargeback row naming a deposit that is neither in
+/// the engine's state nor anywhere in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnlinkedReference {
+    pub row: usize,
+    pub tx_type: TransactionType,
+    pub client: ClientId,
+    pub tx: TxId,
+}
+
+impl fmt::Display for UnlinkedReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "row {}: {} of client {} names unknown deposit {}",
+            self.row, self.tx_type, self.client, self.tx
+        )
+    }
+}
+
+/// What [`check_dispute_links`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkReport {
+    /// Dispute, resolve and chargeback rows read.
+    pub references: usize,
+    pub unlinked: Vec<UnlinkedReference>,
+}
+
+/// A first pass over `source` that matches every dispute, resolve and
+/// chargeback against the deposits of the same client, whether remembered
+/// by `engine` or anywhere in the input, before or after the reference. Read
+/// with the engine's input settings and preprocessors; nothing is applied.
+///
+/// Deposits the engine has already forgotten, under the `drop-history`
+/// memory policy or `admin forget`, count as unknown.
+pub fn check_dispute_links<R: Read>(engine: &Engine, source: R) -> Result<LinkReport, EngineError> {
+    let mut deposits = HashSet::new();
+    engine.visit_clients(|client| {
+        deposits.extend(client.deposits().map(|(tx, _)| (client.id, tx)));
+        Ok(())
+    })?;
+
+    let mut references = Vec::new();
+    let (_, rows) = read_input(source, engine.config(), &engine.preprocessors, false)?;
+    for transaction in rows.flatten() {
+        let Ok(tx) = u32::try_from(transaction.tx).map(TxId) else {
+            continue;
+        };
+        match transaction.tx_type {
+            TransactionType::Deposit => {
+                deposits.insert((transaction.client, tx));
+            }
+            tx_type @ (TransactionType::Dispute
+            | TransactionType::Resolve
+            | TransactionType::Chargeback) => references.push(UnlinkedReference {
+                row: transaction.row,
+                tx_type,
+                client: transaction.client,
+                tx,
+            }),
+            _ => {}
+        }
+    }
+
+    Ok(LinkReport {
+        references: references.len(),
+        unlinked: references
+            .into_iter()
+            .filter(|reference| !deposits.contains(&(reference.client, reference.tx)))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_match_deposits_in_state_or_anywhere_in_the_input() {
+        let mut engine = Engine::new();
+        engine
+            .process("type,client,tx,amount\ndeposit,1,1,5.0\n".as_bytes())
+            .unwrap();
+
+        let input = "type,client,tx,amount\n\
+            dispute,1,1,\n\
+            dispute,2,3,\n\
+            deposit,2,3,1.0\n\
+            chargeback,2,1,\n\
+            resolve,1,9,\n";
+        let report = check_dispute_links(&engine, input.as_bytes()).unwrap();
+        assert_eq!(report.references, 4);
+        assert_eq!(
+            report
+                .unlinked
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "row 4: chargeback of client 2 names unknown deposit 1",
+                "row 5: resolve of client 1 names unknown deposit 9"
+            ]
+        );
+        assert_eq!(engine.row_counts().read, 1);
+    }
+}
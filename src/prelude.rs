@@ -0,0 +1,20 @@
+//! The stable surface most embedders need: `use rust_payments_engine::prelude::*;`.
+
+pub use crate::audit::{AuditAction, AuditEntry};
+pub use crate::backend::PaymentsEngine;
+pub use crate::client::{Capabilities, Capability, Client, Operation};
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use crate::config::EngineConfig;
+pub use crate::errors::{
+    AmountError, ClientTransactionError, EngineError, MoneyError, RowError, ValidationError,
+};
+pub use crate::format::{AmountEncoding, Format};
+pub use crate::guard::ClientGuard;
+pub use crate::ids::{ClientId, TxId};
+pub use crate::money::Money;
+pub use crate::preprocess::Preprocessor;
+pub use crate::rules::{RuleDecision, RuleSet};
+pub use crate::snapshot::Snapshot;
+pub use crate::tenant::TenantEngines;
+pub use crate::transaction::{Transaction, TransactionType};
+pub use crate::{Engine, process_transactions};
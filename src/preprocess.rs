@@ -0,0 +1,127 @@
+pub use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Rewrites each raw CSV record before it is parsed and validated, so a
+/// partner's own format can be fed in without a separate ETL pass.
+/// Register with `Engine::add_preprocessor`; preprocessors run in the order
+/// they were added. JSON input is not preprocessed.
+///
+/// `header` is the input's header, or the positional default for headerless
+/// input, so fields can be found by name. Returning an error rejects the row
+/// with that reason; the dead-letter file still gets the row as read.
+pub trait Preprocessor: Send + Sync {
+    fn preprocess(&self, header: &StringRecord, record: &mut StringRecord) -> Result<(), String>;
+}
+
+impl<F> Preprocessor for F
+where
+    F: Fn(&StringRecord, &mut StringRecord) -> Result<(), String> + Send + Sync,
+{
+    fn preprocess(&self, header: &StringRecord, record: &mut StringRecord) -> Result<(), String> {
+        self(header, record)
+    }
+}
+
+fn column(header: &StringRecord, name: &str) -> Option<usize> {
+    header.iter().position(|column| column == name)
+}
+
+/// Replaces field `index` of `record`, if it has one.
+pub fn set_field(record: &mut StringRecord, index: usize, value: &str) {
+    if index < record.len() {
+        *record = record
+            .iter()
+            .enumerate()
+            .map(|(position, field)| if position == index { value } else { field })
+            .collect();
+    }
+}
+
+/// Maps partner-specific type names onto the engine's, such as `credit` to
+/// `deposit`. Names without a mapping pass through unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct RenameTypes {
+    names: HashMap<String, String>,
+}
+
+impl RenameTypes {
+    pub fn new<'n>(names: impl IntoIterator<Item = (&'n str, &'n str)>) -> Self {
+        RenameTypes {
+            names: names
+                .into_iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Preprocessor for RenameTypes {
+    fn preprocess(&self, header: &StringRecord, record: &mut StringRecord) -> Result<(), String> {
+        let Some(index) = column(header, "type") else {
+            return Ok(());
+        };
+        if let Some(name) = record
+            .get(index)
+            .and_then(|name| self.names.get(name.trim()))
+        {
+            set_field(record, index, name);
+        }
+        Ok(())
+    }
+}
+
+/// Reads amounts given in minor units, such as cents with `places` 2, so
+/// `350` becomes `3.50`. Empty amounts are left alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinorUnitAmounts {
+    pub places: u32,
+}
+
+impl Preprocessor for MinorUnitAmounts {
+    fn preprocess(&self, header: &StringRecord, record: &mut StringRecord) -> Result<(), String> {
+        let Some((index, field)) =
+            column(header, "amount").and_then(|index| Some((index, record.get(index)?.trim())))
+        else {
+            return Ok(());
+        };
+        if field.is_empty() {
+            return Ok(());
+        }
+        let minor: i64 = field
+            .parse()
+            .map_err(|_| format!("invalid amount {field} in minor units"))?;
+        let amount = Decimal::try_new(minor, self.places)
+            .map_err(|_| format!("invalid amount {field} in minor units"))?;
+        set_field(record, index, &amount.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_preprocessors_rewrite_type_and_amount() {
+        let header = StringRecord::from(vec!["type", "client", "tx", "amount"]);
+        let mut record = StringRecord::from(vec!["credit", "1", "1", "350"]);
+        RenameTypes::new([("credit", "deposit")])
+            .preprocess(&header, &mut record)
+            .unwrap();
+        MinorUnitAmounts { places: 2 }
+            .preprocess(&header, &mut record)
+            .unwrap();
+        assert_eq!(
+            record,
+            StringRecord::from(vec!["deposit", "1", "1", "3.50"])
+        );
+
+        let mut record = StringRecord::from(vec!["deposit", "1", "1", "3.5"]);
+        assert!(
+            MinorUnitAmounts { places: 2 }
+                .preprocess(&header, &mut record)
+                .is_err()
+        );
+    }
+}
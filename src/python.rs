@@ -0,0 +1,117 @@
+//! Python bindings, so pandas pipelines can drive the same accounting logic
+//! as the CLI. Only built with the `python` feature; `maturin build` (see
+//! `pyproject.toml`) produces the `payments_engine` extension module.
+//!
+//! Amounts cross the boundary as exact four-place strings, as in the CSV
+//! output; `decimal.Decimal(value)` turns them into numbers without floats.
+
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+
+use crate::{errors::EngineError, formatting::format_decimal};
+
+fn to_py_err(err: EngineError) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+fn accounts<'py>(py: Python<'py>, engine: &crate::Engine) -> PyResult<Bound<'py, PyList>> {
+    let accounts = PyList::empty(py);
+    let mut result = Ok(());
+    engine
+        .visit_clients(|client| {
+            let account = PyDict::new(py);
+            result = account
+                .set_item("client", client.id.0)
+                .and_then(|()| account.set_item("available", format_decimal(client.available)))
+                .and_then(|()| account.set_item("held", format_decimal(client.held.value())))
+                .and_then(|()| account.set_item("total", format_decimal(client.total)))
+                .and_then(|()| account.set_item("locked", client.locked))
+                .and_then(|()| accounts.append(account));
+            Ok(())
+        })
+        .map_err(to_py_err)?;
+    result.map(|()| accounts)
+}
+
+/// `process_transactions(data: bytes) -> list[dict]`: the final accounts
+/// for one CSV document, header included, in client id order.
+#[pyfunction]
+fn process_transactions<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyList>> {
+    let mut engine = crate::Engine::new();
+    engine.process(data).map_err(to_py_err)?;
+    accounts(py, &engine)
+}
+
+/// An engine kept across calls, for input that arrives in pieces.
+#[pyclass(name = "Engine", unsendable)]
+struct PyEngine {
+    engine: crate::Engine,
+}
+
+#[pymethods]
+impl PyEngine {
+    #[new]
+    #[pyo3(signature = (tenant=None))]
+    fn new(tenant: Option<String>) -> Self {
+        let engine = match tenant {
+            Some(tenant) => crate::Engine::with_tenant(tenant),
+            None => crate::Engine::new(),
+        };
+        PyEngine { engine }
+    }
+
+    /// Applies a CSV chunk. Every chunk starts with its own header row.
+    fn process(&mut self, data: &[u8]) -> PyResult<()> {
+        self.engine.process(data).map_err(to_py_err)
+    }
+
+    /// Applies one transaction and returns whether it was accepted. The
+    /// amount is a string, so it is never rounded through a float.
+    #[pyo3(signature = (tx_type, client, tx, amount=None))]
+    fn push(
+        &mut self,
+        tx_type: &str,
+        client: u16,
+        tx: u32,
+        amount: Option<&str>,
+    ) -> PyResult<bool> {
+        let mut row = csv::Writer::from_writer(Vec::new());
+        let client = client.to_string();
+        let tx = tx.to_string();
+        row.write_record(["type", "client", "tx", "amount"])
+            .and_then(|()| row.write_record([tx_type, &client, &tx, amount.unwrap_or("")]))
+            .map_err(|err| to_py_err(err.into()))?;
+        let row = row
+            .into_inner()
+            .map_err(|err| to_py_err(err.into_error().into()))?;
+
+        let rejected = self.engine.row_counts().rejected;
+        self.engine.process(row.as_slice()).map_err(to_py_err)?;
+        Ok(self.engine.row_counts().rejected == rejected)
+    }
+
+    /// Every account so far, as `process_transactions` returns them.
+    fn accounts<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        accounts(py, &self.engine)
+    }
+
+    #[getter]
+    fn rows_read(&self) -> usize {
+        self.engine.row_counts().read
+    }
+
+    #[getter]
+    fn rows_rejected(&self) -> usize {
+        self.engine.row_counts().rejected
+    }
+}
+
+#[pymodule]
+fn payments_engine(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(process_transactions, module)?)?;
+    module.add_class::<PyEngine>()?;
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use serde::Serialize;
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use crate::{engine::Rejection, errors::ClientTransactionError, transaction::TransactionType};
+
+/// How clean one run's input was, so a gateway can quarantine a partner
+/// file without reading the rejected rows. Every fraction is of the rows
+/// read, `0.0` when nothing was read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct QualityReport {
+    pub rows: usize,
+    /// Rows that could not be read into a transaction at all.
+    pub parse_errors: f64,
+    /// Transactions the engine rejected, other than unknown references.
+    pub validation_errors: f64,
+    /// Disputes, resolves, chargebacks and hold releases naming a
+    /// transaction the client does not have.
+    pub unknown_references: f64,
+    /// Deposits and withdrawals reusing the id of an earlier deposit or
+    /// withdrawal of the run, whether or not the engine accepted them.
+    pub duplicates: f64,
+    /// Share of rows none of the above apply to, `1.0` when nothing was
+    /// read.
+    pub score: f64,
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "score {:.4} over {} rows (parse errors {:.4}, validation errors {:.4}, unknown references {:.4}, duplicates {:.4})",
+            self.score,
+            self.rows,
+            self.parse_errors,
+            self.validation_errors,
+            self.unknown_references,
+            self.duplicates
+        )
+    }
+}
+
+/// Counts behind a [`QualityReport`], kept while
+/// `Engine::enable_quality_report` is on.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QualityTracker {
+    rows: usize,
+    parse_errors: usize,
+    validation_errors: usize,
+    unknown_references: usize,
+    duplicates: usize,
+    /// Rows counted under at least one of the above.
+    flagged: usize,
+    tx_ids: HashSet<i64>,
+}
+
+impl QualityTracker {
+    /// Records one row: `transaction` is its type and id, `None` when it
+    /// could not be read.
+    pub(crate) fn record(
+        &mut self,
+        transaction: Option<(TransactionType, i64)>,
+        rejection: Option<&Rejection>,
+    ) {
+        self.rows += 1;
+        let Some((tx_type, tx)) = transaction else {
+            self.parse_errors += 1;
+            self.flagged += 1;
+            return;
+        };
+        let duplicate = matches!(
+            tx_type,
+            TransactionType::Deposit | TransactionType::Withdrawal
+        ) && !self.tx_ids.insert(tx);
+        self.duplicates += usize::from(duplicate);
+        match rejection {
+            Some(Rejection::Client(
+                ClientTransactionError::UnknownTransaction { .. }
+                | ClientTransactionError::UnknownWithdrawalHold { .. },
+            )) => self.unknown_references += 1,
+            Some(_) => self.validation_errors += 1,
+            None => {}
+        }
+        self.flagged += usize::from(duplicate || rejection.is_some());
+    }
+
+    pub(crate) fn report(&self) -> QualityReport {
+        let fraction = |count: usize| {
+            if self.rows == 0 {
+                0.0
+            } else {
+                count as f64 / self.rows as f64
+            }
+        };
+        QualityReport {
+            rows: self.rows,
+            parse_errors: fraction(self.parse_errors),
+            validation_errors: fraction(self.validation_errors),
+            unknown_references: fraction(self.unknown_references),
+            duplicates: fraction(self.duplicates),
+            score: match self.rows {
+                0 => 1.0,
+                _ => fraction(self.rows - self.flagged),
+            },
+        }
+    }
+}
+
+/// Limits on a [`QualityReport`] past which a run counts as failed. Unset
+/// limits are not checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QualityThresholds {
+    pub max_parse_errors: Option<f64>,
+    pub max_validation_errors: Option<f64>,
+    pub max_unknown_references: Option<f64>,
+    pub max_duplicates: Option<f64>,
+    pub min_score: Option<f64>,
+}
+
+impl QualityThresholds {
+    /// One line per limit `report` breaks, empty when it passes.
+    pub fn breaches(&self, report: &QualityReport) -> Vec<String> {
+        let maxima = [
+            ("parse errors", self.max_parse_errors, report.parse_errors),
+            (
+                "validation errors",
+                self.max_validation_errors,
+                report.validation_errors,
+            ),
+            (
+                "unknown references",
+                self.max_unknown_references,
+                report.unknown_references,
+            ),
+            ("duplicates", self.max_duplicates, report.duplicates),
+        ];
+        let mut breaches: Vec<String> = maxima
+            .into_iter()
+            .filter_map(|(name, max, value)| {
+                max.filter(|&max| value > max)
+                    .map(|max| format!("{name} {value:.4} above {max}"))
+            })
+            .collect();
+        if let Some(min) = self.min_score
+            && report.score < min
+        {
+            breaches.push(format!("score {:.4} below {min}", report.score));
+        }
+        breaches
+    }
+}
+
+/// Comma-separated `name=fraction` pairs, where a name is `parse`,
+/// `validation`, `unknown` or `duplicates` (maxima) or `score` (a minimum),
+/// such as `parse=0.01,duplicates=0,score=0.95`.
+impl FromStr for QualityThresholds {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut thresholds = QualityThresholds::default();
+        for pair in value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (name, fraction) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=fraction, got {pair}"))?;
+            let fraction: f64 = fraction
+                .trim()
+                .parse()
+                .ok()
+                .filter(|fraction| (0.0..=1.0).contains(fraction))
+                .ok_or_else(|| format!("expected a fraction from 0 to 1, got {fraction}"))?;
+            let slot = match name.trim() {
+                "parse" => &mut thresholds.max_parse_errors,
+                "validation" => &mut thresholds.max_validation_errors,
+                "unknown" => &mut thresholds.max_unknown_references,
+                "duplicates" => &mut thresholds.max_duplicates,
+                "score" => &mut thresholds.min_score,
+                other => {
+                    return Err(format!(
+                        "unknown quality threshold {other}, expected parse, validation, unknown, duplicates or score"
+                    ));
+                }
+            };
+            *slot = Some(fraction);
+        }
+        Ok(thresholds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn rows_are_scored_by_what_went_wrong() {
+        let mut engine = Engine::new();
+        engine.enable_quality_report();
+        engine
+            .process(
+                "type,client,tx,amount\n\
+                 deposit,1,1,10\n\
+                 deposit,1,1,10\n\
+                 withdrawal,1,2,50\n\
+                 dispute,1,9,\n\
+                 deposit,one,3,1\n"
+                    .as_bytes(),
+            )
+            .unwrap();
+
+        let report = engine.quality_report().unwrap();
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.parse_errors, 0.2);
+        assert_eq!(report.validation_errors, 0.2);
+        assert_eq!(report.unknown_references, 0.2);
+        assert_eq!(report.duplicates, 0.2);
+        assert_eq!(report.score, 0.2);
+
+        let thresholds: QualityThresholds = "duplicates=0.5, score=0.9".parse().unwrap();
+        assert_eq!(thresholds.breaches(&report), ["score 0.2000 below 0.9"]);
+        assert!("typos=0.1".parse::<QualityThresholds>().is_err());
+        assert!("parse=2".parse::<QualityThresholds>().is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr};
+
+use crate::{digest::to_hex, formatting::format_decimal, ids::ClientId, report::reason_category};
+
+/// Hex digits kept from a client's hash. 64 bits leaves collisions between
+/// the 65536 possible ids vanishingly unlikely.
+pub const CLIENT_HASH_LEN: usize = 16;
+
+/// How amounts are disguised in redacted output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AmountRedaction {
+    /// Replaced by the range of this width they fall in, `100..200`.
+    Bucket(Decimal),
+    /// Multiplied by a secret factor. Ratios between amounts survive, so
+    /// sample data stays realistic, but real balances cannot be read off.
+    Scale(Decimal),
+}
+
+impl Default for AmountRedaction {
+    fn default() -> Self {
+        AmountRedaction::Bucket(Decimal::ONE_HUNDRED)
+    }
+}
+
+impl fmt::Debug for AmountRedaction {
+    /// Never prints the scale factor.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountRedaction::Bucket(width) => write!(f, "Bucket({width})"),
+            AmountRedaction::Scale(_) => f.write_str("Scale(..)"),
+        }
+    }
+}
+
+impl FromStr for AmountRedaction {
+    type Err = String;
+
+    /// `bucket:<width>` or `scale:<factor>`, both positive.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!("invalid amount redaction {value}, expected bucket:<width> or scale:<factor>")
+        };
+        let (kind, number) = value.split_once(':').ok_or_else(invalid)?;
+        let number: Decimal = number.parse().map_err(|_| invalid())?;
+        if number <= Decimal::ZERO {
+            return Err(invalid());
+        }
+        match kind {
+            "bucket" => Ok(AmountRedaction::Bucket(number)),
+            "scale" => Ok(AmountRedaction::Scale(number)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Privacy settings for audit trails and reports that are shared outside the
+/// company, for example as vendor samples. Client ids become salted hashes,
+/// amounts are bucketed or scaled, and free-text reasons and partner
+/// references are masked. The same salt always maps a client to the same
+/// hash, so redacted files can still be joined with each other.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Redaction {
+    pub salt: String,
+    pub amounts: AmountRedaction,
+}
+
+impl Redaction {
+    pub fn new(salt: impl Into<String>, amounts: AmountRedaction) -> Self {
+        Redaction {
+            salt: salt.into(),
+            amounts,
+        }
+    }
+
+    pub fn client(&self, client: ClientId) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update(client.0.to_be_bytes());
+        let mut hash = to_hex(&hasher.finalize());
+        hash.truncate(CLIENT_HASH_LEN);
+        hash
+    }
+
+    pub fn amount(&self, amount: Decimal) -> String {
+        match self.amounts {
+            AmountRedaction::Bucket(width) => amount
+                .checked_div(width)
+                .and_then(|buckets| buckets.floor().checked_mul(width))
+                .and_then(|low| Some((low, low.checked_add(width)?)))
+                .map(|(low, high)| format!("{}..{}", low.normalize(), high.normalize()))
+                .unwrap_or_else(|| "overflow".to_string()),
+            AmountRedaction::Scale(factor) => amount
+                .checked_mul(factor)
+                .map(format_decimal)
+                .unwrap_or_else(|| "overflow".to_string()),
+        }
+    }
+
+    /// Ids and amounts inside free text are replaced by `#`.
+    pub fn text(&self, text: &str) -> String {
+        reason_category(text)
+    }
+}
+
+impl fmt::Debug for Redaction {
+    /// Never prints the salt.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redaction")
+            .field("salt", &"..")
+            .field("amounts", &self.amounts)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use rust_decimal::dec;
+
+    #[test]
+    fn clients_hash_consistently_per_salt() {
+        let redaction = Redaction::new("secret", AmountRedaction::default());
+        let other = Redaction::new("other", AmountRedaction::default());
+
+        assert_eq!(redaction.client(ClientId(7)), redaction.client(ClientId(7)));
+        assert_eq!(redaction.client(ClientId(7)).len(), CLIENT_HASH_LEN);
+        assert_ne!(redaction.client(ClientId(7)), redaction.client(ClientId(8)));
+        assert_ne!(redaction.client(ClientId(7)), other.client(ClientId(7)));
+        assert!(!format!("{redaction:?}").contains("secret"));
+    }
+
+    #[test]
+    fn amounts_are_bucketed_or_scaled() {
+        let bucket = Redaction::new("s", "bucket:100".parse().unwrap());
+        assert_eq!(bucket.amount(dec!(153.25)), "100..200");
+        assert_eq!(bucket.amount(dec!(-0.5)), "-100..0");
+        assert_eq!(bucket.amount(dec!(0)), "0..100");
+
+        let scale = Redaction::new("s", "scale:1.5".parse().unwrap());
+        assert_eq!(scale.amount(dec!(10)), "15.0000");
+        assert!(!format!("{scale:?}").contains("1.5"));
+
+        assert!("bucket:0".parse::<AmountRedaction>().is_err());
+        assert!("round:10".parse::<AmountRedaction>().is_err());
+        assert_eq!(scale.text("Client 12: over 300.5"), "Client #: over #");
+    }
+
+    #[test]
+    fn redacted_audit_trail_hides_ids_amounts_and_references() {
+        use crate::{
+            audit::{AuditAction, AuditEntry, write_redacted_audit_entries},
+            client::Client,
+            money::Money,
+        };
+
+        let mut client = Client::new(ClientId(4242));
+        client
+            .deposit(TxId(1), Money::new(dec!(153.25)).unwrap())
+            .unwrap();
+        let entry = AuditEntry::new(AuditAction::RuleFlagged, &client, TxId(1), None)
+            .with_reason("client 4242 over 150")
+            .with_reference(Some("INV-77".to_string()));
+        let redaction = Redaction::new("salt", AmountRedaction::default());
+
+        let mut output = Vec::new();
+        write_redacted_audit_entries(&[entry], &redaction, &mut output, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with(&format!(
+            "rule_flagged,{},1,",
+            redaction.client(ClientId(4242))
+        )));
+        assert!(output.contains("100..200"));
+        assert!(output.contains("client # over #"));
+        for secret in ["4242", "153.25", "INV-77"] {
+            assert!(!output.contains(secret), "{output}");
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+use crate::client::Client;
+use crate::ids::ClientId;
+
+const EMPTY: u32 = u32::MAX;
+const DEFAULT_HOT_CAPACITY: usize = 256;
+const MIN_REBALANCE_INTERVAL: u64 = 4096;
+
+struct ColdEntry<B> {
+    client: Client<B>,
+    hits: u32,
+}
+
+/// Client storage tuned for skewed traffic, where a small share of clients
+/// produce most rows. The busiest clients live in a small open-addressed
+/// table probed through a compact array of ids; everyone else lives in a
+/// regular `HashMap`. Access counts are kept per client and, every so often
+/// (amortised over the number of clients), the most active ones are promoted
+/// into the hot table and the rest demoted. Counts are halved at each
+/// rebalance so the hot set follows shifts in traffic.
+pub struct ClientRegistry<B = Decimal> {
+    hot_ids: Vec<u32>,
+    hot_clients: Vec<Option<Client<B>>>,
+    hot_hits: Vec<u32>,
+    hot_len: usize,
+    cold: HashMap<ClientId, ColdEntry<B>>,
+    accesses: u64,
+    next_rebalance: u64,
+}
+
+impl<B> Default for ClientRegistry<B> {
+    fn default() -> Self {
+        ClientRegistry::with_hot_capacity(DEFAULT_HOT_CAPACITY)
+    }
+}
+
+impl<B> ClientRegistry<B> {
+    pub fn new() -> Self {
+        ClientRegistry::default()
+    }
+
+    /// `capacity` is rounded up to a power of two; at most half of it is
+    /// filled so probe sequences stay short.
+    pub fn with_hot_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(2).next_power_of_two();
+        ClientRegistry {
+            hot_ids: vec![EMPTY; capacity],
+            hot_clients: (0..capacity).map(|_| None).collect(),
+            hot_hits: vec![0; capacity],
+            hot_len: 0,
+            cold: HashMap::new(),
+            accesses: 0,
+            next_rebalance: MIN_REBALANCE_INTERVAL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hot_len + self.cold.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many clients currently sit in the hot table.
+    pub fn hot_len(&self) -> usize {
+        self.hot_len
+    }
+
+    pub fn contains_key(&self, client_id: ClientId) -> bool {
+        self.hot_slot(client_id).is_some() || self.cold.contains_key(&client_id)
+    }
+
+    pub fn get(&self, client_id: ClientId) -> Option<&Client<B>> {
+        match self.hot_slot(client_id) {
+            Some(slot) => self.hot_clients[slot].as_ref(),
+            None => self.cold.get(&client_id).map(|entry| &entry.client),
+        }
+    }
+
+    /// Mutable access, counted towards promotion.
+    pub fn get_mut(&mut self, client_id: ClientId) -> Option<&mut Client<B>> {
+        self.record_access();
+        if let Some(slot) = self.hot_slot(client_id) {
+            self.hot_hits[slot] = self.hot_hits[slot].saturating_add(1);
+            return self.hot_clients[slot].as_mut();
+        }
+        self.cold.get_mut(&client_id).map(|entry| {
+            entry.hits = entry.hits.saturating_add(1);
+            &mut entry.client
+        })
+    }
+
+    pub fn get_or_insert_with(
+        &mut self,
+        client_id: ClientId,
+        f: impl FnOnce() -> Client<B>,
+    ) -> &mut Client<B> {
+        if !self.contains_key(client_id) {
+            self.cold.insert(
+                client_id,
+                ColdEntry {
+                    client: f(),
+                    hits: 0,
+                },
+            );
+        }
+        self.get_mut(client_id)
+            .expect("client was just inserted into the registry")
+    }
+
+    /// Adds or replaces a client. New clients start cold.
+    pub fn insert(&mut self, client: Client<B>) {
+        match self.hot_slot(client.id) {
+            Some(slot) => self.hot_clients[slot] = Some(client),
+            None => {
+                let hits = self.cold.get(&client.id).map_or(0, |entry| entry.hits);
+                self.cold.insert(client.id, ColdEntry { client, hits });
+            }
+        }
+    }
+
+    pub fn remove(&mut self, client_id: ClientId) -> Option<Client<B>> {
+        let Some(slot) = self.hot_slot(client_id) else {
+            return self.cold.remove(&client_id).map(|entry| entry.client);
+        };
+        let client = self.hot_clients[slot].take();
+        self.hot_ids[slot] = EMPTY;
+        self.hot_hits[slot] = 0;
+        self.hot_len -= 1;
+        // Linear probing cannot leave holes behind; re-seat the rest.
+        let resident: Vec<(Client<B>, u32)> = self.drain_hot().collect();
+        for (client, hits) in resident {
+            self.insert_hot(client, hits);
+        }
+        client
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.hot_ids
+            .iter()
+            .filter(|id| **id != EMPTY)
+            .map(|id| ClientId(*id as u16))
+            .chain(self.cold.keys().copied())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Client<B>> {
+        self.hot_clients
+            .iter()
+            .flatten()
+            .chain(self.cold.values().map(|entry| &entry.client))
+    }
+
+    /// Every resident client, without counting as an access.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Client<B>> {
+        self.hot_clients
+            .iter_mut()
+            .flatten()
+            .chain(self.cold.values_mut().map(|entry| &mut entry.client))
+    }
+
+    fn capacity(&self) -> usize {
+        self.hot_ids.len()
+    }
+
+    fn home(&self, client_id: ClientId) -> usize {
+        // Fibonacci hashing: spreads consecutive ids across the table.
+        (u32::from(client_id.0).wrapping_mul(0x9E37_79B9) as usize) & (self.capacity() - 1)
+    }
+
+    fn hot_slot(&self, client_id: ClientId) -> Option<usize> {
+        if self.hot_len == 0 {
+            return None;
+        }
+        let mask = self.capacity() - 1;
+        let mut slot = self.home(client_id);
+        loop {
+            match self.hot_ids[slot] {
+                EMPTY => return None,
+                id if id == u32::from(client_id.0) => return Some(slot),
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    fn insert_hot(&mut self, client: Client<B>, hits: u32) {
+        let mask = self.capacity() - 1;
+        let mut slot = self.home(client.id);
+        while self.hot_ids[slot] != EMPTY {
+            slot = (slot + 1) & mask;
+        }
+        self.hot_ids[slot] = u32::from(client.id.0);
+        self.hot_hits[slot] = hits;
+        self.hot_clients[slot] = Some(client);
+        self.hot_len += 1;
+    }
+
+    fn drain_hot(&mut self) -> impl Iterator<Item = (Client<B>, u32)> + '_ {
+        self.hot_len = 0;
+        self.hot_ids.fill(EMPTY);
+        self.hot_clients
+            .iter_mut()
+            .zip(self.hot_hits.iter_mut())
+            .filter_map(|(client, hits)| client.take().map(|client| (client, std::mem::take(hits))))
+    }
+
+    fn record_access(&mut self) {
+        self.accesses += 1;
+        if self.accesses >= self.next_rebalance {
+            self.rebalance();
+            self.next_rebalance = self.accesses + MIN_REBALANCE_INTERVAL.max(self.len() as u64);
+        }
+    }
+
+    /// Moves the most accessed clients into the hot table and everyone else
+    /// out of it, then halves every count.
+    fn rebalance(&mut self) {
+        let hot_limit = self.capacity() / 2;
+        let hot: Vec<(Client<B>, u32)> = self.drain_hot().collect();
+        for (client, hits) in hot {
+            self.cold.insert(client.id, ColdEntry { client, hits });
+        }
+
+        let mut ranked: Vec<(u32, ClientId)> = self
+            .cold
+            .iter()
+            .filter(|(_, entry)| entry.hits > 0)
+            .map(|(id, entry)| (entry.hits, *id))
+            .collect();
+        if ranked.len() > hot_limit {
+            ranked.select_nth_unstable_by(hot_limit, |left, right| right.cmp(left));
+            ranked.truncate(hot_limit);
+        }
+
+        for entry in self.cold.values_mut() {
+            entry.hits /= 2;
+        }
+        for (_, client_id) in ranked {
+            if let Some(entry) = self.cold.remove(&client_id) {
+                self.insert_hot(entry.client, entry.hits);
+            }
+        }
+    }
+}
+
+impl<B> FromIterator<(ClientId, Client<B>)> for ClientRegistry<B> {
+    fn from_iter<I: IntoIterator<Item = (ClientId, Client<B>)>>(clients: I) -> Self {
+        let mut registry = ClientRegistry::new();
+        for (_, client) in clients {
+            registry.insert(client);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(clients: u16) -> ClientRegistry {
+        let mut registry = ClientRegistry::with_hot_capacity(8);
+        for id in 0..clients {
+            registry.insert(Client::new(ClientId(id)));
+        }
+        registry
+    }
+
+    #[test]
+    fn busiest_clients_are_promoted_and_still_reachable() {
+        let mut registry = registry(100);
+        for _ in 0..MIN_REBALANCE_INTERVAL {
+            registry.get_mut(ClientId(7)).unwrap();
+            registry.get_mut(ClientId(42)).unwrap();
+        }
+
+        assert_eq!(registry.hot_len(), 2);
+        assert!(
+            registry.hot_slot(ClientId(7)).is_some() && registry.hot_slot(ClientId(42)).is_some()
+        );
+        assert_eq!(registry.len(), 100);
+        assert!((0..100).all(|id| {
+            registry
+                .get(ClientId(id))
+                .is_some_and(|client| client.id == ClientId(id))
+        }));
+    }
+
+    #[test]
+    fn removing_a_hot_client_keeps_the_others_reachable() {
+        let mut registry = registry(20);
+        for round in 0..MIN_REBALANCE_INTERVAL {
+            registry.get_mut(ClientId((round % 4) as u16)).unwrap();
+        }
+        assert_eq!(registry.hot_len(), 4);
+
+        assert_eq!(
+            registry.remove(ClientId(1)).map(|client| client.id),
+            Some(ClientId(1))
+        );
+
+        assert!(!registry.contains_key(ClientId(1)));
+        assert_eq!(registry.hot_len(), 3);
+        assert!(
+            [0, 2, 3]
+                .iter()
+                .all(|id| registry.get(ClientId(*id)).is_some())
+        );
+        let mut ids: Vec<u16> = registry.ids().map(u16::from).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..20).filter(|id| *id != 1).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,137 @@
+use log::Level;
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::errors::ClientTransactionError;
+
+/// Rejection categories besides the `ClientTransactionError` variant names.
+pub const OTHER_CATEGORIES: [&str; 6] = [
+    "Parse",
+    "TenantMismatch",
+    "UnknownType",
+    "RuleDenied",
+    "ValidationMismatch",
+    "RowPanic",
+];
+
+/// How loudly one category of rejected rows is logged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RejectionLogLevel {
+    #[default]
+    Error,
+    Warn,
+    /// Not logged at all; the row still counts as rejected.
+    Silent,
+}
+
+impl RejectionLogLevel {
+    fn level(self) -> Option<Level> {
+        match self {
+            RejectionLogLevel::Error => Some(Level::Error),
+            RejectionLogLevel::Warn => Some(Level::Warn),
+            RejectionLogLevel::Silent => None,
+        }
+    }
+}
+
+impl FromStr for RejectionLogLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "error" => Ok(RejectionLogLevel::Error),
+            "warn" => Ok(RejectionLogLevel::Warn),
+            "silent" => Ok(RejectionLogLevel::Silent),
+            other => Err(format!(
+                "unknown rejection log level {other}, expected error, warn or silent"
+            )),
+        }
+    }
+}
+
+/// The level each category of rejected rows is logged at, so expected noise
+/// (a partner's stream of `NotInDispute`, say) can be turned down without
+/// hiding real anomalies. A category is a `ClientTransactionError` variant
+/// name or one of [`OTHER_CATEGORIES`]; unlisted ones use the default level.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RejectionLogging {
+    default: RejectionLogLevel,
+    categories: HashMap<String, RejectionLogLevel>,
+}
+
+impl RejectionLogging {
+    pub fn new(default: RejectionLogLevel) -> Self {
+        RejectionLogging {
+            default,
+            categories: HashMap::new(),
+        }
+    }
+
+    /// Sets the level of `category`, failing on names that are not
+    /// categories.
+    pub fn set(&mut self, category: &str, level: RejectionLogLevel) -> Result<(), String> {
+        if !ClientTransactionError::NAMES.contains(&category)
+            && !OTHER_CATEGORIES.contains(&category)
+        {
+            return Err(format!("unknown rejection category {category}"));
+        }
+        self.categories.insert(category.to_string(), level);
+        Ok(())
+    }
+
+    pub fn level(&self, category: &str) -> RejectionLogLevel {
+        self.categories
+            .get(category)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    pub(crate) fn log(&self, category: &str, message: fmt::Arguments<'_>) {
+        if let Some(level) = self.level(category).level() {
+            log::log!(level, "{message}");
+        }
+    }
+}
+
+/// Comma-separated `category=level` pairs, with `*` for the default, such as
+/// `NotInDispute=silent,AlreadyInDispute=warn`.
+impl FromStr for RejectionLogging {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut logging = RejectionLogging::default();
+        for pair in value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (category, level) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected category=level, got {pair}"))?;
+            let level = level.trim().parse()?;
+            match category.trim() {
+                "*" => logging.default = level,
+                category => logging.set(category, level)?,
+            }
+        }
+        Ok(logging)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_fall_back_to_the_default_level() {
+        let logging: RejectionLogging = "NotInDispute=silent, *=warn,Parse=error".parse().unwrap();
+        assert_eq!(logging.level("NotInDispute"), RejectionLogLevel::Silent);
+        assert_eq!(logging.level("Parse"), RejectionLogLevel::Error);
+        assert_eq!(logging.level("AccountLocked"), RejectionLogLevel::Warn);
+        assert_eq!(
+            RejectionLogging::default().level("NotInDispute"),
+            RejectionLogLevel::Error
+        );
+        assert!("NotInDisput=silent".parse::<RejectionLogging>().is_err());
+        assert!("NotInDispute=quiet".parse::<RejectionLogging>().is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use rust_decimal::Decimal;
+use std::{collections::BTreeMap, fmt::Write as _, io::Write};
+
+use crate::{
+    Engine,
+    client::Client,
+    errors::EngineError,
+    formatting::format_decimal,
+    ids::ClientId,
+    redaction::Redaction,
+    segments::{ALL_CLIENTS, ClientSegments},
+};
+
+/// Accounts listed under "Top balances".
+pub const TOP_BALANCES: usize = 10;
+
+/// Groups rejection reasons that differ only in ids and amounts, so the error
+/// breakdown counts kinds of failure rather than individual rows.
+pub(crate) fn reason_category(reason: &str) -> String {
+    let mut category = String::with_capacity(reason.len());
+    let mut chars = reason.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            while chars.next_if(|c| c.is_ascii_digit() || *c == '.').is_some() {}
+            category.push('#');
+        } else {
+            category.push(c);
+        }
+    }
+    category
+}
+
+pub const SEGMENT_STATS_HEADER: [&str; 6] = [
+    "segment",
+    "accounts",
+    "deposits",
+    "deposit_count",
+    "chargebacks",
+    "chargeback_rate",
+];
+
+/// Lifetime figures of the accounts in one client segment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SegmentStats {
+    pub segment: String,
+    pub accounts: usize,
+    /// Sum of every deposit the accounts took, disputed or not.
+    pub deposits: Decimal,
+    pub deposit_count: u64,
+    pub chargebacks: u64,
+}
+
+impl SegmentStats {
+    /// Chargebacks per deposit, 0 for a segment without deposits.
+    pub fn chargeback_rate(&self) -> f64 {
+        if self.deposit_count == 0 {
+            return 0.0;
+        }
+        self.chargebacks as f64 / self.deposit_count as f64
+    }
+
+    fn add(&mut self, client: &Client) {
+        self.accounts += 1;
+        self.deposits += client.lifetime_deposits();
+        self.deposit_count += u64::from(client.deposit_count());
+        self.chargebacks += u64::from(client.chargeback_count());
+    }
+}
+
+/// Figures for every client (segment [`ALL_CLIENTS`]) and for each segment
+/// with at least one account in the engine, ordered by segment. Figures
+/// cover each account's lifetime, snapshots included, not just this run.
+pub fn segment_stats(
+    engine: &Engine,
+    segments: &ClientSegments,
+) -> Result<Vec<SegmentStats>, EngineError> {
+    let mut stats: BTreeMap<String, SegmentStats> = BTreeMap::new();
+    engine.visit_clients(|client| {
+        for segment in [ALL_CLIENTS]
+            .into_iter()
+            .chain(segments.segments(client.id))
+        {
+            stats
+                .entry(segment.to_string())
+                .or_insert_with(|| SegmentStats {
+                    segment: segment.to_string(),
+                    ..SegmentStats::default()
+                })
+                .add(client);
+        }
+        Ok(())
+    })?;
+    Ok(stats.into_values().collect())
+}
+
+/// Writes `stats` as CSV, header first. The rate has four decimal places.
+/// Deposit totals are disguised with `redaction`, as in the HTML report.
+pub fn write_segment_stats<W: Write>(
+    stats: &[SegmentStats],
+    redaction: Option<&Redaction>,
+    writer: W,
+) -> Result<(), EngineError> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(SEGMENT_STATS_HEADER)?;
+    for segment in stats {
+        csv_writer.write_record([
+            segment.segment.as_str(),
+            &segment.accounts.to_string(),
+            &amount_label(redaction, segment.deposits),
+            &segment.deposit_count.to_string(),
+            &segment.chargebacks.to_string(),
+            &format!("{:.4}", segment.chargeback_rate()),
+        ])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Writes a self-contained HTML page describing the engine's state: totals,
+/// the largest balances, open disputes, why rows were rejected and a
+/// sortable table of every account. Needs no network access to view, so it
+/// can be mailed or attached to a ticket as is.
+///
+/// The error breakdown is only filled in when the engine was processing with
+/// `Engine::enable_rejection_breakdown`. With `EngineConfig::redaction` set,
+/// client ids and amounts are disguised.
+pub fn write_html_report<W: Write>(engine: &Engine, writer: W) -> Result<(), EngineError> {
+    write_html_report_with_segments(engine, None, writer)
+}
+
+/// [`write_html_report`] with a section of [`segment_stats`] after the
+/// summary when `segments` is given.
+pub fn write_html_report_with_segments<W: Write>(
+    engine: &Engine,
+    segments: Option<&ClientSegments>,
+    mut writer: W,
+) -> Result<(), EngineError> {
+    let mut clients = Vec::new();
+    engine.visit_clients(|client| {
+        clients.push(client.clone());
+        Ok(())
+    })?;
+
+    let mut html = String::new();
+    let title = match engine.tenant() {
+        Some(tenant) => format!("Payments report: {}", escape(tenant)),
+        None => "Payments report".to_string(),
+    };
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    let redaction = engine.config().redaction.as_ref();
+    summary_section(&mut html, engine, &clients, redaction);
+    if let Some(segments) = segments {
+        segments_section(&mut html, &segment_stats(engine, segments)?, redaction);
+    }
+    top_balances_section(&mut html, &clients, redaction);
+    disputes_section(&mut html, &clients, redaction);
+    errors_section(&mut html, engine.rejection_breakdown());
+    accounts_section(&mut html, &clients, redaction);
+    let _ = write!(html, "<script>{SCRIPT}</script>\n</body>\n</html>\n");
+
+    writer.write_all(html.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn summary_section(
+    html: &mut String,
+    engine: &Engine,
+    clients: &[Client],
+    redaction: Option<&Redaction>,
+) {
+    let rows = engine.row_counts();
+    let sum = |amount: fn(&Client) -> Decimal| clients.iter().map(amount).sum::<Decimal>();
+    let locked = clients.iter().filter(|client| client.locked).count();
+    let _ = write!(
+        html,
+        "<h2>Summary</h2>\n<table>\n\
+         <tr><th>Rows read</th><td class=\"num\">{}</td></tr>\n\
+         <tr><th>Rows rejected</th><td class=\"num\">{} ({:.2}%)</td></tr>\n\
+         <tr><th>Accounts</th><td class=\"num\">{}</td></tr>\n\
+         <tr><th>Locked accounts</th><td class=\"num\">{locked}</td></tr>\n\
+         <tr><th>Available</th><td class=\"num\">{}</td></tr>\n\
+         <tr><th>Held</th><td class=\"num\">{}</td></tr>\n\
+         <tr><th>Total</th><td class=\"num\">{}</td></tr>\n\
+         </table>\n",
+        rows.read,
+        rows.rejected,
+        rows.error_rate() * 100.0,
+        clients.len(),
+        amount_label(redaction, sum(|client| client.available)),
+        amount_label(redaction, sum(|client| client.held.value())),
+        amount_label(redaction, sum(|client| client.total)),
+    );
+}
+
+fn segments_section(html: &mut String, stats: &[SegmentStats], redaction: Option<&Redaction>) {
+    html.push_str(
+        "<h2>Segments</h2>\n<table>\n<tr><th>Segment</th><th>Accounts</th><th>Deposits</th><th>Deposit count</th><th>Chargebacks</th><th>Chargeback rate</th></tr>\n",
+    );
+    for segment in stats {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{:.2}%</td></tr>",
+            escape(&segment.segment),
+            segment.accounts,
+            amount_label(redaction, segment.deposits),
+            segment.deposit_count,
+            segment.chargebacks,
+            segment.chargeback_rate() * 100.0,
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn top_balances_section(html: &mut String, clients: &[Client], redaction: Option<&Redaction>) {
+    let mut top: Vec<&Client> = clients.iter().collect();
+    top.sort_by(|a, b| b.total.cmp(&a.total).then(a.id.cmp(&b.id)));
+    top.truncate(TOP_BALANCES);
+    let _ = write!(
+        html,
+        "<h2>Top balances</h2>\n<table>\n<tr><th>Client</th><th>Total</th><th>Available</th><th>Held</th></tr>\n"
+    );
+    for client in top {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            client_label(redaction, client.id),
+            amount_label(redaction, client.total),
+            amount_label(redaction, client.available),
+            amount_label(redaction, client.held.value()),
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn disputes_section(html: &mut String, clients: &[Client], redaction: Option<&Redaction>) {
+    let disputed: Vec<&Client> = clients
+        .iter()
+        .filter(|client| client.open_disputes().next().is_some() || client.chargeback_count() > 0)
+        .collect();
+    let open: usize = disputed
+        .iter()
+        .map(|client| client.open_disputes().count())
+        .sum();
+    let chargebacks: u32 = disputed
+        .iter()
+        .map(|client| client.chargeback_count())
+        .sum();
+    let _ = write!(
+        html,
+        "<h2>Disputes</h2>\n<p>{open} open dispute(s), {chargebacks} chargeback(s).</p>\n"
+    );
+    if disputed.is_empty() {
+        return;
+    }
+    html.push_str(
+        "<table>\n<tr><th>Client</th><th>Open disputes</th><th>Held</th><th>Chargebacks</th><th>Locked</th></tr>\n",
+    );
+    for client in disputed {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            client_label(redaction, client.id),
+            client.open_disputes().count(),
+            amount_label(redaction, client.held.value()),
+            client.chargeback_count(),
+            client.locked,
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn errors_section(html: &mut String, breakdown: Option<&BTreeMap<String, usize>>) {
+    html.push_str("<h2>Rejected rows</h2>\n");
+    let Some(breakdown) = breakdown else {
+        html.push_str("<p>Rejection reasons were not tracked for this run.</p>\n");
+        return;
+    };
+    if breakdown.is_empty() {
+        html.push_str("<p>No rows were rejected.</p>\n");
+        return;
+    }
+    let mut reasons: Vec<(&String, &usize)> = breakdown.iter().collect();
+    reasons.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    html.push_str(
+        "<table>\n<tr><th>Rows</th><th>Reason (# stands for ids and amounts)</th></tr>\n",
+    );
+    for (reason, count) in reasons {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"num\">{count}</td><td>{}</td></tr>",
+            escape(reason)
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn accounts_section(html: &mut String, clients: &[Client], redaction: Option<&Redaction>) {
+    html.push_str(
+        "<h2>Accounts</h2>\n<p>Click a column heading to sort.</p>\n<table class=\"sortable\">\n<thead><tr><th>Client</th><th>Available</th><th>Held</th><th>Total</th><th>Locked</th></tr></thead>\n<tbody>\n",
+    );
+    for client in clients {
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            client_label(redaction, client.id),
+            amount_label(redaction, client.available),
+            amount_label(redaction, client.held.value()),
+            amount_label(redaction, client.total),
+            client.locked,
+        );
+    }
+    html.push_str("</tbody>\n</table>\n");
+}
+
+fn client_label(redaction: Option<&Redaction>, client: ClientId) -> String {
+    match redaction {
+        Some(redaction) => redaction.client(client),
+        None => client.to_string(),
+    }
+}
+
+fn amount_label(redaction: Option<&Redaction>, amount: Decimal) -> String {
+    match redaction {
+        Some(redaction) => redaction.amount(amount),
+        None => format_decimal(amount),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+td.num{text-align:right;font-variant-numeric:tabular-nums}\
+table.sortable th{cursor:pointer;background:#f3f3f3}";
+
+const SCRIPT: &str = "document.querySelectorAll('table.sortable').forEach(function(table){\
+var body=table.tBodies[0];\
+table.querySelectorAll('th').forEach(function(th,column){\
+var ascending=true;\
+th.addEventListener('click',function(){\
+var rows=Array.from(body.rows);\
+rows.sort(function(a,b){\
+var x=a.cells[column].textContent,y=b.cells[column].textContent;\
+var order=isNaN(x)||isNaN(y)?x.localeCompare(y):x-y;\
+return ascending?order:-order;});\
+ascending=!ascending;\
+rows.forEach(function(row){body.appendChild(row);});});});});";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reasons_are_grouped_without_ids_and_amounts() {
+        assert_eq!(
+            reason_category("Client 12: invalid amount -3.5 for transaction 7"),
+            "Client #: invalid amount -# for transaction #"
+        );
+        assert_eq!(
+            reason_category("unknown transaction type refund"),
+            "unknown transaction type refund"
+        );
+    }
+
+    #[test]
+    fn report_lists_disputes_errors_and_escaped_tenant() {
+        let mut engine = Engine::with_tenant("<acme & co>");
+        engine.enable_rejection_breakdown();
+        let input = "type,client,tx,amount\ndeposit,1,1,5\ndeposit,2,2,3\ndispute,2,2,\nwithdrawal,1,3,9\nwithdrawal,2,4,9\n";
+        engine.process(Cursor::new(input)).unwrap();
+
+        let mut output = Vec::new();
+        write_html_report(&engine, &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains("<title>Payments report: &lt;acme &amp; co&gt;</title>"));
+        assert!(html.contains("1 open dispute(s), 0 chargeback(s)."));
+        assert!(html.contains("<tr><td class=\"num\">2</td><td>"), "{html}");
+        assert!(!html.contains("<acme"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn stats_are_broken_down_by_segment() {
+        let mut engine = Engine::new();
+        let input = "type,client,tx,amount\n\
+            deposit,1,1,10\n\
+            deposit,1,2,5\n\
+            deposit,2,3,4\n\
+            dispute,2,3,\n\
+            chargeback,2,3,\n\
+            deposit,3,4,1\n";
+        engine.process(Cursor::new(input)).unwrap();
+        let segments =
+            ClientSegments::load("client,segment\n1,vip\n2,vip\n2,<partner>\n".as_bytes()).unwrap();
+
+        let stats = segment_stats(&engine, &segments).unwrap();
+        let mut output = Vec::new();
+        write_segment_stats(&stats, None, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "segment,accounts,deposits,deposit_count,chargebacks,chargeback_rate\n\
+             <partner>,1,4.0000,1,1,1.0000\n\
+             all,3,20.0000,4,1,0.2500\n\
+             vip,2,19.0000,3,1,0.3333\n"
+        );
+
+        let mut output = Vec::new();
+        write_html_report_with_segments(&engine, Some(&segments), &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.contains("<h2>Segments</h2>"));
+        assert!(html.contains("<tr><td>&lt;partner&gt;</td>"));
+        assert!(html.contains("33.33%"));
+
+        let redaction = Redaction::new("salt", Default::default());
+        let mut output = Vec::new();
+        write_segment_stats(&stats, Some(&redaction), &mut output).unwrap();
+        let csv = String::from_utf8(output).unwrap();
+        assert!(csv.contains("all,3,0..100,4,1,0.2500\n"));
+        assert!(!csv.contains("20.0000"));
+    }
+
+    #[test]
+    fn redacted_report_shows_no_ids_or_amounts() {
+        let redaction = Redaction::new("salt", Default::default());
+        let mut engine = Engine::with_config(crate::config::EngineConfig {
+            redaction: Some(redaction.clone()),
+            ..Default::default()
+        });
+        let input = "type,client,tx,amount\ndeposit,4242,1,153.25\n";
+        engine.process(Cursor::new(input)).unwrap();
+
+        let mut output = Vec::new();
+        write_html_report(&engine, &mut output).unwrap();
+        let html = String::from_utf8(output).unwrap();
+
+        assert!(html.contains(&redaction.client(ClientId(4242))));
+        assert!(html.contains("100..200"));
+        assert!(!html.contains("4242"));
+        assert!(!html.contains("153.25"));
+    }
+}
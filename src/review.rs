@@ -0,0 +1,149 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Write};
+
+use crate::{
+    client::Client,
+    errors::EngineError,
+    formatting::format_decimal,
+    ids::{ClientId, TxId},
+    numeric::Balance,
+};
+
+/// Why an account was put on the review queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewReason {
+    /// A registered rule flagged a transaction it allowed.
+    RuleFlagged,
+    /// A transaction took available funds below zero, or was held under
+    /// `EngineConfig::non_negative_balances` because it would have.
+    NegativeAvailable,
+    /// The client opened its `EngineConfig::review_after_disputes`th dispute.
+    RepeatedDisputes,
+    /// Added by an operator or embedder through `Engine::add_review_note`.
+    Note,
+}
+
+impl ReviewReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewReason::RuleFlagged => "rule_flagged",
+            ReviewReason::NegativeAvailable => "negative_available",
+            ReviewReason::RepeatedDisputes => "repeated_disputes",
+            ReviewReason::Note => "note",
+        }
+    }
+}
+
+impl fmt::Display for ReviewReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One account for an analyst to look at, with the balances it had when
+/// the entry was made. Entries stay queued, and in snapshots, until taken
+/// with `Engine::take_review_queue`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub reason: ReviewReason,
+    pub client: ClientId,
+    /// The transaction that raised the entry, `None` for notes.
+    pub tx: Option<TxId>,
+    pub note: String,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    /// Sequence number of the transaction, `None` when it was not accepted.
+    #[serde(default)]
+    pub sequence: Option<u64>,
+}
+
+impl ReviewItem {
+    pub(crate) fn new<B: Balance>(
+        reason: ReviewReason,
+        client: &Client<B>,
+        tx: Option<TxId>,
+        note: impl Into<String>,
+    ) -> Self {
+        ReviewItem {
+            reason,
+            client: client.id,
+            tx,
+            note: note.into(),
+            available: client.available.to_decimal(),
+            held: client.held.into(),
+            total: client.total.to_decimal(),
+            sequence: None,
+        }
+    }
+
+    pub(crate) fn with_sequence(mut self, sequence: Option<u64>) -> Self {
+        self.sequence = sequence;
+        self
+    }
+}
+
+pub const REVIEW_HEADER: [&str; 8] = [
+    "reason",
+    "client",
+    "tx",
+    "note",
+    "available",
+    "held",
+    "total",
+    "sequence",
+];
+
+/// Writes `items` as CSV, header first, in queue order.
+pub fn write_review_queue<W: Write>(items: &[ReviewItem], writer: W) -> Result<(), EngineError> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(REVIEW_HEADER)?;
+    for item in items {
+        csv_writer.write_record([
+            item.reason.as_str(),
+            &item.client.to_string(),
+            &item.tx.map(|tx| tx.to_string()).unwrap_or_default(),
+            &item.note,
+            &format_decimal(item.available),
+            &format_decimal(item.held),
+            &format_decimal(item.total),
+            &item
+                .sequence
+                .map(|sequence| sequence.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use std::io::Cursor;
+
+    #[test]
+    fn notes_are_queued_and_exported_with_the_balances_at_the_time() {
+        let mut engine = Engine::new();
+        engine
+            .process(Cursor::new("type,client,tx,amount\ndeposit,7,1,2.5\n"))
+            .unwrap();
+        engine
+            .add_review_note(ClientId(7), "called, no answer")
+            .unwrap();
+        assert!(engine.add_review_note(ClientId(8), "unknown").is_err());
+
+        let mut exported = Vec::new();
+        write_review_queue(engine.review_queue(), &mut exported).unwrap();
+        assert_eq!(
+            String::from_utf8(exported).unwrap(),
+            "reason,client,tx,note,available,held,total,sequence\n\
+             note,7,,\"called, no answer\",2.5000,0.0000,2.5000,\n"
+        );
+        assert_eq!(engine.take_review_queue()[0].reason, ReviewReason::Note);
+        assert!(engine.review_queue().is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use rust_decimal::{Decimal, dec};
+
+use crate::client::Client;
+use crate::numeric::Balance;
+
+/// Chargebacks at which that part of the score is maxed out.
+const CHARGEBACK_CAP: u32 = 3;
+
+/// Per-client signals for fraud tooling, gathered in the same pass that
+/// computes balances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RiskStats {
+    /// Disputes opened per deposit, `0` to `1`.
+    pub dispute_ratio: Decimal,
+    pub chargeback_count: u32,
+    /// Share of the deposited value already withdrawn, `0` to `1`: money
+    /// that leaves as fast as it arrives scores high.
+    pub withdrawal_velocity: Decimal,
+}
+
+impl RiskStats {
+    pub fn of<B: Balance>(client: &Client<B>) -> Self {
+        let ratio = |part: Decimal, whole: Decimal| {
+            if whole.is_zero() {
+                Decimal::ZERO
+            } else {
+                (part / whole).min(Decimal::ONE)
+            }
+        };
+        RiskStats {
+            dispute_ratio: ratio(client.dispute_count().into(), client.deposit_count().into()),
+            chargeback_count: client.chargeback_count(),
+            withdrawal_velocity: ratio(
+                client.lifetime_withdrawals().to_decimal(),
+                client.lifetime_deposits().to_decimal(),
+            ),
+        }
+    }
+
+    /// `0` (no signal) to `100`: 40 points for the dispute ratio, 40 for
+    /// chargebacks (maxed at three) and 20 for withdrawal velocity.
+    pub fn score(&self) -> u8 {
+        let chargebacks = Decimal::from(self.chargeback_count.min(CHARGEBACK_CAP))
+            / Decimal::from(CHARGEBACK_CAP);
+        let score = dec!(40) * self.dispute_ratio
+            + dec!(40) * chargebacks
+            + dec!(20) * self.withdrawal_velocity;
+        u8::try_from(score.round().mantissa()).unwrap_or(100)
+    }
+}
+
+impl<B: Balance> Client<B> {
+    pub fn risk_stats(&self) -> RiskStats {
+        RiskStats::of(self)
+    }
+
+    /// See [`RiskStats::score`].
+    pub fn risk_score(&self) -> u8 {
+        self.risk_stats().score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{ClientId, TxId};
+    use crate::money::Money;
+
+    #[test]
+    fn scores_grow_with_disputes_chargebacks_and_outflow() {
+        let money = |value| Money::new(value).unwrap();
+        let mut client = Client::new(ClientId(1));
+        assert_eq!(client.risk_score(), 0);
+
+        client.deposit(TxId(1), money(dec!(10))).unwrap();
+        client.deposit(TxId(2), money(dec!(10))).unwrap();
+        client.withdraw(money(dec!(5))).unwrap();
+        assert_eq!(client.risk_stats().withdrawal_velocity, dec!(0.25));
+        assert_eq!(client.risk_score(), 5);
+
+        client.dispute(TxId(1)).unwrap();
+        client.chargeback(TxId(1)).unwrap();
+        assert_eq!(
+            client.risk_stats(),
+            RiskStats {
+                dispute_ratio: dec!(0.5),
+                chargeback_count: 1,
+                withdrawal_velocity: dec!(0.25),
+            }
+        );
+        assert_eq!(client.risk_score(), 38);
+    }
+}
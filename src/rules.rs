@@ -0,0 +1,148 @@
+use rust_decimal::Decimal;
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{
+    client::Client,
+    ids::ClientId,
+    money::Money,
+    numeric::Balance,
+    transaction::{Transaction, TransactionType},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleDecision {
+    Allow,
+    Flag(String),
+    Deny(String),
+}
+
+type Rule<B> = Box<dyn Fn(&Client<B>, &Transaction) -> RuleDecision + Send + Sync>;
+
+/// Ordered set of user supplied checks run before each transaction is
+/// applied. Rules only get shared references and are called one at a time
+/// with no engine lock held, so a rule can never deadlock the engine.
+pub struct RuleSet<B = Decimal> {
+    rules: Vec<(String, Rule<B>)>,
+}
+
+impl<B> Default for RuleSet<B> {
+    fn default() -> Self {
+        RuleSet { rules: Vec::new() }
+    }
+}
+
+/// Result of running every rule against one transaction: the first denial
+/// wins, otherwise all flags are collected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RuleOutcome {
+    Allow { flags: Vec<String> },
+    Deny(String),
+}
+
+impl<B: Balance> RuleSet<B> {
+    pub fn new() -> Self {
+        RuleSet::default()
+    }
+
+    pub fn register<F>(&mut self, name: impl Into<String>, rule: F)
+    where
+        F: Fn(&Client<B>, &Transaction) -> RuleDecision + Send + Sync + 'static,
+    {
+        self.rules.push((name.into(), Box::new(rule)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn evaluate(&self, client: &Client<B>, transaction: &Transaction) -> RuleOutcome {
+        let mut flags = Vec::new();
+        for (name, rule) in &self.rules {
+            match rule(client, transaction) {
+                RuleDecision::Allow => {}
+                RuleDecision::Flag(reason) => flags.push(format!("{name}: {reason}")),
+                RuleDecision::Deny(reason) => {
+                    return RuleOutcome::Deny(format!("{name}: {reason}"));
+                }
+            }
+        }
+        RuleOutcome::Allow { flags }
+    }
+}
+
+/// Denies a withdrawal (or withdrawal hold) once the client's withdrawals
+/// allowed so far in this run would exceed `limit`.
+pub fn max_withdrawal_per_run<B: Balance>(
+    limit: Money,
+) -> impl Fn(&Client<B>, &Transaction) -> RuleDecision + Send + Sync + 'static {
+    let withdrawn: Mutex<HashMap<ClientId, Decimal>> = Mutex::new(HashMap::new());
+    move |_client, transaction| {
+        let (TransactionType::Withdrawal | TransactionType::WithdrawalHold, Some(amount)) =
+            (transaction.tx_type, transaction.amount)
+        else {
+            return RuleDecision::Allow;
+        };
+        let mut withdrawn = withdrawn.lock().unwrap_or_else(|err| err.into_inner());
+        let so_far = withdrawn.entry(transaction.client).or_default();
+        if *so_far + amount.value() > limit.value() {
+            return RuleDecision::Deny(format!(
+                "withdrawals this run would exceed {}",
+                limit.value()
+            ));
+        }
+        *so_far += amount.value();
+        RuleDecision::Allow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::TxId;
+    use rust_decimal::dec;
+
+    fn withdrawal(tx: u32, amount: Decimal) -> Transaction {
+        Transaction {
+            tx_type: TransactionType::Withdrawal,
+            client: ClientId(1),
+            tx: TxId(tx),
+            amount: Some(Money::new(amount).unwrap()),
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn first_denial_wins_and_flags_are_collected() {
+        let mut rules: RuleSet = RuleSet::new();
+        rules.register("watch", |_, _| RuleDecision::Flag("large".to_string()));
+        rules.register("block", |_, _| RuleDecision::Deny("blocked".to_string()));
+        let client = Client::new(ClientId(1));
+
+        assert_eq!(
+            rules.evaluate(&client, &withdrawal(1, dec!(1))),
+            RuleOutcome::Deny("block: blocked".to_string())
+        );
+
+        let mut rules: RuleSet = RuleSet::new();
+        rules.register("watch", |_, _| RuleDecision::Flag("large".to_string()));
+        assert_eq!(
+            rules.evaluate(&client, &withdrawal(1, dec!(1))),
+            RuleOutcome::Allow {
+                flags: vec!["watch: large".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn max_withdrawal_per_run_tracks_cumulative_amounts() {
+        let rule = max_withdrawal_per_run(Money::new(dec!(10)).unwrap());
+        let client: Client = Client::new(ClientId(1));
+
+        assert_eq!(rule(&client, &withdrawal(1, dec!(6))), RuleDecision::Allow);
+        assert!(matches!(
+            rule(&client, &withdrawal(2, dec!(5))),
+            RuleDecision::Deny(_)
+        ));
+        assert_eq!(rule(&client, &withdrawal(3, dec!(4))), RuleDecision::Allow);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use crate::errors::EngineError;
+
+/// What [`sample_clients`] kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SampleStats {
+    pub rows_read: usize,
+    pub rows_written: usize,
+    pub clients: usize,
+}
+
+/// SplitMix64: small, seedable and good enough to pick or shuffle rows,
+/// without pulling in `rand` outside the `fault-injection` feature.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Position of the `client` column in a CSV header.
+pub(crate) fn client_column(header: &csv::ByteRecord) -> Result<usize, EngineError> {
+    header
+        .iter()
+        .position(|column| column.trim_ascii() == b"client")
+        .ok_or_else(|| EngineError::Usage("input has no client column".to_string()))
+}
+
+/// Copies a random sample of the CSV `source` to `writer`, header first,
+/// for building small fixtures out of production files. Each row is picked
+/// with probability `rate`, and every row of a picked row's client is kept,
+/// so disputes still find their deposits and balances replay the same way.
+///
+/// Reads `source` twice. The same `seed` always picks the same rows.
+pub fn sample_clients<R: Read + Seek, W: Write>(
+    mut source: R,
+    writer: W,
+    rate: f64,
+    seed: u64,
+) -> Result<SampleStats, EngineError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(&mut source);
+    let client_index = client_column(reader.byte_headers()?)?;
+
+    let mut random = SplitMix64(seed);
+    let mut sampled = HashSet::new();
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        if random.next_f64() < rate
+            && let Some(client) = record.get(client_index)
+        {
+            sampled.insert(client.trim_ascii().to_vec());
+        }
+    }
+    drop(reader);
+
+    source.seek(SeekFrom::Start(0))?;
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(source);
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(writer);
+    writer.write_byte_record(reader.byte_headers()?)?;
+    let mut stats = SampleStats {
+        clients: sampled.len(),
+        ..SampleStats::default()
+    };
+    while reader.read_byte_record(&mut record)? {
+        stats.rows_read += 1;
+        if record
+            .get(client_index)
+            .is_some_and(|client| sampled.contains(client.trim_ascii()))
+        {
+            writer.write_byte_record(&record)?;
+            stats.rows_written += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn sampled_clients_keep_all_their_rows() {
+        let mut input = String::from("type,client,tx,amount\n");
+        for tx in 0..1000 {
+            input.push_str(&format!("deposit,{},{tx},1.0\n", tx % 50));
+        }
+
+        let mut output = Vec::new();
+        let stats = sample_clients(Cursor::new(&input), &mut output, 0.005, 7).unwrap();
+        assert_eq!(stats.rows_read, 1000);
+        assert!(stats.clients > 0 && stats.clients < 50);
+        assert_eq!(stats.rows_written, stats.clients * 20);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("type,client,tx,amount\n"));
+        assert_eq!(output.lines().count(), stats.rows_written + 1);
+
+        let mut again = Vec::new();
+        sample_clients(Cursor::new(&input), &mut again, 0.005, 7).unwrap();
+        assert_eq!(again, output.as_bytes());
+    }
+}
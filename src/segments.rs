@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use std::{collections::HashMap, io::Read};
+
+use crate::{errors::EngineError, ids::ClientId};
+
+/// Segment name of figures over every client.
+pub const ALL_CLIENTS: &str = "all";
+
+#[derive(Deserialize)]
+struct SegmentRow {
+    client: ClientId,
+    segment: String,
+}
+
+/// Client segments or tags (retail, VIP, test, a partner's name and the
+/// like) that amount histograms and reports break their figures down by.
+/// A client may be in any number of segments; clients in none are only
+/// counted under [`ALL_CLIENTS`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientSegments(HashMap<ClientId, Vec<String>>);
+
+impl ClientSegments {
+    /// Reads a CSV mapping with a `client,segment` header, one row per
+    /// segment a client is in. A segment named [`ALL_CLIENTS`] is rejected,
+    /// since its figures would be counted into those of every client.
+    pub fn load<R: Read>(reader: R) -> Result<Self, EngineError> {
+        let mut segments = ClientSegments::default();
+        for row in csv::Reader::from_reader(reader).deserialize::<SegmentRow>() {
+            let row = row?;
+            if row.segment == ALL_CLIENTS {
+                return Err(EngineError::Usage(format!(
+                    "client {} is in segment {ALL_CLIENTS}, which is reserved for every client",
+                    row.client
+                )));
+            }
+            segments.insert(row.client, row.segment);
+        }
+        Ok(segments)
+    }
+
+    pub fn insert(&mut self, client: ClientId, segment: impl Into<String>) {
+        let segment = segment.into();
+        let client_segments = self.0.entry(client).or_default();
+        if !client_segments.contains(&segment) {
+            client_segments.push(segment);
+        }
+    }
+
+    /// The segments of `client`, in the order they were added.
+    pub fn segments(&self, client: ClientId) -> impl Iterator<Item = &str> {
+        self.0
+            .get(&client)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// The first segment of `client`, from when a client could only be in
+    /// one.
+    #[deprecated(note = "clients may be in several segments; use `segments`")]
+    pub fn segment(&self, client: ClientId) -> Option<&str> {
+        self.segments(client).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_can_be_in_several_segments() {
+        let segments =
+            ClientSegments::load("client,segment\n1,vip\n2,test\n1,partner-x\n1,vip\n".as_bytes())
+                .unwrap();
+        assert_eq!(
+            segments.segments(ClientId(1)).collect::<Vec<_>>(),
+            ["vip", "partner-x"]
+        );
+        assert_eq!(segments.segments(ClientId(2)).collect::<Vec<_>>(), ["test"]);
+        assert_eq!(segments.segments(ClientId(3)).count(), 0);
+        #[allow(deprecated)]
+        let first = segments.segment(ClientId(1));
+        assert_eq!(first, Some("vip"));
+    }
+
+    #[test]
+    fn the_all_clients_segment_is_reserved() {
+        assert!(matches!(
+            ClientSegments::load("client,segment\n1,vip\n2,all\n".as_bytes()),
+            Err(EngineError::Usage(_))
+        ));
+    }
+}
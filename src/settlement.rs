@@ -0,0 +1,199 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{fmt, io::Write};
+
+use crate::{
+    Engine,
+    client::Client,
+    errors::EngineError,
+    format::{AmountEncoding, Format, JsonAmount},
+    formatting::format_decimal,
+    ids::ClientId,
+    money::Money,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    Eligible,
+    BelowMinimum,
+    NoFunds,
+    Locked,
+}
+
+impl PayoutStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayoutStatus::Eligible => "eligible",
+            PayoutStatus::BelowMinimum => "below_minimum",
+            PayoutStatus::NoFunds => "no_funds",
+            PayoutStatus::Locked => "locked",
+        }
+    }
+}
+
+impl fmt::Display for PayoutStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Close-of-day payout for one client. Only `available` funds are paid out;
+/// held funds stay behind until their disputes settle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayoutLine {
+    pub client: ClientId,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub payout: Decimal,
+    pub locked: bool,
+    pub status: PayoutStatus,
+}
+
+fn payout_line(client: &Client, min_payout: Money) -> PayoutLine {
+    let status = if client.locked {
+        PayoutStatus::Locked
+    } else if client.available <= Decimal::ZERO {
+        PayoutStatus::NoFunds
+    } else if client.available < min_payout.value() {
+        PayoutStatus::BelowMinimum
+    } else {
+        PayoutStatus::Eligible
+    };
+    let payout = if status == PayoutStatus::Eligible {
+        client.available
+    } else {
+        Decimal::ZERO
+    };
+    PayoutLine {
+        client: client.id,
+        available: client.available,
+        held: client.held.value(),
+        payout,
+        locked: client.locked,
+        status,
+    }
+}
+
+pub fn settle(engine: &Engine, min_payout: Money) -> Result<Vec<PayoutLine>, EngineError> {
+    let mut lines = Vec::new();
+    engine.visit_clients(|client| {
+        lines.push(payout_line(client, min_payout));
+        Ok(())
+    })?;
+    Ok(lines)
+}
+
+pub const SETTLEMENT_HEADER: [&str; 6] =
+    ["client", "available", "held", "payout", "locked", "status"];
+
+#[derive(Serialize)]
+struct JsonPayoutLine {
+    client: ClientId,
+    available: JsonAmount,
+    held: JsonAmount,
+    payout: JsonAmount,
+    locked: bool,
+    status: PayoutStatus,
+}
+
+pub fn write_settlement_report<W: Write>(
+    lines: &[PayoutLine],
+    format: Format,
+    encoding: AmountEncoding,
+    mut writer: W,
+) -> Result<(), EngineError> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            csv_writer.write_record(SETTLEMENT_HEADER)?;
+            for line in lines {
+                csv_writer.write_record([
+                    line.client.to_string(),
+                    format_decimal(line.available),
+                    format_decimal(line.held),
+                    format_decimal(line.payout),
+                    line.locked.to_string(),
+                    line.status.to_string(),
+                ])?;
+            }
+            csv_writer.flush()?;
+        }
+        Format::Json => {
+            for line in lines {
+                let json_line = JsonPayoutLine {
+                    client: line.client,
+                    available: JsonAmount(line.available, encoding),
+                    held: JsonAmount(line.held, encoding),
+                    payout: JsonAmount(line.payout, encoding),
+                    locked: line.locked,
+                    status: line.status,
+                };
+                serde_json::to_writer(&mut writer, &json_line)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    fn engine(csv: &str) -> Engine {
+        let mut engine = Engine::new();
+        engine.process(Cursor::new(csv.as_bytes())).unwrap();
+        engine
+    }
+
+    #[test]
+    fn pays_out_available_funds_and_excludes_held() {
+        let engine = engine(
+            "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,4.0\ndispute,1,2,\ndeposit,2,3,0.5\n",
+        );
+
+        let lines = settle(&engine, Money::new(dec!(1)).unwrap()).unwrap();
+
+        assert_eq!(lines[0].status, PayoutStatus::Eligible);
+        assert_eq!(lines[0].payout, dec!(10));
+        assert_eq!(lines[0].held, dec!(4));
+        assert_eq!(lines[1].status, PayoutStatus::BelowMinimum);
+        assert_eq!(lines[1].payout, dec!(0));
+    }
+
+    #[test]
+    fn locked_accounts_are_flagged_and_not_paid() {
+        let engine = engine(
+            "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,4.0\ndispute,1,2,\nchargeback,1,2,\n",
+        );
+
+        let lines = settle(&engine, Money::ZERO).unwrap();
+
+        assert_eq!(lines[0].status, PayoutStatus::Locked);
+        assert!(lines[0].locked);
+        assert_eq!(lines[0].payout, dec!(0));
+    }
+
+    #[test]
+    fn csv_report_lists_every_client() {
+        let engine = engine("type,client,tx,amount\ndeposit,1,1,2.0\n");
+        let mut output = Vec::new();
+
+        write_settlement_report(
+            &settle(&engine, Money::ZERO).unwrap(),
+            Format::Csv,
+            AmountEncoding::String,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,payout,locked,status\n1,2.0000,0.0000,2.0000,false,eligible\n"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, BufReader, Read, Write},
+};
+
+use crate::{
+    client::Client, errors::EngineError, history::BalanceHistory, numeric::Balance,
+    review::ReviewItem,
+};
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// First bytes of a snapshot written by `Snapshot::save_encrypted`, followed
+/// by a 12-byte nonce and the ChaCha20-Poly1305 sealed JSON.
+pub const ENCRYPTED_MAGIC: &[u8] = b"PESNAP\x00\x01";
+
+/// Persisted engine state: every known client together with the deposits and
+/// disputes it still needs to honour future dispute/resolve/chargeback rows.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "B: Balance")]
+pub struct Snapshot<B = Decimal> {
+    pub version: u32,
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// SHA-256 digests of every input already applied to this state.
+    #[serde(default)]
+    pub processed_inputs: BTreeSet<String>,
+    pub clients: Vec<Client<B>>,
+    /// See `Engine::last_sequence`.
+    #[serde(default)]
+    pub last_sequence: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_history: Option<BalanceHistory>,
+    /// See `Engine::review_queue`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub review_queue: Vec<ReviewItem>,
+}
+
+impl<B: Balance> Snapshot<B> {
+    /// Reads a plaintext snapshot. An encrypted one is refused with
+    /// `EngineError::Encryption` rather than failing as malformed JSON.
+    pub fn load<R: Read>(reader: R) -> Result<Self, EngineError> {
+        let mut reader = BufReader::new(reader);
+        if reader.fill_buf()?.starts_with(ENCRYPTED_MAGIC) {
+            return Err(EngineError::Encryption(
+                "snapshot is encrypted and needs a key".to_string(),
+            ));
+        }
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), EngineError> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
+
+/// A 256-bit key for `Snapshot::save_encrypted` and `load_encrypted`. Only
+/// built with the `encryption` feature.
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+pub struct SnapshotKey(chacha20poly1305::Key);
+
+#[cfg(feature = "encryption")]
+impl SnapshotKey {
+    pub const LEN: usize = 32;
+
+    pub fn new(bytes: [u8; Self::LEN]) -> Self {
+        SnapshotKey(bytes.into())
+    }
+
+    /// 64 hex digits; surrounding whitespace is ignored.
+    pub fn from_hex(hex: &str) -> Result<Self, EngineError> {
+        let invalid = || EngineError::Encryption(format!("a key is {} hex digits", Self::LEN * 2));
+        let hex = hex.trim().as_bytes();
+        if hex.len() != Self::LEN * 2 {
+            return Err(invalid());
+        }
+        let mut bytes = [0; Self::LEN];
+        for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(SnapshotKey::new(bytes))
+    }
+
+    /// A key file holds either the 64 hex digits or the 32 raw bytes.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, EngineError> {
+        let contents = std::fs::read(path)?;
+        match <[u8; Self::LEN]>::try_from(contents.as_slice()) {
+            Ok(bytes) => Ok(SnapshotKey::new(bytes)),
+            Err(_) => Self::from_hex(&String::from_utf8_lossy(&contents)),
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl std::fmt::Debug for SnapshotKey {
+    /// Never prints the key.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SnapshotKey(..)")
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl<B: Balance> Snapshot<B> {
+    /// Like `save`, but sealed with ChaCha20-Poly1305 under `key` and a
+    /// fresh random nonce, so no balance is ever written in plaintext.
+    pub fn save_encrypted<W: Write>(
+        &self,
+        mut writer: W,
+        key: &SnapshotKey,
+    ) -> Result<(), EngineError> {
+        use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+
+        let cipher = chacha20poly1305::ChaCha20Poly1305::new(&key.0);
+        let nonce = chacha20poly1305::ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &serde_json::to_vec(self)?,
+                    aad: ENCRYPTED_MAGIC,
+                },
+            )
+            .map_err(|_| EngineError::Encryption("sealing failed".to_string()))?;
+        writer.write_all(ENCRYPTED_MAGIC)?;
+        writer.write_all(&nonce)?;
+        writer.write_all(&sealed)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `save_encrypted`. A wrong key, or any
+    /// change to the file, fails authentication rather than loading.
+    pub fn load_encrypted<R: Read>(mut reader: R, key: &SnapshotKey) -> Result<Self, EngineError> {
+        use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+
+        const NONCE_LEN: usize = 12;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let sealed = bytes
+            .strip_prefix(ENCRYPTED_MAGIC)
+            .filter(|sealed| sealed.len() >= NONCE_LEN)
+            .ok_or_else(|| EngineError::Encryption("snapshot is not encrypted".to_string()))?;
+        let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+        let json = chacha20poly1305::ChaCha20Poly1305::new(&key.0)
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: sealed,
+                    aad: ENCRYPTED_MAGIC,
+                },
+            )
+            .map_err(|_| {
+                EngineError::Encryption("wrong key, or the snapshot was altered".to_string())
+            })?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use std::io::Cursor;
+
+    fn snapshot() -> Snapshot {
+        let mut engine = Engine::new();
+        engine
+            .process(Cursor::new("type,client,tx,amount\ndeposit,7,1,123.45\n"))
+            .unwrap();
+        engine.snapshot().unwrap()
+    }
+
+    #[test]
+    fn encrypted_snapshots_round_trip_and_hide_balances() {
+        let key = SnapshotKey::new([7; SnapshotKey::LEN]);
+        let mut sealed = Vec::new();
+        snapshot().save_encrypted(&mut sealed, &key).unwrap();
+
+        assert!(sealed.starts_with(ENCRYPTED_MAGIC));
+        assert!(!String::from_utf8_lossy(&sealed).contains("123.45"));
+        let restored: Snapshot = Snapshot::load_encrypted(sealed.as_slice(), &key).unwrap();
+        assert_eq!(restored.clients[0].total, rust_decimal::dec!(123.45));
+        assert!(matches!(
+            Snapshot::<Decimal>::load(sealed.as_slice()),
+            Err(EngineError::Encryption(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_keys_and_tampering_are_detected() {
+        let key = SnapshotKey::from_hex(&"ab".repeat(SnapshotKey::LEN)).unwrap();
+        let mut sealed = Vec::new();
+        snapshot().save_encrypted(&mut sealed, &key).unwrap();
+
+        let other = SnapshotKey::new([0; SnapshotKey::LEN]);
+        assert!(Snapshot::<Decimal>::load_encrypted(sealed.as_slice(), &other).is_err());
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(Snapshot::<Decimal>::load_encrypted(sealed.as_slice(), &key).is_err());
+        assert!(SnapshotKey::from_hex("abc").is_err());
+        assert!(!format!("{key:?}").contains("ab"));
+    }
+}
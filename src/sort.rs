@@ -0,0 +1,256 @@
+use csv::StringRecord;
+use log::error;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::errors::EngineError;
+
+/// Columns of the merged output, in order. Inputs may carry them in any
+/// order; columns outside this list are dropped.
+pub const SORT_HEADER: [&str; 7] = [
+    "type",
+    "client",
+    "tx",
+    "amount",
+    "tenant",
+    "reference",
+    "timestamp",
+];
+
+const TIMESTAMP: usize = SORT_HEADER.len() - 1;
+
+/// Ordering key for the `timestamp` column. Integers (Unix times) compare
+/// numerically; anything else compares as text, which orders ISO 8601
+/// timestamps correctly as long as they share a format and offset.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Unix(i64),
+    Text(String),
+}
+
+impl SortKey {
+    fn parse(value: &str) -> Self {
+        match value.trim().parse() {
+            Ok(seconds) => SortKey::Unix(seconds),
+            Err(_) => SortKey::Text(value.trim().to_string()),
+        }
+    }
+}
+
+/// Disk-backed sort of CSV inputs by their `timestamp` column, so rows that
+/// arrive out of order across files are applied chronologically. Inputs are
+/// cut into sorted chunks of at most `chunk_rows` rows, spilled to
+/// `spill_dir`, then k-way merged. Rows with equal timestamps keep their input
+/// order (files in the order given, then row order).
+pub struct ExternalSort {
+    spill_dir: PathBuf,
+    chunk_rows: usize,
+}
+
+impl ExternalSort {
+    pub fn new(spill_dir: impl Into<PathBuf>) -> Self {
+        ExternalSort {
+            spill_dir: spill_dir.into(),
+            chunk_rows: 100_000,
+        }
+    }
+
+    pub fn chunk_rows(mut self, rows: usize) -> Self {
+        self.chunk_rows = rows.max(1);
+        self
+    }
+
+    /// Writes every row of `inputs`, ordered by timestamp, to `writer` as CSV
+    /// with a [`SORT_HEADER`] header row.
+    pub fn sort<R: Read, W: Write>(
+        &self,
+        inputs: impl IntoIterator<Item = R>,
+        writer: W,
+    ) -> Result<(), EngineError> {
+        let mut spill = SpillDir::create(&self.spill_dir)?;
+        let mut chunks = Vec::new();
+        let mut rows: Vec<(SortKey, u64, StringRecord)> = Vec::with_capacity(self.chunk_rows);
+        let mut sequence = 0u64;
+
+        for input in inputs {
+            let mut reader = csv::Reader::from_reader(input);
+            let header = reader.headers()?.clone();
+            let positions = project(&header)?;
+
+            for (row_index, result) in reader.into_records().enumerate() {
+                let record = match result {
+                    Ok(record) => record,
+                    Err(err) => {
+                        error!("Error parsing CSV row {}: {}", row_index + 1, err);
+                        continue;
+                    }
+                };
+                let projected: StringRecord = positions
+                    .iter()
+                    .map(|position| position.and_then(|index| record.get(index)).unwrap_or(""))
+                    .collect();
+                if projected[TIMESTAMP].trim().is_empty() {
+                    error!("Skipping CSV row {} without a timestamp", row_index + 1);
+                    continue;
+                }
+                rows.push((SortKey::parse(&projected[TIMESTAMP]), sequence, projected));
+                sequence += 1;
+
+                if rows.len() == self.chunk_rows {
+                    chunks.push(spill.write_chunk(&mut rows)?);
+                }
+            }
+        }
+        if !rows.is_empty() {
+            chunks.push(spill.write_chunk(&mut rows)?);
+        }
+
+        merge(&chunks, writer)
+    }
+}
+
+/// Maps each [`SORT_HEADER`] column to its position in `header`.
+fn project(header: &StringRecord) -> Result<Vec<Option<usize>>, EngineError> {
+    let positions: Vec<Option<usize>> = SORT_HEADER
+        .iter()
+        .map(|column| header.iter().position(|candidate| candidate == *column))
+        .collect();
+    let missing: Vec<String> = ["type", "client", "tx", "timestamp"]
+        .iter()
+        .filter(|column| !header.iter().any(|candidate| candidate == **column))
+        .map(|column| column.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(EngineError::InvalidHeader {
+            missing,
+            unexpected: Vec::new(),
+            duplicated: Vec::new(),
+        });
+    }
+    Ok(positions)
+}
+
+fn merge<W: Write>(chunks: &[PathBuf], writer: W) -> Result<(), EngineError> {
+    let mut readers = chunks
+        .iter()
+        .map(|path| {
+            Ok(csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(BufReader::new(File::open(path)?)))
+        })
+        .collect::<Result<Vec<_>, EngineError>>()?;
+
+    let mut heads: Vec<StringRecord> = Vec::with_capacity(readers.len());
+    let mut heap = BinaryHeap::new();
+    for (chunk, reader) in readers.iter_mut().enumerate() {
+        let mut record = StringRecord::new();
+        if reader.read_record(&mut record)? {
+            heap.push(Reverse(chunk_key(&record, chunk)));
+        }
+        heads.push(record);
+    }
+
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(SORT_HEADER)?;
+    while let Some(Reverse((_, _, chunk))) = heap.pop() {
+        csv_writer.write_record(heads[chunk].iter().skip(1))?;
+        if readers[chunk].read_record(&mut heads[chunk])? {
+            heap.push(Reverse(chunk_key(&heads[chunk], chunk)));
+        }
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Spilled rows are `sequence` followed by the projected columns.
+fn chunk_key(record: &StringRecord, chunk: usize) -> (SortKey, u64, usize) {
+    let sequence = record[0].parse().unwrap_or(u64::MAX);
+    (SortKey::parse(&record[TIMESTAMP + 1]), sequence, chunk)
+}
+
+static SPILL_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Private directory for one sort's chunk files, removed when dropped.
+struct SpillDir {
+    path: PathBuf,
+    chunks: usize,
+}
+
+impl SpillDir {
+    fn create(parent: &Path) -> Result<Self, EngineError> {
+        let path = parent.join(format!(
+            "rust-payments-engine-sort-{}-{}",
+            std::process::id(),
+            SPILL_DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(SpillDir { path, chunks: 0 })
+    }
+
+    fn write_chunk(
+        &mut self,
+        rows: &mut Vec<(SortKey, u64, StringRecord)>,
+    ) -> Result<PathBuf, EngineError> {
+        rows.sort_unstable_by(|left, right| (&left.0, left.1).cmp(&(&right.0, right.1)));
+        let path = self.path.join(format!("chunk-{}.csv", self.chunks));
+        self.chunks += 1;
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(BufWriter::new(File::create(&path)?));
+        for (_, sequence, record) in rows.drain(..) {
+            let sequence = sequence.to_string();
+            csv_writer.write_record(std::iter::once(sequence.as_str()).chain(record.iter()))?;
+        }
+        csv_writer.flush()?;
+        Ok(path)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_files_chronologically_across_spilled_chunks() {
+        let first = "timestamp,type,client,tx,amount\n30,withdrawal,1,3,1.0\n10,deposit,1,1,5.0\n";
+        let second = "type,client,tx,amount,timestamp\ndeposit,1,2,1.0,20\ndeposit,1,4,1.0,10\n";
+        let mut output = Vec::new();
+
+        ExternalSort::new(std::env::temp_dir())
+            .chunk_rows(1)
+            .sort([first.as_bytes(), second.as_bytes()], &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "type,client,tx,amount,tenant,reference,timestamp\n\
+             deposit,1,1,5.0,,,10\n\
+             deposit,1,4,1.0,,,10\n\
+             deposit,1,2,1.0,,,20\n\
+             withdrawal,1,3,1.0,,,30\n"
+        );
+    }
+
+    #[test]
+    fn inputs_without_a_timestamp_column_are_rejected() {
+        let result = ExternalSort::new(std::env::temp_dir())
+            .sort(["type,client,tx,amount\n".as_bytes()], Vec::new());
+
+        assert!(matches!(
+            result,
+            Err(EngineError::InvalidHeader { missing, .. }) if missing == ["timestamp"]
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! Relational export of the final engine state, for analysts who would rather
+//! query results with SQL. Only built with the `sqlite` feature.
+
+use rusqlite::{Connection, params};
+use std::{path::Path, time::UNIX_EPOCH};
+
+use crate::{Engine, errors::EngineError, formatting::format_decimal};
+
+/// Amounts are stored as exact four-place text, like the CSV output, so no
+/// value goes through a float. `CAST(amount AS REAL)` when precision does not
+/// matter.
+const SCHEMA: &str = "
+DROP TABLE IF EXISTS disputes;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS accounts;
+CREATE TABLE accounts (
+    client INTEGER PRIMARY KEY,
+    available TEXT NOT NULL,
+    held TEXT NOT NULL,
+    total TEXT NOT NULL,
+    locked INTEGER NOT NULL
+);
+CREATE TABLE transactions (
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    tx INTEGER NOT NULL,
+    type TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    PRIMARY KEY (client, tx)
+);
+CREATE TABLE disputes (
+    client INTEGER NOT NULL REFERENCES accounts (client),
+    tx INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    opened_at INTEGER,
+    PRIMARY KEY (client, tx)
+);
+";
+
+impl Engine {
+    /// Writes accounts, remembered deposits and open disputes to the SQLite
+    /// database at `path`, replacing any tables a previous export created.
+    /// `disputes.opened_at` is in Unix seconds and null when unknown.
+    pub fn export_to_sqlite(&self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let mut connection = Connection::open(path)?;
+        let export = connection.transaction()?;
+        export.execute_batch(SCHEMA)?;
+        {
+            let mut accounts = export.prepare(
+                "INSERT INTO accounts (client, available, held, total, locked) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            let mut transactions = export.prepare(
+                "INSERT INTO transactions (client, tx, type, amount) VALUES (?1, ?2, 'deposit', ?3)",
+            )?;
+            let mut disputes = export.prepare(
+                "INSERT INTO disputes (client, tx, amount, opened_at) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+
+            self.visit_clients(|client| {
+                accounts.execute(params![
+                    client.id.0,
+                    format_decimal(client.available),
+                    format_decimal(client.held.value()),
+                    format_decimal(client.total),
+                    client.locked,
+                ])?;
+                for (tx, amount) in client.deposits() {
+                    transactions.execute(params![
+                        client.id.0,
+                        tx.0,
+                        format_decimal(amount.value())
+                    ])?;
+                }
+                for (tx, amount) in client.open_disputes() {
+                    let opened_at = client.dispute_opened(tx).map(|opened| {
+                        opened
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs() as i64
+                    });
+                    disputes.execute(params![
+                        client.id.0,
+                        tx.0,
+                        format_decimal(amount.value()),
+                        opened_at,
+                    ])?;
+                }
+                Ok(())
+            })?;
+        }
+        export.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn exports_accounts_deposits_and_open_disputes() {
+        let mut engine = Engine::new();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,2.5\ndispute,1,2,\ndeposit,2,3,1.0\n",
+            ))
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("engine-export-{}.db", std::process::id()));
+
+        engine.export_to_sqlite(&path).unwrap();
+        engine.export_to_sqlite(&path).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let held: String = connection
+            .query_row("SELECT held FROM accounts WHERE client = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let deposits: i64 = connection
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))
+            .unwrap();
+        let disputed: (i64, String) = connection
+            .query_row("SELECT tx, amount FROM disputes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(held, "2.5000");
+        assert_eq!(deposits, 3);
+        assert_eq!(disputed, (2, "2.5000".to_string()));
+    }
+}
@@ -0,0 +1,238 @@
+use rust_decimal::Decimal;
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use crate::{
+    Engine, engine::ingest::read_input, errors::EngineError, formatting::format_decimal,
+    ids::ClientId, transaction::TransactionType,
+};
+
+/// One input row of a client's statement, with the balances right after it.
+/// Rejected rows are listed too, with unchanged balances and the reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementLine {
+    pub row: usize,
+    pub tx_type: TransactionType,
+    pub tx: i64,
+    pub amount: Option<Decimal>,
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+    pub applied: bool,
+    /// Dispute annotations, or why the row was rejected.
+    pub notes: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatementFormat {
+    #[default]
+    Text,
+    Csv,
+}
+
+impl FromStr for StatementFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(StatementFormat::Text),
+            "csv" => Ok(StatementFormat::Csv),
+            other => Err(format!("unknown statement format {other}")),
+        }
+    }
+}
+
+pub const STATEMENT_HEADER: [&str; 10] = [
+    "row",
+    "type",
+    "tx",
+    "amount",
+    "available",
+    "held",
+    "total",
+    "locked",
+    "applied",
+    "notes",
+];
+
+impl Engine {
+    /// Processes `source` like [`Engine::process`] and returns `client`'s
+    /// statement: every row for that client in input order, with the running
+    /// balances after it. Deposits are annotated when they are later
+    /// disputed, resolved or charged back.
+    pub fn statement<R: Read>(
+        &mut self,
+        source: R,
+        client: ClientId,
+    ) -> Result<Vec<StatementLine>, EngineError> {
+        let (_, rows) = read_input(source, self.config(), &self.preprocessors, false)?;
+        let mut lines: Vec<StatementLine> = Vec::new();
+        for transaction in rows.flatten() {
+            if transaction.client != client {
+                self.apply_guarded(transaction)?;
+                continue;
+            }
+            let (row, tx_type, tx, amount) = (
+                transaction.row,
+                transaction.tx_type,
+                transaction.tx,
+                transaction.amount,
+            );
+            let rejection = self.apply_guarded(transaction)?;
+            let Some(state) = self.client(client) else {
+                continue;
+            };
+            let mut notes = Vec::new();
+            match (&rejection, tx_type) {
+                (Some(reason), _) => notes.push(format!("rejected: {reason}")),
+                (None, TransactionType::Dispute) => notes.push(format!("disputes tx {tx}")),
+                (None, TransactionType::Resolve) => {
+                    notes.push(format!("resolves dispute on tx {tx}"))
+                }
+                (None, TransactionType::Chargeback) => {
+                    notes.push(format!("charges back tx {tx}; account locked"))
+                }
+                (None, _) => {}
+            }
+            let dispute_step = matches!(
+                tx_type,
+                TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback
+            );
+            if rejection.is_none() && dispute_step {
+                let deposit = lines.iter_mut().find(|line| {
+                    line.tx == tx && line.tx_type == TransactionType::Deposit && line.applied
+                });
+                if let Some(deposit) = deposit {
+                    deposit.notes.push(format!("{tx_type} at row {row}"));
+                }
+            }
+            lines.push(StatementLine {
+                row,
+                tx_type,
+                tx,
+                amount,
+                available: state.available,
+                held: state.held.value(),
+                total: state.total,
+                locked: state.locked,
+                applied: rejection.is_none(),
+                notes,
+            });
+        }
+        self.evict_idle()?;
+        Ok(lines)
+    }
+}
+
+pub fn write_statement<W: Write>(
+    lines: &[StatementLine],
+    format: StatementFormat,
+    mut writer: W,
+) -> Result<(), EngineError> {
+    let fields = |line: &StatementLine| {
+        [
+            line.row.to_string(),
+            line.tx_type.to_string(),
+            line.tx.to_string(),
+            line.amount.map(format_decimal).unwrap_or_default(),
+            format_decimal(line.available),
+            format_decimal(line.held),
+            format_decimal(line.total),
+            line.locked.to_string(),
+            line.applied.to_string(),
+            line.notes.join("; "),
+        ]
+    };
+    match format {
+        StatementFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            csv_writer.write_record(STATEMENT_HEADER)?;
+            for line in lines {
+                csv_writer.write_record(fields(line))?;
+            }
+            csv_writer.flush()?;
+        }
+        StatementFormat::Text => {
+            // Every column but the trailing notes is padded to its widest value.
+            let rows: Vec<[String; 10]> = std::iter::once(STATEMENT_HEADER.map(String::from))
+                .chain(lines.iter().map(fields))
+                .collect();
+            let mut widths = [0; 9];
+            for row in &rows {
+                for (width, field) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(field.len());
+                }
+            }
+            for row in &rows {
+                let mut text = String::new();
+                for (width, field) in widths.iter().zip(row) {
+                    let _ = write!(text, "{field:<width$}  ");
+                }
+                text.push_str(&row[9]);
+                writeln!(writer, "{}", text.trim_end())?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    fn statement() -> Vec<StatementLine> {
+        let input = "type,client,tx,amount\n\
+                     deposit,7,1,10.0\n\
+                     deposit,8,2,3.0\n\
+                     withdrawal,7,3,4.0\n\
+                     dispute,7,1,\n\
+                     withdrawal,7,4,1.0\n\
+                     resolve,7,1,\n";
+        Engine::new()
+            .statement(Cursor::new(input), ClientId(7))
+            .unwrap()
+    }
+
+    #[test]
+    fn tracks_running_balances_and_annotates_disputes() {
+        let lines = statement();
+
+        let balances: Vec<(usize, Decimal, Decimal)> = lines
+            .iter()
+            .map(|line| (line.row, line.available, line.held))
+            .collect();
+        assert_eq!(
+            balances,
+            [
+                (1, dec!(10), dec!(0)),
+                (3, dec!(6), dec!(0)),
+                (4, dec!(-4), dec!(10)),
+                (5, dec!(-4), dec!(10)),
+                (6, dec!(6), dec!(0)),
+            ]
+        );
+        assert_eq!(lines[0].notes, ["dispute at row 4", "resolve at row 6"]);
+        assert!(!lines[3].applied);
+        assert!(lines[3].notes[0].starts_with("rejected: "));
+    }
+
+    #[test]
+    fn text_statement_aligns_columns() {
+        let mut output = Vec::new();
+        write_statement(&statement()[..2], StatementFormat::Text, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "row  type        tx  amount   available  held    total    locked  applied  notes\n\
+             1    deposit     1   10.0000  10.0000    0.0000  10.0000  false   true     dispute at row 4; resolve at row 6\n\
+             3    withdrawal  3   4.0000   6.0000     0.0000  6.0000   false   true\n"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+};
+
+use crate::{
+    Engine,
+    config::EngineConfig,
+    errors::EngineError,
+    format::Format,
+    formatting::FormattingOptions,
+    output::{AccountSummary, by_client, write_summaries},
+    sample::{SplitMix64, client_column},
+    verify::{Mismatch, verify_accounts},
+};
+
+/// One reordering whose accounts differ from those of the input as given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StressFailure {
+    /// Seed of the reordering, to replay it with a single run.
+    pub seed: u64,
+    /// What differs, expected being the accounts of the input as given.
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Row order for `records` that keeps every client's rows in their input
+/// order but interleaves clients at random. The same `seed` always gives
+/// the same order.
+fn interleave(records: &[csv::ByteRecord], client_index: usize, seed: u64) -> Vec<usize> {
+    fn client(record: &csv::ByteRecord, index: usize) -> &[u8] {
+        record.get(index).unwrap_or_default().trim_ascii()
+    }
+    let mut queues: HashMap<&[u8], VecDeque<usize>> = HashMap::new();
+    for (index, record) in records.iter().enumerate() {
+        queues
+            .entry(client(record, client_index))
+            .or_default()
+            .push_back(index);
+    }
+
+    // Shuffling one client label per row and handing each slot its client's
+    // next row picks uniformly among the orders that keep clients' rows in
+    // sequence.
+    let mut slots: Vec<&[u8]> = records
+        .iter()
+        .map(|record| client(record, client_index))
+        .collect();
+    let mut random = SplitMix64(seed);
+    for last in (1..slots.len()).rev() {
+        let pick = (random.next_u64() % (last as u64 + 1)) as usize;
+        slots.swap(last, pick);
+    }
+    slots
+        .into_iter()
+        .filter_map(|client| queues.get_mut(client).and_then(VecDeque::pop_front))
+        .collect()
+}
+
+fn replay<'a>(
+    header: &csv::ByteRecord,
+    records: impl Iterator<Item = &'a csv::ByteRecord>,
+    config: &EngineConfig,
+) -> Result<Engine, EngineError> {
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+    writer.write_byte_record(header)?;
+    for record in records {
+        writer.write_byte_record(record)?;
+    }
+    let input = writer
+        .into_inner()
+        .map_err(|err| EngineError::from(err.into_error()))?;
+
+    let mut engine = Engine::with_config(EngineConfig {
+        has_headers: true,
+        input_format: Format::Csv,
+        input_delimiter: b',',
+        detect_input_format: false,
+        ..config.clone()
+    });
+    engine.process(input.as_slice())?;
+    Ok(engine)
+}
+
+/// Checks that the CSV `source` does not depend on how its clients' rows
+/// are interleaved, as sharding by client or merging partner files
+/// assumes. The input is replayed `runs` times under `config`, each time
+/// with clients interleaved at random but every client's rows in input
+/// order, and the accounts compared with those of the input as given.
+/// Run `n` uses seed `seed + n`.
+///
+/// The input must have a header with a `client` column; it is held in
+/// memory. Returns the reorderings that changed any account, empty when
+/// every run agreed.
+pub fn stress_test<R: Read>(
+    source: R,
+    config: &EngineConfig,
+    seed: u64,
+    runs: usize,
+) -> Result<Vec<StressFailure>, EngineError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(source);
+    let header = reader.byte_headers()?.clone();
+    let client_index = client_column(&header)?;
+    let records = reader.byte_records().collect::<Result<Vec<_>, _>>()?;
+
+    let baseline = replay(&header, records.iter(), config)?;
+    let mut accounts = Vec::new();
+    baseline.visit_clients(|client| {
+        accounts.push(AccountSummary::from(client));
+        Ok(())
+    })?;
+    let mut expected = Vec::new();
+    write_summaries(
+        &accounts,
+        &mut expected,
+        &FormattingOptions::default(),
+        by_client,
+    )?;
+
+    let mut failures = Vec::new();
+    for run in 0..runs as u64 {
+        let seed = seed.wrapping_add(run);
+        let order = interleave(&records, client_index, seed);
+        let engine = replay(
+            &header,
+            order.into_iter().map(|index| &records[index]),
+            config,
+        )?;
+        let mismatches = verify_accounts(&engine, expected.as_slice())?;
+        if !mismatches.is_empty() {
+            failures.push(StressFailure { seed, mismatches });
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorderings_keep_client_order_and_expose_cross_client_dependencies() {
+        let input = "type,client,tx,amount\n\
+            deposit,1,1,10\n\
+            deposit,2,2,5\n\
+            withdrawal,1,3,4\n\
+            dispute,2,2,\n\
+            deposit,3,4,1\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+        let records: Vec<_> = reader.byte_records().map(Result::unwrap).collect();
+        let order = interleave(&records, 1, 3);
+        let position = |row| order.iter().position(|index| *index == row).unwrap();
+        assert!(position(0) < position(2) && position(1) < position(3));
+        assert_eq!(order, interleave(&records, 1, 3));
+
+        let config = EngineConfig::default();
+        assert!(
+            stress_test(input.as_bytes(), &config, 0, 20)
+                .unwrap()
+                .is_empty()
+        );
+
+        let config = EngineConfig {
+            strict_tx_order: true,
+            ..EngineConfig::default()
+        };
+        let failures = stress_test(input.as_bytes(), &config, 0, 20).unwrap();
+        assert!(!failures.is_empty());
+        let [failure] = stress_test(input.as_bytes(), &config, failures[0].seed, 1)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(failure, failures[0]);
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{
+    Engine, RowCounts,
+    config::EngineConfig,
+    engine::{ingest::read_input, output::account_record},
+    errors::EngineError,
+    format::{Format, write_json_accounts},
+};
+
+/// Keeps one isolated `Engine` per tenant so a single process can serve
+/// several partners without their client ids colliding. Rows without a
+/// `tenant` column value belong to the default tenant.
+pub struct TenantEngines {
+    config: EngineConfig,
+    default_tenant: String,
+    engines: BTreeMap<String, Engine>,
+    rows: RowCounts,
+    interrupt: Option<Arc<AtomicBool>>,
+}
+
+impl TenantEngines {
+    pub fn new(default_tenant: impl Into<String>) -> Self {
+        TenantEngines::with_config(default_tenant, EngineConfig::default())
+    }
+
+    pub fn with_config(default_tenant: impl Into<String>, config: EngineConfig) -> Self {
+        TenantEngines {
+            config,
+            default_tenant: default_tenant.into(),
+            engines: BTreeMap::new(),
+            rows: RowCounts::default(),
+            interrupt: None,
+        }
+    }
+
+    /// Makes `process` stop before the next row once `flag` is set, as
+    /// [`Engine::set_interrupt_flag`] does.
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    pub fn process<R: Read>(&mut self, source: R) -> Result<(), EngineError> {
+        let (_, rows) = read_input(source, &self.config, &[], false)?;
+        let mut last_row = 0;
+        for row in rows {
+            if self
+                .interrupt
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::SeqCst))
+            {
+                return Err(EngineError::Interrupted { row: last_row });
+            }
+            let Ok(transaction) = row else {
+                self.rows.record(true);
+                continue;
+            };
+            last_row = transaction.row;
+            let tenant = transaction
+                .tenant
+                .clone()
+                .unwrap_or_else(|| self.default_tenant.clone());
+            let engine = self.engines.entry(tenant.clone()).or_insert_with(|| {
+                let mut engine = Engine::with_tenant(tenant);
+                engine.set_config(self.config.clone());
+                engine
+            });
+            engine.expire_disputes()?;
+            let rejection = engine.apply_guarded(transaction)?;
+            self.rows.record(rejection.is_some());
+        }
+        Ok(())
+    }
+
+    pub fn row_counts(&self) -> RowCounts {
+        self.rows
+    }
+
+    pub fn engine(&self, tenant: &str) -> Option<&Engine> {
+        self.engines.get(tenant)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = (&str, &Engine)> {
+        self.engines
+            .iter()
+            .map(|(tenant, engine)| (tenant.as_str(), engine))
+    }
+
+    pub fn write_accounts<W: Write>(&self, writer: W) -> Result<(), EngineError> {
+        if self.config.output_format == Format::Json {
+            let accounts = self.engines.iter().flat_map(|(tenant, engine)| {
+                engine
+                    .sorted_clients()
+                    .into_iter()
+                    .map(move |client| (Some(tenant.as_str()), client))
+            });
+            return write_json_accounts(accounts, self.config.amount_encoding, writer);
+        }
+
+        let mut record_writer = self.config.formatting.record_writer(writer);
+        let mut header = vec!["tenant"];
+        header.extend(self.config.account_header());
+        record_writer.write_header(&header)?;
+
+        for (tenant, engine) in &self.engines {
+            for client in engine.sorted_clients() {
+                let mut record = vec![tenant.clone()];
+                record.extend(account_record(client, &self.config));
+                record_writer.write_record(record)?;
+            }
+        }
+
+        record_writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::ClientId;
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    fn process(csv: &str) -> TenantEngines {
+        let mut engines = TenantEngines::new("default");
+        engines.process(Cursor::new(csv.as_bytes())).unwrap();
+        engines
+    }
+
+    #[test]
+    fn same_client_id_is_isolated_per_tenant() {
+        let engines = process(
+            "type,client,tx,amount,tenant\ndeposit,1,1,5.0,acme\ndeposit,1,1,2.0,globex\nwithdrawal,1,2,3.0,globex\n",
+        );
+
+        assert_eq!(
+            engines
+                .engine("acme")
+                .unwrap()
+                .client(ClientId(1))
+                .unwrap()
+                .available,
+            dec!(5)
+        );
+        assert_eq!(
+            engines
+                .engine("globex")
+                .unwrap()
+                .client(ClientId(1))
+                .unwrap()
+                .available,
+            dec!(2)
+        );
+    }
+
+    #[test]
+    fn rows_without_tenant_use_the_default_tenant() {
+        let engines = process("type,client,tx,amount,tenant\ndeposit,1,1,5.0,\n");
+
+        assert!(engines.engine("default").is_some());
+        assert_eq!(engines.tenants().count(), 1);
+    }
+
+    #[test]
+    fn interrupted_runs_stop_before_the_next_row() {
+        let mut engines = TenantEngines::new("default");
+        engines.set_interrupt_flag(Arc::new(AtomicBool::new(true)));
+
+        let result = engines.process(Cursor::new(
+            "type,client,tx,amount,tenant\ndeposit,1,1,5.0,acme\n".as_bytes(),
+        ));
+
+        assert!(matches!(result, Err(EngineError::Interrupted { row: 0 })));
+        assert_eq!(engines.tenants().count(), 0);
+    }
+
+    #[test]
+    fn write_accounts_prefixes_each_row_with_its_tenant() {
+        let engines =
+            process("type,client,tx,amount,tenant\ndeposit,2,1,1.5,b\ndeposit,1,2,2.0,a\n");
+        let mut output = Vec::new();
+        engines.write_accounts(&mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "tenant,client,available,held,total,locked\na,1,2.0000,0.0000,2.0000,false\nb,2,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+}
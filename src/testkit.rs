@@ -0,0 +1,419 @@
+//! Helpers for end-to-end regression suites: build CSV fixtures, run them
+//! through an engine and compare the resulting accounts by value.
+//!
+//! ```
+//! use rust_decimal::dec;
+//! use rust_payments_engine::ids::ClientId;
+//! use rust_payments_engine::testkit::{AccountSummary, Fixture, assert_accounts, run};
+//!
+//! let csv = Fixture::new().deposit(1, 1, "5.0").withdrawal(1, 2, "1.5").to_csv();
+//! assert_accounts(
+//!     &run(&csv).unwrap(),
+//!     &[AccountSummary::new(ClientId(1), dec!(3.5), dec!(0), false)],
+//! );
+//! ```
+//!
+//! [`Scenario`] goes further for dispute sequences, checking balances
+//! between rows:
+//!
+//! ```
+//! use rust_payments_engine::testkit::Scenario;
+//!
+//! Scenario::new()
+//!     .deposit(1, 1, "5.0")
+//!     .dispute(1, 1)
+//!     .expect_available(1, "0")
+//!     .expect_held(1, "5")
+//!     .chargeback(1, 1)
+//!     .expect_total(1, "0")
+//!     .expect_locked(1, true)
+//!     .run();
+//! ```
+
+use rust_decimal::Decimal;
+use std::{
+    io::{Cursor, Read},
+    panic::Location,
+    str::FromStr,
+};
+
+use crate::{Engine, client::Client, config::EngineConfig, errors::EngineError, ids::ClientId};
+
+pub use crate::output::AccountSummary;
+
+/// Joins `lines` into CSV content with a trailing newline.
+pub fn csv_lines(lines: &[&str]) -> String {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+/// Transactions CSV built row by row, starting from the standard
+/// `type,client,tx,amount` header.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    lines: Vec<String>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Fixture::with_header("type,client,tx,amount")
+    }
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Fixture::default()
+    }
+
+    pub fn with_header(header: &str) -> Self {
+        Fixture {
+            lines: vec![header.to_string()],
+        }
+    }
+
+    pub fn deposit(self, client: u16, tx: i64, amount: &str) -> Self {
+        self.row(&format!("deposit,{client},{tx},{amount}"))
+    }
+
+    pub fn withdrawal(self, client: u16, tx: i64, amount: &str) -> Self {
+        self.row(&format!("withdrawal,{client},{tx},{amount}"))
+    }
+
+    pub fn dispute(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("dispute,{client},{tx},"))
+    }
+
+    pub fn resolve(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("resolve,{client},{tx},"))
+    }
+
+    pub fn chargeback(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("chargeback,{client},{tx},"))
+    }
+
+    /// Appends a raw line, for malformed rows or extra columns.
+    pub fn row(mut self, line: &str) -> Self {
+        self.lines.push(line.to_string());
+        self
+    }
+
+    pub fn to_csv(&self) -> String {
+        let lines: Vec<&str> = self.lines.iter().map(String::as_str).collect();
+        csv_lines(&lines)
+    }
+}
+
+/// A balance or state a [`Scenario`] checks once the rows before it ran.
+#[derive(Clone, Debug)]
+enum Check {
+    Available(Decimal),
+    Held(Decimal),
+    Total(Decimal),
+    Locked(bool),
+    /// Rows rejected so far, across every client.
+    Rejected(usize),
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Row(String),
+    Expect {
+        client: ClientId,
+        check: Check,
+        at: &'static Location<'static>,
+    },
+}
+
+/// A transaction sequence interleaved with expectations, run through one
+/// engine. Rows up to each expectation are processed before it is checked,
+/// so intermediate states (funds held mid-dispute, say) can be asserted
+/// too. [`Scenario::run`] panics at the first unmet expectation, naming the
+/// line that set it.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    config: EngineConfig,
+    steps: Vec<Step>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario::with_config(EngineConfig::default())
+    }
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Scenario::default()
+    }
+
+    /// Runs the scenario through an engine with `config`. Rows are written
+    /// as `type,client,tx,amount` CSV, so input settings should match.
+    pub fn with_config(config: EngineConfig) -> Self {
+        Scenario {
+            config,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn deposit(self, client: u16, tx: i64, amount: &str) -> Self {
+        self.row(&format!("deposit,{client},{tx},{amount}"))
+    }
+
+    pub fn withdrawal(self, client: u16, tx: i64, amount: &str) -> Self {
+        self.row(&format!("withdrawal,{client},{tx},{amount}"))
+    }
+
+    pub fn dispute(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("dispute,{client},{tx},"))
+    }
+
+    pub fn resolve(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("resolve,{client},{tx},"))
+    }
+
+    pub fn chargeback(self, client: u16, tx: i64) -> Self {
+        self.row(&format!("chargeback,{client},{tx},"))
+    }
+
+    /// Appends a raw line, for malformed rows or other transaction types.
+    pub fn row(mut self, line: &str) -> Self {
+        self.steps.push(Step::Row(line.to_string()));
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_available(self, client: u16, amount: &str) -> Self {
+        self.expect(client, Check::Available(decimal(amount)))
+    }
+
+    #[track_caller]
+    pub fn expect_held(self, client: u16, amount: &str) -> Self {
+        self.expect(client, Check::Held(decimal(amount)))
+    }
+
+    #[track_caller]
+    pub fn expect_total(self, client: u16, amount: &str) -> Self {
+        self.expect(client, Check::Total(decimal(amount)))
+    }
+
+    #[track_caller]
+    pub fn expect_locked(self, client: u16, locked: bool) -> Self {
+        self.expect(client, Check::Locked(locked))
+    }
+
+    /// Expects `rows` rows, counted over the whole scenario so far, to have
+    /// been rejected.
+    #[track_caller]
+    pub fn expect_rejected(self, rows: usize) -> Self {
+        self.expect(0, Check::Rejected(rows))
+    }
+
+    #[track_caller]
+    fn expect(mut self, client: u16, check: Check) -> Self {
+        self.steps.push(Step::Expect {
+            client: ClientId(client),
+            check,
+            at: Location::caller(),
+        });
+        self
+    }
+
+    /// Processes every row and checks every expectation in order, returning
+    /// the engine for any further assertions.
+    pub fn run(self) -> Engine {
+        let mut engine = Engine::with_config(self.config);
+        let mut pending = Fixture::new();
+        let mut has_rows = false;
+        for step in self.steps {
+            match step {
+                Step::Row(line) => {
+                    pending = pending.row(&line);
+                    has_rows = true;
+                }
+                Step::Expect { client, check, at } => {
+                    if has_rows {
+                        engine
+                            .process(Cursor::new(pending.to_csv()))
+                            .unwrap_or_else(|err| panic!("scenario failed before {at}: {err}"));
+                        pending = Fixture::new();
+                        has_rows = false;
+                    }
+                    if let Err(failure) = check.verify(&engine, client) {
+                        panic!("expectation at {at} not met: {failure}");
+                    }
+                }
+            }
+        }
+        if has_rows {
+            engine
+                .process(Cursor::new(pending.to_csv()))
+                .unwrap_or_else(|err| panic!("scenario failed: {err}"));
+        }
+        engine
+    }
+}
+
+impl Check {
+    fn verify(&self, engine: &Engine, client_id: ClientId) -> Result<(), String> {
+        let client = || {
+            engine
+                .client(client_id)
+                .ok_or_else(|| format!("client {client_id} has no account"))
+        };
+        let compare = |field: &str, expected: Decimal, actual: fn(&Client) -> Decimal| {
+            let actual = actual(client()?);
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "client {client_id}: {field} expected {expected}, got {actual}"
+                ))
+            }
+        };
+        match *self {
+            Check::Available(expected) => compare("available", expected, |c| c.available),
+            Check::Held(expected) => compare("held", expected, |c| c.held.value()),
+            Check::Total(expected) => compare("total", expected, |c| c.total),
+            Check::Locked(expected) => match client()?.locked {
+                actual if actual == expected => Ok(()),
+                actual => Err(format!(
+                    "client {client_id}: locked expected {expected}, got {actual}"
+                )),
+            },
+            Check::Rejected(expected) => match engine.row_counts().rejected {
+                actual if actual == expected => Ok(()),
+                actual => Err(format!("expected {expected} rejected rows, got {actual}")),
+            },
+        }
+    }
+}
+
+#[track_caller]
+fn decimal(amount: &str) -> Decimal {
+    Decimal::from_str(amount).unwrap_or_else(|_| panic!("invalid amount {amount:?}"))
+}
+
+/// Parses CSV accounts output, sorted by client.
+pub fn parse_accounts<R: Read>(output: R) -> Result<Vec<AccountSummary>, EngineError> {
+    let mut accounts = csv::Reader::from_reader(output)
+        .deserialize()
+        .collect::<Result<Vec<AccountSummary>, _>>()?;
+    accounts.sort_by_key(|account| account.client);
+    Ok(accounts)
+}
+
+/// Feeds `csv` to `engine` and returns its accounts. The engine must write
+/// CSV output; its other settings (formatting, rules, tenant) apply as usual.
+pub fn run_engine(engine: &mut Engine, csv: &str) -> Result<Vec<AccountSummary>, EngineError> {
+    engine.process(Cursor::new(csv.as_bytes()))?;
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output)?;
+    parse_accounts(output.as_slice())
+}
+
+/// Runs `csv` through a default engine.
+pub fn run(csv: &str) -> Result<Vec<AccountSummary>, EngineError> {
+    run_engine(&mut Engine::new(), csv)
+}
+
+/// Looks up one client's account.
+pub fn account(accounts: &[AccountSummary], client: ClientId) -> Option<&AccountSummary> {
+    accounts.iter().find(|account| account.client == client)
+}
+
+/// Asserts `actual` holds exactly the `expected` accounts, in any order,
+/// listing every difference on failure.
+#[track_caller]
+pub fn assert_accounts(actual: &[AccountSummary], expected: &[AccountSummary]) {
+    let mut differences = Vec::new();
+    for expected in expected {
+        match account(actual, expected.client) {
+            None => differences.push(format!("client {}: missing", expected.client)),
+            Some(actual) if actual != expected => differences.push(format!(
+                "client {}: expected {expected:?}, got {actual:?}",
+                expected.client
+            )),
+            Some(_) => {}
+        }
+    }
+    for actual in actual {
+        if account(expected, actual.client).is_none() {
+            differences.push(format!("client {}: unexpected {actual:?}", actual.client));
+        }
+    }
+    assert!(
+        differences.is_empty(),
+        "accounts differ:\n{}",
+        differences.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::dec;
+
+    #[test]
+    fn fixture_runs_and_compares_by_value() {
+        let csv = Fixture::new()
+            .deposit(2, 1, "10")
+            .deposit(1, 2, "3.25")
+            .dispute(1, 2)
+            .row("deposit,1,bad,1.0")
+            .to_csv();
+
+        let accounts = run(&csv).unwrap();
+
+        assert_eq!(accounts[0].client, ClientId(1));
+        assert_accounts(
+            &accounts,
+            &[
+                AccountSummary::new(ClientId(1), dec!(0), dec!(3.25), false),
+                AccountSummary::new(ClientId(2), dec!(10.0000), dec!(0), false),
+            ],
+        );
+    }
+
+    #[test]
+    fn scenarios_check_balances_between_rows() {
+        let engine = Scenario::new()
+            .deposit(1, 1, "5.0")
+            .deposit(1, 2, "2.5")
+            .dispute(1, 1)
+            .expect_available(1, "2.5")
+            .expect_held(1, "5")
+            .resolve(1, 1)
+            .expect_held(1, "0")
+            .resolve(1, 1)
+            .expect_rejected(1)
+            .withdrawal(1, 3, "7.5")
+            .run();
+
+        assert_eq!(engine.client(ClientId(1)).unwrap().total, dec!(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "client 1: held expected 1, got 5")]
+    fn unmet_expectations_name_the_field() {
+        Scenario::new()
+            .deposit(1, 1, "5")
+            .dispute(1, 1)
+            .expect_held(1, "1")
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "client 3: missing")]
+    fn assert_accounts_reports_missing_clients() {
+        let accounts = run(&Fixture::new().deposit(1, 1, "1").to_csv()).unwrap();
+
+        assert_accounts(
+            &accounts,
+            &[
+                AccountSummary::new(ClientId(1), dec!(1), dec!(0), false),
+                AccountSummary::new(ClientId(3), dec!(1), dec!(0), false),
+            ],
+        );
+    }
+}
@@ -1,25 +1,133 @@
-use serde::Deserialize;
-use std::fmt;
+use alloc::string::String;
+use core::fmt;
+use rust_decimal::Decimal;
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, Unexpected, Visitor},
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
-#[serde(rename_all = "lowercase")]
+use crate::errors::ValidationError;
+use crate::ids::{ClientId, TxId};
+use crate::money::Money;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TransactionType {
     Deposit,
     Withdrawal,
     Dispute,
     Resolve,
     Chargeback,
+    WithdrawalHold,
+    WithdrawalSettle,
+    WithdrawalCancel,
+    /// A type registered by the embedder with
+    /// `Engine::register_transaction_type`. Input naming a type that is not
+    /// registered is rejected when applied.
+    Custom(CustomType),
 }
 
 impl TransactionType {
-    pub fn as_str(&self) -> &'static str {
+    const BUILT_IN: [TransactionType; 8] = [
+        TransactionType::Deposit,
+        TransactionType::Withdrawal,
+        TransactionType::Dispute,
+        TransactionType::Resolve,
+        TransactionType::Chargeback,
+        TransactionType::WithdrawalHold,
+        TransactionType::WithdrawalSettle,
+        TransactionType::WithdrawalCancel,
+    ];
+
+    /// The built-in type called `name`, or else a custom one; `None` only
+    /// for names a [`CustomType`] cannot hold.
+    pub fn from_name(name: &str) -> Option<Self> {
+        TransactionType::BUILT_IN
+            .into_iter()
+            .find(|tx_type| tx_type.as_str() == name)
+            .or_else(|| CustomType::new(name).map(TransactionType::Custom))
+    }
+
+    /// Whether rows of this built-in type must carry an amount.
+    #[cfg(feature = "std")]
+    pub(crate) fn requires_amount(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Deposit
+                | TransactionType::Withdrawal
+                | TransactionType::WithdrawalHold
+        )
+    }
+
+    pub fn as_str(&self) -> &str {
         match self {
             TransactionType::Deposit => "deposit",
             TransactionType::Withdrawal => "withdrawal",
             TransactionType::Dispute => "dispute",
             TransactionType::Resolve => "resolve",
             TransactionType::Chargeback => "chargeback",
+            TransactionType::WithdrawalHold => "withdrawal_hold",
+            TransactionType::WithdrawalSettle => "withdrawal_settle",
+            TransactionType::WithdrawalCancel => "withdrawal_cancel",
+            TransactionType::Custom(custom) => custom.as_str(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TypeVisitor;
+
+        impl Visitor<'_> for TypeVisitor {
+            type Value = TransactionType;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a transaction type of 1 to {CUSTOM_TYPE_MAX_LEN} bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<TransactionType, E> {
+                TransactionType::from_name(value)
+                    .ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_str(TypeVisitor)
+    }
+}
+
+/// Longest name a custom transaction type can have.
+pub const CUSTOM_TYPE_MAX_LEN: usize = 31;
+
+/// Name of an embedder-defined transaction type, stored inline so
+/// `TransactionType` stays `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomType {
+    len: u8,
+    bytes: [u8; CUSTOM_TYPE_MAX_LEN],
+}
+
+impl CustomType {
+    /// `None` for empty names and names over [`CUSTOM_TYPE_MAX_LEN`] bytes.
+    pub fn new(name: &str) -> Option<Self> {
+        if name.is_empty() || name.len() > CUSTOM_TYPE_MAX_LEN {
+            return None;
         }
+        let mut bytes = [0; CUSTOM_TYPE_MAX_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(CustomType {
+            len: name.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever built from a whole `&str`.
+        core::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for CustomType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
     }
 }
 
@@ -28,3 +136,118 @@ impl fmt::Display for TransactionType {
         f.write_str(self.as_str())
     }
 }
+
+/// A validated transaction as seen by extension points such as rules.
+/// `amount` is only present for deposits and withdrawals. `reference` is the
+/// partner's free-form reference, carried through untouched and never used
+/// in accounting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub tx_type: TransactionType,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub amount: Option<Money>,
+    pub reference: Option<String>,
+}
+
+impl Transaction {
+    pub fn deposit(client: ClientId, tx: TxId, amount: Decimal) -> Result<Self, ValidationError> {
+        Transaction::with_amount(TransactionType::Deposit, client, tx, amount)
+    }
+
+    pub fn withdrawal(
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+    ) -> Result<Self, ValidationError> {
+        Transaction::with_amount(TransactionType::Withdrawal, client, tx, amount)
+    }
+
+    pub fn withdrawal_hold(
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+    ) -> Result<Self, ValidationError> {
+        Transaction::with_amount(TransactionType::WithdrawalHold, client, tx, amount)
+    }
+
+    pub fn dispute(client: ClientId, tx: TxId) -> Self {
+        Transaction::without_amount(TransactionType::Dispute, client, tx)
+    }
+
+    pub fn resolve(client: ClientId, tx: TxId) -> Self {
+        Transaction::without_amount(TransactionType::Resolve, client, tx)
+    }
+
+    pub fn chargeback(client: ClientId, tx: TxId) -> Self {
+        Transaction::without_amount(TransactionType::Chargeback, client, tx)
+    }
+
+    pub fn withdrawal_settle(client: ClientId, tx: TxId) -> Self {
+        Transaction::without_amount(TransactionType::WithdrawalSettle, client, tx)
+    }
+
+    pub fn withdrawal_cancel(client: ClientId, tx: TxId) -> Self {
+        Transaction::without_amount(TransactionType::WithdrawalCancel, client, tx)
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    /// Amounts must be positive and fit [`Money`] (at most four decimal
+    /// places, bounded magnitude), as the engine requires of input rows.
+    fn with_amount(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TxId,
+        amount: Decimal,
+    ) -> Result<Self, ValidationError> {
+        if amount <= Decimal::ZERO {
+            return Err(ValidationError::NonPositiveAmount(amount));
+        }
+        Ok(Transaction {
+            amount: Some(Money::new(amount)?),
+            ..Transaction::without_amount(tx_type, client, tx)
+        })
+    }
+
+    fn without_amount(tx_type: TransactionType, client: ClientId, tx: TxId) -> Self {
+        Transaction {
+            tx_type,
+            client,
+            tx,
+            amount: None,
+            reference: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::MoneyError;
+    use rust_decimal::dec;
+
+    #[test]
+    fn constructors_validate_amounts() {
+        let deposit = Transaction::deposit(ClientId(1), TxId(7), dec!(2.5))
+            .unwrap()
+            .with_reference("INV-1");
+        assert_eq!(deposit.amount, Some(Money::new(dec!(2.5)).unwrap()));
+        assert_eq!(deposit.reference.as_deref(), Some("INV-1"));
+
+        assert_eq!(
+            Transaction::withdrawal(ClientId(1), TxId(8), dec!(0)),
+            Err(ValidationError::NonPositiveAmount(dec!(0)))
+        );
+        assert_eq!(
+            Transaction::deposit(ClientId(1), TxId(9), dec!(1.00001)),
+            Err(ValidationError::Amount(MoneyError::ScaleTooLarge(dec!(
+                1.00001
+            ))))
+        );
+        assert_eq!(Transaction::dispute(ClientId(1), TxId(7)).amount, None);
+    }
+}
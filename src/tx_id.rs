@@ -0,0 +1,133 @@
+use std::{
+    io,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::{errors::EngineError, ids::TxId};
+
+/// One past the largest transaction id.
+const LIMIT: u64 = u32::MAX as u64 + 1;
+
+type PersistHook = Box<dyn Fn(u64) -> io::Result<()> + Send + Sync>;
+
+/// Hands out unique transaction ids to embedders that originate
+/// transactions instead of replaying a partner's file. Ids must be unique
+/// across all clients, as the engine expects, so one allocator should be
+/// shared (it is `Sync`) by every producer.
+///
+/// With [`TxIdAllocator::with_persistence`] ids are reserved in blocks: the
+/// hook is called with a high-water mark before any id below it is handed
+/// out, so [`TxIdAllocator::resume`] from the last stored mark never reissues
+/// an id after a restart. Ids left in a block at shutdown are skipped.
+pub struct TxIdAllocator {
+    next: AtomicU64,
+    reserved: AtomicU64,
+    block: u64,
+    persist: Option<PersistHook>,
+    persist_lock: Mutex<()>,
+}
+
+impl TxIdAllocator {
+    /// Starts at `first`. Pick it above every id already in the engine's input.
+    pub fn new(first: TxId) -> Self {
+        TxIdAllocator::resume(u64::from(first.0))
+    }
+
+    /// Continues from a high-water mark previously passed to the hook.
+    pub fn resume(high_water: u64) -> Self {
+        TxIdAllocator {
+            next: AtomicU64::new(high_water),
+            reserved: AtomicU64::new(LIMIT),
+            block: 1,
+            persist: None,
+            persist_lock: Mutex::new(()),
+        }
+    }
+
+    /// Calls `hook` with a new high-water mark every `block` ids. An id is
+    /// only returned once the mark above it has been stored; if the hook
+    /// fails, the id is dropped and the error returned.
+    pub fn with_persistence(
+        mut self,
+        block: u32,
+        hook: impl Fn(u64) -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.block = u64::from(block.max(1));
+        self.reserved = AtomicU64::new(self.next.load(Ordering::SeqCst));
+        self.persist = Some(Box::new(hook));
+        self
+    }
+
+    pub fn next_id(&self) -> Result<TxId, EngineError> {
+        let id = self.next.fetch_add(1, Ordering::SeqCst);
+        if id >= LIMIT {
+            return Err(EngineError::TxIdsExhausted);
+        }
+        if let Some(persist) = &self.persist
+            && id >= self.reserved.load(Ordering::Acquire)
+        {
+            let _guard = self.persist_lock.lock().unwrap();
+            if id >= self.reserved.load(Ordering::Acquire) {
+                let high_water = (id + self.block).min(LIMIT);
+                persist(high_water)?;
+                self.reserved.store(high_water, Ordering::Release);
+            }
+        }
+        Ok(TxId(id as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, sync::Arc, thread};
+
+    #[test]
+    fn concurrent_callers_get_unique_ids_below_the_stored_mark() {
+        let marks = Arc::new(Mutex::new(Vec::new()));
+        let stored = Arc::clone(&marks);
+        let allocator = Arc::new(
+            TxIdAllocator::new(TxId(10)).with_persistence(16, move |mark| {
+                stored.lock().unwrap().push(mark);
+                Ok(())
+            }),
+        );
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let allocator = Arc::clone(&allocator);
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|_| allocator.next_id().unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let ids: Vec<TxId> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 400);
+        let last_mark = *marks.lock().unwrap().iter().max().unwrap();
+        assert!(ids.iter().all(|id| u64::from(id.0) < last_mark));
+        assert!(TxIdAllocator::resume(last_mark).next_id().unwrap() > *ids.iter().max().unwrap());
+    }
+
+    #[test]
+    fn failed_persistence_is_reported_and_ids_run_out_at_u32_max() {
+        let failing =
+            TxIdAllocator::new(TxId(1)).with_persistence(8, |_| Err(io::Error::other("disk full")));
+        assert!(matches!(failing.next_id(), Err(EngineError::Io(_))));
+
+        let allocator = TxIdAllocator::new(TxId(u32::MAX));
+        assert_eq!(allocator.next_id().unwrap(), TxId(u32::MAX));
+        assert!(matches!(
+            allocator.next_id(),
+            Err(EngineError::TxIdsExhausted)
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, io::Read};
+
+use crate::{Engine, errors::EngineError, formatting::format_decimal, ids::ClientId};
+
+/// One difference between the engine's state and an expected accounts file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The expected file lists a client the engine never produced.
+    Missing { client: ClientId },
+    /// The engine produced a client the expected file does not list.
+    Unexpected { client: ClientId },
+    Field {
+        client: ClientId,
+        field: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Missing { client } => write!(f, "client {client}: expected but not produced"),
+            Mismatch::Unexpected { client } => {
+                write!(f, "client {client}: produced but not expected")
+            }
+            Mismatch::Field {
+                client,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "client {client}: {field} expected {expected}, got {actual}"
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ExpectedAccount {
+    client: ClientId,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: bool,
+}
+
+/// Compares the engine's accounts with an expected accounts CSV, by value:
+/// `1.5` and `1.5000` are equal, and column and row order do not matter.
+/// Returns every mismatch in client order; an empty list means they agree.
+pub fn verify_accounts<R: Read>(
+    engine: &Engine,
+    expected: R,
+) -> Result<Vec<Mismatch>, EngineError> {
+    let mut expected_accounts = BTreeMap::new();
+    for row in csv::Reader::from_reader(expected).deserialize() {
+        let account: ExpectedAccount = row?;
+        expected_accounts.insert(account.client, account);
+    }
+
+    let mut mismatches = Vec::new();
+    engine.visit_clients(|client| {
+        let Some(expected) = expected_accounts.remove(&client.id) else {
+            mismatches.push(Mismatch::Unexpected { client: client.id });
+            return Ok(());
+        };
+        let amounts = [
+            ("available", expected.available, client.available),
+            ("held", expected.held, client.held.value()),
+            ("total", expected.total, client.total),
+        ];
+        for (field, expected, actual) in amounts {
+            if expected != actual {
+                mismatches.push(Mismatch::Field {
+                    client: client.id,
+                    field,
+                    expected: format_decimal(expected),
+                    actual: format_decimal(actual),
+                });
+            }
+        }
+        if expected.locked != client.locked {
+            mismatches.push(Mismatch::Field {
+                client: client.id,
+                field: "locked",
+                expected: expected.locked.to_string(),
+                actual: client.locked.to_string(),
+            });
+        }
+        Ok(())
+    })?;
+    mismatches.extend(
+        expected_accounts
+            .into_keys()
+            .map(|client| Mismatch::Missing { client }),
+    );
+    mismatches.sort_by_key(|mismatch| match mismatch {
+        Mismatch::Missing { client }
+        | Mismatch::Unexpected { client }
+        | Mismatch::Field { client, .. } => *client,
+    });
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,1.5\ndeposit,2,2,3.0\n",
+            ))
+            .unwrap();
+        engine
+    }
+
+    #[test]
+    fn equal_values_match_regardless_of_formatting_and_order() {
+        let expected =
+            "locked,total,held,available,client\nfalse,3,0,3.0,2\nfalse,1.5000,0.0000,1.5000,1\n";
+
+        assert_eq!(
+            verify_accounts(&engine(), Cursor::new(expected)).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn reports_field_differences_and_missing_clients() {
+        let expected = "client,available,held,total,locked\n1,2.0,0,2.0,false\n3,0,0,0,false\n";
+
+        let report: Vec<String> = verify_accounts(&engine(), Cursor::new(expected))
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            report,
+            [
+                "client 1: available expected 2.0000, got 1.5000",
+                "client 1: total expected 2.0000, got 1.5000",
+                "client 2: produced but not expected",
+                "client 3: expected but not produced",
+            ]
+        );
+    }
+}
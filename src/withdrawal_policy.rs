@@ -0,0 +1,32 @@
+use alloc::{format, string::String};
+use core::str::FromStr;
+
+/// Which funds a withdrawal, or a withdrawal hold, may draw on while some of
+/// the client's deposits are disputed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WithdrawalPolicy {
+    /// Only `available`; held funds never count.
+    #[default]
+    Available,
+    /// `available` plus `held`, betting that open disputes resolve. The
+    /// withdrawal may leave `available` negative until they do; a chargeback
+    /// in the meantime leaves it negative for good.
+    Projected,
+    /// Like `Available`, but no withdrawal at all while any dispute is open.
+    FreezeOnOpenDispute,
+}
+
+impl FromStr for WithdrawalPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "available" => Ok(WithdrawalPolicy::Available),
+            "projected" => Ok(WithdrawalPolicy::Projected),
+            "freeze-on-open-dispute" => Ok(WithdrawalPolicy::FreezeOnOpenDispute),
+            other => Err(format!(
+                "unknown withdrawal policy {other}, expected available, projected or freeze-on-open-dispute"
+            )),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+/// What to do with a zero amount on a transaction that needs one, such as
+/// the zero-amount "verification" deposits some partners send.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZeroAmountPolicy {
+    /// Reject the row as `ClientTransactionError::ZeroAmount`.
+    #[default]
+    Reject,
+    /// Accept the row without touching any balance, recording it in the
+    /// audit trail as `AuditAction::ZeroAmountIgnored`. The row still goes
+    /// through the transaction order, rule, lock and capability checks, and
+    /// fails them like any other. Once accepted it takes the next sequence
+    /// number and adds a balance history point at it, with the balances
+    /// unchanged.
+    Ignore,
+}
+
+impl FromStr for ZeroAmountPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "reject" => Ok(ZeroAmountPolicy::Reject),
+            "ignore" => Ok(ZeroAmountPolicy::Ignore),
+            other => Err(format!(
+                "unknown zero amount policy {other}, expected reject or ignore"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Engine,
+        admin::set_capability,
+        audit::AuditAction,
+        client::Capability,
+        config::EngineConfig,
+        engine::Rejection,
+        errors::ClientTransactionError,
+        history::PointInTime,
+        ids::{ClientId, TxId},
+        rules::RuleDecision,
+        transaction::TransactionType,
+    };
+    use rust_decimal::dec;
+    use std::io::Cursor;
+
+    #[test]
+    fn ignored_zero_amounts_are_sequenced_and_recorded_in_history() {
+        let mut engine = Engine::with_config(EngineConfig {
+            zero_amounts: ZeroAmountPolicy::Ignore,
+            ..Default::default()
+        });
+        engine.enable_balance_history();
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,1,5\ndeposit,1,2,0\ndeposit,1,3,1\n",
+            ))
+            .unwrap();
+
+        let ignored = engine
+            .audit_entries()
+            .iter()
+            .find(|entry| entry.action == AuditAction::ZeroAmountIgnored)
+            .unwrap();
+        assert_eq!((ignored.tx, ignored.sequence), (TxId(2), Some(2)));
+        assert_eq!(engine.last_sequence(), 3);
+        let history = engine.balance_history().unwrap();
+        assert_eq!(history.last_seq(), 3);
+        assert_eq!(
+            history
+                .balance_at(ClientId(1), PointInTime::Seq(2))
+                .map(|point| (point.total, point.sequence)),
+            Some((dec!(5), Some(2)))
+        );
+    }
+
+    #[test]
+    fn ignored_zero_amounts_are_checked_like_other_rows() {
+        let mut engine = Engine::with_config(EngineConfig {
+            zero_amounts: ZeroAmountPolicy::Ignore,
+            strict_tx_order: true,
+            ..Default::default()
+        });
+        engine.rules_mut().register("no client 4", |client, _| {
+            if client.id == ClientId(4) {
+                RuleDecision::Deny("blocked".to_string())
+            } else {
+                RuleDecision::Allow
+            }
+        });
+        engine
+            .process(Cursor::new(
+                "type,client,tx,amount\ndeposit,1,5,5\ndeposit,2,6,5\ndeposit,3,7,5\ndispute,2,6,\nchargeback,2,6,\n",
+            ))
+            .unwrap();
+        set_capability(&mut engine, ClientId(3), Capability::Withdraw, false).unwrap();
+        let mut push = |client, tx| {
+            engine
+                .push(
+                    TransactionType::Withdrawal,
+                    ClientId(client),
+                    TxId(tx),
+                    Some(dec!(0)),
+                )
+                .unwrap()
+        };
+
+        assert!(matches!(
+            push(1, 1),
+            Some(Rejection::Client(
+                ClientTransactionError::TxIdOutOfOrder { .. }
+            ))
+        ));
+        assert!(matches!(
+            push(2, 8),
+            Some(Rejection::Client(
+                ClientTransactionError::AccountLocked { .. }
+            ))
+        ));
+        assert!(matches!(
+            push(3, 9),
+            Some(Rejection::Client(
+                ClientTransactionError::CapabilityRevoked {
+                    capability: Capability::Withdraw,
+                    ..
+                }
+            ))
+        ));
+        assert!(matches!(push(4, 10), Some(Rejection::Other(_))));
+        assert_eq!(push(1, 11), None);
+        let ignored: Vec<TxId> = engine
+            .audit_entries()
+            .iter()
+            .filter(|entry| entry.action == AuditAction::ZeroAmountIgnored)
+            .map(|entry| entry.tx)
+            .collect();
+        assert_eq!(ignored, [TxId(11)]);
+    }
+}
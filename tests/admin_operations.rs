@@ -0,0 +1,276 @@
+use rust_decimal::dec;
+use rust_payments_engine::admin::{
+    RepairStrategy, find_balance_mismatches, force_resolve, forget_client, merge_clients,
+    repair_balances, resolve_all, reverse_deposit, set_capability,
+};
+use rust_payments_engine::history::PointInTime;
+use rust_payments_engine::prelude::*;
+use std::io::Cursor;
+
+fn engine_from_raw_csv(csv: &str) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .process(Cursor::new(csv.as_bytes()))
+        .expect("Something failed while processing transactions");
+    engine
+}
+
+fn reload(engine: &Engine) -> Engine {
+    let mut buffer = Vec::new();
+    engine.snapshot().unwrap().save(&mut buffer).unwrap();
+    Engine::from_snapshot(Snapshot::load(Cursor::new(buffer)).unwrap())
+}
+
+#[test]
+fn snapshot_round_trip_keeps_disputes_resolvable() {
+    let engine = engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,3.0\ndispute,1,1,\n");
+    let mut engine = reload(&engine);
+
+    engine
+        .process(Cursor::new(
+            "type,client,tx,amount\nresolve,1,1,\n".as_bytes(),
+        ))
+        .unwrap();
+
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!(client.available, dec!(3));
+    assert_eq!(client.held, dec!(0));
+}
+
+#[test]
+fn reverse_deposit_on_loaded_snapshot_produces_audit_entry() {
+    let engine = engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,5.0\n");
+    let mut engine = reload(&engine);
+
+    let entry = reverse_deposit(&mut engine, ClientId(1), TxId(2)).unwrap();
+
+    assert_eq!(entry.action, AuditAction::ReverseDeposit);
+    assert_eq!(entry.amount, Some(dec!(5)));
+    assert_eq!(entry.available, dec!(5));
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!(client.total, dec!(5));
+    assert_eq!(
+        (client.lifetime_deposits(), client.deposit_count()),
+        (dec!(5), 1)
+    );
+}
+
+#[test]
+fn resolve_all_emits_one_audit_entry_per_released_dispute() {
+    let engine = engine_from_raw_csv(
+        "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,2.0\ndispute,1,1,\ndispute,1,2,\n",
+    );
+    let mut engine = reload(&engine);
+
+    let entries = resolve_all(&mut engine, ClientId(1)).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert!(
+        entries
+            .iter()
+            .all(|entry| entry.action == AuditAction::ResolveAll)
+    );
+    assert_eq!(entries[0].tx, TxId(1));
+    assert_eq!(entries[1].amount, Some(dec!(2)));
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!(client.available, dec!(7));
+    assert_eq!(client.held, dec!(0));
+}
+
+#[test]
+fn admin_operations_reject_unknown_clients() {
+    let mut engine = Engine::new();
+
+    assert!(matches!(
+        force_resolve(&mut engine, ClientId(9), TxId(1)),
+        Err(EngineError::Admin(ClientTransactionError::UnknownClient {
+            client_id: ClientId(9)
+        }))
+    ));
+}
+
+#[test]
+fn client_mut_applies_audited_manual_credits() {
+    let mut engine = engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,5.0\n");
+
+    engine
+        .client_mut(ClientId(1))
+        .unwrap()
+        .credit(TxId(900), Money::new(dec!(2.5)).unwrap(), "promotion")
+        .unwrap();
+
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(7.5));
+    assert_eq!(engine.audit_entries()[0].action, AuditAction::ManualCredit);
+    assert!(matches!(
+        engine.client_mut(ClientId(2)),
+        Err(EngineError::Admin(ClientTransactionError::UnknownClient {
+            client_id: ClientId(2)
+        }))
+    ));
+}
+
+#[test]
+fn process_once_skips_inputs_already_recorded_in_the_snapshot() {
+    let csv = "type,client,tx,amount\ndeposit,1,1,5.0\n";
+    let mut engine = Engine::new();
+    assert!(engine.process_once(Cursor::new(csv.as_bytes())).unwrap());
+
+    let mut engine = reload(&engine);
+    assert!(!engine.process_once(Cursor::new(csv.as_bytes())).unwrap());
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(5));
+
+    let other = "type,client,tx,amount\ndeposit,1,2,1.0\n";
+    assert!(engine.process_once(Cursor::new(other.as_bytes())).unwrap());
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(6));
+}
+
+#[test]
+fn merge_clients_combines_balances_history_and_lock() {
+    let engine = engine_from_raw_csv(
+        "type,client,tx,amount\n\
+         deposit,1,1,5.0\n\
+         dispute,1,1,\n\
+         deposit,2,2,4.0\n\
+         deposit,2,3,1.0\n\
+         dispute,2,3,\n\
+         chargeback,2,3,\n\
+         deposit,3,1,2.0\n",
+    );
+    let mut engine = reload(&engine);
+
+    let entry = merge_clients(&mut engine, ClientId(2), ClientId(1)).unwrap();
+    assert_eq!(entry.action, AuditAction::MergeClients);
+    assert_eq!(entry.reason.as_deref(), Some("merged client 2"));
+    assert!(engine.client(ClientId(2)).is_none());
+    let merged = engine.client(ClientId(1)).unwrap();
+    assert_eq!(
+        (
+            merged.available,
+            merged.held.value(),
+            merged.total,
+            merged.locked
+        ),
+        (dec!(4), dec!(5), dec!(9), true)
+    );
+    assert_eq!(merged.deposits().count(), 3);
+    assert_eq!(merged.open_disputes().count(), 1);
+
+    assert!(matches!(
+        merge_clients(&mut engine, ClientId(3), ClientId(1)),
+        Err(EngineError::Admin(ClientTransactionError::MergeCollision {
+            client_id: ClientId(1),
+            from: ClientId(3),
+            tx_id: TxId(1)
+        }))
+    ));
+    assert_eq!(engine.client(ClientId(3)).unwrap().available, dec!(2));
+    assert_eq!(engine.client(ClientId(1)).unwrap().total, dec!(9));
+}
+
+#[test]
+fn inconsistent_balances_are_reported_and_repaired() {
+    let engine = engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,3.0\n");
+    let mut snapshot = engine.snapshot().unwrap();
+    snapshot.clients[1].total = dec!(4);
+
+    let mut engine = Engine::from_snapshot(snapshot.clone());
+    let mismatches = find_balance_mismatches(&engine);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].client, ClientId(2));
+    assert_eq!(mismatches[0].expected_total(), dec!(3));
+
+    let entries = repair_balances(&mut engine, RepairStrategy::RecomputeTotal);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, AuditAction::RepairTotal);
+    assert_eq!(engine.client(ClientId(2)).unwrap().total, dec!(3));
+    assert!(find_balance_mismatches(&engine).is_empty());
+
+    let mut engine = Engine::from_snapshot(snapshot);
+    let entries = repair_balances(&mut engine, RepairStrategy::Quarantine);
+    assert_eq!(entries[0].action, AuditAction::Quarantine);
+    let client = engine.client(ClientId(2)).unwrap();
+    assert!(client.locked);
+    assert_eq!(client.total, dec!(4));
+}
+
+#[test]
+fn capabilities_restrict_one_kind_of_transaction_and_survive_snapshots() {
+    let engine = engine_from_raw_csv("type,client,tx,amount\ndeposit,1,1,5.0\n");
+    let mut engine = reload(&engine);
+    let entry = set_capability(&mut engine, ClientId(1), Capability::Withdraw, false).unwrap();
+    assert_eq!(entry.action, AuditAction::SetCapability);
+    assert_eq!(entry.reason.as_deref(), Some("can_withdraw=false"));
+
+    let mut engine = reload(&engine);
+    assert!(
+        !engine
+            .client(ClientId(1))
+            .unwrap()
+            .capabilities()
+            .can_withdraw
+    );
+    engine
+        .process(Cursor::new(
+            "type,client,tx,amount\nwithdrawal,1,2,1.0\ndeposit,1,3,2.0\ndispute,1,1,\n".as_bytes(),
+        ))
+        .unwrap();
+    assert_eq!(engine.row_counts().rejected, 1);
+    let client = engine.client(ClientId(1)).unwrap();
+    assert!(!client.locked);
+    assert_eq!((client.available, client.held.value()), (dec!(2), dec!(5)));
+    assert!(matches!(
+        client.clone().withdraw(Money::new(dec!(1)).unwrap()),
+        Err(ClientTransactionError::CapabilityRevoked {
+            capability: Capability::Withdraw,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn forget_client_erases_history_but_keeps_balances() {
+    let mut engine = Engine::new();
+    engine.enable_balance_history();
+    engine
+        .process(Cursor::new(
+            "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,1,2,2.0\ndispute,1,2,\ndeposit,2,3,1.0\n"
+                .as_bytes(),
+        ))
+        .unwrap();
+    assert!(matches!(
+        forget_client(&mut engine, ClientId(1)),
+        Err(EngineError::Admin(
+            ClientTransactionError::OpenTransactions { open: 1, .. }
+        ))
+    ));
+
+    force_resolve(&mut engine, ClientId(1), TxId(2)).unwrap();
+    let entry = forget_client(&mut engine, ClientId(1)).unwrap();
+    assert_eq!(entry.action, AuditAction::ForgetClient);
+    assert_eq!(entry.total, dec!(7));
+
+    let mut engine = reload(&engine);
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!((client.available, client.total), (dec!(7), dec!(7)));
+    assert_eq!(client.deposits().count(), 0);
+    let history = engine.balance_history().unwrap();
+    assert_eq!(
+        history.balance_at(ClientId(1), PointInTime::Seq(1)),
+        None,
+        "earlier balances are gone"
+    );
+    assert_eq!(
+        history
+            .balance_at(ClientId(1), PointInTime::Seq(history.last_seq()))
+            .map(|point| point.total),
+        Some(dec!(7))
+    );
+    assert_eq!(engine.client(ClientId(2)).unwrap().deposits().count(), 1);
+    assert_eq!(
+        engine
+            .push(TransactionType::Dispute, ClientId(1), TxId(1), None)
+            .unwrap()
+            .map(|rejection| rejection.to_string()),
+        Some("Client 1: transaction 1 is unknown".to_string())
+    );
+}
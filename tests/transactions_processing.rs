@@ -1,11 +1,39 @@
-use rust_payments_engine::process_transactions;
+use rust_decimal::dec;
+use rust_payments_engine::alerts::{Alert, AlertThresholds};
+use rust_payments_engine::aliases::ClientAliases;
+use rust_payments_engine::audit::AuditAction;
+use rust_payments_engine::balance_limits::BalanceLimits;
+use rust_payments_engine::clock::{Clock, ManualClock};
+use rust_payments_engine::config::EngineConfig;
+use rust_payments_engine::dispute_expiry::{DisputeDeadline, DisputeExpiry, ExpiryOutcome};
+use rust_payments_engine::engine::Rejection;
+use rust_payments_engine::errors::{ClientTransactionError, EngineError};
+use rust_payments_engine::eviction::{EvictionPolicy, MemoryStore};
+use rust_payments_engine::format::{AmountEncoding, Format};
+use rust_payments_engine::formatting::{
+    DecimalSeparator, FormattingOptions, LineEnding, Quoting, Rounding, ThousandsSeparator,
+};
+use rust_payments_engine::history::{PointInTime, Retention};
+use rust_payments_engine::hold_accrual::{AccrualKind, HoldAccrual};
+use rust_payments_engine::ids::{ClientId, TxId};
+use rust_payments_engine::memory::MemoryPolicy;
+use rust_payments_engine::numeric::MinorUnits;
+use rust_payments_engine::output::OutputSchema;
+use rust_payments_engine::preprocess::{MinorUnitAmounts, RenameTypes, StringRecord, set_field};
+use rust_payments_engine::review::{ReviewReason, write_review_queue};
+use rust_payments_engine::rules::RuleDecision;
+use rust_payments_engine::snapshot::Snapshot;
+use rust_payments_engine::sort::ExternalSort;
+use rust_payments_engine::testkit::{
+    AccountSummary, Fixture, Scenario, assert_accounts, csv_lines, run,
+};
+use rust_payments_engine::transaction::TransactionType;
+use rust_payments_engine::zero_amount::ZeroAmountPolicy;
+use rust_payments_engine::{Engine, process_transactions, process_transactions_with_errors};
 use std::io::Cursor;
-
-fn csv_lines(lines: &[&str]) -> String {
-    let mut content = lines.join("\n");
-    content.push('\n');
-    content
-}
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 fn get_output_from_raw_csv(csv: &str) -> String {
     let mut output = Vec::new();
@@ -39,6 +67,27 @@ fn process_transactions_accumulates_multiple_deposit_records() {
     assert!(output.contains("1,8.0000,0.0000,8.0000,false"));
 }
 
+#[test]
+fn testkit_fixture_covers_a_chargeback_lifecycle() {
+    let csv = Fixture::new()
+        .deposit(1, 1, "10.0")
+        .deposit(1, 2, "4.0")
+        .dispute(1, 2)
+        .chargeback(1, 2)
+        .deposit(2, 3, "1.0")
+        .dispute(2, 3)
+        .resolve(2, 3)
+        .to_csv();
+
+    assert_accounts(
+        &run(&csv).unwrap(),
+        &[
+            AccountSummary::new(ClientId(1), dec!(10), dec!(0), true),
+            AccountSummary::new(ClientId(2), dec!(1), dec!(0), false),
+        ],
+    );
+}
+
 #[test]
 fn process_transactions_skips_non_positive_amount_rows() {
     let csv = csv_lines(&[
@@ -132,3 +181,1510 @@ fn process_transactions_skips_transaction_ids_that_overflow_u32() {
     assert!(output.contains("1,4.0000,0.0000,4.0000,false"));
     assert!(!output.contains("4294967296"));
 }
+
+#[test]
+fn process_transactions_skips_amounts_with_more_than_four_decimal_places() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "withdrawal,1,2,1.00005",
+    ]);
+    let output = get_output_from_raw_csv(&csv);
+    assert!(output.contains("1,5.0000,0.0000,5.0000,false"));
+}
+
+#[test]
+fn tenant_scoped_engine_skips_rows_for_other_tenants() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount,tenant",
+        "deposit,1,1,5.0,acme",
+        "deposit,1,2,3.0,globex",
+        "deposit,1,3,1.0,",
+    ]);
+    let mut engine = Engine::with_tenant("acme");
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+
+    assert!(
+        String::from_utf8(output)
+            .unwrap()
+            .contains("1,6.0000,0.0000,6.0000,false")
+    );
+}
+
+#[test]
+fn process_transactions_maps_columns_in_any_order() {
+    let csv = csv_lines(&["amount,tx,type,client", "5.0,1,deposit,1"]);
+    let output = get_output_from_raw_csv(&csv);
+    assert!(output.contains("1,5.0000,0.0000,5.0000,false"));
+}
+
+#[test]
+fn process_transactions_rejects_files_without_header() {
+    let csv = csv_lines(&["deposit,1,1,5.0", "deposit,1,2,3.0"]);
+    let result = process_transactions(Cursor::new(csv.as_bytes()), Vec::new());
+
+    match result {
+        Err(EngineError::InvalidHeader { missing, .. }) => {
+            assert_eq!(missing, ["type", "client", "tx"]);
+        }
+        other => panic!("expected invalid header, got {other:?}"),
+    }
+}
+
+#[test]
+fn headerless_files_are_read_positionally_when_configured() {
+    let csv = csv_lines(&["deposit,1,1,5.0", "withdrawal,1,2,2.0"]);
+    let mut engine = Engine::with_config(EngineConfig {
+        has_headers: false,
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+
+    assert!(
+        String::from_utf8(output)
+            .unwrap()
+            .contains("1,3.0000,0.0000,3.0000,false")
+    );
+}
+
+#[test]
+fn process_transactions_skips_repeated_header_rows() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "type,client,tx,amount",
+        "deposit,1,2,1.0",
+    ]);
+    let output = get_output_from_raw_csv(&csv);
+    assert!(output.contains("1,6.0000,0.0000,6.0000,false"));
+}
+
+#[test]
+fn process_transactions_ignores_unknown_extra_columns() {
+    let csv = csv_lines(&[
+        "timestamp,type,memo,client,tx,amount",
+        "2024-01-01T00:00:00Z,deposit,\"first, with comma\",1,1,5.0",
+        "2024-01-01T00:00:01Z,withdrawal,,1,2,1.5",
+    ]);
+    let output = get_output_from_raw_csv(&csv);
+    assert!(output.contains("1,3.5000,0.0000,3.5000,false"));
+}
+
+#[test]
+fn strict_columns_mode_rejects_unknown_extra_columns() {
+    let csv = csv_lines(&["type,client,tx,amount,memo", "deposit,1,1,5.0,hello"]);
+    let mut engine = Engine::with_config(EngineConfig {
+        strict_columns: true,
+        ..EngineConfig::default()
+    });
+    let result = engine.process(Cursor::new(csv.as_bytes()));
+
+    assert!(matches!(
+        result,
+        Err(EngineError::InvalidHeader { unexpected, .. }) if unexpected == ["memo"]
+    ));
+    assert!(engine.client(ClientId(1)).is_none());
+}
+
+#[test]
+fn rules_deny_and_flag_transactions_into_the_audit_output() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,500.0",
+        "withdrawal,1,2,50.0",
+        "withdrawal,1,3,300.0",
+    ]);
+    let mut engine = Engine::new();
+    engine
+        .rules_mut()
+        .register("large_withdrawal", |_, transaction| {
+            match (transaction.tx_type, transaction.amount) {
+                (TransactionType::Withdrawal, Some(amount)) if amount.value() > dec!(200) => {
+                    RuleDecision::Deny("over 200".to_string())
+                }
+                (TransactionType::Withdrawal, _) => RuleDecision::Flag("withdrawal".to_string()),
+                _ => RuleDecision::Allow,
+            }
+        });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(450));
+    let audit = engine.audit_entries();
+    assert_eq!(audit.len(), 2);
+    assert_eq!(audit[0].action, AuditAction::RuleFlagged);
+    assert_eq!(audit[0].tx, TxId(2));
+    assert_eq!(audit[0].available, dec!(450));
+    assert_eq!(audit[1].action, AuditAction::RuleDenied);
+    assert_eq!(
+        audit[1].reason.as_deref(),
+        Some("large_withdrawal: over 200")
+    );
+}
+
+#[test]
+fn reference_column_is_carried_into_rules_and_audit_entries() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount,reference",
+        "deposit,1,1,500.0,PARTNER-001",
+        "withdrawal,1,2,50.0,\"PARTNER-002, retry\"",
+        "withdrawal,1,3,20.0,",
+    ]);
+    let mut engine = Engine::new();
+    engine
+        .rules_mut()
+        .register("withdrawal", |_, transaction| match transaction.tx_type {
+            TransactionType::Withdrawal => RuleDecision::Flag("withdrawal".to_string()),
+            _ => RuleDecision::Allow,
+        });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(430));
+    let audit = engine.audit_entries();
+    assert_eq!(audit[0].reference.as_deref(), Some("PARTNER-002, retry"));
+    assert_eq!(audit[1].reference, None);
+}
+
+#[test]
+fn rule_flags_negative_balances_and_repeated_disputes_are_queued_for_review() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,10",
+        "withdrawal,1,2,8",
+        "dispute,1,1,",
+        "deposit,2,3,5",
+        "dispute,2,3,",
+        "resolve,2,3,",
+        "dispute,2,3,",
+    ]);
+    let mut engine = Engine::with_config(EngineConfig {
+        review_after_disputes: Some(2),
+        ..EngineConfig::default()
+    });
+    engine
+        .rules_mut()
+        .register("withdrawal", |_, transaction| match transaction.tx_type {
+            TransactionType::Withdrawal => RuleDecision::Flag("withdrawal".to_string()),
+            _ => RuleDecision::Allow,
+        });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+    engine
+        .add_review_note(ClientId(2), "called the customer")
+        .unwrap();
+    assert!(engine.add_review_note(ClientId(9), "unknown").is_err());
+
+    let queued: Vec<_> = engine
+        .review_queue()
+        .iter()
+        .map(|item| (item.reason, item.client, item.tx, item.sequence))
+        .collect();
+    assert_eq!(
+        queued,
+        [
+            (
+                ReviewReason::RuleFlagged,
+                ClientId(1),
+                Some(TxId(2)),
+                Some(2)
+            ),
+            (
+                ReviewReason::NegativeAvailable,
+                ClientId(1),
+                Some(TxId(1)),
+                Some(3)
+            ),
+            (
+                ReviewReason::RepeatedDisputes,
+                ClientId(2),
+                Some(TxId(3)),
+                Some(7)
+            ),
+            (ReviewReason::Note, ClientId(2), None, None),
+        ]
+    );
+    assert_eq!(engine.review_queue()[1].available, dec!(-8));
+
+    let mut buffer = Vec::new();
+    engine.snapshot().unwrap().save(&mut buffer).unwrap();
+    let mut restored: Engine = Engine::from_snapshot(Snapshot::load(Cursor::new(buffer)).unwrap());
+    assert_eq!(restored.review_queue(), engine.review_queue());
+
+    let mut exported = Vec::new();
+    write_review_queue(&restored.take_review_queue(), &mut exported).unwrap();
+    let exported = String::from_utf8(exported).unwrap();
+    assert_eq!(exported.lines().count(), 5);
+    assert!(exported.contains("note,2,,called the customer,0.0000,5.0000,5.0000,\n"));
+    assert!(restored.review_queue().is_empty());
+}
+
+#[test]
+fn formatting_options_parse_comma_amounts_and_round_output() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,\"3,505\"",
+        "withdrawal,1,2,\"1,5\"",
+    ]);
+    let mut engine = Engine::with_config(EngineConfig {
+        formatting: FormattingOptions {
+            places: 2,
+            rounding: Rounding::HalfUp,
+            decimal_separator: DecimalSeparator::Comma,
+            quoting: Quoting::Always,
+            ..FormattingOptions::default()
+        },
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "\"client\",\"available\",\"held\",\"total\",\"locked\"\n\"1\",\"2.01\",\"0.00\",\"2.01\",\"false\"\n"
+    );
+}
+
+#[test]
+fn accounts_can_be_written_with_crlf_or_in_fixed_width_columns() {
+    let csv = Fixture::new()
+        .deposit(1, 1, "12.5")
+        .deposit(22, 2, "3.0")
+        .to_csv();
+    let output = |formatting: FormattingOptions| {
+        let mut engine = Engine::with_config(EngineConfig {
+            formatting,
+            ..EngineConfig::default()
+        });
+        engine.process(Cursor::new(csv.as_bytes())).unwrap();
+        let mut output = Vec::new();
+        engine
+            .write_accounts(&mut output)
+            .map(|_| String::from_utf8(output).unwrap())
+    };
+
+    assert_eq!(
+        output(FormattingOptions {
+            line_ending: LineEnding::CrLf,
+            ..FormattingOptions::default()
+        })
+        .unwrap(),
+        "client,available,held,total,locked\r\n1,12.5000,0.0000,12.5000,false\r\n22,3.0000,0.0000,3.0000,false\r\n"
+    );
+
+    let fixed = |widths: &str| FormattingOptions {
+        fixed_width: Some(widths.parse().unwrap()),
+        ..FormattingOptions::default()
+    };
+    assert_eq!(
+        output(fixed("3,9,7,9,6")).unwrap(),
+        "  1  12.5000 0.0000  12.5000false \n 22   3.0000 0.0000   3.0000false \n"
+    );
+    assert!(matches!(
+        output(fixed("1,9,7,9,6")),
+        Err(EngineError::FieldTooWide { width: 1, .. })
+    ));
+    assert!(matches!(output(fixed("3,9")), Err(EngineError::Usage(_))));
+}
+
+#[test]
+fn grouped_amounts_are_read_and_ambiguous_rows_skipped() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,\"1.234,56\"",
+        "deposit,1,2,\"1,5.0\"",
+        "withdrawal,1,3,\"34,56\"",
+    ]);
+    let mut engine = Engine::with_config(EngineConfig {
+        formatting: FormattingOptions {
+            decimal_separator: DecimalSeparator::Comma,
+            thousands_separator: ThousandsSeparator::Dot,
+            ..FormattingOptions::default()
+        },
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(1200));
+}
+
+#[test]
+fn raising_the_interrupt_flag_stops_after_the_current_row() {
+    let csv = Fixture::new()
+        .deposit(1, 1, "1.0")
+        .deposit(1, 2, "2.0")
+        .deposit(1, 3, "4.0")
+        .to_csv();
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let mut engine = Engine::new();
+    engine.set_interrupt_flag(Arc::clone(&interrupt));
+    let flag = Arc::clone(&interrupt);
+    engine
+        .rules_mut()
+        .register("interrupt", move |_, transaction| {
+            if transaction.tx == TxId(2) {
+                flag.store(true, Ordering::SeqCst);
+            }
+            RuleDecision::Allow
+        });
+
+    let result = engine.process(Cursor::new(csv.as_bytes()));
+
+    assert!(matches!(result, Err(EngineError::Interrupted { row: 2 })));
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(3));
+}
+
+#[test]
+fn rejected_rows_are_dead_lettered_verbatim_with_a_reason() {
+    let csv = Fixture::with_header("type,client,tx,amount,reference")
+        .row("deposit,1,1,5.0,ok")
+        .row("withdrawal,1,2,9.0,too much")
+        .row("deposit,1,x,1.0,bad id")
+        .row("deposit,1,3")
+        .row("dispute,1,42,,unknown")
+        .to_csv();
+    let path = std::env::temp_dir().join(format!("dead-letter-{}.csv", std::process::id()));
+    let mut engine = Engine::new();
+    engine.set_dead_letter(std::fs::File::create(&path).unwrap());
+
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let dead_letter = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let rows: Vec<Vec<String>> = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(dead_letter.as_bytes())
+        .records()
+        .map(|record| record.unwrap().iter().map(String::from).collect())
+        .collect();
+    assert_eq!(rows.len(), 5);
+    assert_eq!(
+        rows[0],
+        ["type", "client", "tx", "amount", "reference", "reason"]
+    );
+    assert_eq!(rows[1][..5], ["withdrawal", "1", "2", "9.0", "too much"]);
+    assert!(rows[1][5].starts_with("Client 1: insufficient available funds"));
+    assert_eq!(rows[2][..5], ["deposit", "1", "x", "1.0", "bad id"]);
+    assert_eq!(rows[3][..3], ["deposit", "1", "3"]);
+    assert!(rows[3][3].starts_with("found record with 3 fields"));
+    assert_eq!(rows[4][5], "Client 1: transaction 42 is unknown");
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(5));
+}
+
+#[test]
+fn rows_that_are_not_utf8_are_dead_lettered_byte_for_byte() {
+    for lenient_csv in [false, true] {
+        let path = std::env::temp_dir().join(format!(
+            "dead-letter-bytes-{lenient_csv}-{}.csv",
+            std::process::id()
+        ));
+        let mut engine = Engine::with_config(EngineConfig {
+            lenient_csv,
+            ..EngineConfig::default()
+        });
+        engine.set_dead_letter(std::fs::File::create(&path).unwrap());
+
+        engine
+            .process(Cursor::new(
+                b"type,client,tx,amount,memo\ndeposit,1,1,2.0,caf\xe9\ndeposit,1,2,5.0,ok\n",
+            ))
+            .unwrap();
+
+        let dead_letter = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<_> = dead_letter.split(|&byte| byte == b'\n').collect();
+        assert_eq!(rows[0], b"type,client,tx,amount,memo,reason");
+        // The lenient reader keeps a malformed line whole, as one field.
+        let raw: &[u8] = if lenient_csv {
+            b"\"deposit,1,1,2.0,caf\xe9\","
+        } else {
+            b"deposit,1,1,2.0,caf\xe9,"
+        };
+        assert!(rows[1].starts_with(raw), "lenient_csv: {lenient_csv}");
+        assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(5));
+    }
+}
+
+#[test]
+fn withdrawal_holds_block_double_spending_until_confirmed() {
+    let csv = Fixture::new()
+        .deposit(1, 1, "10.0")
+        .row("withdrawal_hold,1,2,8.0")
+        .withdrawal(1, 3, "5.0")
+        .row("withdrawal_hold,1,4,2.0")
+        .row("withdrawal_settle,1,2,")
+        .row("withdrawal_cancel,1,4,")
+        .to_csv();
+
+    assert_accounts(
+        &run(&csv).unwrap(),
+        &[AccountSummary::new(ClientId(1), dec!(2), dec!(0), false)],
+    );
+}
+
+#[test]
+fn output_schema_v2_appends_lifetime_columns() {
+    let csv = Fixture::new()
+        .deposit(1, 1, "10.0")
+        .deposit(1, 2, "5.0")
+        .withdrawal(1, 3, "3.0")
+        .dispute(1, 1)
+        .deposit(2, 4, "1.0")
+        .dispute(2, 4)
+        .chargeback(2, 4)
+        .to_csv();
+    let mut engine = Engine::with_config(EngineConfig {
+        output_schema: OutputSchema::V2,
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "client,available,held,total,locked,open_disputes,lifetime_deposits,lifetime_withdrawals,chargeback_count\n\
+         1,2.0000,10.0000,12.0000,false,1,15.0000,3.0000,0\n\
+         2,0.0000,0.0000,0.0000,true,0,1.0000,0.0000,1\n"
+    );
+}
+
+#[test]
+fn changed_accounts_output_only_lists_clients_moved_by_this_run() {
+    let mut yesterday = Engine::new();
+    let csv = Fixture::new()
+        .deposit(1, 1, "1.0")
+        .deposit(2, 2, "2.0")
+        .deposit(3, 3, "3.0")
+        .to_csv();
+    yesterday.process(Cursor::new(csv.as_bytes())).unwrap();
+    let mut today = Engine::from_snapshot(yesterday.snapshot().unwrap());
+
+    let csv = Fixture::new()
+        .deposit(2, 4, "1.0")
+        .withdrawal(3, 5, "9.0")
+        .deposit(4, 6, "4.0")
+        .to_csv();
+    today.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut output = Vec::new();
+    today.write_changed_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "client,available,held,total,locked\n2,3.0000,0.0000,3.0000,false\n4,4.0000,0.0000,4.0000,false\n"
+    );
+}
+
+#[test]
+fn json_input_and_output_use_configured_amount_encoding() {
+    let input = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":\"2.5\"}\n{\"type\":\"withdrawal\",\"client\":1,\"tx\":2,\"amount\":1}\n";
+    let mut engine = Engine::with_config(EngineConfig {
+        input_format: Format::Json,
+        output_format: Format::Json,
+        amount_encoding: AmountEncoding::Number,
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(input.as_bytes())).unwrap();
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "{\"client\":1,\"available\":1.5000,\"held\":0.0000,\"total\":1.5000,\"locked\":false}\n"
+    );
+}
+
+#[test]
+fn manual_clock_drives_dispute_aging_deterministically() {
+    let clock = Arc::new(ManualClock::default());
+    let mut engine = Engine::new();
+    engine.set_clock(clock.clone());
+
+    let first = csv_lines(&["type,client,tx,amount", "deposit,1,1,5.0", "dispute,1,1,"]);
+    engine.process(Cursor::new(first.as_bytes())).unwrap();
+    clock.advance(Duration::from_secs(3600));
+    let second = csv_lines(&["type,client,tx,amount", "deposit,2,2,5.0", "dispute,2,2,"]);
+    engine.process(Cursor::new(second.as_bytes())).unwrap();
+
+    assert_eq!(
+        engine.disputes_older_than(Duration::from_secs(3600)),
+        [(ClientId(1), TxId(1))]
+    );
+    assert_eq!(
+        engine.disputes_older_than(Duration::ZERO),
+        [(ClientId(1), TxId(1)), (ClientId(2), TxId(2))]
+    );
+}
+
+#[test]
+fn idle_clients_are_spilled_and_transparently_reloaded() {
+    let clock = Arc::new(ManualClock::default());
+    let mut engine = Engine::new();
+    engine.set_clock(clock.clone());
+    engine.set_eviction(EvictionPolicy {
+        idle_for: Duration::from_secs(60),
+        store: Box::new(MemoryStore::default()),
+    });
+
+    let first = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,2,2,1.0",
+    ]);
+    engine.process(Cursor::new(first.as_bytes())).unwrap();
+    assert_eq!(engine.resident_clients(), 2);
+
+    clock.advance(Duration::from_secs(61));
+    let second = csv_lines(&["type,client,tx,amount", "deposit,2,3,1.0"]);
+    engine.process(Cursor::new(second.as_bytes())).unwrap();
+    assert_eq!(engine.resident_clients(), 1);
+    assert!(engine.client(ClientId(1)).is_none());
+
+    let third = csv_lines(&["type,client,tx,amount", "dispute,1,1,"]);
+    engine.process(Cursor::new(third.as_bytes())).unwrap();
+    assert_eq!(engine.client(ClientId(1)).unwrap().held, dec!(5));
+
+    clock.advance(Duration::from_secs(61));
+    engine.evict_idle().unwrap();
+    assert_eq!(engine.resident_clients(), 0);
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "client,available,held,total,locked\n1,0.0000,5.0000,5.0000,false\n2,2.0000,0.0000,2.0000,false\n"
+    );
+}
+
+#[test]
+fn chargeback_after_a_withdrawal_leaves_a_locked_negative_account() {
+    Scenario::new()
+        .deposit(1, 1, "10")
+        .deposit(1, 2, "5")
+        .withdrawal(1, 3, "12")
+        .dispute(1, 1)
+        .expect_available(1, "-7")
+        .expect_held(1, "10")
+        .chargeback(1, 1)
+        .expect_total(1, "-7")
+        .expect_locked(1, true)
+        .deposit(1, 4, "20")
+        .dispute(1, 2)
+        .expect_rejected(2)
+        .expect_available(1, "-7")
+        .run();
+}
+
+#[test]
+fn deposits_above_the_balance_limit_are_rejected_and_audited() {
+    let mut limits = BalanceLimits::with_default(dec!(150));
+    limits.insert(ClientId(2), dec!(1000));
+    let engine = Scenario::with_config(EngineConfig {
+        balance_limits: Some(Arc::new(limits)),
+        ..EngineConfig::default()
+    })
+    .deposit(1, 1, "100")
+    .deposit(1, 2, "60")
+    .expect_total(1, "100")
+    .deposit(1, 3, "50")
+    .deposit(2, 4, "900")
+    .expect_total(1, "150")
+    .expect_total(2, "900")
+    .expect_rejected(1)
+    .run();
+
+    let audit: Vec<_> = engine
+        .audit_entries()
+        .iter()
+        .map(|entry| (entry.action, entry.client, entry.tx))
+        .collect();
+    assert_eq!(
+        audit,
+        [(AuditAction::BalanceLimitExceeded, ClientId(1), TxId(2))]
+    );
+}
+
+#[test]
+fn transactions_driving_balances_negative_are_held_for_review() {
+    let csv = Fixture::new()
+        .row("deposit,1,1,10")
+        .row("withdrawal,1,2,8")
+        .row("dispute,1,1,")
+        .row("deposit,1,3,20")
+        .row("dispute,1,1,")
+        .to_csv();
+    let path = std::env::temp_dir().join(format!("review-{}.csv", std::process::id()));
+    let mut engine = Engine::with_config(EngineConfig {
+        non_negative_balances: true,
+        ..EngineConfig::default()
+    });
+    engine.set_review_output(std::fs::File::create(&path).unwrap());
+
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let review = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!(
+        (client.available, client.held.value()),
+        (dec!(12), dec!(10))
+    );
+    assert_eq!(engine.row_counts().rejected, 1);
+    assert_eq!(review.lines().count(), 2);
+    assert!(review.lines().nth(1).unwrap().starts_with("dispute,1,1,,"));
+    assert_eq!(
+        engine.audit_entries()[0].action,
+        AuditAction::NegativeBalanceReview
+    );
+}
+
+#[test]
+fn compaction_drops_history_older_than_the_retention() {
+    let clock = Arc::new(ManualClock::default());
+    let mut engine = Engine::new();
+    engine.set_clock(clock.clone());
+    engine.enable_balance_history();
+
+    for tx in 1..=3 {
+        let csv = Fixture::new().deposit(1, tx, "1.0").to_csv();
+        engine.process(Cursor::new(csv.as_bytes())).unwrap();
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+    }
+    let yesterday = clock.now() - Duration::from_secs(24 * 60 * 60);
+    let retention: Retention = "1d".parse().unwrap();
+
+    assert_eq!(engine.compact(retention), 1);
+    assert_eq!(engine.compact(retention), 0);
+    assert_eq!(
+        engine
+            .balance_at(ClientId(1), PointInTime::At(yesterday))
+            .map(|balance| balance.available),
+        Some(dec!(3))
+    );
+    assert_eq!(
+        engine
+            .balance_at(
+                ClientId(1),
+                PointInTime::At(yesterday - Duration::from_secs(1))
+            )
+            .map(|balance| balance.available),
+        Some(dec!(2))
+    );
+    assert_eq!(engine.balance_at(ClientId(1), PointInTime::Seq(1)), None);
+    assert_eq!(Engine::new().compact(retention), 0);
+}
+
+#[test]
+fn balance_history_answers_point_in_time_queries() {
+    let clock = Arc::new(ManualClock::default());
+    let start = clock.now();
+    let mut engine = Engine::new();
+    engine.set_clock(clock.clone());
+    engine.enable_balance_history();
+
+    let first = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,2,2,1.0",
+    ]);
+    engine.process(Cursor::new(first.as_bytes())).unwrap();
+    clock.advance(Duration::from_secs(3600));
+    let second = csv_lines(&[
+        "type,client,tx,amount",
+        "withdrawal,1,3,2.0",
+        "dispute,1,1,",
+    ]);
+    engine.process(Cursor::new(second.as_bytes())).unwrap();
+
+    let available = |engine: &Engine, point| {
+        engine
+            .balance_at(ClientId(1), point)
+            .map(|balance| (balance.available, balance.held))
+    };
+    assert_eq!(
+        available(&engine, PointInTime::Seq(1)),
+        Some((dec!(5), dec!(0)))
+    );
+    assert_eq!(
+        available(&engine, PointInTime::Seq(3)),
+        Some((dec!(3), dec!(0)))
+    );
+    assert_eq!(
+        available(&engine, PointInTime::At(start + Duration::from_secs(60))),
+        Some((dec!(5), dec!(0)))
+    );
+    assert_eq!(
+        available(&engine, PointInTime::At(clock.now())),
+        Some((dec!(-2), dec!(5)))
+    );
+    assert_eq!(engine.balance_at(ClientId(2), PointInTime::Seq(1)), None);
+    assert_eq!(engine.balance_history().unwrap().last_seq(), 4);
+
+    let mut buffer = Vec::new();
+    engine.snapshot().unwrap().save(&mut buffer).unwrap();
+    let restored = Engine::from_snapshot(Snapshot::load(Cursor::new(buffer)).unwrap());
+    assert_eq!(
+        available(&restored, PointInTime::Seq(3)),
+        Some((dec!(3), dec!(0)))
+    );
+}
+
+#[test]
+fn alerts_are_delivered_through_a_channel_while_processing() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut engine = Engine::new();
+    engine.set_alerts(
+        AlertThresholds {
+            max_held: Some(dec!(4)),
+            max_locked_total: Some(dec!(1)),
+            ..AlertThresholds::default()
+        },
+        move |alert| sender.send(alert.clone()).unwrap(),
+    );
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,1,2,3.0",
+        "dispute,1,1,",
+        "chargeback,1,1,",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(
+        receiver.try_iter().collect::<Vec<_>>(),
+        [
+            Alert::HighHeld {
+                client: ClientId(1),
+                held: dec!(5.0),
+                threshold: dec!(4)
+            },
+            Alert::LockedTotalExceeded {
+                locked_total: dec!(3.0),
+                threshold: dec!(1)
+            },
+        ]
+    );
+}
+
+#[test]
+fn registered_custom_types_are_dispatched_to_their_handlers() {
+    let mut engine = Engine::new();
+    engine
+        .register_transaction_type("bonus", true, |client, transaction| {
+            let amount = transaction
+                .amount
+                .expect("bonus requires an amount")
+                .value();
+            client.available += amount;
+            client.total += amount;
+            Ok(())
+        })
+        .unwrap();
+    engine
+        .register_transaction_type("broken", false, |client, _| {
+            client.available += dec!(1);
+            Ok(())
+        })
+        .unwrap();
+    assert!(
+        engine
+            .register_transaction_type("deposit", false, |_, _| Ok(()))
+            .is_err()
+    );
+
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "bonus,1,2,2.5",
+        "bonus,1,3,",
+        "broken,1,4,",
+        "mystery,1,5,1.0",
+        "deposit,2,6,1.0",
+        "dispute,2,6,",
+        "chargeback,2,6,",
+        "bonus,2,7,1.0",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!((client.available, client.total), (dec!(7.5), dec!(7.5)));
+    assert_eq!(engine.client(ClientId(2)).unwrap().available, dec!(0));
+    assert_eq!(engine.row_counts().rejected, 4);
+}
+
+#[test]
+fn row_counts_include_unparseable_and_rejected_rows() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,1,2,abc",
+        "withdrawal,1,3,9.0",
+        "type,client,tx,amount",
+        "withdrawal,1,4,1.0",
+    ]);
+    let mut engine = Engine::new();
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let rows = engine.row_counts();
+    assert_eq!((rows.read, rows.rejected), (4, 2));
+    assert_eq!(rows.error_rate(), 0.5);
+}
+
+#[test]
+fn memory_limit_aborts_spills_or_drops_history() {
+    let mut fixture = Fixture::new().deposit(1, 1, "1.0").dispute(1, 1);
+    for tx in 2..=2000 {
+        fixture = fixture.deposit((tx % 20) as u16, tx, "1.0");
+    }
+    let csv = fixture.to_csv();
+    let mut unbounded = Engine::new();
+    unbounded.process(Cursor::new(csv.as_bytes())).unwrap();
+    let limit = unbounded.approximate_memory() / 2;
+
+    let engine_with = |policy| {
+        let mut engine = Engine::new();
+        engine.set_config(EngineConfig {
+            max_memory_bytes: Some(limit),
+            memory_policy: policy,
+            ..EngineConfig::default()
+        });
+        engine
+    };
+
+    let mut aborting = engine_with(MemoryPolicy::Abort);
+    assert!(matches!(
+        aborting.process(Cursor::new(csv.as_bytes())),
+        Err(EngineError::MemoryLimitExceeded { .. })
+    ));
+
+    let mut spilling = engine_with(MemoryPolicy::Spill);
+    spilling.set_eviction(EvictionPolicy {
+        idle_for: Duration::MAX,
+        store: Box::new(MemoryStore::default()),
+    });
+    spilling.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert!(spilling.approximate_memory() <= limit);
+    assert!(spilling.resident_clients() < 20);
+    let (mut spilled, mut expected) = (Vec::new(), Vec::new());
+    spilling.write_accounts(&mut spilled).unwrap();
+    unbounded.write_accounts(&mut expected).unwrap();
+    assert_eq!(spilled, expected);
+
+    let mut pruning = engine_with(MemoryPolicy::DropHistory);
+    pruning.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert!(pruning.approximate_memory() <= limit);
+    assert_eq!(pruning.client(ClientId(1)).unwrap().held, dec!(1));
+    let late = csv_lines(&["type,client,tx,amount", "dispute,2,2,", "dispute,19,1999,"]);
+    pruning.process(Cursor::new(late.as_bytes())).unwrap();
+    assert_eq!(pruning.client(ClientId(2)).unwrap().held, dec!(0));
+    assert_eq!(pruning.client(ClientId(19)).unwrap().held, dec!(1));
+}
+
+#[cfg(feature = "fault-injection")]
+#[test]
+fn fault_injector_corrupts_rows_and_fails_output_writes() {
+    use rust_payments_engine::fault::FaultInjector;
+
+    let csv = csv_lines(&["type,client,tx,amount", "deposit,1,1,5.0"]);
+    let mut engine = Engine::new();
+    engine.set_fault_injector(Arc::new(
+        FaultInjector::new(3).corrupt_rows(1.0).fail_writes(1.0),
+    ));
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert!(engine.client(ClientId(1)).is_none());
+    assert!(matches!(
+        engine.write_accounts(Vec::new()),
+        Err(EngineError::Io(_))
+    ));
+}
+
+#[test]
+fn externally_sorted_inputs_apply_deposits_before_later_withdrawals() {
+    let withdrawals = csv_lines(&["timestamp,type,client,tx,amount", "20,withdrawal,1,2,4.0"]);
+    let deposits = csv_lines(&["type,client,tx,amount,timestamp", "deposit,1,1,5.0,10"]);
+    let mut merged = Vec::new();
+    ExternalSort::new(std::env::temp_dir())
+        .sort([withdrawals.as_bytes(), deposits.as_bytes()], &mut merged)
+        .unwrap();
+
+    let mut engine = Engine::with_config(EngineConfig {
+        strict_columns: true,
+        ..EngineConfig::default()
+    });
+    engine.process(Cursor::new(merged)).unwrap();
+
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(1));
+}
+
+fn lenient_engine() -> Engine {
+    Engine::with_config(EngineConfig {
+        lenient_csv: true,
+        ..EngineConfig::default()
+    })
+}
+
+#[test]
+fn lenient_csv_recovers_after_every_broken_corpus_file() {
+    let corpus = std::fs::read_dir("tests/fixtures/broken_csv").unwrap();
+    let mut files = 0;
+    for entry in corpus {
+        let path = entry.unwrap().path();
+        let input = std::fs::read(&path).unwrap();
+        files += 1;
+
+        let mut engine = lenient_engine();
+        engine.process(Cursor::new(&input)).unwrap();
+        let sentinel = engine.client(ClientId(9)).map(|client| client.available);
+        assert_eq!(sentinel, Some(dec!(1)), "{}", path.display());
+
+        // Every single-quote insertion and every truncation after the header
+        // must still be read row by row, never abort the run.
+        let body = input.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+        for at in body..=input.len() {
+            let mut quoted = input.clone();
+            quoted.insert(at, b'"');
+            lenient_engine().process(Cursor::new(quoted)).unwrap();
+            lenient_engine().process(Cursor::new(&input[..at])).unwrap();
+        }
+    }
+    assert!(files >= 5);
+}
+
+#[test]
+fn plain_csv_reads_an_unterminated_quote_to_the_end_of_input() {
+    let input = std::fs::read("tests/fixtures/broken_csv/unterminated_quote.csv").unwrap();
+    let mut engine = Engine::new();
+    engine.process(Cursor::new(&input)).unwrap();
+    assert!(engine.client(ClientId(9)).is_none());
+
+    let mut engine = lenient_engine();
+    engine.process(Cursor::new(&input)).unwrap();
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(3));
+    assert_eq!(engine.row_counts().rejected, 1);
+}
+
+#[test]
+fn accepted_transactions_carry_a_global_sequence_number() {
+    let mut engine = Engine::new();
+    engine.enable_balance_history();
+    engine
+        .rules_mut()
+        .register("flag_withdrawals", |_, transaction| {
+            match transaction.tx_type {
+                TransactionType::Withdrawal => RuleDecision::Flag("withdrawal".to_string()),
+                _ => RuleDecision::Allow,
+            }
+        });
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "withdrawal,2,2,1.0",
+        "deposit,2,3,2.0",
+        "withdrawal,2,4,1.0",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    // The first withdrawal is rejected for insufficient funds.
+    assert_eq!(engine.last_sequence(), 3);
+    let flagged: Vec<_> = engine
+        .audit_entries()
+        .iter()
+        .map(|entry| (entry.tx, entry.sequence))
+        .collect();
+    assert_eq!(flagged, [(TxId(2), None), (TxId(4), Some(3))]);
+    let withdrawal = engine
+        .balance_at(ClientId(2), PointInTime::Seq(u64::MAX))
+        .unwrap();
+    assert_eq!(withdrawal.sequence, Some(3));
+
+    let mut buffer = Vec::new();
+    engine.snapshot().unwrap().save(&mut buffer).unwrap();
+    let mut restored: Engine = Engine::from_snapshot(Snapshot::load(Cursor::new(buffer)).unwrap());
+    let more = csv_lines(&["type,client,tx,amount", "deposit,1,5,1.0"]);
+    restored.process(Cursor::new(more.as_bytes())).unwrap();
+    assert_eq!(restored.last_sequence(), 4);
+    let deposit = restored
+        .balance_at(ClientId(1), PointInTime::Seq(u64::MAX))
+        .unwrap();
+    assert_eq!(deposit.sequence, Some(4));
+}
+
+#[test]
+fn pushed_transactions_report_typed_rejections() {
+    let mut engine = Engine::new();
+
+    let deposit = engine.push(
+        TransactionType::Deposit,
+        ClientId(1),
+        TxId(1),
+        Some(dec!(2)),
+    );
+    let withdrawal = engine.push(
+        TransactionType::Withdrawal,
+        ClientId(1),
+        TxId(2),
+        Some(dec!(5)),
+    );
+    let refund = engine.push(
+        TransactionType::from_name("refund").unwrap(),
+        ClientId(1),
+        TxId(3),
+        None,
+    );
+
+    assert_eq!(deposit.unwrap(), None);
+    assert!(matches!(
+        withdrawal.unwrap(),
+        Some(Rejection::Client(
+            ClientTransactionError::InsufficientAvailableFunds {
+                client_id: ClientId(1),
+                ..
+            }
+        ))
+    ));
+    assert_eq!(
+        refund.unwrap(),
+        Some(Rejection::Other(
+            "unknown transaction type refund".to_string()
+        ))
+    );
+    let rows = engine.row_counts();
+    assert_eq!((rows.read, rows.rejected), (3, 2));
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(2));
+}
+
+#[test]
+fn stale_disputes_expire_after_a_number_of_transactions() {
+    let mut engine = Engine::with_config(EngineConfig {
+        dispute_expiry: Some(DisputeExpiry {
+            deadline: DisputeDeadline::Sequence(2),
+            outcome: ExpiryOutcome::Resolve,
+        }),
+        ..Default::default()
+    });
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "dispute,1,1,",
+        "deposit,2,2,1.0",
+        "deposit,2,3,1.0",
+        "deposit,2,4,1.0",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    // Two transactions after the dispute, it is resolved before the third.
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!((client.available, client.held.value()), (dec!(5), dec!(0)));
+    let [entry] = engine.audit_entries() else {
+        panic!("expected one audit entry");
+    };
+    assert_eq!(entry.action, AuditAction::ExpiredResolve);
+    assert_eq!(
+        (entry.client, entry.tx, entry.amount),
+        (ClientId(1), TxId(1), Some(dec!(5)))
+    );
+}
+
+#[test]
+fn prolonged_holds_accrue_a_penalty_when_resolved() {
+    let clock = Arc::new(ManualClock::default());
+    let mut engine = Engine::with_config(EngineConfig {
+        hold_accrual: Some(HoldAccrual {
+            grace: DisputeDeadline::Age(Duration::from_secs(86_400)),
+            rate: dec!(0.01),
+            kind: AccrualKind::Penalty,
+        }),
+        ..Default::default()
+    });
+    engine.set_clock(clock.clone());
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,50.0",
+        "deposit,2,2,50.0",
+        "dispute,1,1,",
+        "dispute,2,2,",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    clock.advance(Duration::from_secs(3 * 86_400));
+    let more = csv_lines(&["type,client,tx,amount", "resolve,1,1,", "chargeback,2,2,"]);
+    engine.process(Cursor::new(more.as_bytes())).unwrap();
+
+    // Two days past the grace period at 1% a day; chargebacks accrue nothing.
+    let client = engine.client(ClientId(1)).unwrap();
+    assert_eq!((client.available, client.total), (dec!(49), dec!(49)));
+    assert_eq!(engine.client(ClientId(2)).unwrap().total, dec!(0));
+    let [entry] = engine.audit_entries() else {
+        panic!("expected one audit entry");
+    };
+    assert_eq!(entry.action, AuditAction::AccruedPenalty);
+    assert_eq!(
+        (entry.client, entry.tx, entry.amount, entry.sequence),
+        (ClientId(1), TxId(1), Some(dec!(1)), Some(5))
+    );
+}
+
+#[test]
+fn restored_disputes_expire_by_age_with_the_configured_outcome() {
+    let clock = Arc::new(ManualClock::default());
+    let mut engine = Engine::new();
+    engine.set_clock(clock.clone());
+    let csv = csv_lines(&["type,client,tx,amount", "deposit,1,1,5.0", "dispute,1,1,"]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut restored = Engine::from_snapshot(engine.snapshot().unwrap());
+    restored.set_clock(clock.clone());
+    restored.set_config(EngineConfig {
+        dispute_expiry: Some(DisputeExpiry {
+            deadline: DisputeDeadline::Age(Duration::from_secs(86_400)),
+            outcome: ExpiryOutcome::Chargeback,
+        }),
+        ..Default::default()
+    });
+    let more = csv_lines(&["type,client,tx,amount", "deposit,2,2,1.0"]);
+    restored.process(Cursor::new(more.as_bytes())).unwrap();
+    assert!(!restored.client(ClientId(1)).unwrap().locked);
+
+    clock.advance(Duration::from_secs(86_400));
+    restored.process(Cursor::new(more.as_bytes())).unwrap();
+    let client = restored.client(ClientId(1)).unwrap();
+    assert!(client.locked);
+    assert_eq!(client.total, dec!(0));
+    assert_eq!(
+        restored.audit_entries()[0].action,
+        AuditAction::ExpiredChargeback
+    );
+}
+
+#[test]
+fn external_client_ids_resolve_on_input_and_key_output() {
+    let aliases =
+        ClientAliases::load("external_id,client\ncus_alice,1\ncus_bob,2\n".as_bytes()).unwrap();
+    let mut engine = Engine::with_config(EngineConfig {
+        client_aliases: Some(Arc::new(aliases)),
+        output_external_ids: true,
+        ..Default::default()
+    });
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,cus_alice,1,5.0",
+        "deposit,2,2,3.0",
+        "withdrawal,cus_bob,3,1.0",
+        "deposit,3,4,1.0",
+        "deposit,cus_carol,5,1.0",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(engine.row_counts().rejected, 1);
+
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        csv_lines(&[
+            "client,available,held,total,locked",
+            "cus_alice,5.0000,0.0000,5.0000,false",
+            "cus_bob,2.0000,0.0000,2.0000,false",
+            "3,1.0000,0.0000,1.0000,false",
+        ])
+    );
+}
+
+#[test]
+fn amounts_on_dispute_rows_are_ignored_unless_strict() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "dispute,1,1,5.0",
+        "resolve,1,1,",
+        "dispute,1,1,",
+        "chargeback,1,1,1.0",
+    ]);
+
+    let mut lenient = Engine::new();
+    lenient.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(lenient.row_counts().rejected, 0);
+    assert!(lenient.client(ClientId(1)).unwrap().locked);
+
+    let mut strict = Engine::with_config(EngineConfig {
+        reject_unexpected_amounts: true,
+        ..Default::default()
+    });
+    strict.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(strict.row_counts().rejected, 3);
+    let client = strict.client(ClientId(1)).unwrap();
+    assert!(!client.locked);
+    assert_eq!((client.available, client.held.value()), (dec!(0), dec!(5)));
+    assert_eq!(
+        strict
+            .push(
+                TransactionType::Resolve,
+                ClientId(1),
+                TxId(1),
+                Some(dec!(5))
+            )
+            .unwrap(),
+        Some(Rejection::Client(
+            ClientTransactionError::UnexpectedAmount {
+                client_id: ClientId(1),
+                tx_type: TransactionType::Resolve,
+                tx: TxId(1),
+                amount: dec!(5),
+            }
+        ))
+    );
+}
+
+#[test]
+fn strict_tx_order_rejects_reused_and_out_of_order_ids() {
+    let csv = "type,client,tx,amount\n\
+        deposit,1,1,5.0\n\
+        deposit,2,3,5.0\n\
+        withdrawal,1,2,1.0\n\
+        dispute,1,1,\n\
+        deposit,2,3,2.0\n\
+        withdrawal,2,4,1.0\n";
+    let mut engine = Engine::with_config(EngineConfig {
+        strict_tx_order: true,
+        ..Default::default()
+    });
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(engine.row_counts().rejected, 2);
+    assert_eq!(engine.tx_order_violations(), 2);
+    assert_eq!(engine.client(ClientId(1)).unwrap().held.value(), dec!(5));
+    assert_eq!(engine.client(ClientId(2)).unwrap().available, dec!(4));
+    assert_eq!(
+        engine
+            .push(
+                TransactionType::Deposit,
+                ClientId(1),
+                TxId(4),
+                Some(dec!(1))
+            )
+            .unwrap(),
+        Some(Rejection::Client(ClientTransactionError::TxIdOutOfOrder {
+            client_id: ClientId(1),
+            tx: TxId(4),
+            last: TxId(4),
+        }))
+    );
+}
+
+#[test]
+fn zero_amounts_are_rejected_or_ignored_by_policy() {
+    let csv = "type,client,tx,amount\ndeposit,1,1,0\ndeposit,1,2,3.0\nwithdrawal,1,3,0.0\n";
+    let mut strict = Engine::new();
+    strict.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(strict.row_counts().rejected, 2);
+    assert_eq!(
+        strict
+            .push(
+                TransactionType::Deposit,
+                ClientId(1),
+                TxId(4),
+                Some(dec!(0))
+            )
+            .unwrap(),
+        Some(Rejection::Client(ClientTransactionError::ZeroAmount {
+            client_id: ClientId(1),
+            tx_type: TransactionType::Deposit,
+            tx: TxId(4),
+        }))
+    );
+
+    let mut lenient = Engine::with_config(EngineConfig {
+        zero_amounts: ZeroAmountPolicy::Ignore,
+        ..Default::default()
+    });
+    lenient.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(lenient.row_counts().rejected, 0);
+    assert_eq!(lenient.client(ClientId(1)).unwrap().available, dec!(3));
+    let ignored: Vec<_> = lenient
+        .audit_entries()
+        .iter()
+        .map(|entry| (entry.action, entry.tx, entry.reason.as_deref()))
+        .collect();
+    assert_eq!(
+        ignored,
+        [
+            (
+                AuditAction::ZeroAmountIgnored,
+                TxId(1),
+                Some("zero-amount deposit")
+            ),
+            (
+                AuditAction::ZeroAmountIgnored,
+                TxId(3),
+                Some("zero-amount withdrawal")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn row_panics_can_be_caught_and_the_run_continues() {
+    let csv = "type,client,tx,amount\ndeposit,1,1,5.0\nexplode,1,2,\ndeposit,1,3,1.0\n";
+    let guarded = || {
+        let mut engine = Engine::with_config(EngineConfig {
+            catch_row_panics: true,
+            ..Default::default()
+        });
+        engine
+            .register_transaction_type("explode", false, |_, _| panic!("pathological row"))
+            .unwrap();
+        engine
+    };
+
+    let mut engine = guarded();
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+    assert_eq!(engine.row_counts().rejected, 1);
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(6));
+    assert_eq!(
+        engine
+            .push(
+                TransactionType::from_name("explode").unwrap(),
+                ClientId(1),
+                TxId(4),
+                None
+            )
+            .unwrap(),
+        Some(Rejection::Other(
+            EngineError::RowPanic { row: 4 }.to_string()
+        ))
+    );
+
+    let mut unguarded = guarded();
+    unguarded.set_config(EngineConfig::default());
+    let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        unguarded.process(Cursor::new(csv.as_bytes()))
+    }));
+    assert!(outcome.is_err());
+}
+
+#[test]
+fn preprocessors_adapt_partner_rows_before_validation() {
+    let mut engine = Engine::new();
+    engine.add_preprocessor(RenameTypes::new([
+        ("credit", "deposit"),
+        ("debit", "withdrawal"),
+    ]));
+    engine.add_preprocessor(MinorUnitAmounts { places: 2 });
+    engine.add_preprocessor(|header: &StringRecord, record: &mut StringRecord| {
+        let client = header.iter().position(|column| column == "client").unwrap();
+        match record
+            .get(client)
+            .map(|id| id.trim_start_matches("acct-").to_string())
+        {
+            Some(id) => {
+                set_field(record, client, &id);
+                Ok(())
+            }
+            None => Err("missing client".to_string()),
+        }
+    });
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "credit,acct-1,1,1050",
+        "debit,acct-1,2,50",
+        "credit,acct-2,3,1.5",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    assert_eq!(engine.row_counts().rejected, 1);
+    assert_eq!(engine.client(ClientId(1)).unwrap().available, dec!(10));
+    assert!(engine.client(ClientId(2)).is_none());
+}
+
+#[test]
+fn rejections_stream_to_a_channel_while_processing() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,x,2,1.0",
+        "withdrawal,1,3,9.0",
+    ]);
+    let (rejections, handle) = process_transactions_with_errors(Cursor::new(csv), Vec::new());
+    let rejections: Vec<_> = rejections.iter().collect();
+    handle.join().unwrap().unwrap();
+
+    assert_eq!(rejections.len(), 2);
+    assert_eq!(rejections[0].row, 2);
+    assert_eq!(rejections[0].fields, ["deposit", "x", "2", "1.0"]);
+    assert_eq!(rejections[0].rejection, None);
+    assert_eq!(rejections[1].row, 3);
+    assert!(matches!(
+        rejections[1].rejection,
+        Some(Rejection::Client(
+            ClientTransactionError::InsufficientAvailableFunds { .. }
+        ))
+    ));
+}
+
+#[test]
+fn risk_scores_can_be_appended_to_account_output() {
+    let mut engine = Engine::with_config(EngineConfig {
+        output_risk_score: true,
+        ..Default::default()
+    });
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,5.0",
+        "deposit,2,2,5.0",
+        "dispute,2,2,",
+        "chargeback,2,2,",
+    ]);
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        csv_lines(&[
+            "client,available,held,total,locked,risk_score",
+            "1,5.0000,0.0000,5.0000,false,0",
+            "2,0.0000,0.0000,0.0000,true,53",
+        ])
+    );
+}
+
+#[test]
+fn engine_runs_on_minor_unit_balances() {
+    let csv = csv_lines(&[
+        "type,client,tx,amount",
+        "deposit,1,1,10.5",
+        "withdrawal,1,2,2.25",
+        "deposit,2,3,4.0",
+        "dispute,2,3,",
+        "deposit,3,4,900000000000000",
+        "deposit,3,5,100000000000000",
+    ]);
+    let mut engine = Engine::<MinorUnits>::default();
+    engine.set_config(EngineConfig::default());
+    engine.process(Cursor::new(csv.as_bytes())).unwrap();
+
+    let mut output = Vec::new();
+    engine.write_accounts(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        csv_lines(&[
+            "client,available,held,total,locked",
+            "1,8.2500,0.0000,8.2500,false",
+            "2,0.0000,4.0000,4.0000,false",
+            "3,900000000000000.0000,0.0000,900000000000000.0000,false",
+        ])
+    );
+    // The second deposit of client 3 does not fit an i64 of minor units.
+    assert_eq!(engine.row_counts().rejected, 1);
+
+    let mut buffer = Vec::new();
+    engine.snapshot().unwrap().save(&mut buffer).unwrap();
+    let restored =
+        Engine::from_snapshot(Snapshot::<MinorUnits>::load(Cursor::new(buffer)).unwrap());
+    assert_eq!(
+        restored.client(ClientId(1)).unwrap().available,
+        MinorUnits::from_minor(82_500)
+    );
+}